@@ -53,6 +53,7 @@ impl Config for Game {
             effects: Default::default(),
             min_y: 0,
             height: 256,
+            ..Default::default()
         }]
     }
 
@@ -67,6 +68,8 @@ impl Config for Game {
             max_players: -1,
             player_sample: Default::default(),
             description: "Player Benchmark Server".into(),
+            version_name: None,
+            previews_chat: false,
             favicon_png: None,
         }
     }
@@ -386,6 +386,19 @@ pub fn build() -> anyhow::Result<TokenStream> {
             }
         });
 
+        let all_tracked_data_stmts = fields.iter().map(|&field| {
+            let field_name = ident(&field.name);
+            let field_index = field.index;
+            let type_id = field.default_value.type_id();
+            let encodable = field.default_value.encodable_expr(quote!(self.#field_name));
+
+            quote! {
+                data.push(#field_index);
+                VarInt(#type_id).encode(&mut *data).unwrap();
+                #encodable.encode(&mut *data).unwrap();
+            }
+        });
+
         quote! {
             pub struct #struct_name {
                 /// Contains a set bit for every modified field.
@@ -411,6 +424,10 @@ pub fn build() -> anyhow::Result<TokenStream> {
                     }
                 }
 
+                pub(crate) fn all_tracked_data(&self, data: &mut Vec<u8>) {
+                    #(#all_tracked_data_stmts)*
+                }
+
                 pub(crate) fn clear_modifications(&mut self) {
                     self.__modified_flags = 0;
                 }
@@ -432,6 +449,8 @@ pub fn build() -> anyhow::Result<TokenStream> {
         }
     });
 
+    let entity_kind_count = concrete_entity_names.len();
+
     Ok(quote! {
         /// Contains a variant for each concrete entity type.
         #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
@@ -440,6 +459,9 @@ pub fn build() -> anyhow::Result<TokenStream> {
         }
 
         impl EntityKind {
+            /// Every entity kind.
+            pub const ALL: [Self; #entity_kind_count] = [#(Self::#concrete_entity_names,)*];
+
             pub fn translation_key(self) -> &'static str {
                 match self {
                     #(#translation_key_arms)*
@@ -488,6 +510,19 @@ pub fn build() -> anyhow::Result<TokenStream> {
                 }
             }
 
+            /// Writes every field, including those with their default value.
+            pub(super) fn write_all_tracked_data(&self, buf: &mut Vec<u8>) {
+                buf.clear();
+
+                match self {
+                    #(Self::#concrete_entity_names(e) => e.all_tracked_data(buf),)*
+                }
+
+                if !buf.is_empty() {
+                    buf.push(0xff);
+                }
+            }
+
             pub(super) fn clear_modifications(&mut self) {
                 match self {
                     #(Self::#concrete_entity_names(e) => e.clear_modifications(),)*
@@ -0,0 +1,25 @@
+use valence::prelude::*;
+
+pub fn main() -> ShutdownResult {
+    let config = ServerConfigBuilder::new()
+        .online_mode(false)
+        .init(|server| {
+            let dimension = server.shared.dimensions().next().unwrap().0;
+            let (world_id, world) = server.worlds.insert(dimension, ());
+            world.chunks.insert([0, 0], UnloadedChunk::default(), ());
+            world.chunks.set_block_state([0, 63, 0], BlockState::STONE);
+            server.state = world_id;
+        })
+        .update(|server| {
+            server.clients.retain(|_, client| {
+                if client.created_this_tick() {
+                    client.respawn(server.state);
+                    client.teleport([0.5, 64.0, 0.5], 0.0, 0.0);
+                }
+                !client.is_disconnected()
+            });
+        })
+        .build();
+
+    valence::start_server(config, WorldId::default())
+}
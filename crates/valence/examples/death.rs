@@ -96,6 +96,8 @@ impl Config for Game {
             online_players: self.player_count.load(Ordering::SeqCst) as i32,
             max_players: MAX_PLAYERS as i32,
             description: "Hello Valence!".color(Color::AQUA),
+            version_name: None,
+            previews_chat: false,
             favicon_png: Some(
                 include_bytes!("../../../assets/logo-64x64.png")
                     .as_slice()
@@ -187,7 +189,7 @@ impl Config for Game {
                 if client.position().y < 0.0 {
                     client.can_respawn = true;
                     client.kill(None, "You fell");
-                    // You could have also killed the player with `Client::set_health_and_food`,
+                    // You could have also killed the player with `Client::set_health`,
                     // however you cannot send a message to the death screen
                     // that way
                     if client.world() == server.state.third_world {
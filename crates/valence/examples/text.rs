@@ -46,6 +46,8 @@ impl Config for Game {
             online_players: -1,
             max_players: -1,
             description: "Hello Valence! ".into_text() + "Text Example".color(Color::AQUA),
+            version_name: None,
+            previews_chat: false,
             favicon_png: Some(
                 include_bytes!("../../../assets/logo-64x64.png")
                     .as_slice()
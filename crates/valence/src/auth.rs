@@ -0,0 +1,433 @@
+//! Player authentication in [online mode].
+//!
+//! [online mode]: crate::config::ConnectionMode::Online
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use rand_core::CryptoRngCore;
+use reqwest::{Client as ReqwestClient, StatusCode};
+use serde::Deserialize;
+use tracing::warn;
+use uuid::Uuid;
+use valence_protocol::types::SignedPropertyOwned;
+use valence_protocol::Username;
+
+/// Resolves the profile of a player logging in with online mode enabled.
+///
+/// The encryption handshake is performed by the server before the
+/// authenticator is invoked. Implement this trait to verify players with a
+/// backend other than the official Minecraft session server.
+///
+/// This trait uses the [async_trait] attribute macro.
+///
+/// [async_trait]: https://docs.rs/async-trait/latest/async_trait/
+#[async_trait]
+pub trait Authenticator: Send + Sync + 'static {
+    /// Verifies that the player with the given username has joined the
+    /// server and returns their profile.
+    ///
+    /// `shared_secret` is the symmetric key negotiated with the client and
+    /// `server_hash` is the hex digest the client sent to its session server.
+    /// `ip` is the remote address of the client.
+    ///
+    /// If an error is returned, the client is disconnected with a message
+    /// saying that their username could not be verified.
+    async fn verify(
+        &self,
+        username: Username<&str>,
+        shared_secret: &[u8],
+        server_hash: &str,
+        ip: IpAddr,
+    ) -> anyhow::Result<ResolvedProfile>;
+
+    /// Generates the verify token sent to a client in the encryption request.
+    /// The client proves that it received the server's public key by sending
+    /// the token back encrypted with it. The server checks the token it gets
+    /// back against the one returned here.
+    ///
+    /// The token must be between 1 and 117 bytes long so that it fits in a
+    /// single block of the server's RSA key. Otherwise, the client is
+    /// disconnected.
+    ///
+    /// `rng` is the operating system's random number generator unless it
+    /// was replaced with `Config::rng` for testing.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns 16 random bytes from `rng`.
+    fn verify_token(&self, rng: &mut dyn CryptoRngCore) -> Vec<u8> {
+        let mut token = vec![0; 16];
+        rng.fill_bytes(&mut token);
+        token
+    }
+}
+
+/// The profile of a player produced by an [`Authenticator`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ResolvedProfile {
+    /// The UUID of the player.
+    pub uuid: Uuid,
+    /// The username of the player. This must match the username the player
+    /// logged in with.
+    pub username: Username<String>,
+    /// The profile properties of the player. The `textures` property is
+    /// required.
+    pub properties: Vec<SignedPropertyOwned>,
+}
+
+/// An [`Authenticator`] using a session server with the same API as the
+/// official Minecraft session server. This includes many third party yggdrasil
+/// implementations.
+///
+/// Resolved profiles can optionally be cached with
+/// [`Self::with_profile_cache`] to keep players logging in while the session
+/// server is unavailable. See [`Self::fail_open`].
+pub struct MojangAuthenticator {
+    session_server: String,
+    prevent_proxy_connections: bool,
+    http_client: ReqwestClient,
+    cache: Option<Mutex<ProfileCache>>,
+    fail_open: bool,
+}
+
+impl MojangAuthenticator {
+    /// The URL of the `hasJoined` endpoint of the official Minecraft session
+    /// server.
+    pub const SESSION_SERVER: &'static str =
+        "https://sessionserver.mojang.com/session/minecraft/hasJoined";
+
+    /// Creates an authenticator for the official Minecraft session server.
+    ///
+    /// If `prevent_proxy_connections` is enabled, the session server also
+    /// checks that the client connected to it with the same IP it connected to
+    /// this server with.
+    pub fn new(prevent_proxy_connections: bool) -> Self {
+        Self::with_session_server(Self::SESSION_SERVER, prevent_proxy_connections)
+    }
+
+    /// Creates an authenticator for a custom session server. `url` is the full
+    /// URL of the `hasJoined` endpoint, excluding the query string.
+    pub fn with_session_server(url: impl Into<String>, prevent_proxy_connections: bool) -> Self {
+        Self {
+            session_server: url.into(),
+            prevent_proxy_connections,
+            http_client: ReqwestClient::new(),
+            cache: None,
+            fail_open: false,
+        }
+    }
+
+    /// Enables caching of up to `capacity` resolved profiles for `ttl`. When
+    /// the cache is full, the least recently used profile is evicted.
+    ///
+    /// The session server is still asked to verify every player. Cached
+    /// profiles are only used in place of the session server's answer when it
+    /// fails to respond and [`Self::fail_open`] is enabled.
+    pub fn with_profile_cache(mut self, capacity: usize, ttl: Duration) -> Self {
+        self.cache = Some(Mutex::new(ProfileCache::new(capacity, ttl)));
+        self
+    }
+
+    /// Sets whether a cached profile which is still valid should be used when
+    /// the session server fails to respond, e.g. during an outage. This has no
+    /// effect unless the profile cache is enabled.
+    ///
+    /// The cached profile is only used if the player is connecting from the
+    /// same IP address it was fetched for. Because the session server is what
+    /// proves that a player owns their account, a short cache `ttl` should be
+    /// used.
+    ///
+    /// The default is `false` (fail closed).
+    pub fn fail_open(mut self, fail_open: bool) -> Self {
+        self.fail_open = fail_open;
+        self
+    }
+
+    /// Requests the player's profile from the session server. Returns
+    /// `Ok(None)` if the session server could not verify the player.
+    async fn request_profile(
+        &self,
+        username: Username<&str>,
+        server_hash: &str,
+        ip: IpAddr,
+    ) -> anyhow::Result<Option<ResolvedProfile>> {
+        let url = if self.prevent_proxy_connections {
+            format!(
+                "{}?username={username}&serverId={server_hash}&ip={ip}",
+                self.session_server
+            )
+        } else {
+            format!(
+                "{}?username={username}&serverId={server_hash}",
+                self.session_server
+            )
+        };
+
+        let resp = self.http_client.get(url).send().await?;
+
+        match resp.status() {
+            StatusCode::OK => {}
+            StatusCode::NO_CONTENT => return Ok(None),
+            status => bail!("session server GET request failed (status code {status})"),
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct AuthResponse {
+            id: String,
+            name: Username<String>,
+            properties: Vec<SignedPropertyOwned>,
+        }
+
+        let data: AuthResponse = resp.json().await?;
+
+        Ok(Some(ResolvedProfile {
+            uuid: Uuid::parse_str(&data.id).context("failed to parse player's UUID")?,
+            username: data.name,
+            properties: data.properties,
+        }))
+    }
+}
+
+#[async_trait]
+impl Authenticator for MojangAuthenticator {
+    async fn verify(
+        &self,
+        username: Username<&str>,
+        _shared_secret: &[u8],
+        server_hash: &str,
+        ip: IpAddr,
+    ) -> anyhow::Result<ResolvedProfile> {
+        let Some(cache) = &self.cache else {
+            return self
+                .request_profile(username, server_hash, ip)
+                .await?
+                .context("session server could not verify username");
+        };
+
+        match self.request_profile(username, server_hash, ip).await {
+            Ok(Some(profile)) => {
+                cache
+                    .lock()
+                    .unwrap()
+                    .insert(profile.clone(), ip, Instant::now());
+                Ok(profile)
+            }
+            // Never fail open if the session server explicitly rejected the player.
+            Ok(None) => bail!("session server could not verify username"),
+            Err(e) if self.fail_open => {
+                match cache
+                    .lock()
+                    .unwrap()
+                    .get(username.as_str(), ip, Instant::now())
+                {
+                    Some(profile) => {
+                        warn!("using cached profile for {username} after error: {e:#}");
+                        Ok(profile)
+                    }
+                    None => Err(e),
+                }
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// A least recently used cache of resolved profiles keyed by username.
+struct ProfileCache {
+    capacity: usize,
+    ttl: Duration,
+    entries: HashMap<String, CachedProfile>,
+}
+
+struct CachedProfile {
+    profile: ResolvedProfile,
+    /// The IP of the client when the profile was fetched.
+    ip: IpAddr,
+    fetched_at: Instant,
+    last_used: Instant,
+}
+
+impl ProfileCache {
+    fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Gets an unexpired profile fetched for the same IP.
+    fn get(&mut self, username: &str, ip: IpAddr, now: Instant) -> Option<ResolvedProfile> {
+        let entry = self.entries.get_mut(username)?;
+
+        if now.saturating_duration_since(entry.fetched_at) > self.ttl {
+            self.entries.remove(username);
+            return None;
+        }
+
+        if entry.ip != ip {
+            return None;
+        }
+
+        entry.last_used = now;
+        Some(entry.profile.clone())
+    }
+
+    fn insert(&mut self, profile: ResolvedProfile, ip: IpAddr, now: Instant) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let username = profile.username.as_str();
+
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(username) {
+            let lru = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(name, _)| name.clone());
+
+            if let Some(lru) = lru {
+                self.entries.remove(&lru);
+            }
+        }
+
+        self.entries.insert(
+            username.to_owned(),
+            CachedProfile {
+                profile,
+                ip,
+                fetched_at: now,
+                last_used: now,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    fn profile(name: &str) -> ResolvedProfile {
+        ResolvedProfile {
+            uuid: Uuid::nil(),
+            username: Username::new(name.to_owned()).unwrap(),
+            properties: vec![],
+        }
+    }
+
+    #[test]
+    fn profile_cache_expiry_and_eviction() {
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let other_ip = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+        let start = Instant::now();
+        let ttl = Duration::from_secs(60);
+
+        let mut cache = ProfileCache::new(2, ttl);
+
+        let later = start + Duration::from_secs(1);
+
+        cache.insert(profile("foo"), ip, start);
+        cache.insert(profile("bar"), ip, start);
+
+        assert!(cache.get("foo", ip, later).is_some());
+        assert!(cache.get("foo", other_ip, later).is_none());
+
+        // "bar" is the least recently used.
+        cache.insert(profile("baz"), ip, later);
+        assert!(cache.get("bar", ip, start).is_none());
+        assert!(cache.get("foo", ip, start).is_some());
+
+        assert!(cache.get("baz", ip, start + ttl * 2).is_none());
+        assert!(cache.get("baz", ip, start).is_none());
+    }
+
+    /// Starts a stand-in for the session server which answers one request
+    /// with each of the given status codes in order. Returns the URL of its
+    /// `hasJoined` endpoint and the number of requests it has received.
+    async fn session_server(statuses: Vec<u16>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hasJoined", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            for status in statuses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+
+                let mut req = vec![];
+                while !req.ends_with(b"\r\n\r\n") {
+                    req.push(stream.read_u8().await.unwrap());
+                }
+
+                counter.fetch_add(1, Ordering::SeqCst);
+
+                let body = match status {
+                    200 => {
+                        r#"{"id":"069a79f444e94726a5befca90e38aaf5","name":"Steve","properties":[]}"#
+                    }
+                    _ => "",
+                };
+
+                let resp = format!(
+                    "HTTP/1.1 {status} Status\r\nContent-Length: {}\r\nConnection: \
+                     close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(resp.as_bytes()).await.unwrap();
+            }
+        });
+
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn cached_profiles_are_verified() {
+        let (url, requests) = session_server(vec![200, 204, 500, 500]).await;
+
+        let auth = MojangAuthenticator::with_session_server(url, false)
+            .with_profile_cache(8, Duration::from_secs(60))
+            .fail_open(true);
+
+        let steve = Username::new("Steve").unwrap();
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let other_ip = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+
+        assert!(auth.verify(steve, &[], "hash", ip).await.is_ok());
+
+        // The cached profile does not stand in for the session server.
+        assert!(auth.verify(steve, &[], "hash", ip).await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        // It is only used when the session server fails, and only for the same IP.
+        let profile = auth.verify(steve, &[], "hash", ip).await.unwrap();
+        assert_eq!(profile.username.as_str(), "Steve");
+        assert!(auth.verify(steve, &[], "hash", other_ip).await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn verify_token_from_seeded_rng() {
+        let auth = MojangAuthenticator::new(false);
+
+        let token = auth.verify_token(&mut StdRng::seed_from_u64(42));
+
+        assert_eq!(token.len(), 16);
+        assert_eq!(token, auth.verify_token(&mut StdRng::seed_from_u64(42)));
+        assert_ne!(token, auth.verify_token(&mut StdRng::seed_from_u64(43)));
+    }
+}
@@ -2,7 +2,7 @@
 
 use std::collections::HashSet;
 
-use anyhow::ensure;
+use anyhow::{ensure, Context};
 use tracing::warn;
 use valence_nbt::{compound, Compound};
 use valence_protocol::ident;
@@ -21,7 +21,11 @@ pub struct BiomeId(pub(crate) u16);
 /// Contains the configuration for a biome.
 ///
 /// Biomes are registered once at startup through
-/// [`biomes`](crate::config::Config::biomes).
+/// [`biomes`](crate::config::Config::biomes). Use the [`Default`]
+/// implementation to fill in any effects you don't care about.
+///
+/// Colors are RGB values in the form `0xRRGGBB`. The server will fail to start
+/// if a biome contains an invalid color or an out of range effect value.
 #[derive(Clone, Debug)]
 pub struct Biome {
     /// The unique name for this biome. The name can be
@@ -52,7 +56,7 @@ pub struct Biome {
 
 impl Biome {
     pub(crate) fn to_biome_registry_item(&self, id: i32) -> Compound {
-        let mut reg = compound! {
+        compound! {
             "name" => self.name.clone(),
             "id" => id,
             "element" => compound! {
@@ -118,24 +122,19 @@ impl Biome {
                         });
                     }
 
+                    if let Some(p) = &self.particle {
+                        eff.insert("particle", compound! {
+                            "probability" => p.probability,
+                            "options" => compound! {
+                                "type" => p.kind.clone(),
+                            }
+                        });
+                    }
+
                     eff
                 },
             }
-        };
-
-        if let Some(p) = &self.particle {
-            reg.insert(
-                "particle",
-                compound! {
-                    "probability" => p.probability,
-                    "options" => compound! {
-                        "type" => p.kind.clone(),
-                    }
-                },
-            );
         }
-
-        reg
     }
 }
 
@@ -155,6 +154,8 @@ pub(crate) fn validate_biomes(biomes: &[Biome]) -> anyhow::Result<()> {
             "biome \"{}\" already exists",
             biome.name
         );
+
+        validate_biome(biome).with_context(|| format!("invalid biome \"{}\"", biome.name))?;
     }
 
     if !names.contains(&ident!("plains")) {
@@ -167,6 +168,56 @@ pub(crate) fn validate_biomes(biomes: &[Biome]) -> anyhow::Result<()> {
     Ok(())
 }
 
+fn validate_biome(biome: &Biome) -> anyhow::Result<()> {
+    let colors = [
+        ("sky_color", Some(biome.sky_color)),
+        ("water_fog_color", Some(biome.water_fog_color)),
+        ("fog_color", Some(biome.fog_color)),
+        ("water_color", Some(biome.water_color)),
+        ("foliage_color", biome.foliage_color),
+        ("grass_color", biome.grass_color),
+    ];
+
+    for (field, color) in colors {
+        if let Some(color) = color {
+            ensure!(
+                color <= 0xffffff,
+                "{field} is not a valid RGB color (got {color:#x})"
+            );
+        }
+    }
+
+    if let Some(music) = &biome.music {
+        ensure!(
+            0 <= music.min_delay && music.min_delay <= music.max_delay,
+            "music delays must satisfy 0 <= min_delay <= max_delay"
+        );
+    }
+
+    if let Some(sound) = &biome.additions_sound {
+        ensure!(
+            (0.0..=1.0).contains(&sound.tick_chance),
+            "additions_sound tick_chance is out of range"
+        );
+    }
+
+    if let Some(sound) = &biome.mood_sound {
+        ensure!(
+            sound.tick_delay >= 0 && sound.block_search_extent >= 0,
+            "mood_sound tick_delay and block_search_extent must be nonnegative"
+        );
+    }
+
+    if let Some(particle) = &biome.particle {
+        ensure!(
+            (0.0..=1.0).contains(&particle.probability),
+            "particle probability is out of range"
+        );
+    }
+
+    Ok(())
+}
+
 impl Default for Biome {
     fn default() -> Self {
         Self {
@@ -233,3 +284,70 @@ pub struct BiomeParticle {
     pub probability: f32,
     pub kind: Ident<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use valence_nbt::Value;
+
+    use super::*;
+
+    #[test]
+    fn biome_effects_are_encoded() {
+        let biome = Biome {
+            name: ident!("custom"),
+            ambient_sound: Some(ident!("ambient.cave")),
+            particle: Some(BiomeParticle {
+                probability: 0.5,
+                kind: ident!("ash"),
+            }),
+            ..Default::default()
+        };
+
+        let item = biome.to_biome_registry_item(3);
+
+        assert_eq!(item.get("id"), Some(&Value::Int(3)));
+
+        let Some(Value::Compound(element)) = item.get("element") else {
+            panic!("missing biome element");
+        };
+
+        let Some(Value::Compound(effects)) = element.get("effects") else {
+            panic!("missing biome effects");
+        };
+
+        assert_eq!(effects.get("sky_color"), Some(&Value::Int(7907327)));
+        assert!(effects.contains_key("ambient_sound"));
+
+        let Some(Value::Compound(particle)) = effects.get("particle") else {
+            panic!("missing biome particle");
+        };
+
+        assert_eq!(particle.get("probability"), Some(&Value::Float(0.5)));
+        assert!(item.get("particle").is_none());
+    }
+
+    #[test]
+    fn invalid_biomes_are_rejected() {
+        assert!(validate_biomes(&[Biome::default()]).is_ok());
+
+        let bad_color = Biome {
+            fog_color: 0x1000000,
+            ..Default::default()
+        };
+
+        let err = validate_biomes(&[bad_color]).unwrap_err();
+        assert!(format!("{err:#}").contains("plains"));
+
+        let bad_music = Biome {
+            music: Some(BiomeMusic {
+                replace_current_music: false,
+                sound: ident!("music.game"),
+                min_delay: 100,
+                max_delay: 10,
+            }),
+            ..Default::default()
+        };
+
+        assert!(validate_biomes(&[bad_music]).is_err());
+    }
+}
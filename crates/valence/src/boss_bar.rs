@@ -0,0 +1,384 @@
+//! Boss bars displayed at the top of the screen.
+
+use std::collections::HashSet;
+use std::iter::FusedIterator;
+use std::ops::{Index, IndexMut};
+
+use uuid::Uuid;
+use valence_protocol::packets::s2c::play::BossBar as BossBarS2c;
+use valence_protocol::types::{BossBarAction, BossBarColor, BossBarDivision, BossBarFlags};
+use valence_protocol::Text;
+
+use crate::client::{ClientId, Clients};
+use crate::config::Config;
+use crate::packet::WritePacket;
+use crate::slab_versioned::{Key, VersionedSlab};
+
+/// A container for all [`BossBar`]s on a server.
+pub struct BossBars {
+    slab: VersionedSlab<BossBar>,
+}
+
+impl BossBars {
+    pub(crate) fn new() -> Self {
+        Self {
+            slab: VersionedSlab::new(),
+        }
+    }
+
+    /// Creates a new boss bar and returns an exclusive reference to it along
+    /// with its ID.
+    ///
+    /// The boss bar has no viewers initially. Use [`BossBar::add_viewer`] to
+    /// show it to clients.
+    pub fn insert(
+        &mut self,
+        title: impl Into<Text>,
+        color: BossBarColor,
+        division: BossBarDivision,
+    ) -> (BossBarId, &mut BossBar) {
+        let (key, bar) = self.slab.insert(BossBar {
+            title: title.into(),
+            progress: 1.0,
+            color,
+            division,
+            flags: BossBarFlags::new(),
+            viewers: HashSet::new(),
+            modified_title: false,
+            modified_progress: false,
+            modified_style: false,
+            modified_flags: false,
+        });
+
+        (BossBarId(key), bar)
+    }
+
+    /// Removes a boss bar from the server. The boss bar is hidden from all of
+    /// its viewers at the end of the tick.
+    ///
+    /// If the given boss bar ID is valid, `true` is returned and the boss bar
+    /// is deleted. Otherwise, `false` is returned and the function has no
+    /// effect.
+    pub fn remove(&mut self, id: BossBarId) -> bool {
+        self.slab.remove(id.0).is_some()
+    }
+
+    /// Returns the number of boss bars on the server.
+    pub fn len(&self) -> usize {
+        self.slab.len()
+    }
+
+    /// Returns `true` if there are no boss bars on the server.
+    pub fn is_empty(&self) -> bool {
+        self.slab.len() == 0
+    }
+
+    /// Gets a shared reference to the boss bar with the given ID. If the ID is
+    /// invalid, then `None` is returned.
+    pub fn get(&self, id: BossBarId) -> Option<&BossBar> {
+        self.slab.get(id.0)
+    }
+
+    /// Gets an exclusive reference to the boss bar with the given ID. If the
+    /// ID is invalid, then `None` is returned.
+    pub fn get_mut(&mut self, id: BossBarId) -> Option<&mut BossBar> {
+        self.slab.get_mut(id.0)
+    }
+
+    /// Returns an iterator over all boss bars on the server in an unspecified
+    /// order.
+    pub fn iter(
+        &self,
+    ) -> impl ExactSizeIterator<Item = (BossBarId, &BossBar)> + FusedIterator + Clone + '_ {
+        self.slab.iter().map(|(k, bar)| (BossBarId(k), bar))
+    }
+
+    /// Returns a mutable iterator over all boss bars on the server in an
+    /// unspecified order.
+    pub fn iter_mut(
+        &mut self,
+    ) -> impl ExactSizeIterator<Item = (BossBarId, &mut BossBar)> + FusedIterator + '_ {
+        self.slab.iter_mut().map(|(k, bar)| (BossBarId(k), bar))
+    }
+
+    pub(crate) fn update<C: Config>(&mut self, clients: &Clients<C>) {
+        for (_, bar) in self.iter_mut() {
+            bar.viewers.retain(|&id| clients.get(id).is_some());
+            bar.modified_title = false;
+            bar.modified_progress = false;
+            bar.modified_style = false;
+            bar.modified_flags = false;
+        }
+    }
+}
+
+impl Index<BossBarId> for BossBars {
+    type Output = BossBar;
+
+    fn index(&self, index: BossBarId) -> &Self::Output {
+        self.get(index).expect("invalid boss bar ID")
+    }
+}
+
+impl IndexMut<BossBarId> for BossBars {
+    fn index_mut(&mut self, index: BossBarId) -> &mut Self::Output {
+        self.get_mut(index).expect("invalid boss bar ID")
+    }
+}
+
+/// An identifier for a [`BossBar`] on the server.
+///
+/// Boss bar IDs are either _valid_ or _invalid_. Valid boss bar IDs point to
+/// boss bars that have not been deleted, while invalid IDs point to those that
+/// have. Once an ID becomes invalid, it will never become valid again.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+pub struct BossBarId(Key);
+
+impl BossBarId {
+    /// The value of the default boss bar ID which is always invalid.
+    pub const NULL: Self = Self(Key::NULL);
+
+    /// The UUID identifying this boss bar to clients.
+    pub(crate) fn uuid(self) -> Uuid {
+        Uuid::from_u64_pair(self.0.index() as u64, self.0.version().get() as u64)
+    }
+}
+
+/// A boss bar shown to a set of viewing clients.
+pub struct BossBar {
+    title: Text,
+    progress: f32,
+    color: BossBarColor,
+    division: BossBarDivision,
+    flags: BossBarFlags,
+    viewers: HashSet<ClientId>,
+    modified_title: bool,
+    modified_progress: bool,
+    modified_style: bool,
+    modified_flags: bool,
+}
+
+impl BossBar {
+    /// Gets the title of this boss bar.
+    pub fn title(&self) -> &Text {
+        &self.title
+    }
+
+    /// Sets the title of this boss bar.
+    pub fn set_title(&mut self, title: impl Into<Text>) {
+        let title = title.into();
+        if self.title != title {
+            self.title = title;
+            self.modified_title = true;
+        }
+    }
+
+    /// Gets the progress of this boss bar in the range `0.0..=1.0`.
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+
+    /// Sets the progress of this boss bar. The value is clamped to
+    /// `0.0..=1.0`.
+    pub fn set_progress(&mut self, progress: f32) {
+        let progress = if progress.is_nan() {
+            0.0
+        } else {
+            progress.clamp(0.0, 1.0)
+        };
+
+        if self.progress != progress {
+            self.progress = progress;
+            self.modified_progress = true;
+        }
+    }
+
+    /// Gets the color of this boss bar.
+    pub fn color(&self) -> BossBarColor {
+        self.color
+    }
+
+    /// Sets the color of this boss bar.
+    pub fn set_color(&mut self, color: BossBarColor) {
+        if self.color != color {
+            self.color = color;
+            self.modified_style = true;
+        }
+    }
+
+    /// Gets the number of divisions (notches) of this boss bar.
+    pub fn division(&self) -> BossBarDivision {
+        self.division
+    }
+
+    /// Sets the number of divisions (notches) of this boss bar.
+    pub fn set_division(&mut self, division: BossBarDivision) {
+        if self.division != division {
+            self.division = division;
+            self.modified_style = true;
+        }
+    }
+
+    /// Gets the flags of this boss bar.
+    pub fn flags(&self) -> BossBarFlags {
+        self.flags
+    }
+
+    /// Sets the flags of this boss bar.
+    pub fn set_flags(&mut self, flags: BossBarFlags) {
+        if self.flags != flags {
+            self.flags = flags;
+            self.modified_flags = true;
+        }
+    }
+
+    /// Returns an iterator over the clients viewing this boss bar in an
+    /// unspecified order.
+    pub fn viewers(&self) -> impl ExactSizeIterator<Item = ClientId> + FusedIterator + '_ {
+        self.viewers.iter().cloned()
+    }
+
+    /// Returns `true` if the given client is viewing this boss bar.
+    pub fn is_viewer(&self, client: ClientId) -> bool {
+        self.viewers.contains(&client)
+    }
+
+    /// Shows this boss bar to the given client. Returns `false` if the client
+    /// was already a viewer.
+    pub fn add_viewer(&mut self, client: ClientId) -> bool {
+        self.viewers.insert(client)
+    }
+
+    /// Hides this boss bar from the given client. Returns `false` if the client
+    /// was not a viewer.
+    pub fn remove_viewer(&mut self, client: ClientId) -> bool {
+        self.viewers.remove(&client)
+    }
+
+    /// Writes the packet needed to show this boss bar to a client.
+    pub(crate) fn write_add_packet(
+        &self,
+        id: BossBarId,
+        mut writer: impl WritePacket,
+    ) -> anyhow::Result<()> {
+        writer.write_packet(&BossBarS2c {
+            id: id.uuid(),
+            action: BossBarAction::Add {
+                title: self.title.clone(),
+                health: self.progress,
+                color: self.color,
+                division: self.division,
+                flags: self.flags,
+            },
+        })
+    }
+
+    /// Writes the packets needed to update this boss bar from the previous
+    /// state to the current state for a client already viewing it.
+    pub(crate) fn write_update_packets(
+        &self,
+        id: BossBarId,
+        mut writer: impl WritePacket,
+    ) -> anyhow::Result<()> {
+        let uuid = id.uuid();
+
+        if self.modified_title {
+            writer.write_packet(&BossBarS2c {
+                id: uuid,
+                action: BossBarAction::UpdateTitle(self.title.clone()),
+            })?;
+        }
+
+        if self.modified_progress {
+            writer.write_packet(&BossBarS2c {
+                id: uuid,
+                action: BossBarAction::UpdateHealth(self.progress),
+            })?;
+        }
+
+        if self.modified_style {
+            writer.write_packet(&BossBarS2c {
+                id: uuid,
+                action: BossBarAction::UpdateStyle(self.color, self.division),
+            })?;
+        }
+
+        if self.modified_flags {
+            writer.write_packet(&BossBarS2c {
+                id: uuid,
+                action: BossBarAction::UpdateFlags(self.flags),
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes the packet needed to hide a boss bar from a client.
+pub(crate) fn write_remove_packet(
+    id: BossBarId,
+    mut writer: impl WritePacket,
+) -> anyhow::Result<()> {
+    writer.write_packet(&BossBarS2c {
+        id: id.uuid(),
+        action: BossBarAction::Remove,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::PacketDecoder;
+
+    use super::*;
+    use crate::config::MockConfig;
+    use crate::packet::PacketWriter;
+
+    fn decode_all(bytes: &[u8]) -> Vec<BossBarAction> {
+        let mut dec = PacketDecoder::new();
+        dec.queue_slice(bytes);
+
+        let mut actions = vec![];
+        while let Some(pkt) = dec.try_next_packet::<S2cPlayPacket>().unwrap() {
+            match pkt {
+                S2cPlayPacket::BossBar(p) => actions.push(p.action),
+                other => panic!("unexpected packet {other:?}"),
+            }
+        }
+        actions
+    }
+
+    #[test]
+    fn progress_change_sends_single_update() {
+        let mut bars = BossBars::new();
+        let (id, bar) = bars.insert("Boss", BossBarColor::Red, BossBarDivision::NoDivision);
+
+        let mut buf = vec![];
+        let mut scratch = vec![];
+
+        bar.write_add_packet(id, PacketWriter::new(&mut buf, None, &mut scratch))
+            .unwrap();
+        assert!(matches!(
+            decode_all(&buf)[..],
+            [BossBarAction::Add { health, .. }] if health == 1.0
+        ));
+
+        bars.update(&Clients::<MockConfig>::new());
+        bars[id].set_progress(0.25);
+
+        buf.clear();
+        bars[id]
+            .write_update_packets(id, PacketWriter::new(&mut buf, None, &mut scratch))
+            .unwrap();
+        assert_eq!(decode_all(&buf), [BossBarAction::UpdateHealth(0.25)]);
+
+        // Nothing is sent once the modification has been flushed.
+        bars.update(&Clients::<MockConfig>::new());
+
+        buf.clear();
+        bars[id]
+            .write_update_packets(id, PacketWriter::new(&mut buf, None, &mut scratch))
+            .unwrap();
+        assert!(buf.is_empty());
+    }
+}
@@ -6,30 +6,39 @@
 //! In addition to blocks, chunks also contain [biomes](crate::biome::Biome).
 //! Every 4x4x4 segment of blocks in a chunk corresponds to a biome.
 
+use std::array;
+use std::borrow::Borrow;
 use std::collections::hash_map::Entry;
 use std::io::Write;
 use std::iter::FusedIterator;
 use std::mem;
 use std::ops::{Deref, DerefMut, Index, IndexMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Mutex, MutexGuard};
 
 use entity_partition::PartitionCell;
+use light::{compute_light, ChunkLight, LightScratch};
 use paletted_container::PalettedContainer;
 pub use pos::ChunkPos;
-use rayon::iter::{IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator};
-use rustc_hash::FxHashMap;
+use rayon::iter::{
+    IntoParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator,
+};
+use rustc_hash::{FxHashMap, FxHashSet};
+use tracing::info;
 use valence_nbt::compound;
 use valence_protocol::packets::s2c::play::{
     BlockUpdate, ChunkDataAndUpdateLightEncode, UpdateSectionBlocksEncode,
 };
-use valence_protocol::{BlockPos, BlockState, Encode, LengthPrefixedArray, VarInt, VarLong};
+use valence_protocol::{BlockPos, BlockState, Encode, VarInt, VarLong};
 
 use crate::biome::BiomeId;
 use crate::config::Config;
 use crate::packet::{PacketWriter, WritePacket};
 use crate::util::bit_width;
+use crate::Ticks;
 
 pub(crate) mod entity_partition;
+mod light;
 mod paletted_container;
 mod pos;
 
@@ -41,12 +50,12 @@ pub struct Chunks<C: Config> {
     chunks: FxHashMap<ChunkPos, (Option<LoadedChunk<C>>, PartitionCell)>,
     dimension_height: i32,
     dimension_min_y: i32,
-    filler_sky_light_mask: Box<[u64]>,
-    /// Sending filler light data causes the vanilla client to lag
-    /// less. Hopefully we can remove this in the future.
-    filler_sky_light_arrays: Box<[LengthPrefixedArray<u8, 2048>]>,
     biome_registry_len: usize,
     compression_threshold: Option<u32>,
+    /// Set with [`Self::set_auto_relight`].
+    auto_relight: bool,
+    /// The number of loaded chunks in `chunks`.
+    loaded_count: usize,
 }
 
 impl<C: Config> Chunks<C> {
@@ -56,25 +65,35 @@ impl<C: Config> Chunks<C> {
         biome_registry_len: usize,
         compression_threshold: Option<u32>,
     ) -> Self {
-        let section_count = (dimension_height / 16 + 2) as usize;
-
-        let mut sky_light_mask = vec![0; num::Integer::div_ceil(&section_count, &16)];
-
-        for i in 0..section_count {
-            sky_light_mask[i / 64] |= 1 << (i % 64);
-        }
-
         Self {
             chunks: FxHashMap::default(),
             dimension_height,
             dimension_min_y,
-            filler_sky_light_mask: sky_light_mask.into(),
-            filler_sky_light_arrays: vec![LengthPrefixedArray([0xff; 2048]); section_count].into(),
             biome_registry_len,
             compression_threshold,
+            auto_relight: false,
+            loaded_count: 0,
         }
     }
 
+    /// Returns whether the light of chunks is recomputed when blocks in or
+    /// around them are modified. See [`Self::set_auto_relight`].
+    pub fn auto_relight(&self) -> bool {
+        self.auto_relight
+    }
+
+    /// Sets whether the light of chunks is recomputed when blocks in or
+    /// around them are modified. The sections whose light changed are then
+    /// sent to clients.
+    ///
+    /// This is disabled by default. Clients update light on their own when
+    /// blocks change, and recomputing the light of every chunk around a
+    /// modified block is expensive when many chunks are modified each tick.
+    /// Individual chunks can be relit with [`LoadedChunk::relight`] instead.
+    pub fn set_auto_relight(&mut self, enabled: bool) {
+        self.auto_relight = enabled;
+    }
+
     /// Consumes an [`UnloadedChunk`] and creates a [`LoadedChunk`] at a given
     /// position. An exclusive reference to the new chunk is returned.
     ///
@@ -98,17 +117,82 @@ impl<C: Config> Chunks<C> {
 
         match self.chunks.entry(pos.into()) {
             Entry::Occupied(mut oe) => {
+                if oe.get().0.is_none() {
+                    self.loaded_count += 1;
+                }
+
                 oe.get_mut().0 = Some(loaded);
                 oe.into_mut().0.as_mut().unwrap()
             }
-            Entry::Vacant(ve) => ve
-                .insert((Some(loaded), PartitionCell::new()))
-                .0
-                .as_mut()
-                .unwrap(),
+            Entry::Vacant(ve) => {
+                self.loaded_count += 1;
+
+                ve.insert((Some(loaded), PartitionCell::new()))
+                    .0
+                    .as_mut()
+                    .unwrap()
+            }
         }
     }
 
+    /// Generates and inserts every chunk within `radius` chunks of `center`
+    /// which is not already loaded. Returns the number of chunks generated.
+    ///
+    /// `generate` is called for each missing chunk in parallel on the rayon
+    /// thread pool. This is intended to be called from [`Config::init`] or
+    /// [`Config::post_start`] so that the chunks around the spawn point are
+    /// ready by the time the first client joins. Progress is logged at the
+    /// `info` level.
+    ///
+    /// Like all inserted chunks, preloaded chunks are sent to clients in full
+    /// when they come into view and never generate block change packets on
+    /// the tick they are created.
+    pub fn preload_region<F>(
+        &mut self,
+        center: impl Into<ChunkPos>,
+        radius: u16,
+        generate: F,
+    ) -> usize
+    where
+        F: Fn(ChunkPos) -> (UnloadedChunk, C::ChunkState) + Sync,
+    {
+        let center = center.into();
+        let r = radius as i32;
+
+        let missing: Vec<_> = (center.x - r..=center.x + r)
+            .flat_map(|x| (center.z - r..=center.z + r).map(move |z| ChunkPos::new(x, z)))
+            .filter(|&pos| {
+                let dx = pos.x - center.x;
+                let dz = pos.z - center.z;
+                dx * dx + dz * dz <= r * r && self.get(pos).is_none()
+            })
+            .collect();
+
+        let total = missing.len();
+        let step = (total / 10).max(1);
+        let done = AtomicUsize::new(0);
+
+        let generated: Vec<_> = missing
+            .into_par_iter()
+            .map(|pos| {
+                let res = generate(pos);
+
+                let n = done.fetch_add(1, Ordering::Relaxed) + 1;
+                if n.is_multiple_of(step) {
+                    info!(done = n, total, "preloading chunks");
+                }
+
+                (pos, res)
+            })
+            .collect();
+
+        for (pos, (chunk, state)) in generated {
+            self.insert(pos, chunk, state);
+        }
+
+        total
+    }
+
     /// Returns the height of all loaded chunks in the world. This returns the
     /// same value as [`Chunk::section_count`] multiplied by 16 for all loaded
     /// chunks.
@@ -148,6 +232,17 @@ impl<C: Config> Chunks<C> {
         self.chunks.get_mut(&pos.into())?.0.as_mut()
     }
 
+    /// Returns the number of loaded chunks in the world, including chunks
+    /// which are deleted at the end of the tick.
+    pub fn len(&self) -> usize {
+        self.loaded_count
+    }
+
+    /// Returns `true` if there are no loaded chunks in the world.
+    pub fn is_empty(&self) -> bool {
+        self.loaded_count == 0
+    }
+
     /// Returns an iterator over all chunks in the world in an unspecified
     /// order.
     pub fn iter(&self) -> impl FusedIterator<Item = (ChunkPos, &LoadedChunk<C>)> + Clone + '_ {
@@ -238,41 +333,144 @@ impl<C: Config> Chunks<C> {
             return BlockState::AIR;
         }
 
-        let chunk = match self.chunks.entry(ChunkPos::from(pos)) {
-            Entry::Occupied(oe) => oe.into_mut().0.get_or_insert_with(|| {
-                let dimension_section_count = (self.dimension_height / 16) as usize;
-                LoadedChunk::new(
-                    UnloadedChunk::default(),
-                    dimension_section_count,
-                    Default::default(),
-                )
-            }),
+        let chunk = self.get_or_insert_empty(ChunkPos::from(pos));
+
+        chunk.set_block_state(
+            pos.x.rem_euclid(16) as usize,
+            y,
+            pos.z.rem_euclid(16) as usize,
+            block,
+        )
+    }
+
+    /// Sets every block in the box between the corners `min` and `max`
+    /// (inclusive) to `block`. The box is clamped to the height of the world.
+    ///
+    /// Like [`Self::set_block_state`], chunks are created where the box
+    /// intersects positions that are not inside of a loaded chunk.
+    ///
+    /// This is much faster than setting the blocks individually when the box
+    /// covers entire chunk sections. At the end of the tick, clients are sent
+    /// either the changed blocks or the entire chunk for each modified chunk,
+    /// whichever is smaller.
+    pub fn fill_block_states(
+        &mut self,
+        min: impl Into<BlockPos>,
+        max: impl Into<BlockPos>,
+        block: BlockState,
+    ) where
+        C::ChunkState: Default,
+    {
+        let (a, b) = (min.into(), max.into());
+        let min = BlockPos::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+        let max = BlockPos::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+
+        // Convert to chunk space and clamp to the world height.
+        let min_y = (min.y as i64 - self.dimension_min_y as i64).max(0);
+        let max_y =
+            (max.y as i64 - self.dimension_min_y as i64).min(self.dimension_height as i64 - 1);
+
+        if min_y > max_y {
+            return;
+        }
+
+        let (min_y, max_y) = (min_y as usize, max_y as usize);
+
+        for chunk_z in min.z.div_euclid(16)..=max.z.div_euclid(16) {
+            for chunk_x in min.x.div_euclid(16)..=max.x.div_euclid(16) {
+                let min_x = (min.x - chunk_x * 16).max(0) as usize;
+                let max_x = (max.x - chunk_x * 16).min(15) as usize;
+                let min_z = (min.z - chunk_z * 16).max(0) as usize;
+                let max_z = (max.z - chunk_z * 16).min(15) as usize;
+
+                let covers_columns = min_x == 0 && max_x == 15 && min_z == 0 && max_z == 15;
+
+                let chunk = self.get_or_insert_empty(ChunkPos::new(chunk_x, chunk_z));
+
+                let mut y = min_y;
+
+                while y <= max_y {
+                    if covers_columns && y % 16 == 0 && y + 15 <= max_y {
+                        chunk.fill_block_states(y / 16, block);
+                        y += 16;
+                        continue;
+                    }
+
+                    for z in min_z..=max_z {
+                        for x in min_x..=max_x {
+                            chunk.set_block_state(x, y, z, block);
+                        }
+                    }
+
+                    y += 1;
+                }
+            }
+        }
+    }
+
+    /// Gets the chunk at the given position, creating an empty chunk if there
+    /// is none.
+    fn get_or_insert_empty(&mut self, pos: ChunkPos) -> &mut LoadedChunk<C>
+    where
+        C::ChunkState: Default,
+    {
+        let dimension_section_count = (self.dimension_height / 16) as usize;
+        let new_chunk = || {
+            LoadedChunk::new(
+                UnloadedChunk::default(),
+                dimension_section_count,
+                Default::default(),
+            )
+        };
+
+        match self.chunks.entry(pos) {
+            Entry::Occupied(oe) => {
+                let chunk = &mut oe.into_mut().0;
+
+                if chunk.is_none() {
+                    self.loaded_count += 1;
+                }
+
+                chunk.get_or_insert_with(new_chunk)
+            }
             Entry::Vacant(ve) => {
-                let dimension_section_count = (self.dimension_height / 16) as usize;
-                let loaded = LoadedChunk::new(
-                    UnloadedChunk::default(),
-                    dimension_section_count,
-                    Default::default(),
-                );
+                self.loaded_count += 1;
 
-                ve.insert((Some(loaded), PartitionCell::new()))
+                ve.insert((Some(new_chunk()), PartitionCell::new()))
                     .0
                     .as_mut()
                     .unwrap()
             }
-        };
+        }
+    }
 
-        chunk.set_block_state(
-            pos.x.rem_euclid(16) as usize,
-            y,
-            pos.z.rem_euclid(16) as usize,
-            block,
+    /// Computes the light of the chunk at `pos` from the chunks around it.
+    fn compute_light_at(&self, pos: ChunkPos, scratch: &mut LightScratch) -> ChunkLight {
+        compute_light(
+            area_around(pos).map(|pos| {
+                self.get(pos)
+                    .filter(|chunk| !chunk.deleted)
+                    .map(|chunk| &*chunk.sections)
+            }),
+            scratch,
         )
     }
 
     pub(crate) fn update_caches(&mut self) {
         let min_y = self.dimension_min_y;
 
+        // Chunks whose blocks changed this tick. The light of every chunk around
+        // them may have changed as a result.
+        let changed: FxHashSet<_> = self
+            .chunks
+            .iter()
+            .filter_map(|(&pos, (chunk, _))| {
+                let chunk = chunk.as_ref()?;
+                (chunk.created_this_tick || chunk.deleted || chunk.any_blocks_modified())
+                    .then_some(pos)
+            })
+            .collect();
+
         self.chunks.par_iter_mut().for_each(|(&pos, (chunk, _))| {
             let Some(chunk) = chunk else {
                 // There is no chunk at this position.
@@ -380,6 +578,103 @@ impl<C: Config> Chunks<C> {
                 chunk.any_biomes_modified = false;
                 chunk.cached_init_packet.get_mut().unwrap().clear();
             }
+        });
+
+        let lights: FxHashMap<_, _> = self
+            .chunks
+            .par_iter()
+            .filter_map(|(&pos, (chunk, _))| {
+                let chunk = chunk.as_ref()?;
+
+                // Chunks which may be resent need their light for the chunk data packet.
+                let needs_light = !chunk.deleted
+                    && (chunk.created_this_tick
+                        || chunk.relight
+                        || chunk.cached_update_packets.len() > MIN_CHUNK_RESEND_LEN
+                        || self.auto_relight
+                            && area_around(pos).iter().any(|p| changed.contains(p)));
+
+                needs_light.then_some(pos)
+            })
+            .map_init(LightScratch::default, |scratch, pos| {
+                (pos, self.compute_light_at(pos, scratch))
+            })
+            .collect();
+
+        self.chunks.par_iter_mut().for_each(|(&pos, (chunk, _))| {
+            let Some(chunk) = chunk else {
+                return;
+            };
+
+            if chunk.deleted {
+                return;
+            }
+
+            let mut compression_scratch = vec![];
+
+            let light = lights.get(&pos);
+            let light_hashes = light.map(|light| light.section_hashes());
+            let relight = mem::take(&mut chunk.relight);
+
+            // Find the sections whose light has to be sent again. Only the
+            // sections which changed are sent, unless a relight was requested.
+            let light_update = match (light, &light_hashes) {
+                (Some(light), Some(hashes))
+                    if !chunk.created_this_tick && (relight || self.auto_relight) =>
+                {
+                    let old_hashes = &chunk.light_hashes;
+
+                    light.update_packet(pos, |i| relight || old_hashes.get(i) != Some(&hashes[i]))
+                }
+                _ => None,
+            };
+
+            if light_update.is_some() {
+                // The cached chunk data packet contains the old light.
+                chunk.cached_init_packet.get_mut().unwrap().clear();
+            }
+
+            let mut resent = false;
+
+            // When a large part of the chunk was modified, resending the entire chunk may
+            // be cheaper than sending the individual block changes. The light of the chunk
+            // is part of the chunk data packet, so it is not compared.
+            if chunk.cached_update_packets.len() > MIN_CHUNK_RESEND_LEN {
+                let init = {
+                    let init = chunk.get_chunk_data_packet(
+                        &mut compression_scratch,
+                        pos,
+                        self.biome_registry_len,
+                        self.compression_threshold,
+                        || light.expect("modified chunks are always relit"),
+                    );
+
+                    (init.len() < chunk.cached_update_packets.len()).then(|| init.clone())
+                };
+
+                if let Some(init) = init {
+                    chunk.cached_update_packets = init;
+                    resent = true;
+                }
+            }
+
+            if let (Some(pkt), false) = (light_update, resent) {
+                let mut writer = PacketWriter::new(
+                    &mut chunk.cached_update_packets,
+                    self.compression_threshold,
+                    &mut compression_scratch,
+                );
+
+                writer.write_packet(&pkt).unwrap();
+            }
+
+            match light_hashes {
+                Some(hashes) => chunk.light_hashes = hashes,
+                // Without automatic relighting, the light may have changed since the
+                // hashes were computed.
+                None if !self.auto_relight => chunk.light_hashes.clear(),
+                None => {}
+            }
 
             // Initialize the chunk data cache on new chunks here so this work can be done
             // in parallel.
@@ -390,9 +685,8 @@ impl<C: Config> Chunks<C> {
                     &mut compression_scratch,
                     pos,
                     self.biome_registry_len,
-                    &self.filler_sky_light_mask,
-                    &self.filler_sky_light_arrays,
                     self.compression_threshold,
+                    || light.expect("new chunks are always lit"),
                 );
             }
         });
@@ -401,10 +695,13 @@ impl<C: Config> Chunks<C> {
     /// Clears changes to partition cells and removes deleted chunks and
     /// partition cells.
     pub(crate) fn update(&mut self) {
+        let loaded_count = &mut self.loaded_count;
+
         self.chunks.retain(|_, (chunk_opt, cell)| {
             if let Some(chunk) = chunk_opt {
                 if chunk.deleted {
                     *chunk_opt = None;
+                    *loaded_count -= 1;
                 } else {
                     chunk.created_this_tick = false;
                 }
@@ -465,6 +762,42 @@ pub trait Chunk {
     /// must be less than 16 while `y` must be less than `section_count() * 16`.
     fn set_block_state(&mut self, x: usize, y: usize, z: usize, block: BlockState) -> BlockState;
 
+    /// Like [`Self::block_state`], but returns `None` instead of panicking if
+    /// the offsets are out of bounds.
+    ///
+    /// `y = 0` is the bottom of the chunk, which is at the `min_y` of the
+    /// chunk's [`Dimension`] when the chunk is loaded in a world.
+    ///
+    /// [`Dimension`]: crate::dimension::Dimension
+    fn get_block_state(&self, x: usize, y: usize, z: usize) -> Option<BlockState> {
+        if x < 16 && y < self.section_count() * 16 && z < 16 {
+            Some(self.block_state(x, y, z))
+        } else {
+            None
+        }
+    }
+
+    /// Like [`Self::set_block_state`], but returns `None` and leaves the chunk
+    /// unchanged instead of panicking if the offsets are out of bounds.
+    ///
+    /// `y = 0` is the bottom of the chunk, which is at the `min_y` of the
+    /// chunk's [`Dimension`] when the chunk is loaded in a world.
+    ///
+    /// [`Dimension`]: crate::dimension::Dimension
+    fn try_set_block_state(
+        &mut self,
+        x: usize,
+        y: usize,
+        z: usize,
+        block: BlockState,
+    ) -> Option<BlockState> {
+        if x < 16 && y < self.section_count() * 16 && z < 16 {
+            Some(self.set_block_state(x, y, z, block))
+        } else {
+            None
+        }
+    }
+
     /// Sets every block in a section to the given block state.
     ///
     /// This is semantically equivalent to setting every block in the section
@@ -668,8 +1001,21 @@ pub struct LoadedChunk<C: Config> {
     cached_update_packets: Vec<u8>,
     /// If any of the biomes in this chunk were modified this tick.
     any_biomes_modified: bool,
+    /// If the light of every section of this chunk should be sent to clients
+    /// this tick.
+    relight: bool,
+    /// Hashes of the light of each section as it was last sent to clients.
+    /// See [`ChunkLight::section_hashes`].
+    light_hashes: Vec<u64>,
     created_this_tick: bool,
     deleted: bool,
+    /// If this chunk was never marked as saved or its blocks or biomes were
+    /// modified since.
+    unsaved: bool,
+    /// The last tick this chunk was in range of a client. Only tracked while
+    /// the world has a [`ChunkRetention`](crate::world::ChunkRetention)
+    /// policy.
+    pub(crate) last_in_range: Option<Ticks>,
     /// For debugging purposes.
     #[cfg(debug_assertions)]
     uuid: uuid::Uuid,
@@ -714,6 +1060,16 @@ impl Default for ChunkSection {
 }
 
 const SECTION_BLOCK_COUNT: usize = 4096;
+
+/// Returns the positions of the 3x3 area of chunks centered on `pos`, ordered
+/// by Z and then X.
+fn area_around(pos: ChunkPos) -> [ChunkPos; 9] {
+    array::from_fn(|i| ChunkPos::new(pos.x + i as i32 % 3 - 1, pos.z + i as i32 / 3 - 1))
+}
+
+/// The size in bytes the block change packets of a chunk must exceed before
+/// resending the entire chunk is considered instead.
+const MIN_CHUNK_RESEND_LEN: usize = 4096;
 const USIZE_BITS: usize = usize::BITS as _;
 
 impl ChunkSection {
@@ -740,8 +1096,12 @@ impl<C: Config> LoadedChunk<C> {
             cached_init_packet: Mutex::new(vec![]),
             cached_update_packets: vec![],
             any_biomes_modified: false,
+            relight: false,
+            light_hashes: vec![],
             created_this_tick: true,
             deleted: false,
+            unsaved: true,
+            last_in_range: None,
             #[cfg(debug_assertions)]
             uuid: uuid::Uuid::from_u128(rand::random()),
         }
@@ -757,6 +1117,12 @@ impl<C: Config> LoadedChunk<C> {
         unloaded
     }
 
+    fn any_blocks_modified(&self) -> bool {
+        self.sections
+            .iter()
+            .any(|sect| sect.modified_blocks.iter().any(|&bits| bits != 0))
+    }
+
     /// Returns `true` if this chunk was created during the current tick.
     pub fn created_this_tick(&self) -> bool {
         self.created_this_tick
@@ -770,6 +1136,38 @@ impl<C: Config> LoadedChunk<C> {
         self.deleted = deleted;
     }
 
+    /// Returns `true` if this chunk was never [marked as saved] or its blocks
+    /// or biomes were modified since.
+    ///
+    /// Newly inserted chunks are unsaved, including chunks which were generated
+    /// and never modified. Chunks loaded from storage should be marked as saved
+    /// after they are inserted.
+    ///
+    /// [marked as saved]: Self::mark_saved
+    pub fn is_unsaved(&self) -> bool {
+        self.unsaved
+    }
+
+    /// Marks the current contents of this chunk as persisted. Call this after
+    /// writing the chunk to storage.
+    pub fn mark_saved(&mut self) {
+        self.unsaved = false;
+    }
+
+    /// Recomputes the light of this chunk and sends the light of every
+    /// section to all clients which can see the chunk at the end of the tick.
+    ///
+    /// Light is always computed when the chunk is first sent to a client.
+    /// Clients update light on their own when blocks are changed, so unless
+    /// [automatic relighting] is enabled, this is only needed when the light
+    /// computed by the client differs from the server's, e.g. after large
+    /// modifications.
+    ///
+    /// [automatic relighting]: Chunks::set_auto_relight
+    pub fn relight(&mut self) {
+        self.relight = true;
+    }
+
     /// Queues the chunk data packet for this chunk with the given position.
     /// This will initialize the chunk for the client.
     pub(crate) fn write_chunk_data_packet(
@@ -789,24 +1187,23 @@ impl<C: Config> LoadedChunk<C> {
             scratch,
             pos,
             chunks.biome_registry_len,
-            &chunks.filler_sky_light_mask,
-            &chunks.filler_sky_light_arrays,
             chunks.compression_threshold,
+            || chunks.compute_light_at(pos, &mut LightScratch::default()),
         );
 
         writer.write_bytes(&bytes)
     }
 
     /// Gets the bytes of the cached chunk data packet, initializing the cache
-    /// if it is empty.
-    fn get_chunk_data_packet(
+    /// if it is empty. `light` is only called to get the light of the chunk
+    /// when the cache is initialized.
+    fn get_chunk_data_packet<L: Borrow<ChunkLight>>(
         &self,
         scratch: &mut Vec<u8>,
         pos: ChunkPos,
         biome_registry_len: usize,
-        filler_sky_light_mask: &[u64],
-        filler_sky_light_arrays: &[LengthPrefixedArray<u8, 2048>],
         compression_threshold: Option<u32>,
+        light: impl FnOnce() -> L,
     ) -> MutexGuard<Vec<u8>> {
         let mut lck = self.cached_init_packet.lock().unwrap();
 
@@ -837,6 +1234,9 @@ impl<C: Config> LoadedChunk<C> {
                     .unwrap();
             }
 
+            let light = light();
+            let light = light.borrow();
+
             let mut compression_scratch = vec![];
 
             let mut writer =
@@ -852,12 +1252,12 @@ impl<C: Config> LoadedChunk<C> {
                     blocks_and_biomes: scratch,
                     block_entities: &[],
                     trust_edges: true,
-                    sky_light_mask: filler_sky_light_mask,
-                    block_light_mask: &[],
-                    empty_sky_light_mask: &[],
-                    empty_block_light_mask: &[],
-                    sky_light_arrays: filler_sky_light_arrays,
-                    block_light_arrays: &[],
+                    sky_light_mask: &light.sky_light_mask,
+                    block_light_mask: &light.block_light_mask,
+                    empty_sky_light_mask: &light.empty_sky_light_mask,
+                    empty_block_light_mask: &light.empty_block_light_mask,
+                    sky_light_arrays: &light.sky_light_arrays,
+                    block_light_arrays: &light.block_light_arrays,
                 })
                 .unwrap();
         }
@@ -909,6 +1309,7 @@ impl<C: Config> Chunk for LoadedChunk<C> {
             }
 
             sect.mark_block_as_modified(idx);
+            self.unsaved = true;
         }
 
         old_block
@@ -927,11 +1328,13 @@ impl<C: Config> Chunk for LoadedChunk<C> {
         if let PalettedContainer::Single(single) = &sect.block_states {
             if block != *single {
                 sect.mark_all_blocks_as_modified();
+                self.unsaved = true;
             }
         } else {
             for i in 0..SECTION_BLOCK_COUNT {
                 if block != sect.block_states.get(i) {
                     sect.mark_block_as_modified(i);
+                    self.unsaved = true;
                 }
             }
         }
@@ -966,6 +1369,7 @@ impl<C: Config> Chunk for LoadedChunk<C> {
 
         if biome != old_biome {
             self.any_biomes_modified = true;
+            self.unsaved = true;
         }
 
         old_biome
@@ -983,6 +1387,7 @@ impl<C: Config> Chunk for LoadedChunk<C> {
 
         // TODO: this is set to true unconditionally, but it doesn't have to be.
         self.any_biomes_modified = true;
+        self.unsaved = true;
     }
 
     fn optimize(&mut self) {
@@ -1098,4 +1503,194 @@ mod tests {
         check_invariants(&loaded.sections);
         check_invariants(&unloaded.sections);
     }
+
+    #[test]
+    fn fill_block_states_across_chunks() {
+        let mut chunks = Chunks::<MockConfig>::new(384, -64, 1, None);
+
+        chunks.fill_block_states([20, 0, 40], [-3, -70, 5], BlockState::STONE);
+
+        assert_eq!(chunks.iter().count(), 9);
+        assert_eq!(chunks.block_state([-3, -64, 5]), Some(BlockState::STONE));
+        assert_eq!(chunks.block_state([20, 0, 40]), Some(BlockState::STONE));
+        assert_eq!(chunks.block_state([8, -30, 20]), Some(BlockState::STONE));
+        assert_eq!(chunks.block_state([21, 0, 40]), Some(BlockState::AIR));
+        assert_eq!(chunks.block_state([20, 1, 40]), Some(BlockState::AIR));
+        assert_eq!(chunks.block_state([-4, 0, 5]), Some(BlockState::AIR));
+        assert_eq!(chunks.block_state([-17, 0, 5]), None);
+
+        for (_, chunk) in chunks.iter() {
+            check_invariants(&chunk.sections);
+        }
+
+        // Entirely out of bounds.
+        chunks.fill_block_states([100, 400, 100], [110, 500, 110], BlockState::STONE);
+        assert_eq!(chunks.iter().count(), 9);
+    }
+
+    #[test]
+    fn large_changes_resend_chunk() {
+        let mut chunks = Chunks::<MockConfig>::new(384, -64, 1, None);
+
+        chunks.insert([0, 0], UnloadedChunk::default(), ());
+        chunks.update_caches();
+        chunks.update();
+
+        chunks.set_block_state([0, 0, 0], BlockState::STONE);
+        chunks.update_caches();
+
+        let chunk = chunks.get([0, 0]).unwrap();
+        assert!(chunk.cached_update_packets.len() < MIN_CHUNK_RESEND_LEN);
+
+        chunks.fill_block_states([0, -64, 0], [15, 100, 15], BlockState::STONE);
+        chunks.update_caches();
+
+        let chunk = chunks.get([0, 0]).unwrap();
+        assert_eq!(
+            chunk.cached_update_packets,
+            *chunk.cached_init_packet.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn no_relight_by_default() {
+        let mut chunks = Chunks::<MockConfig>::new(64, 0, 1, None);
+
+        chunks.insert([0, 0], UnloadedChunk::default(), ());
+        chunks.insert([1, 0], UnloadedChunk::default(), ());
+        chunks.update_caches();
+        chunks.update();
+
+        chunks.set_block_state([15, 10, 8], BlockState::GLOWSTONE);
+        chunks.update_caches();
+
+        assert!(chunks.get([1, 0]).unwrap().cached_update_packets.is_empty());
+    }
+
+    #[test]
+    fn block_changes_relight_neighbors() {
+        let mut chunks = Chunks::<MockConfig>::new(64, 0, 1, None);
+        chunks.set_auto_relight(true);
+
+        chunks.insert([0, 0], UnloadedChunk::default(), ());
+        chunks.insert([1, 0], UnloadedChunk::default(), ());
+        chunks.insert([5, 0], UnloadedChunk::default(), ());
+        chunks.update_caches();
+        chunks.update();
+
+        chunks.set_block_state([15, 10, 8], BlockState::GLOWSTONE);
+        chunks.update_caches();
+
+        let neighbor = chunks.get([1, 0]).unwrap();
+        assert!(!neighbor.cached_update_packets.is_empty());
+        assert_eq!(
+            neighbor.light_hashes,
+            chunks
+                .compute_light_at(ChunkPos::new(1, 0), &mut LightScratch::default())
+                .section_hashes()
+        );
+
+        let far = chunks.get([5, 0]).unwrap();
+        assert!(far.cached_update_packets.is_empty());
+
+        // Relit chunks resend their light, but unchanged light is not sent again.
+        chunks.update();
+        chunks.get_mut([1, 0]).unwrap().relight();
+        chunks
+            .get_mut([0, 0])
+            .unwrap()
+            .set_block_state(15, 10, 8, BlockState::GLOWSTONE);
+        chunks.update_caches();
+
+        assert!(!chunks.get([1, 0]).unwrap().cached_update_packets.is_empty());
+        assert!(chunks.get([0, 0]).unwrap().cached_update_packets.is_empty());
+    }
+
+    #[test]
+    fn checked_block_access_at_boundaries() {
+        let mut chunk = LoadedChunk::<MockConfig>::new(UnloadedChunk::default(), 24, ());
+        let top = 24 * 16 - 1;
+
+        assert_eq!(
+            chunk.try_set_block_state(15, top, 15, BlockState::STONE),
+            Some(BlockState::AIR)
+        );
+        assert_eq!(chunk.get_block_state(15, top, 15), Some(BlockState::STONE));
+
+        assert_eq!(
+            chunk.try_set_block_state(0, top + 1, 0, BlockState::STONE),
+            None
+        );
+        assert_eq!(chunk.get_block_state(0, top + 1, 0), None);
+        assert_eq!(chunk.try_set_block_state(16, 0, 0, BlockState::STONE), None);
+
+        assert_eq!(
+            chunk.try_set_block_state(0, 0, 0, BlockState::DIRT),
+            Some(BlockState::AIR)
+        );
+        assert_eq!(chunk.get_block_state(0, 0, 0), Some(BlockState::DIRT));
+
+        // Writes on either side of a section boundary only modify their own
+        // section.
+        for sect in &mut chunk.sections {
+            sect.modified_blocks.fill(0);
+        }
+        chunk.try_set_block_state(3, 15, 3, BlockState::GLASS);
+        chunk.try_set_block_state(3, 16, 3, BlockState::GLASS);
+
+        assert!(chunk.sections[0].is_block_modified(3 + 3 * 16 + 15 * 16 * 16));
+        assert!(chunk.sections[1].is_block_modified(3 + 3 * 16));
+        assert!(!chunk.sections[2].is_block_modified(3 + 3 * 16));
+
+        // Setting a block to its current state is not a modification.
+        for sect in &mut chunk.sections {
+            sect.modified_blocks.fill(0);
+        }
+        chunk.try_set_block_state(3, 16, 3, BlockState::GLASS);
+        assert!(!chunk.sections[1].is_block_modified(3 + 3 * 16));
+    }
+
+    #[test]
+    fn preload_region_skips_loaded_chunks() {
+        let mut chunks = Chunks::<MockConfig>::new(384, -64, 1, None);
+
+        let mut existing = UnloadedChunk::new(24);
+        existing.set_block_state(0, 0, 0, BlockState::STONE);
+        chunks.insert([0, 0], existing, ());
+
+        let generated = chunks.preload_region([0, 0], 2, |_| (UnloadedChunk::new(24), ()));
+
+        // 13 chunks are within a radius of 2, one of which was already loaded.
+        assert_eq!(generated, 12);
+        assert_eq!(chunks.iter().count(), 13);
+        assert!(chunks.get([2, 0]).unwrap().created_this_tick());
+        assert!(chunks.get([2, 1]).is_none());
+        assert_eq!(chunks[[0, 0]].block_state(0, 0, 0), BlockState::STONE);
+
+        assert_eq!(chunks.preload_region([0, 0], 2, |_| unreachable!()), 0);
+    }
+
+    #[test]
+    fn unsaved_changes_are_tracked() {
+        let mut chunks = Chunks::<MockConfig>::new(384, -64, 1, None);
+
+        let chunk = chunks.insert([0, 0], UnloadedChunk::new(24), ());
+        assert!(chunk.is_unsaved());
+
+        chunk.mark_saved();
+        assert!(!chunk.is_unsaved());
+
+        // Setting a block to its current state is not a change.
+        chunk.set_block_state(0, 0, 0, BlockState::AIR);
+        assert!(!chunk.is_unsaved());
+
+        chunk.set_block_state(0, 0, 0, BlockState::STONE);
+        assert!(chunk.is_unsaved());
+
+        chunk.mark_saved();
+        assert!(!chunk.is_unsaved());
+
+        chunk.fill_biomes(0, BiomeId::default());
+        assert!(chunk.is_unsaved());
+    }
 }
@@ -0,0 +1,485 @@
+//! Sky and block light computation for chunks.
+//!
+//! Light is propagated through every block that is not opaque, decreasing by
+//! one level per block. Sky light travels straight down from the top of the
+//! world without decreasing until it reaches an opaque block.
+//!
+//! Light spreads across chunk borders. Since it never travels further than 15
+//! blocks, the light of a chunk only depends on the chunks directly around it,
+//! so the light of each chunk is computed from the 3x3 area of chunks centered
+//! on it.
+
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+
+use rustc_hash::FxHasher;
+use valence_protocol::packets::s2c::play::UpdateLight;
+use valence_protocol::{LengthPrefixedArray, VarInt};
+
+use crate::chunk::paletted_container::PalettedContainer;
+use crate::chunk::{ChunkPos, ChunkSection, SECTION_BLOCK_COUNT};
+
+/// The width in blocks of the area light is computed in.
+const AREA_WIDTH: usize = 48;
+
+/// The number of blocks in one horizontal layer of the area.
+const AREA_LAYER: usize = AREA_WIDTH * AREA_WIDTH;
+
+/// The light of a chunk in the format expected by clients. The chunk's
+/// sections are preceded and followed by an extra section each.
+pub(crate) struct ChunkLight {
+    pub sky_light_mask: Vec<u64>,
+    pub sky_light_arrays: Vec<LengthPrefixedArray<u8, 2048>>,
+    pub block_light_mask: Vec<u64>,
+    pub block_light_arrays: Vec<LengthPrefixedArray<u8, 2048>>,
+    /// Sections without any sky light, which are not included in
+    /// `sky_light_arrays`.
+    pub empty_sky_light_mask: Vec<u64>,
+    /// Sections without any block light, which are not included in
+    /// `block_light_arrays`.
+    pub empty_block_light_mask: Vec<u64>,
+    /// The number of sections, including the extra sections.
+    section_count: usize,
+}
+
+impl ChunkLight {
+    /// Gets the sky light of a section, or `None` if it has none.
+    pub fn sky_light(&self, i: usize) -> Option<&LengthPrefixedArray<u8, 2048>> {
+        section_array(&self.sky_light_mask, &self.sky_light_arrays, i)
+    }
+
+    /// Gets the block light of a section, or `None` if it has none.
+    pub fn block_light(&self, i: usize) -> Option<&LengthPrefixedArray<u8, 2048>> {
+        section_array(&self.block_light_mask, &self.block_light_arrays, i)
+    }
+
+    /// Returns a hash of the light in each section, including the extra
+    /// sections. Used to find the sections whose light has changed.
+    pub fn section_hashes(&self) -> Vec<u64> {
+        (0..self.section_count)
+            .map(|i| {
+                let mut hasher = FxHasher::default();
+                self.sky_light(i).map(|arr| arr.0).hash(&mut hasher);
+                self.block_light(i).map(|arr| arr.0).hash(&mut hasher);
+                hasher.finish()
+            })
+            .collect()
+    }
+
+    /// Creates a packet which sends the light of the sections for which
+    /// `include` returns `true`, or `None` if there are no such sections.
+    pub fn update_packet(
+        &self,
+        pos: ChunkPos,
+        mut include: impl FnMut(usize) -> bool,
+    ) -> Option<UpdateLight> {
+        let mask_len = self.sky_light_mask.len();
+
+        let mut pkt = UpdateLight {
+            chunk_x: VarInt(pos.x),
+            chunk_z: VarInt(pos.z),
+            trust_edges: true,
+            sky_light_mask: vec![0; mask_len],
+            block_light_mask: vec![0; mask_len],
+            empty_sky_light_mask: vec![0; mask_len],
+            empty_block_light_mask: vec![0; mask_len],
+            sky_light_arrays: vec![],
+            block_light_arrays: vec![],
+        };
+
+        let mut any_included = false;
+
+        for i in (0..self.section_count).filter(|&i| include(i)) {
+            any_included = true;
+
+            // Empty sections clear any light the client still has in them.
+            match self.sky_light(i) {
+                Some(sky) => {
+                    set(&mut pkt.sky_light_mask, i);
+                    pkt.sky_light_arrays.push(*sky);
+                }
+                None => set(&mut pkt.empty_sky_light_mask, i),
+            }
+
+            match self.block_light(i) {
+                Some(block) => {
+                    set(&mut pkt.block_light_mask, i);
+                    pkt.block_light_arrays.push(*block);
+                }
+                None => set(&mut pkt.empty_block_light_mask, i),
+            }
+        }
+
+        any_included.then_some(pkt)
+    }
+}
+
+/// Gets the array of section `i` from arrays which only contain the sections
+/// set in `mask`.
+fn section_array<'a>(
+    mask: &[u64],
+    arrays: &'a [LengthPrefixedArray<u8, 2048>],
+    i: usize,
+) -> Option<&'a LengthPrefixedArray<u8, 2048>> {
+    is_set(mask, i).then(|| &arrays[(0..i).filter(|&j| is_set(mask, j)).count()])
+}
+
+fn is_set(mask: &[u64], i: usize) -> bool {
+    mask[i / 64] >> (i % 64) & 1 == 1
+}
+
+fn set(mask: &mut [u64], i: usize) {
+    mask[i / 64] |= 1 << (i % 64);
+}
+
+/// Buffers used by [`compute_light`], which can be reused between calls to
+/// avoid allocating them for every chunk.
+#[derive(Default)]
+pub(crate) struct LightScratch {
+    opaque: Vec<bool>,
+    sky_light: Vec<u8>,
+    block_light: Vec<u8>,
+    queue: VecDeque<usize>,
+}
+
+/// Computes the sky and block light of the chunk in the middle of a 3x3 area
+/// of chunks.
+///
+/// `area` contains the sections of the chunks in the area ordered by Z and
+/// then X, or `None` where there is no chunk. Missing chunks are treated as
+/// solid, so no light enters the middle chunk through them.
+pub(crate) fn compute_light(
+    area: [Option<&[ChunkSection]>; 9],
+    scratch: &mut LightScratch,
+) -> ChunkLight {
+    let section_count = area[4].expect("missing middle chunk").len();
+    let len = section_count * 16 * AREA_LAYER;
+
+    let LightScratch {
+        opaque,
+        sky_light,
+        block_light,
+        queue,
+    } = scratch;
+
+    reset(opaque, len, false);
+    reset(sky_light, len, 0);
+    reset(block_light, len, 0);
+    queue.clear();
+
+    // One above the Y coordinate of the highest non-air block.
+    let mut top = 0;
+
+    for (chunk_idx, chunk) in area.iter().enumerate() {
+        let base_x = chunk_idx % 3 * 16;
+        let base_z = chunk_idx / 3 * 16;
+
+        // Translates a block index in a section to an index in the area.
+        let area_idx = |sect_y: usize, i: usize| {
+            let x = base_x + i % 16;
+            let z = base_z + i / 16 % 16;
+            let y = sect_y * 16 + i / 256;
+            y * AREA_LAYER + z * AREA_WIDTH + x
+        };
+
+        let Some(sections) = chunk else {
+            for sect_y in 0..section_count {
+                for i in 0..SECTION_BLOCK_COUNT {
+                    opaque[area_idx(sect_y, i)] = true;
+                }
+            }
+
+            continue;
+        };
+
+        for (sect_y, sect) in sections.iter().enumerate().take(section_count) {
+            if sect.non_air_count == 0 {
+                continue;
+            }
+
+            top = top.max((sect_y + 1) * 16);
+
+            if let PalettedContainer::Single(state) = &sect.block_states {
+                if !state.is_opaque() && state.luminance() == 0 {
+                    continue;
+                }
+            }
+
+            for i in 0..SECTION_BLOCK_COUNT {
+                let state = sect.block_states.get(i);
+                let idx = area_idx(sect_y, i);
+
+                opaque[idx] = state.is_opaque();
+
+                if state.luminance() > 0 {
+                    block_light[idx] = state.luminance();
+                    queue.push_back(idx);
+                }
+            }
+        }
+    }
+
+    propagate(block_light, opaque, queue);
+
+    // Fast path for the sky light above the terrain. Missing chunks are opaque,
+    // so their columns stay dark.
+    for column in 0..AREA_LAYER {
+        for y in (0..section_count * 16).rev() {
+            let idx = y * AREA_LAYER + column;
+
+            if opaque[idx] {
+                break;
+            }
+
+            sky_light[idx] = 15;
+        }
+    }
+
+    // Light above the terrain can only spread into the area below it.
+    queue.extend((0..((top + 1) * AREA_LAYER).min(len)).filter(|&idx| sky_light[idx] == 15));
+
+    propagate(sky_light, opaque, queue);
+
+    let mask_len = num::Integer::div_ceil(&(section_count + 2), &64);
+
+    let mut light = ChunkLight {
+        sky_light_mask: vec![0; mask_len],
+        sky_light_arrays: vec![],
+        block_light_mask: vec![0; mask_len],
+        block_light_arrays: vec![],
+        empty_sky_light_mask: vec![0; mask_len],
+        empty_block_light_mask: vec![0; mask_len],
+        section_count: section_count + 2,
+    };
+
+    // There is no light below the chunk.
+    set(&mut light.empty_sky_light_mask, 0);
+    set(&mut light.empty_block_light_mask, 0);
+
+    let mut section = Vec::with_capacity(SECTION_BLOCK_COUNT);
+
+    for sect_y in 0..section_count {
+        let i = sect_y + 1;
+
+        copy_middle_section(sky_light, sect_y, &mut section);
+
+        if section.iter().any(|&l| l > 0) {
+            set(&mut light.sky_light_mask, i);
+            light.sky_light_arrays.push(pack_nibbles(&section));
+        } else {
+            set(&mut light.empty_sky_light_mask, i);
+        }
+
+        copy_middle_section(block_light, sect_y, &mut section);
+
+        if section.iter().any(|&l| l > 0) {
+            set(&mut light.block_light_mask, i);
+            light.block_light_arrays.push(pack_nibbles(&section));
+        } else {
+            set(&mut light.empty_block_light_mask, i);
+        }
+    }
+
+    // The sky above the chunk is fully lit.
+    set(&mut light.sky_light_mask, section_count + 1);
+    light
+        .sky_light_arrays
+        .push(LengthPrefixedArray([0xff; 2048]));
+    set(&mut light.empty_block_light_mask, section_count + 1);
+
+    light
+}
+
+/// Clears `buf` and fills it with `len` copies of `value`, keeping its
+/// allocation.
+fn reset<T: Copy>(buf: &mut Vec<T>, len: usize, value: T) {
+    buf.clear();
+    buf.resize(len, value);
+}
+
+/// Copies the levels of a section of the middle chunk out of the area into
+/// `out`.
+fn copy_middle_section(levels: &[u8], sect_y: usize, out: &mut Vec<u8>) {
+    out.clear();
+
+    for y in sect_y * 16..sect_y * 16 + 16 {
+        for z in 16..32 {
+            let row = y * AREA_LAYER + z * AREA_WIDTH + 16;
+            out.extend_from_slice(&levels[row..row + 16]);
+        }
+    }
+}
+
+/// Spreads light outwards from the positions in `queue` through blocks that
+/// are not opaque, leaving `queue` empty.
+fn propagate(levels: &mut [u8], opaque: &[bool], queue: &mut VecDeque<usize>) {
+    let height = levels.len() / AREA_LAYER;
+
+    while let Some(idx) = queue.pop_front() {
+        let level = levels[idx];
+
+        if level <= 1 {
+            continue;
+        }
+
+        let x = idx % AREA_WIDTH;
+        let z = idx / AREA_WIDTH % AREA_WIDTH;
+        let y = idx / AREA_LAYER;
+
+        let mut spread = |neighbor: usize| {
+            if !opaque[neighbor] && levels[neighbor] < level - 1 {
+                levels[neighbor] = level - 1;
+                queue.push_back(neighbor);
+            }
+        };
+
+        if x > 0 {
+            spread(idx - 1);
+        }
+        if x + 1 < AREA_WIDTH {
+            spread(idx + 1);
+        }
+        if z > 0 {
+            spread(idx - AREA_WIDTH);
+        }
+        if z + 1 < AREA_WIDTH {
+            spread(idx + AREA_WIDTH);
+        }
+        if y > 0 {
+            spread(idx - AREA_LAYER);
+        }
+        if y + 1 < height {
+            spread(idx + AREA_LAYER);
+        }
+    }
+}
+
+fn pack_nibbles(levels: &[u8]) -> LengthPrefixedArray<u8, 2048> {
+    let mut arr = [0; 2048];
+
+    for (i, pair) in levels.chunks_exact(2).enumerate() {
+        arr[i] = pair[0] | pair[1] << 4;
+    }
+
+    LengthPrefixedArray(arr)
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::BlockState;
+
+    use super::*;
+    use crate::chunk::{Chunk, UnloadedChunk};
+
+    /// Gets a light level from a packed array by its offsets in the chunk.
+    fn level(arr: &LengthPrefixedArray<u8, 2048>, x: usize, y: usize, z: usize) -> u8 {
+        let idx = x + z * 16 + y % 16 * 256;
+        arr.0[idx / 2] >> (idx % 2 * 4) & 0xf
+    }
+
+    /// An area with only the given chunk in the middle.
+    fn middle(chunk: &UnloadedChunk) -> [Option<&[ChunkSection]>; 9] {
+        let mut area = [None; 9];
+        area[4] = Some(&*chunk.sections);
+        area
+    }
+
+    #[test]
+    fn sky_and_block_light() {
+        let mut chunk = UnloadedChunk::new(4);
+
+        chunk.fill_block_states(0, BlockState::STONE);
+
+        for z in 0..16 {
+            for x in 0..16 {
+                chunk.set_block_state(x, 20, z, BlockState::STONE);
+            }
+        }
+
+        // A hole in the roof.
+        chunk.set_block_state(8, 20, 8, BlockState::AIR);
+        chunk.set_block_state(2, 17, 2, BlockState::GLOWSTONE);
+
+        let light = compute_light(middle(&chunk), &mut LightScratch::default());
+
+        // The section below the chunk comes first.
+        let sky = |x, y, z| {
+            light
+                .sky_light(y / 16 + 1)
+                .map_or(0, |arr| level(arr, x, y, z))
+        };
+
+        // The section below the chunk and the section filled with stone are
+        // dark.
+        assert_eq!(light.sky_light_mask, [0b111100]);
+        assert_eq!(light.empty_sky_light_mask, [0b000011]);
+        assert_eq!(sky(0, 21, 0), 15);
+        assert_eq!(sky(0, 63, 15), 15);
+        assert_eq!(sky(8, 16, 8), 15);
+        assert_eq!(sky(9, 16, 8), 14);
+        assert_eq!(sky(0, 19, 0), 0);
+        assert_eq!(sky(8, 5, 8), 0);
+
+        // Only the section containing the glowstone has block light.
+        assert_eq!(light.block_light_mask, [0b100]);
+        assert_eq!(light.block_light_arrays.len(), 1);
+
+        let block = |x, y, z| level(&light.block_light_arrays[0], x, y, z);
+
+        assert_eq!(block(2, 17, 2), 15);
+        assert_eq!(block(3, 17, 2), 14);
+        assert_eq!(block(2, 19, 4), 11);
+    }
+
+    #[test]
+    fn light_crosses_chunk_borders() {
+        let mut middle_chunk = UnloadedChunk::new(4);
+        let mut west_chunk = UnloadedChunk::new(4);
+
+        for chunk in [&mut middle_chunk, &mut west_chunk] {
+            chunk.fill_block_states(0, BlockState::STONE);
+            chunk.fill_block_states(1, BlockState::STONE);
+        }
+
+        // Leave a cave at the bottom of both chunks so the light can pass.
+        for y in 4..8 {
+            for z in 0..16 {
+                for x in 0..16 {
+                    middle_chunk.set_block_state(x, y, z, BlockState::AIR);
+                    west_chunk.set_block_state(x, y, z, BlockState::AIR);
+                }
+            }
+        }
+
+        west_chunk.set_block_state(15, 5, 8, BlockState::GLOWSTONE);
+
+        let mut area = middle(&middle_chunk);
+        area[3] = Some(&west_chunk.sections);
+
+        let mut scratch = LightScratch::default();
+        let light = compute_light(area, &mut scratch);
+
+        assert_eq!(light.block_light_mask, [0b10]);
+
+        let block = |x, y, z| level(light.block_light(1).unwrap(), x, y, z);
+
+        assert_eq!(block(0, 5, 8), 14);
+        assert_eq!(block(3, 5, 8), 11);
+        assert_eq!(block(0, 4, 4), 9);
+
+        // No sky light reaches the cave.
+        let sky = |x, y, z| {
+            light
+                .sky_light(y / 16 + 1)
+                .map_or(0, |arr| level(arr, x, y, z))
+        };
+
+        assert_eq!(sky(0, 5, 8), 0);
+        assert_eq!(sky(0, 40, 8), 15);
+
+        // Buffers left over from another area don't affect the result.
+        compute_light(middle(&west_chunk), &mut scratch);
+        let again = compute_light(area, &mut scratch);
+        assert_eq!(again.section_hashes(), light.section_hashes());
+    }
+}
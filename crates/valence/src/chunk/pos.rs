@@ -38,6 +38,19 @@ impl ChunkPos {
         diff_x * diff_x + diff_z * diff_z <= dist * dist
     }
 
+    /// Like [`Self::is_in_view`], but additionally requires `other` to be
+    /// within `range` chunks of `self`. Used to decide if a client standing in
+    /// `self` can see an entity in `other` with the given tracking range.
+    #[inline]
+    pub(crate) fn is_in_tracking_range(self, other: Self, view_dist: u8, range: i32) -> bool {
+        let range = range as i64;
+
+        let diff_x = other.x as i64 - self.x as i64;
+        let diff_z = other.z as i64 - self.z as i64;
+
+        self.is_in_view(other, view_dist) && diff_x * diff_x + diff_z * diff_z <= range * range
+    }
+
     /// Returns an iterator over all chunk positions within a view distance
     /// centered on `self`. The `self` position is included in the output.
     pub fn in_view(self, view_dist: u8) -> impl FusedIterator<Item = Self> {
@@ -114,4 +127,26 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn tracking_range() {
+        let center = ChunkPos::new(-3, 7);
+
+        assert!(center.is_in_tracking_range(center, 2, 0));
+        assert!(!center.is_in_tracking_range(ChunkPos::new(-2, 7), 2, 0));
+
+        // An entity just inside the range is visible, but one just outside
+        // is not.
+        assert!(center.is_in_tracking_range(ChunkPos::new(1, 7), 10, 4));
+        assert!(!center.is_in_tracking_range(ChunkPos::new(2, 7), 10, 4));
+        assert!(!center.is_in_tracking_range(ChunkPos::new(0, 10), 10, 4));
+
+        // The range is limited by the view distance.
+        for pos in center.in_view(32) {
+            assert_eq!(
+                center.is_in_tracking_range(pos, 6, i32::MAX),
+                center.is_in_view(pos, 6)
+            );
+        }
+    }
 }
@@ -4,35 +4,46 @@ use std::iter::FusedIterator;
 use std::net::IpAddr;
 use std::num::Wrapping;
 use std::ops::{Deref, DerefMut};
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{array, fmt, mem};
 
 use anyhow::{bail, ensure, Context};
 pub use bitfield_struct::bitfield;
-pub use event::ClientEvent;
+pub use event::{BlockChange, ClientEvent};
 use rayon::iter::ParallelIterator;
+use rustc_hash::FxHashSet;
 use tokio::sync::OwnedSemaphorePermit;
 use tracing::{info, warn};
 use uuid::Uuid;
 use valence_protocol::packets::s2c::particle::{Particle, ParticleS2c};
 use valence_protocol::packets::s2c::play::{
-    AcknowledgeBlockChange, ClearTitles, CloseContainerS2c, CombatDeath, DisconnectPlay,
-    EntityAnimationS2c, EntityEvent, GameEvent, KeepAliveS2c, LoginPlayOwned, OpenScreen,
-    PluginMessageS2c, RemoveEntitiesEncode, ResourcePackS2c, RespawnOwned, SetActionBarText,
-    SetCenterChunk, SetContainerContentEncode, SetContainerSlotEncode, SetDefaultSpawnPosition,
-    SetEntityMetadata, SetEntityVelocity, SetExperience, SetHealth, SetRenderDistance,
-    SetSubtitleText, SetTitleAnimationTimes, SetTitleText, SynchronizePlayerPosition,
-    SystemChatMessage, UnloadChunk, UpdateAttributes, UpdateTime,
+    AcknowledgeBlockChange, BlockUpdate, ClearTitles, CloseContainerS2c, CombatDeath,
+    CommandSuggestionResponse, DisconnectPlay, EntityAnimationS2c, EntityEvent, FeatureFlags,
+    GameEvent, KeepAliveS2c, LoginPlayOwned, OpenScreen, PlayerAbilitiesS2c, PluginMessageS2c,
+    RemoveEntitiesEncode, ResourcePackS2c, RespawnOwned, SetActionBarText, SetCenterChunk,
+    SetContainerContentEncode, SetContainerSlotEncode, SetCooldown, SetDefaultSpawnPosition,
+    SetEntityMetadata, SetEntityVelocity, SetExperience, SetHealth, SetHeldItemS2c,
+    SetRenderDistance, SetSubtitleText, SetTitleAnimationTimes, SetTitleText, SoundEffect,
+    SynchronizePlayerPosition, SystemChatMessage, UnloadChunk, UpdateAdvancements,
+    UpdateAttributes, UpdateTime,
+};
+use valence_protocol::packets::s2c::update_advancements::{
+    Advancement, AdvancementCriteria, AdvancementDisplay, AdvancementRequirements, FrameType,
 };
 use valence_protocol::types::{
-    AttributeProperty, DisplayedSkinParts, GameEventKind, GameMode, SyncPlayerPosLookFlags,
+    AttributeProperty, DisplayedSkinParts, GameEventKind, GameMode, PlayerAbilitiesFlags,
+    SoundCategory, SoundId, SyncPlayerPosLookFlags,
 };
 use valence_protocol::{
-    BlockPos, EncodePacket, Ident, ItemStack, RawBytes, Text, Username, VarInt,
+    BlockPos, Encode, EncodePacket, Ident, ItemKind, ItemStack, RawBytes, RawPacket, Text,
+    Username, VarInt,
 };
 use vek::Vec3;
 
+use crate::boss_bar::{self, BossBarId, BossBars};
 use crate::chunk::ChunkPos;
 use crate::client::event::next_event_fallible;
+use crate::command::{CommandSuggestionMatch, CommandTree};
 use crate::config::Config;
 use crate::dimension::DimensionId;
 use crate::entity::data::Player;
@@ -40,7 +51,10 @@ use crate::entity::{self, velocity_to_packet_units, Entities, EntityId, StatusOr
 use crate::inventory::{Inventories, InventoryId};
 use crate::player_list::{PlayerListId, PlayerLists};
 use crate::player_textures::SignedPlayerTextures;
-use crate::server::{NewClientData, PlayPacketReceiver, PlayPacketSender, SharedServer};
+use crate::server::{
+    inspect_packets, ClientTally, NewClientData, PlayPacketReceiver, PlayPacketSender,
+    SharedServer, SlowClientError,
+};
 use crate::slab_versioned::{Key, VersionedSlab};
 use crate::world::{WorldId, Worlds};
 use crate::Ticks;
@@ -54,16 +68,22 @@ mod event;
 /// they disconnect. This can be checked with [`Client::is_disconnected`].
 pub struct Clients<C: Config> {
     slab: VersionedSlab<Client<C>>,
+    /// The join sequence number of the next client to be inserted.
+    next_join_seq: u64,
 }
 
 impl<C: Config> Clients<C> {
     pub(crate) fn new() -> Self {
         Self {
             slab: VersionedSlab::new(),
+            next_join_seq: 0,
         }
     }
 
-    pub(crate) fn insert(&mut self, client: Client<C>) -> (ClientId, &mut Client<C>) {
+    pub(crate) fn insert(&mut self, mut client: Client<C>) -> (ClientId, &mut Client<C>) {
+        client.join_seq = self.next_join_seq;
+        self.next_join_seq += 1;
+
         let (k, client) = self.slab.insert(client);
         (ClientId(k), client)
     }
@@ -71,22 +91,25 @@ impl<C: Config> Clients<C> {
     /// Removes a client from the server.
     ///
     /// If the given client ID is valid, the client's `ClientState` is returned
-    /// and the client is deleted. Otherwise, `None` is returned and the
-    /// function has no effect.
+    /// and the client is deleted after calling [`Config::client_left`].
+    /// Otherwise, `None` is returned and the function has no effect.
     pub fn remove(&mut self, client: ClientId) -> Option<C::ClientState> {
-        self.slab.remove(client.0).map(|c| {
+        self.slab.remove(client.0).map(|mut c| {
             info!(username = %c.username, uuid = %c.uuid, ip = %c.ip, "removing client");
+            c.left();
             c.state
         })
     }
 
     /// Deletes all clients from the server for which `f` returns `false`.
+    /// [`Config::client_left`] is called for each deleted client.
     ///
     /// All clients are visited in an unspecified order.
     pub fn retain(&mut self, mut f: impl FnMut(ClientId, &mut Client<C>) -> bool) {
         self.slab.retain(|k, v| {
             if !f(ClientId(k), v) {
                 info!(username = %v.username, uuid = %v.uuid, ip = %v.ip, "removing client");
+                v.left();
                 false
             } else {
                 true
@@ -134,6 +157,21 @@ impl<C: Config> Clients<C> {
         self.slab.iter_mut().map(|(k, v)| (ClientId(k), v))
     }
 
+    /// Returns a mutable iterator over all clients on the server in the order
+    /// they joined. Useful for logic which must not depend on the order of
+    /// the client IDs, such as assigning teams by join order.
+    ///
+    /// This sorts the clients on every call, so it is slower than
+    /// [`Self::iter_mut`] and [`Self::par_iter_mut`]. Prefer those when the
+    /// order does not matter.
+    pub fn iter_mut_ordered(
+        &mut self,
+    ) -> impl ExactSizeIterator<Item = (ClientId, &mut Client<C>)> + FusedIterator + '_ {
+        let mut clients: Vec<_> = self.iter_mut().collect();
+        clients.sort_unstable_by_key(|(_, client)| client.join_seq);
+        clients.into_iter()
+    }
+
     /// Returns a parallel iterator over all clients on the server in an
     /// unspecified order.
     pub fn par_iter(&self) -> impl ParallelIterator<Item = (ClientId, &Client<C>)> + Clone + '_ {
@@ -147,6 +185,98 @@ impl<C: Config> Clients<C> {
     ) -> impl ParallelIterator<Item = (ClientId, &mut Client<C>)> + '_ {
         self.slab.par_iter_mut().map(|(k, v)| (ClientId(k), v))
     }
+
+    /// Plays a sound to every client in the given world which is within
+    /// hearing range of the sound's position. See [`Client::play_sound`] and
+    /// [`sound_range`] for more information.
+    pub fn play_sound<'a>(
+        &mut self,
+        world: WorldId,
+        sound: impl Into<SoundId<'a>>,
+        category: SoundCategory,
+        position: impl Into<Vec3<f64>>,
+        volume: f32,
+        pitch: f32,
+    ) {
+        let sound = sound.into();
+        let position = position.into();
+
+        let range = match sound {
+            SoundId::Direct {
+                range: Some(range), ..
+            } => range,
+            _ => sound_range(volume),
+        } as f64;
+
+        for (_, client) in self.iter_mut() {
+            if client.world == world && client.position.distance_squared(position) <= range * range
+            {
+                client.play_sound(sound, category, position, volume, pitch);
+            }
+        }
+    }
+}
+
+/// Describes why a client was disconnected. Passed to [`Config::client_left`]
+/// and available from [`Client::disconnect_reason`].
+#[derive(Clone, PartialEq, Debug)]
+pub enum DisconnectReason {
+    /// The client closed the connection, e.g. by leaving the game.
+    ClientQuit,
+    /// The client stopped responding to keepalives.
+    Timeout,
+    /// The client was disconnected by the server with the given message, or
+    /// removed from [`Clients`] while still connected. The message is empty
+    /// for [`Client::disconnect_abrupt`].
+    Kicked(Text),
+    /// The client did not read the packets sent to it fast enough. See
+    /// [`Config::overflow_policy`].
+    SlowClient,
+    /// The connection was closed because of an error, such as an invalid
+    /// packet from the client or a network error.
+    ProtocolError(String),
+    /// The server shut down while the client was connected.
+    ServerShutdown,
+}
+
+impl DisconnectReason {
+    /// Gets the reason for disconnecting a client after an error while sending
+    /// packets to it.
+    fn from_send_error(e: &anyhow::Error) -> Self {
+        if e.downcast_ref::<SlowClientError>().is_some() {
+            Self::SlowClient
+        } else {
+            Self::ProtocolError(format!("{e:#}"))
+        }
+    }
+}
+
+/// The flying speed of vanilla players.
+pub const DEFAULT_FLYING_SPEED: f32 = 0.05;
+
+/// The walking speed of vanilla players.
+pub const DEFAULT_WALKING_SPEED: f32 = 0.1;
+
+/// The identifier of the advancement used by [`Client::grant_toast`].
+const TOAST_ADVANCEMENT: &str = "valence:toast";
+
+/// The maximum health of vanilla players.
+pub const MAX_HEALTH: f32 = 20.0;
+
+/// The maximum food level of vanilla players.
+pub const MAX_FOOD: i32 = 20;
+
+/// The food saturation of vanilla players when they spawn, which is also the
+/// maximum.
+pub const DEFAULT_FOOD_SATURATION: f32 = 5.0;
+
+/// Returns the distance in blocks that vanilla clients can hear a sound with
+/// the given volume from.
+///
+/// Sounds with a volume of `1.0` or less can be heard from 16 blocks away. Louder
+/// sounds can be heard from `16 * volume` blocks away.
+pub fn sound_range(volume: f32) -> f32 {
+    16.0 * volume.max(1.0)
 }
 
 /// An identifier for a [`Client`] on the server.
@@ -194,6 +324,7 @@ pub struct Client<C: Config> {
     pub state: C::ClientState,
     send: Option<PlayPacketSender>,
     recv: PlayPacketReceiver,
+    shared: SharedServer<C>,
     /// To make sure we're not loading already loaded chunks, or unloading
     /// unloaded chunks.
     #[cfg(debug_assertions)]
@@ -211,6 +342,10 @@ pub struct Client<C: Config> {
     uuid: Uuid,
     ip: IpAddr,
     textures: Option<SignedPlayerTextures>,
+    /// The brand sent by the client in the `minecraft:brand` plugin channel.
+    brand: Option<Box<str>>,
+    /// Counts this client's brand and locale in the server metrics.
+    tally: ClientTally,
     /// World client is currently in. Default value is **invalid** and must
     /// be set by calling [`Client::respawn`].
     world: WorldId,
@@ -218,6 +353,8 @@ pub struct Client<C: Config> {
     player_list: Option<PlayerListId>,
     /// Player list from the previous tick.
     old_player_list: Option<PlayerListId>,
+    /// The boss bars the client is currently displaying.
+    visible_boss_bars: Vec<BossBarId>,
     position: Vec3<f64>,
     /// Position from the previous tick.
     old_position: Vec3<f64>,
@@ -227,6 +364,12 @@ pub struct Client<C: Config> {
     pitch: f32,
     view_distance: u8,
     old_view_distance: u8,
+    /// Chunks in view of the client which have not been sent yet due to
+    /// [`Config::max_chunks_per_tick`].
+    queued_chunks: FxHashSet<ChunkPos>,
+    /// Entities whose update packets were dropped because the client was
+    /// falling behind. Their complete state is sent again once there is room.
+    desynced_entities: FxHashSet<EntityId>,
     /// Counts up as teleports are made.
     teleport_id_counter: u32,
     /// The number of pending client teleports that have yet to receive a
@@ -234,10 +377,34 @@ pub struct Client<C: Config> {
     /// this is nonzero.
     pending_teleports: u32,
     death_location: Option<(DimensionId, BlockPos)>,
+    /// The position and angle set with [`Client::set_spawn_position`].
+    spawn_position: Option<(BlockPos, f32)>,
+    /// The position of this client in the order clients were added to
+    /// [`Clients`].
+    join_seq: u64,
     /// The ID of the last keepalive sent.
     last_keepalive_id: u64,
+    /// Why the client was disconnected. Only the first reason is kept.
+    disconnect_reason: Option<DisconnectReason>,
+    /// The IDs of keepalives sent while the server was paused. Responses to
+    /// these are accepted in any order once they arrive.
+    paused_keepalive_ids: Vec<u64>,
     game_mode: GameMode,
+    abilities: PlayerAbilitiesFlags,
+    flying_speed: f32,
+    walking_speed: f32,
     block_change_sequence: i32,
+    health: f32,
+    food: i32,
+    food_saturation: f32,
+    /// Progress on the experience bar in `0.0..=1.0`.
+    xp_bar: f32,
+    level: i32,
+    total_xp: i32,
+    /// The number of unknown packets discarded from this client.
+    skipped_packets: u64,
+    /// Blocks to re-send to the client after a rejected block change.
+    blocks_to_resync: Vec<BlockPos>,
     /// The data for the client's own player entity.
     player_data: Player,
     /// The client's inventory slots.
@@ -248,6 +415,8 @@ pub struct Client<C: Config> {
     /// Counts up as inventory modifications are made by the server. Used to
     /// prevent desync.
     inv_state_id: Wrapping<i32>,
+    /// The selected hotbar slot in `0..=8`.
+    held_slot: u8,
     /// The item currently held by the client's cursor in the inventory.
     cursor_item: Option<ItemStack>,
     /// The currently open inventory. The client can close the screen, making
@@ -258,7 +427,7 @@ pub struct Client<C: Config> {
     bits: ClientBits,
 }
 
-#[bitfield(u8)]
+#[bitfield(u16)]
 struct ClientBits {
     created_this_tick: bool,
     respawn: bool,
@@ -269,8 +438,18 @@ struct ClientBits {
     respawn_screen: bool,
     cursor_item_modified: bool,
     open_inventory_modified: bool,
-    //#[bits(1)]
-    //_pad: u8,
+    health_modified: bool,
+    experience_modified: bool,
+    /// If the time was set with [`Client::set_time`] this tick.
+    time_set: bool,
+    /// If the weather was set with [`Client::set_raining`] or the rain or
+    /// thunder level setters this tick.
+    weather_set: bool,
+    /// If the game mode was changed since the client's player list entry was
+    /// last updated.
+    game_mode_modified: bool,
+    #[bits(3)]
+    _pad: u8,
 }
 
 impl<C: Config> Deref for Client<C> {
@@ -289,16 +468,23 @@ impl<C: Config> DerefMut for Client<C> {
 
 impl<C: Config> Client<C> {
     pub(crate) fn new(
-        send: PlayPacketSender,
+        mut send: PlayPacketSender,
         recv: PlayPacketReceiver,
         permit: OwnedSemaphorePermit,
         ncd: NewClientData,
+        shared: SharedServer<C>,
         state: C::ClientState,
     ) -> Self {
+        let game_mode = shared.default_game_mode();
+        let tally = shared.new_client_tally();
+
+        send.set_overflow_policy(shared.overflow_policy());
+
         Self {
             state,
             send: Some(send),
             recv,
+            shared,
             #[cfg(debug_assertions)]
             loaded_chunks: Default::default(),
             _permit: permit,
@@ -309,26 +495,47 @@ impl<C: Config> Client<C> {
             uuid: ncd.uuid,
             ip: ncd.ip,
             textures: ncd.textures,
+            brand: None,
+            tally,
             world: WorldId::NULL,
             old_world: WorldId::NULL,
             player_list: None,
             old_player_list: None,
+            visible_boss_bars: vec![],
             position: Vec3::default(),
             old_position: Vec3::default(),
             yaw: 0.0,
             pitch: 0.0,
             view_distance: 2,
             old_view_distance: 2,
+            queued_chunks: FxHashSet::default(),
+            desynced_entities: FxHashSet::default(),
             teleport_id_counter: 0,
             pending_teleports: 0,
             death_location: None,
+            spawn_position: None,
+            join_seq: 0,
             last_keepalive_id: 0,
-            game_mode: GameMode::Survival,
+            paused_keepalive_ids: vec![],
+            disconnect_reason: None,
+            game_mode,
+            abilities: PlayerAbilitiesFlags::new(),
+            flying_speed: DEFAULT_FLYING_SPEED,
+            walking_speed: DEFAULT_WALKING_SPEED,
             block_change_sequence: 0,
+            health: MAX_HEALTH,
+            food: MAX_FOOD,
+            food_saturation: DEFAULT_FOOD_SATURATION,
+            xp_bar: 0.0,
+            level: 0,
+            total_xp: 0,
+            skipped_packets: 0,
+            blocks_to_resync: vec![],
             player_data: Player::new(),
             slots: Box::new(array::from_fn(|_| None)),
             modified_slots: 0,
             inv_state_id: Wrapping(0),
+            held_slot: 0,
             cursor_item: None,
             open_inventory: None,
             window_id: 0,
@@ -338,7 +545,17 @@ impl<C: Config> Client<C> {
         }
     }
 
-    /// Attempts to enqueue a play packet to be sent to this client.
+    /// Starts passing this client's packets to [`Config::inspect_outgoing`]
+    /// and [`Config::inspect_incoming`].
+    pub(crate) fn inspect_packets(&mut self, id: ClientId) {
+        if let Some(send) = &mut self.send {
+            inspect_packets(&self.shared, id, send, &mut self.recv);
+        }
+    }
+
+    /// Attempts to enqueue a play packet to be sent to this client. This
+    /// accepts any type implementing [`EncodePacket`], including custom
+    /// packets defined with the derive macros.
     ///
     /// If encoding the packet fails, the client is disconnected. Has no
     /// effect if the client is already disconnected.
@@ -354,7 +571,28 @@ impl<C: Config> Client<C> {
                     ip = %self.ip,
                     "failed to queue packet: {e:#}"
                 );
-                self.send = None;
+                self.set_disconnected(DisconnectReason::ProtocolError(format!("{e:#}")));
+            }
+        }
+    }
+
+    /// Like [`Self::queue_packet`], but the packet may be dropped if the client
+    /// is falling behind. See [`OverflowPolicy::DropNonEssentialPackets`].
+    ///
+    /// [`OverflowPolicy::DropNonEssentialPackets`]: crate::config::OverflowPolicy::DropNonEssentialPackets
+    fn queue_nonessential_packet<P>(&mut self, pkt: &P)
+    where
+        P: EncodePacket + fmt::Debug + ?Sized,
+    {
+        if let Some(send) = &mut self.send {
+            if let Err(e) = send.append_nonessential_packet(pkt) {
+                warn!(
+                    username = %self.username,
+                    uuid = %self.uuid,
+                    ip = %self.ip,
+                    "failed to queue packet: {e:#}"
+                );
+                self.set_disconnected(DisconnectReason::ProtocolError(format!("{e:#}")));
             }
         }
     }
@@ -379,12 +617,31 @@ impl<C: Config> Client<C> {
         self.ip
     }
 
+    /// Returns the total number of bytes sent to this client over the network,
+    /// including bytes sent while logging in.
+    pub fn bytes_sent(&self) -> u64 {
+        self.recv.byte_counters().sent()
+    }
+
+    /// Returns the total number of bytes received from this client over the
+    /// network, including bytes received while logging in.
+    pub fn bytes_received(&self) -> u64 {
+        self.recv.byte_counters().received()
+    }
+
     /// Gets the player textures of this client. If the client does not have
     /// a skin, then `None` is returned.
     pub fn textures(&self) -> Option<&SignedPlayerTextures> {
         self.textures.as_ref()
     }
 
+    /// Gets the brand of this client's game (e.g. "vanilla" or "fabric") as
+    /// reported in the `minecraft:brand` plugin channel. Returns `None` if the
+    /// client has not sent its brand yet.
+    pub fn brand(&self) -> Option<&str> {
+        self.brand.as_deref()
+    }
+
     /// Gets the world this client is located in.
     pub fn world(&self) -> WorldId {
         self.world
@@ -444,6 +701,11 @@ impl<C: Config> Client<C> {
 
     /// Gets the absolute position of this client in the world it is located
     /// in.
+    ///
+    /// This is the last position reported by the client, unless the client
+    /// has been teleported since. Teleporting sets the position immediately,
+    /// and the position reports sent by the client before it confirms the
+    /// teleport are discarded. See [`Self::has_pending_teleport`].
     pub fn position(&self) -> Vec3<f64> {
         self.position
     }
@@ -453,15 +715,59 @@ impl<C: Config> Client<C> {
     ///
     /// If you want to change the client's world, use [`Self::respawn`].
     pub fn teleport(&mut self, pos: impl Into<Vec3<f64>>, yaw: f32, pitch: f32) {
-        self.position = pos.into();
-        self.yaw = yaw;
-        self.pitch = pitch;
+        self.teleport_with_flags(pos, yaw, pitch, SyncPlayerPosLookFlags::new());
+    }
+
+    /// Like [`Self::teleport`], but the components of the position and
+    /// rotation marked in `flags` are interpreted as offsets from the client's
+    /// current position and rotation rather than absolute values.
+    ///
+    /// Movement packets from the client are ignored until it has confirmed
+    /// the teleport. See [`Self::has_pending_teleport`].
+    pub fn teleport_with_flags(
+        &mut self,
+        pos: impl Into<Vec3<f64>>,
+        yaw: f32,
+        pitch: f32,
+        flags: SyncPlayerPosLookFlags,
+    ) {
+        let pos = pos.into();
+
+        if flags.x() {
+            self.position.x += pos.x;
+        } else {
+            self.position.x = pos.x;
+        }
+
+        if flags.y() {
+            self.position.y += pos.y;
+        } else {
+            self.position.y = pos.y;
+        }
+
+        if flags.z() {
+            self.position.z += pos.z;
+        } else {
+            self.position.z = pos.z;
+        }
+
+        if flags.y_rot() {
+            self.yaw += yaw;
+        } else {
+            self.yaw = yaw;
+        }
+
+        if flags.x_rot() {
+            self.pitch += pitch;
+        } else {
+            self.pitch = pitch;
+        }
 
         self.queue_packet(&SynchronizePlayerPosition {
-            position: self.position.into_array(),
+            position: pos.into_array(),
             yaw,
             pitch,
-            flags: SyncPlayerPosLookFlags::new(),
+            flags,
             teleport_id: VarInt(self.teleport_id_counter as i32),
             dismount_vehicle: false,
         });
@@ -470,6 +776,37 @@ impl<C: Config> Client<C> {
         self.teleport_id_counter = self.teleport_id_counter.wrapping_add(1);
     }
 
+    /// Returns `true` if this client has been teleported but has not yet
+    /// confirmed the teleport.
+    ///
+    /// While a teleport is pending, position updates sent by the client refer
+    /// to its location before the teleport and are discarded.
+    pub fn has_pending_teleport(&self) -> bool {
+        self.pending_teleports != 0
+    }
+
+    /// Sends a packet with an arbitrary ID and contents to this client. This
+    /// bypasses the packets modeled by Valence and is intended for
+    /// experimentation.
+    ///
+    /// `data` is the contents of the packet following the packet ID. The packet
+    /// is compressed and encrypted like any other packet.
+    pub fn send_raw_packet(&mut self, packet_id: VarInt, data: &[u8]) {
+        self.queue_packet(&RawPacket {
+            id: packet_id,
+            data,
+        });
+    }
+
+    /// Returns the number of packets with unrecognized IDs that have been
+    /// discarded from this client. This is always zero unless
+    /// [`Config::unknown_packet_policy`] returns [`UnknownPacketPolicy::Skip`].
+    ///
+    /// [`UnknownPacketPolicy::Skip`]: crate::config::UnknownPacketPolicy::Skip
+    pub fn skipped_packet_count(&self) -> u64 {
+        self.skipped_packets
+    }
+
     /// Sets the client's velocity in m/s.
     pub fn set_velocity(&mut self, velocity: impl Into<Vec3<f32>>) {
         self.queue_packet(&SetEntityVelocity {
@@ -478,23 +815,40 @@ impl<C: Config> Client<C> {
         })
     }
 
-    /// Gets this client's yaw.
+    /// Gets this client's yaw in degrees. Like [`Self::position`], this is the
+    /// last value reported by the client or set by a teleport.
     pub fn yaw(&self) -> f32 {
         self.yaw
     }
 
-    /// Gets this client's pitch.
+    /// Gets this client's pitch in degrees. Like [`Self::position`], this is
+    /// the last value reported by the client or set by a teleport.
     pub fn pitch(&self) -> f32 {
         self.pitch
     }
 
+    /// Gets the position and yaw in degrees set with
+    /// [`Self::set_spawn_position`], or `None` if it has not been called.
+    pub fn spawn_position(&self) -> Option<(BlockPos, f32)> {
+        self.spawn_position
+    }
+
     /// Sets the spawn position. The client will see `minecraft:compass` items
     /// point at the provided position.
+    ///
+    /// If this is not called, the spawn position is the client's position when
+    /// it joins the game.
     pub fn set_spawn_position(&mut self, pos: impl Into<BlockPos>, yaw_degrees: f32) {
-        self.queue_packet(&SetDefaultSpawnPosition {
-            position: pos.into(),
-            angle: yaw_degrees,
-        });
+        let pos = pos.into();
+        self.spawn_position = Some((pos, yaw_degrees));
+
+        // The spawn position is sent with the initial packets when joining.
+        if !self.created_this_tick() {
+            self.queue_packet(&SetDefaultSpawnPosition {
+                position: pos,
+                angle: yaw_degrees,
+            });
+        }
     }
 
     /// Gets the last death location of this client. The client will see
@@ -515,6 +869,9 @@ impl<C: Config> Client<C> {
     ///
     /// Changes to the last death location take effect when the client
     /// (re)spawns.
+    ///
+    /// The given [`DimensionId`] must be valid. Otherwise, the client is
+    /// disconnected.
     pub fn set_death_location(&mut self, location: Option<(DimensionId, BlockPos)>) {
         self.death_location = location;
     }
@@ -525,9 +882,16 @@ impl<C: Config> Client<C> {
     }
 
     /// Sets the client's game mode.
+    ///
+    /// If the client's [player list](Self::player_list) has an entry with the
+    /// client's UUID, the game mode of the entry is updated to match at the
+    /// end of the tick. See [`PlayerListEntry::set_game_mode`].
+    ///
+    /// [`PlayerListEntry::set_game_mode`]: crate::player_list::PlayerListEntry::set_game_mode
     pub fn set_game_mode(&mut self, game_mode: GameMode) {
         if self.game_mode != game_mode {
             self.game_mode = game_mode;
+            self.bits.set_game_mode_modified(true);
 
             if !self.created_this_tick() {
                 self.queue_packet(&GameEvent {
@@ -538,7 +902,67 @@ impl<C: Config> Client<C> {
         }
     }
 
-    /// Sets whether or not the client sees rain.
+    /// Returns whether the game mode was changed since the last call, and
+    /// resets the flag.
+    pub(crate) fn take_game_mode_modified(&mut self) -> bool {
+        let modified = self.bits.game_mode_modified();
+        self.bits.set_game_mode_modified(false);
+        modified
+    }
+
+    /// Gets the client's ability flags, such as whether or not they are
+    /// allowed to fly.
+    pub fn abilities(&self) -> PlayerAbilitiesFlags {
+        self.abilities
+    }
+
+    /// Gets the client's flying speed.
+    pub fn flying_speed(&self) -> f32 {
+        self.flying_speed
+    }
+
+    /// Gets the client's walking speed, which also affects their field of
+    /// view.
+    pub fn walking_speed(&self) -> f32 {
+        self.walking_speed
+    }
+
+    /// Sets the client's abilities along with their flying and walking
+    /// speeds. The speeds are clamped to `0.0..=1.0`. The vanilla defaults are
+    /// [`DEFAULT_FLYING_SPEED`] and [`DEFAULT_WALKING_SPEED`].
+    ///
+    /// Note that the client resets its abilities to the defaults for its game
+    /// mode when the game mode is changed, so this should be called again
+    /// after [`Self::set_game_mode`].
+    pub fn set_abilities(
+        &mut self,
+        abilities: PlayerAbilitiesFlags,
+        flying_speed: f32,
+        walking_speed: f32,
+    ) {
+        let flying_speed = flying_speed.clamp(0.0, 1.0);
+        let walking_speed = walking_speed.clamp(0.0, 1.0);
+
+        if self.abilities != abilities
+            || self.flying_speed != flying_speed
+            || self.walking_speed != walking_speed
+        {
+            self.abilities = abilities;
+            self.flying_speed = flying_speed;
+            self.walking_speed = walking_speed;
+
+            self.queue_packet(&PlayerAbilitiesS2c {
+                flags: abilities,
+                flying_speed,
+                fov_modifier: walking_speed,
+            });
+        }
+    }
+
+    /// Sets whether or not the client sees rain. To change the weather for all
+    /// clients in a world, use [`World::set_weather`] instead.
+    ///
+    /// [`World::set_weather`]: crate::world::World::set_weather
     pub fn set_raining(&mut self, raining: bool) {
         self.queue_packet(&GameEvent {
             kind: if raining {
@@ -548,6 +972,7 @@ impl<C: Config> Client<C> {
             },
             value: 0.0,
         });
+        self.bits.set_weather_set(true);
     }
 
     /// Sets the client's rain level. This changes the sky color and lightning
@@ -559,6 +984,7 @@ impl<C: Config> Client<C> {
             kind: GameEventKind::RainLevelChange,
             value: rain_level.clamp(0.0, 1.0),
         });
+        self.bits.set_weather_set(true);
     }
 
     /// Sets the client's thunder level. This changes the sky color and
@@ -574,6 +1000,7 @@ impl<C: Config> Client<C> {
             kind: GameEventKind::ThunderLevelChange,
             value: thunder_level.clamp(0.0, 1.0),
         });
+        self.bits.set_weather_set(true);
     }
 
     pub fn play_particle(
@@ -585,7 +1012,7 @@ impl<C: Config> Client<C> {
         max_speed: f32,
         count: i32,
     ) {
-        self.queue_packet(&ParticleS2c {
+        self.queue_nonessential_packet(&ParticleS2c {
             particle: particle.clone(),
             long_distance,
             position: position.into().into_array(),
@@ -595,6 +1022,37 @@ impl<C: Config> Client<C> {
         })
     }
 
+    /// Plays a sound to this client at the given position.
+    ///
+    /// The sound can be a named sound such as `minecraft:entity.player.levelup`
+    /// or a custom sound from a resource pack. Sounds from the sound event
+    /// registry can also be played by their numeric ID with
+    /// [`SoundId::Reference`].
+    ///
+    /// The volume is clamped to be nonnegative and the pitch is clamped to
+    /// `0.5..=2.0`. Vanilla clients can hear a sound from up to
+    /// [`sound_range(volume)`](sound_range) blocks away, unless the sound has a
+    /// fixed range.
+    pub fn play_sound<'a>(
+        &mut self,
+        sound: impl Into<SoundId<'a>>,
+        category: SoundCategory,
+        position: impl Into<Vec3<f64>>,
+        volume: f32,
+        pitch: f32,
+    ) {
+        let position = position.into();
+
+        self.queue_nonessential_packet(&SoundEffect {
+            id: sound.into(),
+            category,
+            position: (position * 8.0).as_::<i32>().into_array(),
+            volume: volume.max(0.0),
+            pitch: pitch.clamp(0.5, 2.0),
+            seed: rand::random(),
+        });
+    }
+
     /// Sets the title this client sees.
     ///
     /// A title is a large piece of text displayed in the center of the screen
@@ -621,7 +1079,29 @@ impl<C: Config> Client<C> {
         }
     }
 
-    /// Sets the action bar for this client.
+    /// Shows a title and an optional subtitle to this client with the given
+    /// fade in, stay, and fade out durations measured in ticks.
+    ///
+    /// This is a convenience over [`Self::set_title`]. An error is returned
+    /// and nothing is sent if any of the durations are negative or do not fit
+    /// in an `i32`.
+    pub fn send_title(
+        &mut self,
+        title: impl Into<Text>,
+        subtitle: impl Into<Option<Text>>,
+        fade_in: Ticks,
+        stay: Ticks,
+        fade_out: Ticks,
+    ) -> anyhow::Result<()> {
+        let animation = title_animation_times(fade_in, stay, fade_out)?;
+
+        self.set_title(title, subtitle.into().unwrap_or_default(), animation);
+        Ok(())
+    }
+
+    /// Sets the action bar for this client. The action bar is a line of text
+    /// shown above the hotbar which fades out after a few seconds, so it must
+    /// be resent periodically to display persistent status text.
     pub fn set_action_bar(&mut self, text: impl Into<Text>) {
         self.queue_packet(&SetActionBarText(text.into()));
     }
@@ -655,35 +1135,187 @@ impl<C: Config> Client<C> {
         self.queue_packet(&ClearTitles { reset: true });
     }
 
+    /// Sends a [`CommandTree`] to the client, replacing the commands it
+    /// autocompletes. This is typically done when the client joins, but the
+    /// tree can be resent at any time, such as when the client's permissions
+    /// change.
+    pub fn send_command_tree(&mut self, tree: &CommandTree) {
+        self.queue_packet(&tree.packet());
+    }
+
+    /// Responds to [`ClientEvent::CommandSuggestionsRequest`] with the given
+    /// suggestions.
+    ///
+    /// `start` and `length` are the range of characters in the request's text
+    /// to replace with the selected suggestion. The leading `/` of the text is
+    /// counted.
+    pub fn send_command_suggestions<'a>(
+        &mut self,
+        transaction_id: i32,
+        start: usize,
+        length: usize,
+        suggestions: impl IntoIterator<Item = CommandSuggestionMatch<'a>>,
+    ) {
+        self.queue_packet(&CommandSuggestionResponse {
+            id: VarInt(transaction_id),
+            start: VarInt(start as i32),
+            length: VarInt(length as i32),
+            matches: suggestions.into_iter().collect(),
+        });
+    }
+
+    /// Shows an advancement toast in the top right corner of the client's
+    /// screen with the given icon and title. The frame type determines the
+    /// header and color of the toast.
+    ///
+    /// This is done by sending a hidden advancement that is immediately
+    /// completed and then removed, so it does not show up in the client's
+    /// advancements screen.
+    pub fn grant_toast(&mut self, icon: ItemStack, title: impl Into<Text>, frame: FrameType) {
+        let id = Ident::new(TOAST_ADVANCEMENT).unwrap();
+
+        let completed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as i64);
+
+        self.queue_packet(&UpdateAdvancements {
+            reset: false,
+            advancement_mapping: vec![(
+                id,
+                Advancement {
+                    parent_id: None,
+                    display_data: Some(AdvancementDisplay {
+                        title: title.into(),
+                        description: Text::default(),
+                        icon: Some(icon),
+                        frame_type: frame,
+                        // Show a toast and hide the advancement.
+                        flags: 0x02 | 0x04,
+                        background_texture: None,
+                        x_coord: 0.0,
+                        y_coord: 0.0,
+                    }),
+                    // The client ignores advancements without any requirements.
+                    criteria: vec![(id, ())],
+                    requirements: vec![AdvancementRequirements {
+                        requirement: vec![TOAST_ADVANCEMENT],
+                    }],
+                },
+            )],
+            identifiers: vec![],
+            progress_mapping: vec![(
+                id,
+                vec![AdvancementCriteria {
+                    criterion_identifier: id,
+                    criterion_progress: Some(completed_at),
+                }],
+            )],
+        });
+
+        self.queue_packet(&UpdateAdvancements {
+            reset: false,
+            advancement_mapping: vec![],
+            identifiers: vec![id],
+            progress_mapping: vec![],
+        });
+    }
+
+    /// Gets the progress on the XP bar in `0.0..=1.0`.
+    pub fn xp_bar(&self) -> f32 {
+        self.xp_bar
+    }
+
+    /// Gets the level displayed above the XP bar.
+    pub fn level(&self) -> i32 {
+        self.level
+    }
+
+    /// Gets the total experience of the client. This is displayed on the death
+    /// screen.
+    pub fn total_xp(&self) -> i32 {
+        self.total_xp
+    }
+
     /// Sets the XP bar visible above hotbar and total experience.
     ///
     /// # Arguments
-    /// * `bar` - Floating value in the range `0.0..=1.0` indicating progress on
-    ///   the XP bar.
-    /// * `level` - Number above the XP bar.
-    /// * `total_xp` - TODO.
+    /// * `bar` - Floating value indicating progress on the XP bar. Clamped to
+    ///   `0.0..=1.0`.
+    /// * `level` - Number above the XP bar. Clamped to be nonnegative.
+    /// * `total_xp` - The score displayed on the death screen. Clamped to be
+    ///   nonnegative.
+    pub fn set_experience(&mut self, bar: f32, level: i32, total_xp: i32) {
+        let bar = bar.clamp(0.0, 1.0);
+        let level = level.max(0);
+        let total_xp = total_xp.max(0);
+
+        if self.xp_bar != bar || self.level != level || self.total_xp != total_xp {
+            self.xp_bar = bar;
+            self.level = level;
+            self.total_xp = total_xp;
+            self.bits.set_experience_modified(true);
+        }
+    }
+
+    /// Equivalent to [`Self::set_experience`].
     pub fn set_level(&mut self, bar: f32, level: i32, total_xp: i32) {
-        self.queue_packet(&SetExperience {
-            bar,
-            level: level.into(),
-            total_xp: total_xp.into(),
-        })
+        self.set_experience(bar, level, total_xp);
     }
 
-    /// Sets the health and food of the player.
-    /// You can read more about hunger and saturation [here](https://minecraft.fandom.com/wiki/Food#Hunger_vs._Saturation).
+    /// Gets the health of the client in `0.0..=20.0`.
+    pub fn health(&self) -> f32 {
+        self.health
+    }
+
+    /// Sets the health of the client, clamped to `0.0..=20.0`.
+    ///
+    /// Setting the health to zero kills the client and displays the death
+    /// screen if [`Self::has_respawn_screen`] is `true`. Once the client
+    /// clicks the respawn button, [`ClientEvent::PerformRespawn`] is
+    /// received. The server is then responsible for placing the client, e.g.
+    /// with [`Self::respawn`] and [`Self::teleport`]. Respawning a dead client
+    /// restores its health, food and saturation to their maximums.
+    pub fn set_health(&mut self, health: f32) {
+        let health = health.clamp(0.0, MAX_HEALTH);
+
+        if self.health != health {
+            self.health = health;
+            self.bits.set_health_modified(true);
+        }
+    }
+
+    /// Gets the food level of the client in `0..=20`.
+    pub fn food(&self) -> i32 {
+        self.food
+    }
+
+    /// Gets the food saturation of the client in `0.0..=5.0`.
+    pub fn food_saturation(&self) -> f32 {
+        self.food_saturation
+    }
+
+    /// Sets the food level and saturation of the client. You can read more
+    /// about hunger and saturation [here](https://minecraft.fandom.com/wiki/Food#Hunger_vs._Saturation).
     ///
     /// # Arguments
-    /// * `health` - Float in range `0.0..=20.0`. Value `<=0` is legal and will
-    ///   kill the player.
-    /// * `food` - Integer in range `0..=20`.
-    /// * `food_saturation` - Float in range `0.0..=5.0`.
+    /// * `food` - Integer clamped to `0..=20`.
+    /// * `food_saturation` - Float clamped to `0.0..=5.0`.
+    pub fn set_food(&mut self, food: i32, food_saturation: f32) {
+        let food = food.clamp(0, MAX_FOOD);
+        let food_saturation = food_saturation.clamp(0.0, DEFAULT_FOOD_SATURATION);
+
+        if self.food != food || self.food_saturation != food_saturation {
+            self.food = food;
+            self.food_saturation = food_saturation;
+            self.bits.set_health_modified(true);
+        }
+    }
+
+    /// Sets the health and food of the player. Equivalent to calling
+    /// [`Self::set_health`] and [`Self::set_food`].
     pub fn set_health_and_food(&mut self, health: f32, food: i32, food_saturation: f32) {
-        self.queue_packet(&SetHealth {
-            health,
-            food: food.into(),
-            food_saturation,
-        })
+        self.set_health(health);
+        self.set_food(food, food_saturation);
     }
 
     /// Kills the client and shows `message` on the death screen. If an entity
@@ -753,6 +1385,12 @@ impl<C: Config> Client<C> {
         self.send.is_none()
     }
 
+    /// Returns why the client was disconnected, or `None` if the client is
+    /// still connected.
+    pub fn disconnect_reason(&self) -> Option<&DisconnectReason> {
+        self.disconnect_reason.as_ref()
+    }
+
     /// Sends an entity event for the client's own player data.
     pub fn send_entity_event(&mut self, event: entity::EntityEvent) {
         match event.status_or_animation() {
@@ -783,6 +1421,69 @@ impl<C: Config> Client<C> {
         self.view_distance = dist.clamp(2, 32);
     }
 
+    /// Returns the number of chunks in view of this client which are waiting
+    /// to be sent because of [`Config::max_chunks_per_tick`].
+    pub fn queued_chunk_count(&self) -> usize {
+        self.queued_chunks.len()
+    }
+
+    /// Returns the positions of the chunks which the server believes this
+    /// client has loaded as of the end of the previous tick. `worlds` must be
+    /// the worlds of the server this client is on.
+    ///
+    /// This is the server's internal view of the client and is intended for
+    /// debugging. It is an approximation derived from the client's previous
+    /// location and is not guaranteed to match what the client actually has
+    /// loaded.
+    pub fn loaded_chunks<'a>(
+        &'a self,
+        worlds: &'a Worlds<C>,
+    ) -> impl Iterator<Item = ChunkPos> + 'a {
+        let chunks = worlds.get(self.old_world).map(|w| &w.chunks);
+
+        ChunkPos::at(self.old_position.x, self.old_position.z)
+            .in_view(self.old_view_distance)
+            .filter(move |&pos| {
+                // Chunks created this tick have not been sent yet.
+                chunks
+                    .and_then(|chunks| chunks.get(pos))
+                    .is_some_and(|chunk| !chunk.created_this_tick())
+                    && !self.queued_chunks.contains(&pos)
+            })
+    }
+
+    /// Returns the entities which the server believes are spawned on this
+    /// client as of the end of the previous tick. `shared`, `entities` and
+    /// `worlds` must belong to the server this client is on.
+    ///
+    /// Like [`Self::loaded_chunks`], this is the server's internal view of the
+    /// client and is not guaranteed to match the actual client.
+    pub fn visible_entities<'a>(
+        &'a self,
+        shared: &'a SharedServer<C>,
+        entities: &'a Entities<C>,
+        worlds: &'a Worlds<C>,
+    ) -> impl Iterator<Item = EntityId> + 'a {
+        let world = worlds.get(self.old_world);
+        let center = ChunkPos::at(self.old_position.x, self.old_position.z);
+
+        center
+            .in_view(self.old_view_distance)
+            .filter_map(move |pos| {
+                let (_, cell) = world?.chunks.chunk_and_cell(pos)?;
+                Some(cell.entities().map(move |id| (pos, id)))
+            })
+            .flatten()
+            .filter(move |&(pos, id)| {
+                id != self.self_entity
+                    && entities.get(id).is_some_and(|entity| {
+                        let range = shared.entity_tracking_range(entity.kind());
+                        center.is_in_tracking_range(pos, self.old_view_distance, range)
+                    })
+            })
+            .map(|(_, id)| id)
+    }
+
     /// Enables hardcore mode. This changes the design of the client's hearts.
     ///
     /// To have any visible effect, this function must be called on the same
@@ -825,30 +1526,87 @@ impl<C: Config> Client<C> {
     ///
     /// To stop time from passing, the `time_of_day` parameter must be
     /// negative. The client stops the time at the absolute value.
+    ///
+    /// The time is overwritten when the client moves to another world or the
+    /// time of its world changes in a later tick. To change the time for all
+    /// clients in a world, use [`World::set_time_of_day`] instead.
+    ///
+    /// [`World::set_time_of_day`]: crate::world::World::set_time_of_day
     pub fn set_time(&mut self, world_age: i64, time_of_day: i64) {
         self.queue_packet(&UpdateTime {
             world_age,
             time_of_day,
         });
+        self.bits.set_time_set(true);
     }
 
     /// Disconnects this client from the server with the provided reason. This
-    /// has no effect if the client is already disconnected.
+    /// has no effect if the client is already disconnected, such as when the
+    /// client closed the connection on its own.
+    ///
+    /// The disconnect packet is sent along with any other queued packets and
+    /// the connection is closed shortly after. All future calls to
+    /// [`Self::is_disconnected`] will return `true`, so the client will be
+    /// removed by the usual cleanup of disconnected clients, e.g.
     ///
-    /// All future calls to [`Self::is_disconnected`] will return `true`.
+    /// ```
+    /// # use valence::prelude::*;
+    /// # fn f(clients: &mut Clients<impl Config>) {
+    /// clients.retain(|_, client| !client.is_disconnected());
+    /// # }
+    /// ```
     pub fn disconnect(&mut self, reason: impl Into<Text>) {
+        let reason = reason.into();
         self.queue_packet(&DisconnectPlay {
-            reason: reason.into(),
+            reason: reason.clone(),
         });
-        self.disconnect_abrupt();
+        self.set_disconnected(DisconnectReason::Kicked(reason));
+        self.recv.close();
+    }
+
+    /// Asks this client to connect to the server at `host` and `port` instead.
+    ///
+    /// Clients only support being transferred to another server with the
+    /// transfer packet as of Minecraft 1.20.5. Since this version of the
+    /// protocol predates that packet, the client is disconnected with a
+    /// message containing the address of the other server.
+    pub fn transfer(&mut self, host: &str, port: u16) {
+        self.disconnect(format!(
+            "You have been sent to another server. Please connect to {host}:{port}."
+        ));
     }
 
     /// Like [`Self::disconnect`], but no reason for the disconnect is
     /// sent to the client.
     pub fn disconnect_abrupt(&mut self) {
+        self.set_disconnected(DisconnectReason::Kicked(Text::default()));
+        self.recv.close();
+    }
+
+    /// Stops sending packets to the client and records the reason, unless the
+    /// client was already disconnected.
+    fn set_disconnected(&mut self, reason: DisconnectReason) {
+        self.disconnect_reason.get_or_insert(reason);
         self.send = None;
     }
 
+    pub(crate) fn disconnect_on_shutdown(&mut self) {
+        self.set_disconnected(DisconnectReason::ServerShutdown);
+        self.recv.close();
+    }
+
+    /// Called when the client is removed from [`Clients`].
+    fn left(&mut self) {
+        // The reason stays available from `disconnect_reason` in the callback.
+        let reason = self
+            .disconnect_reason
+            .get_or_insert(DisconnectReason::Kicked(Text::default()))
+            .clone();
+
+        let shared = self.shared.clone();
+        shared.config().client_left(&shared, self, reason);
+    }
+
     /// Returns an immutable reference to the client's own [`Player`] data.
     pub fn player(&self) -> &Player {
         &self.player_data
@@ -861,6 +1619,39 @@ impl<C: Config> Client<C> {
         &mut self.player_data
     }
 
+    /// Gets the hotbar slot the client has selected in `0..=8`. The
+    /// corresponding inventory slot is `36 + held_slot`.
+    pub fn held_slot(&self) -> u8 {
+        self.held_slot
+    }
+
+    /// Changes the hotbar slot the client has selected.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `slot` is not in `0..=8`.
+    pub fn set_held_slot(&mut self, slot: u8) {
+        assert!(slot <= 8, "held slot of {slot} is out of range");
+
+        if self.held_slot != slot {
+            self.held_slot = slot;
+            self.queue_packet(&SetHeldItemS2c { slot });
+        }
+    }
+
+    /// Puts all items of the given kind on cooldown for `ticks` ticks. Like in
+    /// vanilla, the cooldown applies to every item of the kind rather than a
+    /// single item stack. A cooldown of zero ticks removes the cooldown.
+    ///
+    /// The client only displays the cooldown. Uses of the item during the
+    /// cooldown must be prevented by the server.
+    pub fn set_cooldown(&mut self, item: ItemKind, ticks: u32) {
+        self.queue_packet(&SetCooldown {
+            item_id: VarInt(item.to_raw() as i32),
+            cooldown_ticks: VarInt(ticks.min(i32::MAX as u32) as i32),
+        });
+    }
+
     pub fn slot(&self, idx: u16) -> Option<&ItemStack> {
         self.slots
             .get(idx as usize)
@@ -928,7 +1719,7 @@ impl<C: Config> Client<C> {
                     ip = %self.ip,
                     "failed to get next event: {e:#}"
                 );
-                self.send = None;
+                self.set_disconnected(DisconnectReason::ProtocolError(format!("{e:#}")));
                 None
             }
         }
@@ -936,28 +1727,82 @@ impl<C: Config> Client<C> {
 
     pub(crate) fn prepare_c2s_packets(&mut self) {
         if !self.recv.try_recv() {
-            self.disconnect_abrupt();
+            let reason = match self.recv.io_error() {
+                Some(e) => DisconnectReason::ProtocolError(e),
+                None => DisconnectReason::ClientQuit,
+            };
+
+            self.set_disconnected(reason);
+            self.recv.close();
         }
     }
 
+    /// Called instead of [`Self::update`] while the server is paused.
+    /// `paused_ticks` is the number of updates since the server was paused.
+    ///
+    /// Keepalives continue to be sent so that the client does not time out,
+    /// but their responses are not required until the server resumes.
+    ///
+    /// Received packets are left in the incoming byte channel, so a client
+    /// which keeps sending is limited by [`Config::incoming_capacity`] until
+    /// the server resumes.
+    pub(crate) fn update_paused(&mut self, paused_ticks: Ticks, shared: &SharedServer<C>) {
+        // Nothing more can arrive once the connection is closed, so taking the
+        // remaining bytes only serves to notice the disconnect.
+        if self.recv.is_disconnected() {
+            self.prepare_c2s_packets();
+        }
+
+        // Clients which joined while paused have not been sent the join packet
+        // yet, so they cannot receive keepalives.
+        if self.bits.created_this_tick() || paused_ticks % (shared.tick_rate() * 10) != 0 {
+            return;
+        }
+
+        if let Some(send) = &mut self.send {
+            let id = rand::random();
+
+            match send
+                .append_packet(&KeepAliveS2c { id })
+                .and_then(|()| send.flush())
+            {
+                Ok(()) => self.paused_keepalive_ids.push(id),
+                Err(e) => {
+                    warn!(
+                        username = %self.username,
+                        uuid = %self.uuid,
+                        ip = %self.ip,
+                        "error updating paused client: {e:#}"
+                    );
+                    self.set_disconnected(DisconnectReason::from_send_error(&e));
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn update(
         &mut self,
+        id: ClientId,
         current_tick: Ticks,
         shared: &SharedServer<C>,
         entities: &Entities<C>,
         worlds: &Worlds<C>,
         player_lists: &PlayerLists<C>,
         inventories: &Inventories<C>,
+        boss_bars: &BossBars,
     ) {
         if let Some(mut send) = self.send.take() {
             match self.update_fallible(
                 &mut send,
+                id,
                 current_tick,
                 shared,
                 entities,
                 worlds,
                 player_lists,
                 inventories,
+                boss_bars,
             ) {
                 Ok(()) => self.send = Some(send),
                 Err(e) => {
@@ -968,6 +1813,7 @@ impl<C: Config> Client<C> {
                         ip = %self.ip,
                         "error updating client: {e:#}"
                     );
+                    self.set_disconnected(DisconnectReason::from_send_error(&e));
                 }
             }
         }
@@ -982,12 +1828,14 @@ impl<C: Config> Client<C> {
     fn update_fallible(
         &mut self,
         send: &mut PlayPacketSender,
+        id: ClientId,
         current_tick: Ticks,
         shared: &SharedServer<C>,
         entities: &Entities<C>,
         worlds: &Worlds<C>,
         player_lists: &PlayerLists<C>,
         inventories: &Inventories<C>,
+        boss_bars: &BossBars,
     ) -> anyhow::Result<()> {
         debug_assert!(self.entities_to_unload.is_empty());
 
@@ -997,6 +1845,13 @@ impl<C: Config> Client<C> {
 
         ensure!(!world.deleted(), "client is in a deleted world");
 
+        if let Some((dim, _)) = self.death_location {
+            ensure!(
+                shared.dimensions().any(|(id, _)| id == dim),
+                "client's death location is in an invalid dimension"
+            );
+        }
+
         // Send the login (play) packet and other initial packets. We defer this until
         // now so that the user can set the client's initial location, game
         // mode, etc.
@@ -1020,7 +1875,7 @@ impl<C: Config> Client<C> {
                 registry_codec: shared.registry_codec().clone(),
                 dimension_type_name: world.dimension().dimension_type_name(),
                 dimension_name: world.dimension().dimension_name(),
-                hashed_seed: 10,
+                hashed_seed: world.hashed_seed(),
                 max_players: VarInt(0), // Unused
                 view_distance: VarInt(self.view_distance() as i32),
                 simulation_distance: VarInt(16),
@@ -1033,16 +1888,38 @@ impl<C: Config> Client<C> {
                     .map(|(id, pos)| (id.dimension_name(), pos)),
             })?;
 
-            /*
-            // TODO: enable all the features?
+            self.scratch.clear();
+            shared.server_brand().encode(&mut self.scratch)?;
+
+            send.append_packet(&PluginMessageS2c {
+                channel: Ident::new("minecraft:brand").unwrap(),
+                data: RawBytes(&self.scratch),
+            })?;
+
             send.append_packet(&FeatureFlags {
-                features: vec![Ident::new("vanilla").unwrap()],
+                features: shared
+                    .feature_flags()
+                    .iter()
+                    .map(|flag| flag.as_str_ident())
+                    .collect(),
             })?;
-            */
 
             if let Some(id) = &self.player_list {
                 player_lists[id].write_init_packets(&mut *send)?;
             }
+
+            world.scoreboard.write_init_packets(&mut *send)?;
+            world.border.write_init_packets(&mut *send)?;
+
+            // The time and weather set on the client this tick take precedence
+            // over the world's. Clients join without rain.
+            if !self.bits.time_set() {
+                world.write_time_init_packets(&mut *send)?;
+            }
+
+            if !self.bits.weather_set() && world.rain_level() > 0.0 {
+                world.write_weather_init_packets(&mut *send)?;
+            }
         } else {
             if self.view_distance != self.old_view_distance {
                 // Change the render distance fog.
@@ -1052,10 +1929,20 @@ impl<C: Config> Client<C> {
             if self.bits.respawn() {
                 self.bits.set_respawn(false);
 
+                if self.health <= 0.0 {
+                    self.health = MAX_HEALTH;
+                    self.food = MAX_FOOD;
+                    self.food_saturation = DEFAULT_FOOD_SATURATION;
+                }
+
+                // The client resets its health and experience when respawning.
+                self.bits.set_health_modified(true);
+                self.bits.set_experience_modified(true);
+
                 send.append_packet(&RespawnOwned {
                     dimension_type_name: world.dimension().dimension_type_name(),
                     dimension_name: world.dimension().dimension_name(),
-                    hashed_seed: 0,
+                    hashed_seed: world.hashed_seed() as u64,
                     game_mode: self.game_mode(),
                     previous_game_mode: -1,
                     is_debug: false,
@@ -1084,6 +1971,83 @@ impl<C: Config> Client<C> {
                 // Otherwise, update current player list.
                 player_lists[id].write_update_packets(&mut *send)?;
             }
+
+            if self.old_world != self.world {
+                // Replace the scoreboard of the old world with the new one.
+                if let Some(old_world) = worlds.get(self.old_world) {
+                    old_world.scoreboard.write_clear_packets(&mut *send)?;
+                }
+
+                world.scoreboard.write_init_packets(&mut *send)?;
+                world.border.write_init_packets(&mut *send)?;
+                // The respawn packet resets the time and weather of the client.
+                world.write_time_init_packets(&mut *send)?;
+                world.write_weather_init_packets(&mut *send)?;
+            } else {
+                world.scoreboard.write_update_packets(&mut *send)?;
+                world.border.write_update_packets(&mut *send)?;
+
+                if !self.bits.time_set() {
+                    world.write_time_update_packets(&mut *send)?;
+                }
+
+                if !self.bits.weather_set() {
+                    world.write_weather_update_packets(&mut *send)?;
+                }
+            }
+        }
+
+        self.bits.set_time_set(false);
+        self.bits.set_weather_set(false);
+
+        if self.bits.health_modified() {
+            self.bits.set_health_modified(false);
+
+            send.append_packet(&SetHealth {
+                health: self.health,
+                food: VarInt(self.food),
+                food_saturation: self.food_saturation,
+            })?;
+        }
+
+        if self.bits.experience_modified() {
+            self.bits.set_experience_modified(false);
+
+            send.append_packet(&SetExperience {
+                bar: self.xp_bar,
+                level: VarInt(self.level),
+                total_xp: VarInt(self.total_xp),
+            })?;
+        }
+
+        for pos in self.blocks_to_resync.drain(..) {
+            if let Some(block) = world.chunks.block_state(pos) {
+                send.append_packet(&BlockUpdate {
+                    position: pos,
+                    block_id: VarInt(block.to_raw() as _),
+                })?;
+            }
+        }
+
+        world.write_particle_packets(self.position, &mut *send)?;
+
+        // Hide or update the boss bars the client is already displaying.
+        for &bar_id in &self.visible_boss_bars {
+            match boss_bars.get(bar_id) {
+                Some(bar) if bar.is_viewer(id) => bar.write_update_packets(bar_id, &mut *send)?,
+                _ => boss_bar::write_remove_packet(bar_id, &mut *send)?,
+            }
+        }
+
+        self.visible_boss_bars
+            .retain(|&bar_id| matches!(boss_bars.get(bar_id), Some(bar) if bar.is_viewer(id)));
+
+        // Show the boss bars the client has become a viewer of.
+        for (bar_id, bar) in boss_bars.iter() {
+            if bar.is_viewer(id) && !self.visible_boss_bars.contains(&bar_id) {
+                bar.write_add_packet(bar_id, &mut *send)?;
+                self.visible_boss_bars.push(bar_id);
+            }
         }
 
         // Check if it's time to send another keepalive.
@@ -1094,34 +2058,28 @@ impl<C: Config> Client<C> {
                 self.last_keepalive_id = id;
                 self.bits.set_got_keepalive(false);
             } else {
+                self.disconnect_reason
+                    .get_or_insert(DisconnectReason::Timeout);
                 bail!("timed out (no keepalive response)");
             }
         }
 
-        let self_entity_pos;
-        let self_entity_world;
-        let self_entity_range;
-
         // Get the entity with the same UUID as the client (if it exists).
-        if let Some(entity) = entities.get(self.self_entity) {
-            self_entity_pos = ChunkPos::at(entity.position().x, entity.position().z);
-            self_entity_world = entity.world();
-            self_entity_range = entity.self_update_range.clone();
-        } else if let Some(id) = entities.get_with_uuid(self.uuid) {
-            self.self_entity = id;
-            let entity = &entities[id];
-            self_entity_pos = ChunkPos::at(entity.position().x, entity.position().z);
-            self_entity_world = entity.world();
-            self_entity_range = entity.self_update_range.clone();
-        } else {
-            // There is no entity with the same UUID as the client.
-            self_entity_pos = ChunkPos::new(0, 0);
-            self_entity_world = WorldId::NULL;
-            self_entity_range = 0..0;
+        if entities.get(self.self_entity).is_none() {
+            self.self_entity = entities.get_with_uuid(self.uuid).unwrap_or(EntityId::NULL);
         }
 
         let old_chunk_pos = ChunkPos::at(self.old_position.x, self.old_position.z);
         let chunk_pos = ChunkPos::at(self.position.x, self.position.z);
+        let old_view_distance = self.old_view_distance;
+        let view_distance = self.view_distance;
+
+        // Checks if a client standing in `center` can see the entity `id` in
+        // the chunk at `pos`.
+        let entity_in_view = |center: ChunkPos, view_dist: u8, id: EntityId, pos: ChunkPos| {
+            let range = shared.entity_tracking_range(entities[id].kind());
+            center.is_in_tracking_range(pos, view_dist, range)
+        };
 
         // Make sure the center chunk is set before loading chunks!
         if old_chunk_pos != chunk_pos {
@@ -1134,14 +2092,20 @@ impl<C: Config> Client<C> {
 
         // Iterate over all visible chunks from the previous tick.
         if let Some(old_world) = worlds.get(self.old_world) {
-            old_chunk_pos.try_for_each_in_view(self.old_view_distance, |pos| {
+            old_chunk_pos.try_for_each_in_view(old_view_distance, |pos| {
                 if let Some((chunk, cell)) = old_world.chunks.chunk_and_cell(pos) {
                     if let Some(chunk) = chunk {
                         // Decide if the chunk should be loaded, unloaded, or updated.
                         match (chunk.created_this_tick(), chunk.deleted()) {
                             (false, false) => {
-                                // Update the chunk.
-                                chunk.write_block_change_packets(&mut *send)?;
+                                // Update the chunk. Queued chunks are up to date
+                                // once they are sent.
+                                if !self.queued_chunks.contains(&pos) {
+                                    chunk.write_block_change_packets(&mut *send)?;
+                                }
+                            }
+                            (true, false) if self.queued_chunks.contains(&pos) => {
+                                // The chunk will be loaded once it is dequeued.
                             }
                             (true, false) => {
                                 // Chunk needs initialization. Send packet to load it.
@@ -1158,6 +2122,10 @@ impl<C: Config> Client<C> {
                                 #[cfg(debug_assertions)]
                                 self.loaded_chunks.insert(pos);
                             }
+                            (false, true) if self.queued_chunks.remove(&pos) => {
+                                // Chunk was never sent, so there is nothing to
+                                // unload.
+                            }
                             (false, true) => {
                                 // Chunk was previously loaded and is now deleted.
                                 send.append_packet(&UnloadChunk {
@@ -1177,11 +2145,13 @@ impl<C: Config> Client<C> {
 
                     // Send entity spawn packets for entities entering the client's view.
                     for &(id, src_pos) in cell.incoming() {
-                        if src_pos.map_or(true, |p| {
-                            !old_chunk_pos.is_in_view(p, self.old_view_distance)
-                        }) {
-                            // The incoming entity originated from outside the view distance, so it
-                            // must be spawned.
+                        if entity_in_view(old_chunk_pos, old_view_distance, id, pos)
+                            && !src_pos.is_some_and(|p| {
+                                entity_in_view(old_chunk_pos, old_view_distance, id, p)
+                            })
+                        {
+                            // The incoming entity originated from outside the tracking range, so
+                            // it must be spawned.
                             let entity = &entities[id];
                             debug_assert!(!entity.deleted());
 
@@ -1201,24 +2171,46 @@ impl<C: Config> Client<C> {
                     // Send entity despawn packets for entities exiting the client's view.
                     for &(id, dest_pos) in cell.outgoing() {
                         if id != self.self_entity
-                            && dest_pos.map_or(true, |p| {
-                                !old_chunk_pos.is_in_view(p, self.old_view_distance)
+                            && entity_in_view(old_chunk_pos, old_view_distance, id, pos)
+                            && !dest_pos.is_some_and(|p| {
+                                entity_in_view(old_chunk_pos, old_view_distance, id, p)
                             })
                         {
-                            // The outgoing entity moved outside the view distance, so it must be
+                            // The outgoing entity moved outside the tracking range, so it must be
                             // despawned.
                             self.entities_to_unload.push(VarInt(id.to_raw()));
                         }
                     }
 
-                    // Update all the entities in the chunk.
-                    if pos == self_entity_pos && self.old_world == self_entity_world {
-                        // Don't update the entity with the same UUID as the client.
-                        let bytes = cell.cached_update_packets();
-                        send.append_bytes(&bytes[..self_entity_range.start]);
-                        send.append_bytes(&bytes[self_entity_range.end..]);
-                    } else {
-                        send.append_bytes(cell.cached_update_packets());
+                    // Update the visible entities in the chunk. The entity with the same UUID
+                    // as the client is not updated.
+                    let bytes = cell.cached_update_packets();
+
+                    for id in cell.entities() {
+                        if id != self.self_entity
+                            && entity_in_view(old_chunk_pos, old_view_distance, id, pos)
+                        {
+                            let entity = &entities[id];
+                            let update = &bytes[entity.self_update_range.clone()];
+
+                            if send.is_nearly_full() {
+                                // Relative movement and metadata changes are lost for good if
+                                // dropped, so the whole entity is resent later instead.
+                                if !update.is_empty() {
+                                    self.desynced_entities.insert(id);
+                                }
+                            } else {
+                                send.append_bytes(update);
+
+                                if self.desynced_entities.remove(&id) {
+                                    entity.write_resync_packets(
+                                        &mut *send,
+                                        id,
+                                        &mut self.scratch,
+                                    )?;
+                                }
+                            }
+                        }
                     }
                 }
 
@@ -1233,19 +2225,26 @@ impl<C: Config> Client<C> {
             }
         }
 
+        // Entities which are spawned again get their complete state anyway.
+        if !self.desynced_entities.is_empty() {
+            self.desynced_entities
+                .retain(|&id| entities.get(id).is_some());
+        }
+
         if self.old_world != self.world {
             // Client changed the world they're in.
+            self.desynced_entities.clear();
 
             // Unload all chunks and entities in old view.
             if let Some(old_world) = worlds.get(self.old_world) {
                 // TODO: only send unload packets when old dimension == new dimension, since the
                 //       client will do the unloading for us in that case?
 
-                old_chunk_pos.try_for_each_in_view(self.old_view_distance, |pos| {
+                old_chunk_pos.try_for_each_in_view(old_view_distance, |pos| {
                     if let Some((chunk, cell)) = old_world.chunks.chunk_and_cell(pos) {
                         if let Some(chunk) = chunk {
                             // Deleted chunks were already unloaded above.
-                            if !chunk.deleted() {
+                            if !chunk.deleted() && !self.queued_chunks.remove(&pos) {
                                 send.append_packet(&UnloadChunk {
                                     chunk_x: pos.x,
                                     chunk_z: pos.z,
@@ -1258,7 +2257,10 @@ impl<C: Config> Client<C> {
 
                         self.entities_to_unload.extend(
                             cell.entities()
-                                .filter(|&id| id != self.self_entity)
+                                .filter(|&id| {
+                                    id != self.self_entity
+                                        && entity_in_view(old_chunk_pos, old_view_distance, id, pos)
+                                })
                                 .map(|id| VarInt(id.to_raw())),
                         );
                     }
@@ -1274,20 +2276,14 @@ impl<C: Config> Client<C> {
                 }
             }
 
+            self.queued_chunks.clear();
+
             // Load all chunks and entities in new view.
-            chunk_pos.try_for_each_in_view(self.view_distance, |pos| {
+            chunk_pos.try_for_each_in_view(view_distance, |pos| {
                 if let Some((chunk, cell)) = world.chunks.chunk_and_cell(pos) {
                     if let Some(chunk) = chunk {
                         if !chunk.deleted() {
-                            chunk.write_chunk_data_packet(
-                                &mut *send,
-                                &mut self.scratch,
-                                pos,
-                                &world.chunks,
-                            )?;
-
-                            #[cfg(debug_assertions)]
-                            assert!(self.loaded_chunks.insert(pos));
+                            self.queued_chunks.insert(pos);
                         }
                     }
 
@@ -1295,7 +2291,9 @@ impl<C: Config> Client<C> {
                         let entity = &entities[id];
                         debug_assert!(!entity.deleted());
 
-                        if entity.uuid() != self.uuid {
+                        if entity.uuid() != self.uuid
+                            && entity_in_view(chunk_pos, view_distance, id, pos)
+                        {
                             entity.send_init_packets(
                                 send,
                                 entity.position(),
@@ -1308,34 +2306,40 @@ impl<C: Config> Client<C> {
 
                 Ok(())
             })?;
-        } else if old_chunk_pos != chunk_pos || self.old_view_distance != self.view_distance {
+        } else if old_chunk_pos != chunk_pos || old_view_distance != view_distance {
             // Client changed their view without changing the world.
-            // We need to unload chunks and entities in the old view and load
-            // chunks and entities in the new view. We don't need to do any
-            // work where the old and new view overlap.
+            // We need to unload chunks in the old view and load chunks in the
+            // new view. We don't need to do any work for chunks where the old
+            // and new view overlap. Entities in the overlap may still enter
+            // or leave their tracking range.
 
-            old_chunk_pos.try_for_each_in_view(self.old_view_distance, |pos| {
-                if !pos.is_in_view(chunk_pos, self.view_distance) {
-                    if let Some((chunk, cell)) = world.chunks.chunk_and_cell(pos) {
-                        if let Some(chunk) = chunk {
-                            // Deleted chunks were already unloaded above.
-                            if !chunk.deleted() {
-                                send.append_packet(&UnloadChunk {
-                                    chunk_x: pos.x,
-                                    chunk_z: pos.z,
-                                })?;
+            old_chunk_pos.try_for_each_in_view(old_view_distance, |pos| {
+                if let Some((chunk, cell)) = world.chunks.chunk_and_cell(pos) {
+                    if let Some(chunk) = chunk {
+                        // Deleted chunks were already unloaded above.
+                        if !pos.is_in_view(chunk_pos, view_distance)
+                            && !chunk.deleted()
+                            && !self.queued_chunks.remove(&pos)
+                        {
+                            send.append_packet(&UnloadChunk {
+                                chunk_x: pos.x,
+                                chunk_z: pos.z,
+                            })?;
 
-                                #[cfg(debug_assertions)]
-                                assert!(self.loaded_chunks.remove(&pos));
-                            }
+                            #[cfg(debug_assertions)]
+                            assert!(self.loaded_chunks.remove(&pos));
                         }
-
-                        self.entities_to_unload.extend(
-                            cell.entities()
-                                .filter(|&id| id != self.self_entity)
-                                .map(|id| VarInt(id.to_raw())),
-                        );
                     }
+
+                    self.entities_to_unload.extend(
+                        cell.entities()
+                            .filter(|&id| {
+                                id != self.self_entity
+                                    && entity_in_view(old_chunk_pos, old_view_distance, id, pos)
+                                    && !entity_in_view(chunk_pos, view_distance, id, pos)
+                            })
+                            .map(|id| VarInt(id.to_raw())),
+                    );
                 }
 
                 Ok(())
@@ -1348,35 +2352,28 @@ impl<C: Config> Client<C> {
                 self.entities_to_unload.clear();
             }
 
-            chunk_pos.try_for_each_in_view(self.view_distance, |pos| {
-                if !pos.is_in_view(old_chunk_pos, self.old_view_distance) {
-                    if let Some((chunk, cell)) = world.chunks.chunk_and_cell(pos) {
-                        if let Some(chunk) = chunk {
-                            if !chunk.deleted() {
-                                chunk.write_chunk_data_packet(
-                                    &mut *send,
-                                    &mut self.scratch,
-                                    pos,
-                                    &world.chunks,
-                                )?;
-
-                                #[cfg(debug_assertions)]
-                                assert!(self.loaded_chunks.insert(pos));
-                            }
+            chunk_pos.try_for_each_in_view(view_distance, |pos| {
+                if let Some((chunk, cell)) = world.chunks.chunk_and_cell(pos) {
+                    if let Some(chunk) = chunk {
+                        if !pos.is_in_view(old_chunk_pos, old_view_distance) && !chunk.deleted() {
+                            self.queued_chunks.insert(pos);
                         }
+                    }
 
-                        for id in cell.entities() {
-                            let entity = &entities[id];
-                            debug_assert!(!entity.deleted());
+                    for id in cell.entities() {
+                        let entity = &entities[id];
+                        debug_assert!(!entity.deleted());
 
-                            if entity.uuid() != self.uuid {
-                                entity.send_init_packets(
-                                    send,
-                                    entity.position(),
-                                    id,
-                                    &mut self.scratch,
-                                )?;
-                            }
+                        if entity.uuid() != self.uuid
+                            && entity_in_view(chunk_pos, view_distance, id, pos)
+                            && !entity_in_view(old_chunk_pos, old_view_distance, id, pos)
+                        {
+                            entity.send_init_packets(
+                                send,
+                                entity.position(),
+                                id,
+                                &mut self.scratch,
+                            )?;
                         }
                     }
                 }
@@ -1385,13 +2382,47 @@ impl<C: Config> Client<C> {
             })?;
         }
 
+        // Send the queued chunks nearest to the client first.
+        if !self.queued_chunks.is_empty() {
+            let mut queued: Vec<_> = self.queued_chunks.iter().copied().collect();
+            queued
+                .sort_unstable_by_key(|p| (p.x - chunk_pos.x).pow(2) + (p.z - chunk_pos.z).pow(2));
+
+            let mut budget = shared.max_chunks_per_tick();
+
+            for pos in queued {
+                if budget == 0 {
+                    break;
+                }
+
+                self.queued_chunks.remove(&pos);
+
+                if let Some(chunk) = world.chunks.get(pos) {
+                    if !chunk.deleted() {
+                        chunk.write_chunk_data_packet(
+                            &mut *send,
+                            &mut self.scratch,
+                            pos,
+                            &world.chunks,
+                        )?;
+
+                        #[cfg(debug_assertions)]
+                        assert!(self.loaded_chunks.insert(pos));
+
+                        budget -= 1;
+                    }
+                }
+            }
+        }
+
         if self.bits.created_this_tick() {
             // This closes the "downloading terrain" screen.
             // Send this after the initial chunks are loaded.
-            send.append_packet(&SetDefaultSpawnPosition {
-                position: BlockPos::at(self.position.into_array()),
-                angle: self.yaw,
-            })?;
+            let (position, angle) = self
+                .spawn_position
+                .unwrap_or((BlockPos::at(self.position.into_array()), self.yaw));
+
+            send.append_packet(&SetDefaultSpawnPosition { position, angle })?;
         }
 
         // Update the client's own player metadata.
@@ -1509,3 +2540,40 @@ impl<C: Config> Client<C> {
         Ok(())
     }
 }
+
+fn title_animation_times(
+    fade_in: Ticks,
+    stay: Ticks,
+    fade_out: Ticks,
+) -> anyhow::Result<SetTitleAnimationTimes> {
+    let convert = |ticks: Ticks, name: &str| {
+        ensure!(
+            ticks >= 0,
+            "title {name} duration of {ticks} ticks is negative"
+        );
+        i32::try_from(ticks).with_context(|| format!("title {name} duration is too long"))
+    };
+
+    Ok(SetTitleAnimationTimes {
+        fade_in: convert(fade_in, "fade in")?,
+        stay: convert(stay, "stay")?,
+        fade_out: convert(fade_out, "fade out")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn title_animation_times_validated() {
+        let anim = title_animation_times(10, 70, 20).unwrap();
+        assert_eq!((anim.fade_in, anim.stay, anim.fade_out), (10, 70, 20));
+
+        assert!(title_animation_times(0, 0, 0).is_ok());
+        assert!(title_animation_times(-1, 70, 20).is_err());
+        assert!(title_animation_times(10, -70, 20).is_err());
+        assert!(title_animation_times(10, 70, -1).is_err());
+        assert!(title_animation_times(10, i32::MAX as Ticks + 1, 20).is_err());
+    }
+}
@@ -1,6 +1,6 @@
 use std::cmp;
 
-use anyhow::bail;
+use anyhow::{bail, ensure};
 use uuid::Uuid;
 use valence_protocol::entity_meta::Pose;
 use valence_protocol::packets::c2s::play::{
@@ -12,10 +12,10 @@ use valence_protocol::types::{
     DisplayedSkinParts, EntityInteraction, Hand, MainHand, RecipeBookId, StructureBlockAction,
     StructureBlockFlags, StructureBlockMirror, StructureBlockMode, StructureBlockRotation,
 };
-use valence_protocol::{BlockFace, BlockPos, Ident, ItemStack, VarLong};
+use valence_protocol::{BlockFace, BlockPos, Decode, Ident, ItemStack, RawPacket, VarLong};
 
 use crate::client::Client;
-use crate::config::Config;
+use crate::config::{Config, UnknownPacketPolicy};
 use crate::entity::{Entity, EntityEvent, TrackedData};
 
 /// A discrete action performed by a client.
@@ -35,6 +35,8 @@ pub enum ClientEvent {
         last_seen: Vec<(Uuid, Box<[u8]>)>,
         last_received: Option<(Uuid, Box<[u8]>)>,
     },
+    /// A command typed by the client, without the leading `/`. See
+    /// [`CommandTree`](crate::command::CommandTree).
     ChatCommand {
         command: Box<str>,
         timestamp: u64,
@@ -44,6 +46,9 @@ pub enum ClientEvent {
         timestamp: u64,
     },
     ChatPreview,
+    /// Sent when the client clicks the respawn button after dying, or
+    /// immediately after dying if the respawn screen is disabled. See
+    /// [`Client::set_health`].
     PerformRespawn,
     RequestStats,
     UpdateSettings {
@@ -61,6 +66,11 @@ pub enum ClientEvent {
         enable_text_filtering: bool,
         allow_server_listings: bool,
     },
+    /// The client requested suggestions for an argument with
+    /// [`Suggestion::AskServer`]. Respond with
+    /// [`Client::send_command_suggestions`].
+    ///
+    /// [`Suggestion::AskServer`]: crate::command::Suggestion::AskServer
     CommandSuggestionsRequest {
         transaction_id: i32,
         text: Box<str>,
@@ -220,6 +230,8 @@ pub enum ClientEvent {
         primary_effect: Option<i32>,
         secondary_effect: Option<i32>,
     },
+    /// The client selected a different hotbar slot. The new slot is in
+    /// `0..=8` and is also returned by [`Client::held_slot`].
     SetHeldItem {
         slot: i16,
     },
@@ -288,16 +300,74 @@ pub enum ClientEvent {
         hand: Hand,
         sequence: i32,
     },
+    /// A packet with an ID the server does not recognize. Only sent if
+    /// [`Config::unknown_packet_policy`] returns
+    /// [`UnknownPacketPolicy::Forward`].
+    ///
+    /// Custom packets can be decoded from `data` with [`Decode`]. See the
+    /// [`protocol`](crate::protocol) module for an example of defining
+    /// custom packets.
+    ///
+    /// [`Decode`]: crate::protocol::Decode
+    UnknownPacket {
+        packet_id: i32,
+        /// The contents of the packet following the packet ID.
+        data: Vec<u8>,
+    },
+}
+
+/// A block interaction reported by a client. These are passed to
+/// [`Config::on_block_change`] before the corresponding [`ClientEvent`] is
+/// generated.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BlockChange {
+    /// The client started digging a block. Blocks that break instantly (and
+    /// all blocks in creative mode) are broken without a subsequent
+    /// [`Self::FinishDigging`].
+    StartDigging { position: BlockPos, face: BlockFace },
+    /// The client finished digging a block in survival mode.
+    FinishDigging { position: BlockPos, face: BlockFace },
+    /// The client used an item on a block, which may place a block against
+    /// the given face.
+    Place {
+        hand: Hand,
+        position: BlockPos,
+        face: BlockFace,
+    },
 }
 
 pub(super) fn next_event_fallible<C: Config>(
     client: &mut Client<C>,
 ) -> anyhow::Result<Option<ClientEvent>> {
     loop {
-        let Some(pkt) = client.recv.try_next_packet::<C2sPlayPacket>()? else {
+        let Some(RawPacket { id, data }) = client.recv.try_next_raw_packet()? else {
             return Ok(None)
         };
 
+        let mut r = data;
+
+        let Some(pkt) = C2sPlayPacket::decode_with_id(id.0, &mut r)? else {
+            match client.shared.unknown_packet_policy() {
+                UnknownPacketPolicy::Reject => bail!("unknown packet ID {:#02x}", id.0),
+                UnknownPacketPolicy::Forward => {
+                    return Ok(Some(ClientEvent::UnknownPacket {
+                        packet_id: id.0,
+                        data: data.to_vec(),
+                    }))
+                }
+                UnknownPacketPolicy::Skip => {
+                    client.skipped_packets += 1;
+                    continue;
+                }
+            }
+        };
+
+        ensure!(
+            r.is_empty(),
+            "packet contents were not read completely ({} bytes remain)",
+            r.len()
+        );
+
         return Ok(Some(match pkt {
             C2sPlayPacket::ConfirmTeleport(p) => {
                 if client.pending_teleports == 0 {
@@ -346,16 +416,20 @@ pub(super) fn next_event_fallible<C: Config>(
                 ClientCommand::PerformRespawn => ClientEvent::PerformRespawn,
                 ClientCommand::RequestStats => ClientEvent::RequestStats,
             },
-            C2sPlayPacket::ClientInformation(p) => ClientEvent::UpdateSettings {
-                locale: p.locale.into(),
-                view_distance: p.view_distance,
-                chat_mode: p.chat_mode,
-                chat_colors: p.chat_colors,
-                displayed_skin_parts: p.displayed_skin_parts,
-                main_hand: p.main_hand,
-                enable_text_filtering: p.enable_text_filtering,
-                allow_server_listings: p.allow_server_listings,
-            },
+            C2sPlayPacket::ClientInformation(p) => {
+                client.tally.record_locale(p.locale);
+
+                ClientEvent::UpdateSettings {
+                    locale: p.locale.into(),
+                    view_distance: p.view_distance,
+                    chat_mode: p.chat_mode,
+                    chat_colors: p.chat_colors,
+                    displayed_skin_parts: p.displayed_skin_parts,
+                    main_hand: p.main_hand,
+                    enable_text_filtering: p.enable_text_filtering,
+                    allow_server_listings: p.allow_server_listings,
+                }
+            }
             C2sPlayPacket::CommandSuggestionsRequest(p) => ClientEvent::CommandSuggestionsRequest {
                 transaction_id: p.transaction_id.0,
                 text: p.text.into(),
@@ -365,12 +439,27 @@ pub(super) fn next_event_fallible<C: Config>(
                 button_id: p.button_id,
             },
             C2sPlayPacket::ClickContainer(p) => {
+                if p.window_id != 0
+                    && (client.open_inventory.is_none() || p.window_id != client.window_id)
+                {
+                    // The click is for a window that has since been closed or replaced.
+                    continue;
+                }
+
                 // TODO: check that the slot modifications are legal.
                 // TODO: update cursor item.
+                // TODO: update the player's inventory when clicking in other windows.
 
-                for (idx, item) in &p.slots {
-                    // TODO: check bounds on indices.
-                    client.slots[*idx as usize] = item.clone();
+                if p.window_id == 0 {
+                    for (idx, item) in &p.slots {
+                        let Some(slot) = usize::try_from(*idx)
+                            .ok()
+                            .and_then(|idx| client.slots.get_mut(idx)) else {
+                            bail!("invalid slot index of {idx} in click container packet")
+                        };
+
+                        *slot = item.clone();
+                    }
                 }
 
                 ClientEvent::ClickContainer {
@@ -391,10 +480,19 @@ pub(super) fn next_event_fallible<C: Config>(
                     window_id: p.window_id,
                 }
             }
-            C2sPlayPacket::PluginMessageC2s(p) => ClientEvent::PluginMessage {
-                channel: p.channel.into(),
-                data: p.data.0.into(),
-            },
+            C2sPlayPacket::PluginMessageC2s(p) => {
+                if p.channel.as_str() == "minecraft:brand" {
+                    let mut r = p.data.0;
+                    let brand = <&str>::decode(&mut r)?;
+                    client.tally.record_brand(brand);
+                    client.brand = Some(brand.into());
+                }
+
+                ClientEvent::PluginMessage {
+                    channel: p.channel.into(),
+                    data: p.data.0.into(),
+                }
+            }
             C2sPlayPacket::EditBook(p) => ClientEvent::EditBook {
                 slot: p.slot.0,
                 entries: p.entries.into_iter().map(From::from).collect(),
@@ -415,7 +513,13 @@ pub(super) fn next_event_fallible<C: Config>(
                 keep_jigsaws: p.keep_jigsaws,
             },
             C2sPlayPacket::KeepAliveC2s(p) => {
-                if client.bits.got_keepalive() {
+                if let Some(idx) = client
+                    .paused_keepalive_ids
+                    .iter()
+                    .position(|&id| id == p.id)
+                {
+                    client.paused_keepalive_ids.swap_remove(idx);
+                } else if client.bits.got_keepalive() {
                     bail!("unexpected keepalive");
                 } else if p.id != client.last_keepalive_id {
                     bail!(
@@ -431,7 +535,7 @@ pub(super) fn next_event_fallible<C: Config>(
             }
             C2sPlayPacket::LockDifficulty(p) => ClientEvent::LockDifficulty(p.0),
             C2sPlayPacket::SetPlayerPosition(p) => {
-                if client.pending_teleports != 0 {
+                if client.pending_teleports != 0 || !accept_movement(client, p.position) {
                     continue;
                 }
 
@@ -443,7 +547,7 @@ pub(super) fn next_event_fallible<C: Config>(
                 }
             }
             C2sPlayPacket::SetPlayerPositionAndRotation(p) => {
-                if client.pending_teleports != 0 {
+                if client.pending_teleports != 0 || !accept_movement(client, p.position) {
                     continue;
                 }
 
@@ -520,8 +624,14 @@ pub(super) fn next_event_fallible<C: Config>(
                 make_all: p.make_all,
             },
             C2sPlayPacket::PlayerAbilitiesC2s(p) => match p {
-                PlayerAbilitiesC2s::StopFlying => ClientEvent::StopFlying,
-                PlayerAbilitiesC2s::StartFlying => ClientEvent::StartFlying,
+                PlayerAbilitiesC2s::StopFlying => {
+                    client.abilities.set_flying(false);
+                    ClientEvent::StopFlying
+                }
+                PlayerAbilitiesC2s::StartFlying => {
+                    client.abilities.set_flying(true);
+                    ClientEvent::StartFlying
+                }
             },
             C2sPlayPacket::PlayerAction(p) => {
                 if p.sequence.0 != 0 {
@@ -530,21 +640,43 @@ pub(super) fn next_event_fallible<C: Config>(
                 }
 
                 match p.status {
-                    DiggingStatus::StartedDigging => ClientEvent::StartDigging {
-                        position: p.position,
-                        face: p.face,
-                        sequence: p.sequence.0,
-                    },
+                    DiggingStatus::StartedDigging => {
+                        let change = BlockChange::StartDigging {
+                            position: p.position,
+                            face: p.face,
+                        };
+
+                        if !accept_block_change(client, change) {
+                            continue;
+                        }
+
+                        ClientEvent::StartDigging {
+                            position: p.position,
+                            face: p.face,
+                            sequence: p.sequence.0,
+                        }
+                    }
                     DiggingStatus::CancelledDigging => ClientEvent::CancelDigging {
                         position: p.position,
                         face: p.face,
                         sequence: p.sequence.0,
                     },
-                    DiggingStatus::FinishedDigging => ClientEvent::FinishDigging {
-                        position: p.position,
-                        face: p.face,
-                        sequence: p.sequence.0,
-                    },
+                    DiggingStatus::FinishedDigging => {
+                        let change = BlockChange::FinishDigging {
+                            position: p.position,
+                            face: p.face,
+                        };
+
+                        if !accept_block_change(client, change) {
+                            continue;
+                        }
+
+                        ClientEvent::FinishDigging {
+                            position: p.position,
+                            face: p.face,
+                            sequence: p.sequence.0,
+                        }
+                    }
                     DiggingStatus::DropItemStack => ClientEvent::DropItemStack,
                     DiggingStatus::DropItem => ClientEvent::DropItem,
                     DiggingStatus::UpdateHeldItemState => ClientEvent::UpdateHeldItemState,
@@ -594,7 +726,16 @@ pub(super) fn next_event_fallible<C: Config>(
                 primary_effect: p.primary_effect.map(|i| i.0),
                 secondary_effect: p.secondary_effect.map(|i| i.0),
             },
-            C2sPlayPacket::SetHeldItemC2s(p) => ClientEvent::SetHeldItem { slot: p.slot },
+            C2sPlayPacket::SetHeldItemC2s(p) => {
+                ensure!(
+                    (0..=8).contains(&p.slot),
+                    "invalid held item slot of {}",
+                    p.slot
+                );
+
+                client.held_slot = p.slot as u8;
+                ClientEvent::SetHeldItem { slot: p.slot }
+            }
             C2sPlayPacket::ProgramCommandBlock(p) => ClientEvent::ProgramCommandBlock {
                 position: p.position,
                 command: p.command.into(),
@@ -650,6 +791,16 @@ pub(super) fn next_event_fallible<C: Config>(
                         cmp::max(p.sequence.0, client.block_change_sequence);
                 }
 
+                let change = BlockChange::Place {
+                    hand: p.hand,
+                    position: p.position,
+                    face: p.face,
+                };
+
+                if !accept_block_change(client, change) {
+                    continue;
+                }
+
                 ClientEvent::UseItemOnBlock {
                     hand: p.hand,
                     position: p.position,
@@ -674,6 +825,50 @@ pub(super) fn next_event_fallible<C: Config>(
     }
 }
 
+/// Checks a block interaction with [`Config::on_block_change`]. If the change
+/// is rejected, the affected blocks are re-sent to the client at the end of the
+/// tick to undo its prediction.
+fn accept_block_change<C: Config>(client: &mut Client<C>, change: BlockChange) -> bool {
+    if client.shared.config().on_block_change(client, change) {
+        true
+    } else {
+        match change {
+            BlockChange::StartDigging { position, .. }
+            | BlockChange::FinishDigging { position, .. } => {
+                client.blocks_to_resync.push(position);
+            }
+            BlockChange::Place { position, face, .. } => {
+                // The placed block may either replace the clicked block or
+                // appear next to it.
+                client.blocks_to_resync.push(position);
+                client
+                    .blocks_to_resync
+                    .push(position.get_in_direction(face));
+            }
+        }
+
+        false
+    }
+}
+
+/// Checks a position reported by the client with
+/// [`Config::validate_movement`]. If the movement is rejected, the client is
+/// teleported back to its last accepted position.
+fn accept_movement<C: Config>(client: &mut Client<C>, new: [f64; 3]) -> bool {
+    let old = client.position;
+
+    if client
+        .shared
+        .config()
+        .validate_movement(client, old, new.into())
+    {
+        true
+    } else {
+        client.teleport(old, client.yaw, client.pitch);
+        false
+    }
+}
+
 impl ClientEvent {
     /// Takes a client event, a client, and an entity representing the client
     /// and expresses the event in a reasonable way.
@@ -0,0 +1,264 @@
+//! Command trees used by clients for command autocompletion.
+
+use valence_protocol::packets::s2c::commands::{Node, NodeData};
+pub use valence_protocol::packets::s2c::commands::{Parser, StringArg, Suggestion};
+use valence_protocol::packets::s2c::play::Commands;
+pub use valence_protocol::types::CommandSuggestionMatch;
+use valence_protocol::VarInt;
+
+/// The commands known to a client, in the form of a [Brigadier] command
+/// graph.
+///
+/// The client uses the tree to autocomplete and validate commands as they are
+/// typed, but has no knowledge of what the commands do. Commands sent by the
+/// client are received as [`ClientEvent::ChatCommand`] and must be executed by
+/// the server. The tree can be sent with [`Client::send_command_tree`], usually
+/// when the client joins.
+///
+/// ```
+/// use valence::command::{CommandTree, Parser};
+///
+/// let mut tree = CommandTree::new();
+///
+/// // `/teleport <x> <y> <z>`
+/// let teleport = tree.add_literal(tree.root(), "teleport");
+/// let pos = tree.add_argument(teleport, "position", Parser::Vec3);
+/// tree.set_executable(pos, true);
+///
+/// // `/tp` is an alias for `/teleport`.
+/// let tp = tree.add_literal(tree.root(), "tp");
+/// tree.set_redirect(tp, Some(teleport));
+/// ```
+///
+/// [Brigadier]: https://github.com/Mojang/brigadier
+/// [`ClientEvent::ChatCommand`]: crate::client::ClientEvent::ChatCommand
+/// [`Client::send_command_tree`]: crate::client::Client::send_command_tree
+#[derive(Clone, Debug)]
+pub struct CommandTree {
+    nodes: Vec<CommandNode>,
+}
+
+/// Identifies a node in a [`CommandTree`]. IDs are only meaningful for the
+/// tree that created them.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct CommandNodeId(usize);
+
+#[derive(Clone, Debug)]
+struct CommandNode {
+    kind: NodeKind,
+    children: Vec<CommandNodeId>,
+    executable: bool,
+    redirect: Option<CommandNodeId>,
+}
+
+#[derive(Clone, Debug)]
+enum NodeKind {
+    Root,
+    Literal(String),
+    Argument {
+        name: String,
+        parser: Parser<'static>,
+        suggestion: Option<Suggestion>,
+    },
+}
+
+impl CommandTree {
+    /// Creates a tree containing only the root node.
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![CommandNode {
+                kind: NodeKind::Root,
+                children: vec![],
+                executable: false,
+                redirect: None,
+            }],
+        }
+    }
+
+    /// Returns the root node of the tree. The children of the root are the
+    /// names of the available commands.
+    pub fn root(&self) -> CommandNodeId {
+        CommandNodeId(0)
+    }
+
+    /// Adds a literal node matching `name` exactly as a child of `parent` and
+    /// returns its ID.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parent` is not a node in this tree.
+    pub fn add_literal(&mut self, parent: CommandNodeId, name: impl Into<String>) -> CommandNodeId {
+        self.add_node(parent, NodeKind::Literal(name.into()))
+    }
+
+    /// Adds an argument node parsed by `parser` as a child of `parent` and
+    /// returns its ID. `name` is displayed to the client while typing the
+    /// argument.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parent` is not a node in this tree.
+    pub fn add_argument(
+        &mut self,
+        parent: CommandNodeId,
+        name: impl Into<String>,
+        parser: Parser<'static>,
+    ) -> CommandNodeId {
+        self.add_node(
+            parent,
+            NodeKind::Argument {
+                name: name.into(),
+                parser,
+                suggestion: None,
+            },
+        )
+    }
+
+    fn add_node(&mut self, parent: CommandNodeId, kind: NodeKind) -> CommandNodeId {
+        assert!(parent.0 < self.nodes.len(), "invalid command node ID");
+
+        let id = CommandNodeId(self.nodes.len());
+
+        self.nodes.push(CommandNode {
+            kind,
+            children: vec![],
+            executable: false,
+            redirect: None,
+        });
+
+        self.nodes[parent.0].children.push(id);
+        id
+    }
+
+    /// Sets whether the command typed so far is complete when it ends at
+    /// `node`. The client refuses to send commands that do not end at an
+    /// executable node.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node` is not a node in this tree.
+    pub fn set_executable(&mut self, node: CommandNodeId, executable: bool) {
+        self.nodes[node.0].executable = executable;
+    }
+
+    /// Sets the node that parsing continues from after `node`. This is used
+    /// for aliases and for commands such as `/execute` that accept other
+    /// commands.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node` or `target` is not a node in this tree.
+    pub fn set_redirect(&mut self, node: CommandNodeId, target: Option<CommandNodeId>) {
+        if let Some(target) = target {
+            assert!(target.0 < self.nodes.len(), "invalid command node ID");
+        }
+
+        self.nodes[node.0].redirect = target;
+    }
+
+    /// Sets where the client gets suggestions for the argument at `node` from.
+    /// With [`Suggestion::AskServer`], the client sends
+    /// [`ClientEvent::CommandSuggestionsRequest`] while the argument is typed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node` is not an argument node in this tree.
+    ///
+    /// [`ClientEvent::CommandSuggestionsRequest`]: crate::client::ClientEvent::CommandSuggestionsRequest
+    pub fn set_suggestion(&mut self, node: CommandNodeId, suggestion: Option<Suggestion>) {
+        match &mut self.nodes[node.0].kind {
+            NodeKind::Argument { suggestion: s, .. } => *s = suggestion,
+            _ => panic!("suggestions can only be set on argument nodes"),
+        }
+    }
+
+    pub(crate) fn packet(&self) -> Commands<'_> {
+        let to_var_int = |id: CommandNodeId| VarInt(id.0 as i32);
+
+        Commands {
+            commands: self
+                .nodes
+                .iter()
+                .map(|node| Node {
+                    children: node.children.iter().copied().map(to_var_int).collect(),
+                    data: match &node.kind {
+                        NodeKind::Root => NodeData::Root,
+                        NodeKind::Literal(name) => NodeData::Literal { name },
+                        NodeKind::Argument {
+                            name,
+                            parser,
+                            suggestion,
+                        } => NodeData::Argument {
+                            name,
+                            parser: parser.clone(),
+                            suggestion: *suggestion,
+                        },
+                    },
+                    executable: node.executable,
+                    redirect_node: node.redirect.map(to_var_int),
+                })
+                .collect(),
+            root_index: to_var_int(self.root()),
+        }
+    }
+}
+
+impl Default for CommandTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::{Decode, Encode};
+
+    use super::*;
+
+    #[test]
+    fn command_tree_round_trip() {
+        let mut tree = CommandTree::new();
+
+        let give = tree.add_literal(tree.root(), "give");
+        let count = tree.add_argument(
+            give,
+            "count",
+            Parser::Integer {
+                min: Some(1),
+                max: None,
+            },
+        );
+        tree.set_executable(count, true);
+        tree.set_suggestion(count, Some(Suggestion::AskServer));
+
+        let alias = tree.add_literal(tree.root(), "g");
+        tree.set_redirect(alias, Some(give));
+
+        let mut buf = vec![];
+        tree.packet().encode(&mut buf).unwrap();
+
+        let mut r = buf.as_slice();
+        let pkt = Commands::decode(&mut r).unwrap();
+        assert!(r.is_empty());
+
+        assert_eq!(pkt.root_index.0, 0);
+        assert_eq!(pkt.commands.len(), 4);
+        assert_eq!(pkt.commands[0].children, [VarInt(1), VarInt(3)]);
+
+        let node = &pkt.commands[2];
+        assert!(node.executable);
+        assert!(matches!(
+            node.data,
+            NodeData::Argument {
+                name: "count",
+                parser: Parser::Integer {
+                    min: Some(1),
+                    max: None
+                },
+                suggestion: Some(Suggestion::AskServer),
+            }
+        ));
+
+        assert_eq!(pkt.commands[3].redirect_node, Some(VarInt(1)));
+    }
+}
@@ -1,23 +1,40 @@
 //! Configuration for the server.
 
 use std::borrow::Cow;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
+pub use builder::{ServerConfig, ServerConfigBuilder};
+#[cfg(feature = "deterministic_rng")]
+use rand_core::CryptoRngCore;
 use serde::Serialize;
-use tokio::runtime::Handle as TokioHandle;
+use tokio::runtime::{Handle as TokioHandle, Runtime as TokioRuntime};
 use uuid::Uuid;
+use valence_protocol::packets::{C2sPlayPacket, S2cPlayPacket};
 use valence_protocol::text::Text;
-use valence_protocol::username::Username;
-use valence_protocol::MAX_PACKET_SIZE;
+use valence_protocol::types::GameMode;
+use valence_protocol::{ident, Ident, Username, MAX_PACKET_SIZE};
+use vek::Vec3;
 
+use crate::auth::{Authenticator, MojangAuthenticator};
 use crate::biome::Biome;
+use crate::chunk::{ChunkPos, LoadedChunk};
+use crate::client::{BlockChange, Client, ClientId, DisconnectReason};
 use crate::dimension::Dimension;
-use crate::server::{NewClientData, Server, SharedServer};
+use crate::entity::EntityKind;
+use crate::server::{ConnectionError, ConnectionHandler, NewClientData, Server, SharedServer};
+use crate::world::{WorldId, Worlds};
 use crate::{Ticks, STANDARD_TPS};
 
+mod builder;
+
 /// A trait for the configuration of a server.
 ///
+/// Simple servers can use [`ServerConfigBuilder`] instead of implementing this
+/// trait.
+///
 /// This trait uses the [async_trait] attribute macro. It is exported at the
 /// root of this crate. async_trait will be removed once async fns in traits
 /// are stabilized.
@@ -60,7 +77,11 @@ pub trait Config: Sized + Send + Sync + 'static {
     }
 
     /// Called once at startup to get the socket address the server will
-    /// be bound to.
+    /// be bound to. The accept loop binds exactly this address, so any
+    /// hostname must be resolved beforehand. [`resolve_address`] can be used
+    /// for this when constructing the config.
+    ///
+    /// [`resolve_address`]: crate::util::resolve_address
     ///
     /// # Default Implementation
     ///
@@ -69,12 +90,58 @@ pub trait Config: Sized + Send + Sync + 'static {
         SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 25565).into()
     }
 
+    /// Called once at startup to determine if the server listens for TCP
+    /// connections on [`Self::address`].
+    ///
+    /// An integrated server embedded in a client can return `false` and
+    /// connect its player with [`SharedServer::connect_local`] instead. Such
+    /// servers usually only need a single [worker thread](Self::worker_threads)
+    /// as well.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `true`.
+    fn listen(&self) -> bool {
+        true
+    }
+
+    /// Called once at startup to get the maximum number of pending
+    /// connections the operating system should queue on the listening socket
+    /// before they are accepted by the server.
+    ///
+    /// A larger backlog helps avoid refused connections when many players
+    /// connect at the same time, e.g. right after a popular server restarts.
+    /// The operating system may silently cap this value.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `1024`.
+    fn listen_backlog(&self) -> u32 {
+        1024
+    }
+
+    /// Called once at startup to get the number of tasks accepting incoming
+    /// connections concurrently. The value must be nonzero.
+    ///
+    /// All accept tasks share the same listening socket and connection limit,
+    /// so [`Self::max_connections`] is enforced globally regardless of this
+    /// value.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `1`.
+    fn accept_concurrency(&self) -> usize {
+        1
+    }
+
     /// Called once at startup to get the tick rate, which is the number of game
     /// updates that should occur in one second.
     ///
     /// On each game update (tick), the server is expected to update game logic
     /// and respond to packets from clients. Once this is complete, the server
-    /// will sleep for any remaining time until a full tick has passed.
+    /// will sleep for any remaining time until a full tick has passed. If the
+    /// server falls more than [`MAX_TICKS_BEHIND`] ticks behind schedule, the
+    /// missed ticks are skipped rather than caught up on.
     ///
     /// The tick rate must be greater than zero.
     ///
@@ -84,6 +151,8 @@ pub trait Config: Sized + Send + Sync + 'static {
     /// # Default Implementation
     ///
     /// Returns [`STANDARD_TPS`].
+    ///
+    /// [`MAX_TICKS_BEHIND`]: crate::server::MAX_TICKS_BEHIND
     fn tick_rate(&self) -> Ticks {
         STANDARD_TPS
     }
@@ -105,47 +174,73 @@ pub trait Config: Sized + Send + Sync + 'static {
     ///
     /// # Default Implementation
     ///
-    /// If the connection mode is [`ConnectionMode::Online`], `Some(256)` is
-    /// returned. Otherwise, compression is disabled.
+    /// If the connection mode is [`ConnectionMode::Online`] or
+    /// [`ConnectionMode::Transfer`], `Some(256)` is returned. Otherwise,
+    /// compression is disabled.
     fn compression_threshold(&self) -> Option<u32> {
         match self.connection_mode() {
-            ConnectionMode::Online => Some(256),
+            ConnectionMode::Online | ConnectionMode::Transfer { .. } => Some(256),
             _ => None,
         }
     }
 
-    /// Called upon every client login to obtain the full URL to use for session
-    /// server requests. This is done to authenticate player accounts. This
-    /// method is not called unless [online mode] is enabled.
+    /// Called for each client after authentication to decide how compression
+    /// is set up for the connection. `server_address` is the address from the
+    /// client's handshake, which contains any data forwarded by BungeeCord.
+    ///
+    /// Returning [`CompressionSetup::Preset`] skips sending the SetCompression
+    /// packet and configures the connection as if the given threshold had
+    /// already been negotiated. This is intended for backends behind a proxy
+    /// which has already agreed on compression parameters with the backend out
+    /// of band, or for trusted local links where compression is wasted CPU.
+    ///
+    /// Valence uses the preset threshold both to decide which outgoing packets
+    /// to compress and to decide whether incoming packets are in the
+    /// compressed format. If the other end of the connection is not using the
+    /// exact same parameters from the same point in the login sequence, every
+    /// subsequent packet will fail to decode and the client will be
+    /// disconnected. When in doubt, use [`CompressionSetup::Negotiate`].
     ///
-    /// It is assumed that upon successful request, a structure matching the
-    /// description in the [wiki](https://wiki.vg/Protocol_Encryption#Server) was obtained.
-    /// Providing a URL that does not return such a structure will result in a
-    /// disconnect for every client that connects.
+    /// This method is called from within a tokio runtime.
     ///
-    /// The arguments are described in the linked wiki article.
+    /// # Default Implementation
+    ///
+    /// Returns [`CompressionSetup::Negotiate`].
+    fn compression_setup(&self, ncd: &NewClientData, server_address: &str) -> CompressionSetup {
+        CompressionSetup::Negotiate
+    }
+
+    /// Called once at startup to get the [`Authenticator`] used to verify
+    /// players and resolve their profiles. The authenticator is only used if
+    /// [online mode] is enabled.
     ///
     /// # Default Implementation
     ///
-    /// Uses the official Minecraft session server. This is formatted as
-    /// `https://sessionserver.mojang.com/session/minecraft/hasJoined?username=<username>&serverId=<auth-digest>&ip=<player-ip>`.
+    /// Uses the official Minecraft session server through a
+    /// [`MojangAuthenticator`], with the option returned by
+    /// [`Self::prevent_proxy_connections`].
     ///
     /// [online mode]: crate::config::ConnectionMode::Online
-    fn session_server(
-        &self,
-        server: &SharedServer<Self>,
-        username: Username<&str>,
-        auth_digest: &str,
-        player_ip: &IpAddr,
-    ) -> String {
-        if self.prevent_proxy_connections() {
-            format!("https://sessionserver.mojang.com/session/minecraft/hasJoined?username={username}&serverId={auth_digest}&ip={player_ip}")
-        } else {
-            format!("https://sessionserver.mojang.com/session/minecraft/hasJoined?username={username}&serverId={auth_digest}")
-        }
+    fn authenticator(&self) -> Box<dyn Authenticator> {
+        Box::new(MojangAuthenticator::new(self.prevent_proxy_connections()))
+    }
+
+    /// Called once at startup to get an optional [`ConnectionHandler`]. If
+    /// one is returned, it takes over every connection which completes login
+    /// and clients are never added to the server's [`Clients`].
+    ///
+    /// [`Config::login`] is still called before the handler.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `None`. Clients join the server normally.
+    ///
+    /// [`Clients`]: crate::client::Clients
+    fn connection_handler(&self) -> Option<Box<dyn ConnectionHandler<Self>>> {
+        None
     }
 
-    /// Called from the default implementation of [`Config::session_server`] to
+    /// Called from the default implementation of [`Config::authenticator`] to
     /// get the "prevent-proxy-connections" option, which determines if client
     /// IP validation should take place.
     ///
@@ -177,6 +272,187 @@ pub trait Config: Sized + Send + Sync + 'static {
         MAX_PACKET_SIZE as usize
     }
 
+    /// Called once at startup to get the server brand sent to clients in the
+    /// `minecraft:brand` plugin channel. The brand is displayed in the
+    /// client's debug screen.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `"valence"`.
+    fn server_brand(&self) -> Cow<'_, str> {
+        Cow::Borrowed("valence")
+    }
+
+    /// Called once at startup to get the feature flags sent to clients when
+    /// they join. Feature flags enable the content of built-in data packs on
+    /// the client, such as `minecraft:bundle` for bundles. Without
+    /// `minecraft:vanilla`, clients disable most vanilla items and blocks.
+    ///
+    /// The list must not contain duplicates.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `["minecraft:vanilla"]`.
+    fn feature_flags(&self) -> Vec<Ident<String>> {
+        vec![ident!("minecraft:vanilla")]
+    }
+
+    /// Called once at startup to get the maximum length (in bytes) of the
+    /// packets a connection may send before it has finished logging in.
+    ///
+    /// Handshake and login packets are small, so a tight limit protects the
+    /// server from clients claiming huge packet lengths before they are
+    /// authenticated. The limit is lifted to [`MAX_PACKET_SIZE`] once the
+    /// client enters the play state.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `32768`.
+    fn max_initial_packet_size(&self) -> usize {
+        32768
+    }
+
+    /// Called once at startup to get the maximum length of the usernames
+    /// accepted from clients during login.
+    ///
+    /// Vanilla usernames are at most 16 characters long. Players joining
+    /// through a Bedrock Edition bridge such as Geyser may have longer
+    /// usernames with a `.` or `*` prefix added to avoid collisions with Java
+    /// Edition players, as may players using some modded clients. Raising this
+    /// limit allows such players to join. See [`Username::new_with_max_len`]
+    /// for the exact rules.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `16`.
+    ///
+    fn max_username_len(&self) -> usize {
+        16
+    }
+
+    /// Called once at startup to get the time connections have to send the
+    /// first login packet once the handshake is complete. The duration must be
+    /// nonzero.
+    ///
+    /// This replaces the [idle](Self::connection_idle_timeout) and
+    /// [packet](Self::connection_packet_timeout) timeouts for the first login
+    /// packet so that connections which stop after the handshake, such as port
+    /// scanners, give up their connection slot quickly. Clients send the login
+    /// packet immediately after the handshake.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns 2 seconds.
+    fn login_start_timeout(&self) -> Duration {
+        Duration::from_secs(2)
+    }
+
+    /// Called once at startup to get the maximum time connections which have
+    /// not finished logging in may wait between packets. The timer starts once
+    /// a packet has been received and stops when the first bytes of the next
+    /// packet arrive. The duration must be nonzero.
+    ///
+    /// Once clients are in the play state, connections are not timed out by
+    /// the server. Idle clients are detected with keepalives instead.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns 5 seconds.
+    fn connection_idle_timeout(&self) -> Duration {
+        Duration::from_secs(5)
+    }
+
+    /// Called once at startup to get the maximum time a single read or write
+    /// may make no progress while a packet is being received or sent during
+    /// login. The duration must be nonzero.
+    ///
+    /// The timer restarts every time more bytes of the packet arrive, so large
+    /// packets which arrive slowly but steadily are not timed out.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns 5 seconds.
+    fn connection_read_timeout(&self) -> Duration {
+        Duration::from_secs(5)
+    }
+
+    /// Called once at startup to get the maximum total time to receive a
+    /// single packet from a connection which has not finished logging in,
+    /// including the time spent waiting for it to begin arriving. The
+    /// duration must be nonzero.
+    ///
+    /// Unlike the [idle](Self::connection_idle_timeout) and
+    /// [read](Self::connection_read_timeout) timeouts, this does not restart
+    /// when more bytes arrive. Connections which send a packet a byte at a
+    /// time cannot keep their connection slot for longer than this.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns 10 seconds.
+    fn connection_packet_timeout(&self) -> Duration {
+        Duration::from_secs(10)
+    }
+
+    /// Called once at startup to get the policy for play packets with IDs the
+    /// server does not recognize.
+    ///
+    /// Forwarding unknown packets is useful for experimenting with packets that
+    /// are not yet modeled by Valence. See also
+    /// [`Client::send_raw_packet`](crate::client::Client::send_raw_packet).
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns [`UnknownPacketPolicy::Reject`].
+    fn unknown_packet_policy(&self) -> UnknownPacketPolicy {
+        UnknownPacketPolicy::Reject
+    }
+
+    /// Called once at startup to determine if the packets exchanged with
+    /// clients in the play state are passed to [`Self::inspect_outgoing`] and
+    /// [`Self::inspect_incoming`].
+    ///
+    /// Inspection requires every packet to be decoded an extra time, so it is
+    /// intended for debugging and packet logging.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `false`.
+    fn inspect_packets(&self) -> bool {
+        false
+    }
+
+    /// Called for every packet sent to a client in the play state if
+    /// [`Self::inspect_packets`] returned `true`. This is a read-only tap and
+    /// cannot modify or drop the packet.
+    ///
+    /// This method is called from a networking task rather than the thread
+    /// running [`Self::update`], so it should not block. Packets sent to the
+    /// same client are inspected in the order they are sent, but possibly a
+    /// short time after they are sent. If inspection falls too far behind,
+    /// packets are skipped instead of being buffered without limit and a
+    /// warning is logged. Packets which Valence cannot decode, such as those
+    /// sent with [`Client::send_raw_packet`](crate::client::Client::send_raw_packet),
+    /// are not inspected.
+    ///
+    /// # Default Implementation
+    ///
+    /// The default implementation does nothing.
+    fn inspect_outgoing(&self, client: ClientId, pkt: &S2cPlayPacket) {}
+
+    /// Called for every packet received from a client in the play state if
+    /// [`Self::inspect_packets`] returned `true`. This is a read-only tap and
+    /// cannot modify or drop the packet.
+    ///
+    /// Like [`Self::inspect_outgoing`], this method is called from a
+    /// networking task rather than the thread running [`Self::update`].
+    /// Packets are inspected when they are processed by the server, so
+    /// packets the client sends after it is disconnected are not inspected.
+    ///
+    /// # Default Implementation
+    ///
+    /// The default implementation does nothing.
+    fn inspect_incoming(&self, client: ClientId, pkt: &C2sPlayPacket) {}
+
     /// Called once at startup to get the maximum capacity (in bytes) of the
     /// buffer used to hold outgoing packets.
     ///
@@ -192,11 +468,106 @@ pub trait Config: Sized + Send + Sync + 'static {
         MAX_PACKET_SIZE as usize * 4
     }
 
+    /// Called once at startup to decide what happens when the buffer of
+    /// outgoing packets for a client is full, which happens when the client
+    /// cannot keep up with the packets sent to it. See
+    /// [`outgoing_capacity`](Self::outgoing_capacity).
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns [`OverflowPolicy::DisconnectSlowClient`].
+    fn overflow_policy(&self) -> OverflowPolicy {
+        OverflowPolicy::DisconnectSlowClient
+    }
+
+    /// Called once at startup to get the maximum number of chunks loaded by
+    /// each client per tick. The value must be nonzero.
+    ///
+    /// When a client joins, teleports far away, or increases its view
+    /// distance, the chunks entering its view are queued and sent over the
+    /// following ticks, nearest chunks first. This avoids large bursts of
+    /// chunk data which could fill up the client's
+    /// [outgoing buffer](Self::outgoing_capacity). The number of chunks still
+    /// queued is available from [`Client::queued_chunk_count`].
+    ///
+    /// Chunks created while already in view of a client are sent immediately
+    /// and do not count towards this limit.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `usize::MAX` so that all chunks are sent immediately.
+    fn max_chunks_per_tick(&self) -> usize {
+        usize::MAX
+    }
+
+    /// Called once at startup for each [`EntityKind`] to get the distance in
+    /// blocks at which entities of that kind are visible to clients. The
+    /// value must not be negative.
+    ///
+    /// The distance is rounded up to a whole number of chunks and measured
+    /// from the chunk the client is in. Entities are never visible beyond a
+    /// client's view distance, so tracking ranges larger than the view
+    /// distance have no effect.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns the tracking range used by vanilla servers for each kind of
+    /// entity. For instance, players are visible at 512 blocks, most mobs
+    /// at 128 blocks and projectiles at 64 blocks.
+    fn entity_tracking_range(&self, kind: EntityKind) -> i32 {
+        use EntityKind::*;
+
+        let chunks = match kind {
+            Player => 32,
+            EndCrystal | Lightning | Warden => 16,
+            AreaEffectCloud | ArmorStand | Axolotl | Bee | Boat | Camel | Cat | ChestBoat
+            | Chicken | Cow | Dolphin | Donkey | ElderGuardian | EnderDragon | FallingBlock
+            | Fox | Frog | Ghast | Giant | GlowItemFrame | GlowSquid | Goat | Horse | IronGolem
+            | ItemFrame | LeashKnot | Llama | Mooshroom | Mule | Ocelot | Painting | Panda
+            | Parrot | Pig | PolarBear | Rabbit | Ravager | Sheep | Shulker | SkeletonHorse
+            | Slime | SnowGolem | Strider | Tadpole | Tnt | TraderLlama | Turtle | Villager
+            | WanderingTrader | Wither | Wolf | ZombieHorse => 10,
+            Item | ExperienceOrb | EvokerFangs => 6,
+            Bat => 5,
+            Arrow | Cod | DragonFireball | Egg | EnderPearl | ExperienceBottle | EyeOfEnder
+            | Fireball | FireworkRocket | FishingBobber | LlamaSpit | Potion | Pufferfish
+            | Salmon | SmallFireball | Snowball | SpectralArrow | Trident | TropicalFish
+            | WitherSkull => 4,
+            Marker => 0,
+            // Monsters, minecarts and everything else.
+            _ => 8,
+        };
+
+        chunks * 16
+    }
+
+    /// Called once at startup to get the game mode clients are in when they
+    /// join the server.
+    ///
+    /// The game mode of an individual client can be overridden by calling
+    /// [`Client::set_game_mode`] during the tick the client is added to the
+    /// server, i.e. while [`Client::created_this_tick`] is `true`. The game
+    /// mode is then sent as part of the join packet rather than as a separate
+    /// change.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns [`GameMode::Survival`].
+    fn default_game_mode(&self) -> GameMode {
+        GameMode::Survival
+    }
+
     /// Called once at startup to get a handle to the tokio runtime the server
     /// will use.
     ///
-    /// If a handle is not provided, the server will create its own tokio
-    /// runtime.
+    /// If neither a handle nor a runtime from [`Self::tokio_runtime`] is
+    /// provided, the server will create its own tokio runtime. A handle and a
+    /// runtime cannot both be provided.
+    ///
+    /// The server does not keep the runtime behind a handle alive, so the
+    /// caller must ensure the runtime outlives the server. Use
+    /// [`Self::tokio_runtime`] to share ownership of the runtime with the
+    /// server instead.
     ///
     /// # Default Implementation
     ///
@@ -205,6 +576,64 @@ pub trait Config: Sized + Send + Sync + 'static {
         None
     }
 
+    /// Called once at startup to get the tokio runtime the server will use.
+    ///
+    /// Unlike [`Self::tokio_handle`], the server holds on to the runtime for
+    /// as long as the [`SharedServer`] exists, so it cannot be dropped while
+    /// the server is using it. The runtime is configured by the caller, so
+    /// [`Self::worker_threads`] and [`Self::thread_name`] have no effect.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `None`.
+    fn tokio_runtime(&self) -> Option<Arc<TokioRuntime>> {
+        None
+    }
+
+    /// Called once at startup to get the random number generator used to
+    /// generate the server's RSA key and the verify tokens sent to clients
+    /// during login.
+    ///
+    /// Replacing the generator with a seeded one makes the login handshake
+    /// reproducible in tests. This method is only available with the
+    /// `deterministic_rng` feature, which must never be enabled in
+    /// production.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns [`OsRng`](rand::rngs::OsRng).
+    #[cfg(feature = "deterministic_rng")]
+    fn rng(&self) -> Box<dyn CryptoRngCore + Send> {
+        Box::new(rand::rngs::OsRng)
+    }
+
+    /// Called once at startup to get the number of worker threads of the tokio
+    /// runtime created by the server. The value must be nonzero. This has no
+    /// effect if [`Self::tokio_handle`] or [`Self::tokio_runtime`] returns a
+    /// value.
+    ///
+    /// Limiting the number of worker threads is useful in containerized
+    /// environments, where the number of available cores may exceed the CPU
+    /// quota of the server.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `None` to use one worker thread per core.
+    fn worker_threads(&self) -> Option<usize> {
+        None
+    }
+
+    /// Called once at startup to get the name given to the threads of the
+    /// tokio runtime created by the server. This has no effect if
+    /// [`Self::tokio_handle`] or [`Self::tokio_runtime`] returns a value.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `"tokio-runtime-worker"`.
+    fn thread_name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("tokio-runtime-worker")
+    }
+
     /// Called once at startup to get the list of [`Dimension`]s usable on the
     /// server.
     ///
@@ -246,6 +675,14 @@ pub trait Config: Sized + Send + Sync + 'static {
     /// Called when the server receives a Server List Ping query.
     /// Data for the response can be provided or the query can be ignored.
     ///
+    /// `protocol_version` is the protocol version of the client sending the
+    /// query, which can be used to tailor the response to the client's
+    /// version. For instance, a custom [`version_name`] can be shown to
+    /// clients with a protocol version other than [`PROTOCOL_VERSION`].
+    ///
+    /// [`version_name`]: ServerListPing::Respond::version_name
+    /// [`PROTOCOL_VERSION`]: valence_protocol::PROTOCOL_VERSION
+    ///
     /// This method is called from within a tokio runtime.
     ///
     /// # Default Implementation
@@ -260,6 +697,32 @@ pub trait Config: Sized + Send + Sync + 'static {
         ServerListPing::Ignore
     }
 
+    /// Called once at startup to get the duration for which the result of
+    /// [`Self::server_list_ping`] is reused.
+    ///
+    /// If this returns `Some(ttl)`, the serialized response is cached and used
+    /// for every status ping received within `ttl` of the callback. Pings from
+    /// clients on the server's protocol version share one response, and pings
+    /// from every other version share another. The callback is only invoked
+    /// again once the cache expires or is invalidated with
+    /// [`SharedServer::invalidate_status_cache`]. This protects servers with
+    /// expensive responses from ping floods, but means the `remote_addr` and
+    /// `protocol_version` arguments of the callback only reflect the ping that
+    /// filled the cache.
+    ///
+    /// [`ServerListPing::Ignore`] is never cached, so ignoring the ping of one
+    /// address does not ignore everyone else. However, a cached response is
+    /// given to every address while it is valid, including addresses the
+    /// callback would have ignored. Don't enable the cache if responses depend
+    /// on the address, e.g. to hide the server from banned IPs.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `None` so that the callback is invoked for every ping.
+    fn server_list_ping_cache_ttl(&self) -> Option<Duration> {
+        None
+    }
+
     /// Called asynchronously for each client after successful authentication
     /// (if online mode is enabled) to determine if they can join
     /// the server. On success, the new client is added to the server's
@@ -281,6 +744,121 @@ pub trait Config: Sized + Send + Sync + 'static {
         Ok(())
     }
 
+    /// Called asynchronously for each client after [`login`](Self::login)
+    /// allowed them to join, to replace the username and UUID the client joins
+    /// with. This is useful to enforce a canonical casing of usernames or to
+    /// assign deterministic UUIDs in offline mode.
+    ///
+    /// The replacement is applied before the client is sent the login success
+    /// packet. The username must still be valid according to
+    /// [`max_username_len`](Self::max_username_len), otherwise the client is
+    /// disconnected. If the UUID is changed, the client's textures and
+    /// profile properties are discarded since they are signed for the
+    /// original profile.
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `None` so that the profile is left unchanged.
+    async fn login_profile(
+        &self,
+        shared: &SharedServer<Self>,
+        ncd: &NewClientData,
+    ) -> Option<(Username<String>, Uuid)> {
+        None
+    }
+
+    /// Called when a connection ends with an error before the client is added
+    /// to the server, or when accepting a connection fails. This includes
+    /// clients disconnecting in the middle of logging in.
+    ///
+    /// Repeated errors from the same address can indicate abuse, so this is
+    /// useful for building automated mitigation such as temporary IP bans.
+    /// Errors in the play state are not included since clients which are
+    /// disconnected are visible with [`Client::is_disconnected`].
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// The default implementation does nothing. Errors are logged regardless.
+    fn connection_error(&self, shared: &SharedServer<Self>, error: &ConnectionError) {}
+
+    /// Called when a client is removed from [`Clients`], with the reason the
+    /// client was disconnected. Clients which are removed while still
+    /// connected are considered [kicked](DisconnectReason::Kicked). When the
+    /// server shuts down, all remaining clients are removed with
+    /// [`DisconnectReason::ServerShutdown`] unless they had already
+    /// disconnected.
+    ///
+    /// This is useful for logging sessions. The client's state is still
+    /// available, including [`Client::disconnect_reason`], but it can no
+    /// longer be sent packets.
+    ///
+    /// # Default Implementation
+    ///
+    /// The default implementation does nothing.
+    ///
+    /// [`Clients`]: crate::client::Clients
+    fn client_left(
+        &self,
+        shared: &SharedServer<Self>,
+        client: &Client<Self>,
+        reason: DisconnectReason,
+    ) {
+    }
+
+    /// Called whenever a client reports a new position to decide if the
+    /// movement from `old` to `new` should be accepted.
+    ///
+    /// If `false` is returned, the new position is discarded and the client is
+    /// teleported back to `old`. No movement event is generated in that case.
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// If [`Self::max_movement_per_tick`] returns a distance, movements
+    /// longer than it are rejected. Otherwise, all movements are accepted.
+    fn validate_movement(&self, client: &Client<Self>, old: Vec3<f64>, new: Vec3<f64>) -> bool {
+        match self.max_movement_per_tick() {
+            Some(max) => old.distance_squared(new) <= max * max,
+            None => true,
+        }
+    }
+
+    /// Called when a client attempts to break or place a block, before the
+    /// corresponding [`ClientEvent`] is generated.
+    ///
+    /// If `false` is returned, the event is discarded and the original block
+    /// states are re-sent to the client so that its prediction is undone.
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// All block changes are allowed.
+    ///
+    /// [`ClientEvent`]: crate::client::ClientEvent
+    fn on_block_change(&self, client: &Client<Self>, change: BlockChange) -> bool {
+        true
+    }
+
+    /// Called by the default implementation of [`Self::validate_movement`] to
+    /// get the maximum distance (in blocks) a client may move with a single
+    /// position update. If `None` is returned, movement is not limited.
+    ///
+    /// Note that legitimate clients can move long distances in one update,
+    /// for instance while flying with elytra or riding a fast vehicle.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `None`.
+    fn max_movement_per_tick(&self) -> Option<f64> {
+        None
+    }
+
     /// Called after the server is created, but prior to accepting connections
     /// and entering the update loop.
     ///
@@ -294,6 +872,112 @@ pub trait Config: Sized + Send + Sync + 'static {
     /// The default implementation does nothing.
     fn init(&self, server: &mut Server<Self>) {}
 
+    /// The fallible version of [`init`](Self::init). Called in its place after
+    /// the server is created, but prior to accepting connections and entering
+    /// the update loop.
+    ///
+    /// If an error is returned, the server does not start and the error is
+    /// returned from [`start_server`]. This is the appropriate way to report
+    /// failures such as world data that could not be loaded.
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// Calls [`init`](Self::init) and returns `Ok(())`.
+    ///
+    /// [`start_server`]: crate::start_server
+    fn try_init(&self, server: &mut Server<Self>) -> anyhow::Result<()> {
+        self.init(server);
+        Ok(())
+    }
+
+    /// Called for each client as it joins the server to get the world the
+    /// client is placed in. If `Some` is returned, the client is
+    /// [respawned](Client::respawn) in the world and teleported to
+    /// [`Self::default_spawn_position`] before [`Self::update`] sees the
+    /// client. Otherwise, the client must be moved into a world manually.
+    ///
+    /// This is also called once after [`Self::init`], and the server fails to
+    /// start if the returned world does not exist.
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `None`.
+    fn default_world(&self, server: &Server<Self>) -> Option<WorldId> {
+        None
+    }
+
+    /// Called by the server to get the position new clients are teleported to
+    /// when they join the world returned by [`Self::default_world`].
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns the origin of the world.
+    fn default_spawn_position(&self, server: &Server<Self>, world: WorldId) -> Vec3<f64> {
+        Vec3::zero()
+    }
+
+    /// Called after [`Self::update`] for each client located in a world that
+    /// was removed or [deleted](crate::world::World::set_deleted), e.g. when
+    /// the world of a finished minigame match is cleaned up.
+    ///
+    /// If `Some((world, position))` is returned, the client is respawned in
+    /// `world` at `position`. If `None` is returned or the returned world is
+    /// also invalid, the client is disconnected.
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `None`.
+    fn relocate_client(
+        &self,
+        client: &Client<Self>,
+        worlds: &Worlds<Self>,
+    ) -> Option<(WorldId, Vec3<f64>)> {
+        None
+    }
+
+    /// Called before an [unsaved](LoadedChunk::is_unsaved) chunk is unloaded
+    /// by the [`ChunkRetention`] policy of its world. This is the place to
+    /// write the chunk to storage.
+    ///
+    /// If `true` is returned, the chunk is marked as saved and unloaded at the
+    /// end of the tick. Otherwise, the chunk stays loaded and this is called
+    /// again once the chunk has been out of range for another
+    /// [timeout](crate::world::ChunkRetention::timeout).
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `false`, so unsaved chunks are never unloaded. Chunks which can
+    /// be regenerated as needed may be discarded by returning `true`.
+    ///
+    /// [`ChunkRetention`]: crate::world::ChunkRetention
+    fn save_chunk(&self, world: WorldId, pos: ChunkPos, chunk: &mut LoadedChunk<Self>) -> bool {
+        false
+    }
+
+    /// Called exactly once after the first tick of the update loop has
+    /// completed, but before the second call to [`Self::update`].
+    ///
+    /// Unlike [`Self::init`], connections are being accepted at this point and
+    /// the first batch of clients (if any) has been added to the server. This
+    /// is useful for one-time setup that needs a warm world.
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// The default implementation does nothing.
+    fn post_start(&self, server: &mut Server<Self>) {}
+
     /// Called once at the beginning of every server update (also known as
     /// "tick"). This is likely where the majority of your code will be.
     ///
@@ -323,6 +1007,14 @@ pub enum ServerListPing<'a> {
         player_sample: Cow<'a, [PlayerSampleEntry<'a>]>,
         /// A description of the server.
         description: Text,
+        /// The version name displayed to clients with a different protocol
+        /// version than the server, such as outdated clients. If `None`,
+        /// [`MINECRAFT_VERSION`] is used.
+        ///
+        /// [`MINECRAFT_VERSION`]: valence_protocol::MINECRAFT_VERSION
+        version_name: Option<Cow<'a, str>>,
+        /// Whether clients should show the chat preview.
+        previews_chat: bool,
         /// The server's icon as the bytes of a PNG image.
         /// The image must be 64x64 pixels.
         ///
@@ -345,17 +1037,79 @@ pub struct PlayerSampleEntry<'a> {
     pub id: Uuid,
 }
 
+/// Describes how packets from clients with unrecognized packet IDs are
+/// handled. Returned by [`Config::unknown_packet_policy`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum UnknownPacketPolicy {
+    /// The client is disconnected.
+    Reject,
+    /// The packet is delivered as a [`ClientEvent::UnknownPacket`].
+    ///
+    /// [`ClientEvent::UnknownPacket`]: crate::client::ClientEvent::UnknownPacket
+    Forward,
+    /// The packet is discarded. The number of discarded packets is available
+    /// from [`Client::skipped_packet_count`].
+    ///
+    /// This keeps clients with slightly different protocol implementations
+    /// connected as long as the unknown packets are harmless.
+    ///
+    /// [`Client::skipped_packet_count`]: crate::client::Client::skipped_packet_count
+    Skip,
+}
+
+/// Describes what happens when a client's buffer of outgoing packets is full.
+/// Returned by [`Config::overflow_policy`].
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum OverflowPolicy {
+    /// The client is disconnected.
+    #[default]
+    DisconnectSlowClient,
+    /// Once the buffer is nearly full, packets which the client can do
+    /// without are dropped. This includes entity updates, particles, and
+    /// sounds. Other packets such as chunks and inventory updates are always
+    /// sent, and the client is disconnected if those do not fit.
+    ///
+    /// Entities whose updates were dropped have their complete position and
+    /// metadata sent again once there is room, so only entity events such as
+    /// animations are lost for good.
+    DropNonEssentialPackets,
+    /// The update loop waits up to `timeout` for the packets to fit in the
+    /// buffer, and the client is disconnected if they still do not fit. No
+    /// packets are lost, but a slow client stalls the whole server for up to
+    /// `timeout` on every tick.
+    Block { timeout: Duration },
+}
+
+/// Describes how compression is set up for a connection. Returned by
+/// [`Config::compression_setup`].
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum CompressionSetup {
+    /// The SetCompression packet is sent to the client with the threshold from
+    /// [`Config::compression_threshold`], or not at all if the threshold is
+    /// `None`.
+    #[default]
+    Negotiate,
+    /// No packet is sent and the connection is assumed to already be using
+    /// the given compression threshold. `None` means the connection is not
+    /// compressed.
+    ///
+    /// The threshold takes effect at the point where SetCompression would
+    /// otherwise have been sent: after authentication and before the
+    /// LoginSuccess packet.
+    Preset(Option<u32>),
+}
+
 /// Describes how new connections to the server are handled.
 #[non_exhaustive]
 #[derive(Clone, PartialEq, Default)]
 pub enum ConnectionMode {
     /// The "online mode" fetches all player data (username, UUID, and skin)
-    /// from the [configured session server] and enables encryption.
+    /// from the [configured authenticator] and enables encryption.
     ///
     /// This mode should be used for all publicly exposed servers which are not
     /// behind a proxy.
     ///
-    /// [configured session server]: Config::session_server
+    /// [configured authenticator]: Config::authenticator
     #[default]
     Online,
     /// Disables client authentication with the configured session server.
@@ -398,6 +1152,38 @@ pub enum ConnectionMode {
         /// The proxy and Valence must be configured to use the same secret key.
         secret: String,
     },
+    /// Like [`ConnectionMode::Online`], but lets players moving between
+    /// servers in the same network skip authentication with the session
+    /// server.
+    ///
+    /// A server issues a token for a player with
+    /// [`SharedServer::issue_transfer_token`] before sending them to another
+    /// server. When logging in, the client is asked for the token through the
+    /// `valence:transfer_token` login plugin channel. If the client (or a
+    /// proxy acting on its behalf) responds with a valid token, the profile in
+    /// the token is used and the login completes without encryption. Clients
+    /// which do not understand the request are authenticated normally.
+    ///
+    /// Tokens are signed with the secret key, expire 30 seconds after they
+    /// are issued, and are only accepted once by the server they were issued
+    /// for.
+    ///
+    /// **The session of a client which presents a valid token is not
+    /// encrypted**, and the token itself is sent in plaintext. Anyone who can
+    /// observe the connection can read everything sent to the player, and can
+    /// use a token they intercepted to join the target server as the player
+    /// if they get there first. This mode should only be used where the
+    /// connection between the client (or proxy) and the server is trusted,
+    /// such as behind a proxy on a private network.
+    Transfer {
+        /// The secret key shared by all servers in the network. Anyone who
+        /// knows the key can join with any profile they choose, so it must be
+        /// kept private.
+        secret: String,
+        /// The ID of this server within the network. Tokens are only accepted
+        /// by the server whose ID they were issued for.
+        server_id: String,
+    },
 }
 
 /// A minimal `Config` implementation for testing purposes.
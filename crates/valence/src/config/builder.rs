@@ -0,0 +1,300 @@
+use std::borrow::Cow;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+
+use async_trait::async_trait;
+use valence_protocol::text::Text;
+
+use super::{Config, ConnectionMode, ServerListPing};
+use crate::biome::Biome;
+use crate::dimension::Dimension;
+use crate::server::{NewClientData, Server, SharedServer};
+use crate::util::DEFAULT_PORT;
+use crate::{Ticks, STANDARD_TPS};
+
+type InitFn<S> = Box<dyn Fn(&mut Server<ServerConfig<S>>) -> anyhow::Result<()> + Send + Sync>;
+type UpdateFn<S> = Box<dyn Fn(&mut Server<ServerConfig<S>>) + Send + Sync>;
+type LoginFn<S> =
+    Box<dyn Fn(&SharedServer<ServerConfig<S>>, &NewClientData) -> Result<(), Text> + Send + Sync>;
+type ServerListPingFn<S> = Box<
+    dyn Fn(&SharedServer<ServerConfig<S>>, SocketAddr, i32) -> ServerListPing<'static>
+        + Send
+        + Sync,
+>;
+
+/// A [`Config`] built from closures with a [`ServerConfigBuilder`].
+///
+/// `S` is the [`ServerState`](Config::ServerState). The other custom state
+/// types are all `()`. Servers needing more control should implement
+/// [`Config`] directly.
+pub struct ServerConfig<S: Send + Sync + 'static = ()> {
+    address: SocketAddr,
+    tick_rate: Ticks,
+    connection_mode: ConnectionMode,
+    dimensions: Vec<Dimension>,
+    biomes: Vec<Biome>,
+    init: Option<InitFn<S>>,
+    update: Option<UpdateFn<S>>,
+    login: Option<LoginFn<S>>,
+    server_list_ping: Option<ServerListPingFn<S>>,
+}
+
+/// Builds a [`ServerConfig`] as a lightweight alternative to implementing
+/// [`Config`].
+///
+/// Every setting which is not set uses the value of the corresponding
+/// [`Config`] method's default implementation.
+///
+/// ```no_run
+/// use valence::prelude::*;
+///
+/// let config = ServerConfigBuilder::new()
+///     .online_mode(false)
+///     .update(|server| {
+///         server.clients.retain(|_, client| !client.is_disconnected());
+///     })
+///     .build();
+///
+/// valence::start_server(config, ()).unwrap();
+/// ```
+pub struct ServerConfigBuilder<S: Send + Sync + 'static = ()> {
+    config: ServerConfig<S>,
+}
+
+impl<S: Send + Sync + 'static> ServerConfigBuilder<S> {
+    /// Creates a builder with the default settings and no callbacks.
+    pub fn new() -> Self {
+        Self {
+            config: ServerConfig {
+                address: SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, DEFAULT_PORT).into(),
+                tick_rate: STANDARD_TPS,
+                connection_mode: ConnectionMode::Online,
+                dimensions: vec![Dimension::default()],
+                biomes: vec![Biome::default()],
+                init: None,
+                update: None,
+                login: None,
+                server_list_ping: None,
+            },
+        }
+    }
+
+    /// Sets the value returned by [`Config::address`].
+    pub fn address(mut self, address: impl Into<SocketAddr>) -> Self {
+        self.config.address = address.into();
+        self
+    }
+
+    /// Sets the value returned by [`Config::tick_rate`].
+    pub fn tick_rate(mut self, tick_rate: Ticks) -> Self {
+        self.config.tick_rate = tick_rate;
+        self
+    }
+
+    /// Sets the value returned by [`Config::connection_mode`].
+    pub fn connection_mode(mut self, mode: ConnectionMode) -> Self {
+        self.config.connection_mode = mode;
+        self
+    }
+
+    /// Sets the connection mode to [`ConnectionMode::Online`] if `online` is
+    /// true and [`ConnectionMode::Offline`] otherwise.
+    pub fn online_mode(self, online: bool) -> Self {
+        self.connection_mode(if online {
+            ConnectionMode::Online
+        } else {
+            ConnectionMode::Offline
+        })
+    }
+
+    /// Sets the value returned by [`Config::dimensions`].
+    pub fn dimensions(mut self, dimensions: Vec<Dimension>) -> Self {
+        self.config.dimensions = dimensions;
+        self
+    }
+
+    /// Sets the value returned by [`Config::biomes`].
+    pub fn biomes(mut self, biomes: Vec<Biome>) -> Self {
+        self.config.biomes = biomes;
+        self
+    }
+
+    /// Sets the closure called by [`Config::init`].
+    pub fn init(
+        mut self,
+        f: impl Fn(&mut Server<ServerConfig<S>>) + Send + Sync + 'static,
+    ) -> Self {
+        self.config.init = Some(Box::new(move |server| {
+            f(server);
+            Ok(())
+        }));
+        self
+    }
+
+    /// Sets the closure called by [`Config::try_init`]. Replaces the closure
+    /// set with [`init`](Self::init).
+    pub fn try_init(
+        mut self,
+        f: impl Fn(&mut Server<ServerConfig<S>>) -> anyhow::Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.config.init = Some(Box::new(f));
+        self
+    }
+
+    /// Sets the closure called by [`Config::update`].
+    pub fn update(
+        mut self,
+        f: impl Fn(&mut Server<ServerConfig<S>>) + Send + Sync + 'static,
+    ) -> Self {
+        self.config.update = Some(Box::new(f));
+        self
+    }
+
+    /// Sets the closure called by [`Config::login`]. Unlike the trait method,
+    /// the closure is not async and should not block.
+    pub fn login(
+        mut self,
+        f: impl Fn(&SharedServer<ServerConfig<S>>, &NewClientData) -> Result<(), Text>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.config.login = Some(Box::new(f));
+        self
+    }
+
+    /// Sets the closure called by [`Config::server_list_ping`]. Unlike the
+    /// trait method, the closure is not async and should not block.
+    pub fn server_list_ping(
+        mut self,
+        f: impl Fn(&SharedServer<ServerConfig<S>>, SocketAddr, i32) -> ServerListPing<'static>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.config.server_list_ping = Some(Box::new(f));
+        self
+    }
+
+    /// Finishes building the config. The result can be passed to
+    /// [`start_server`](crate::start_server).
+    pub fn build(self) -> ServerConfig<S> {
+        self.config
+    }
+}
+
+impl<S: Send + Sync + 'static> Default for ServerConfigBuilder<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Config for ServerConfig<S> {
+    type ServerState = S;
+    type ClientState = ();
+    type EntityState = ();
+    type WorldState = ();
+    type ChunkState = ();
+    type PlayerListState = ();
+    type InventoryState = ();
+
+    fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    fn tick_rate(&self) -> Ticks {
+        self.tick_rate
+    }
+
+    fn connection_mode(&self) -> ConnectionMode {
+        self.connection_mode.clone()
+    }
+
+    fn dimensions(&self) -> Vec<Dimension> {
+        self.dimensions.clone()
+    }
+
+    fn biomes(&self) -> Vec<Biome> {
+        self.biomes.clone()
+    }
+
+    async fn server_list_ping(
+        &self,
+        shared: &SharedServer<Self>,
+        remote_addr: SocketAddr,
+        protocol_version: i32,
+    ) -> ServerListPing {
+        let Some(f) = &self.server_list_ping else {
+            return ServerListPing::Ignore;
+        };
+
+        match f(shared, remote_addr, protocol_version) {
+            // `player_sample` is invariant over its lifetime, so it is rebuilt
+            // to shorten the lifetime of the response.
+            ServerListPing::Respond {
+                online_players,
+                max_players,
+                player_sample,
+                description,
+                version_name,
+                previews_chat,
+                favicon_png,
+            } => ServerListPing::Respond {
+                online_players,
+                max_players,
+                player_sample: Cow::Owned(player_sample.into_owned()),
+                description,
+                version_name,
+                previews_chat,
+                favicon_png,
+            },
+            ServerListPing::Ignore => ServerListPing::Ignore,
+        }
+    }
+
+    async fn login(&self, shared: &SharedServer<Self>, ncd: &NewClientData) -> Result<(), Text> {
+        match &self.login {
+            Some(f) => f(shared, ncd),
+            None => Ok(()),
+        }
+    }
+
+    fn try_init(&self, server: &mut Server<Self>) -> anyhow::Result<()> {
+        match &self.init {
+            Some(f) => f(server),
+            None => Ok(()),
+        }
+    }
+
+    fn update(&self, server: &mut Server<Self>) {
+        if let Some(f) = &self.update {
+            f(server);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_defaults_match_trait() {
+        let config = ServerConfigBuilder::<()>::new().build();
+
+        assert_eq!(config.address.port(), 25565);
+        assert_eq!(config.tick_rate, STANDARD_TPS);
+        assert!(config.connection_mode == ConnectionMode::Online);
+        assert_eq!(config.dimensions.len(), 1);
+        assert_eq!(config.biomes.len(), 1);
+
+        let config = ServerConfigBuilder::<()>::new()
+            .address(([127, 0, 0, 1], 25566))
+            .tick_rate(10)
+            .online_mode(false)
+            .build();
+
+        assert_eq!(config.address().to_string(), "127.0.0.1:25566");
+        assert_eq!(config.tick_rate(), 10);
+        assert!(config.connection_mode() == ConnectionMode::Offline);
+    }
+}
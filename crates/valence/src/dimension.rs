@@ -70,34 +70,48 @@ pub struct Dimension {
     /// * `0 <= height <= 4064`
     /// * `min_y + height <= 2032`
     pub height: i32,
+    /// Whether the dimension has a bedrock ceiling, like the Nether. This
+    /// affects the behavior of maps and the sky in the client.
+    pub has_ceiling: bool,
+    /// When true, water evaporates and lava spreads faster and further.
+    pub ultrawarm: bool,
+    /// When false, piglins and hoglins zombify.
+    pub piglin_safe: bool,
+    /// When false, beds explode when used.
+    pub bed_works: bool,
+    /// When false, respawn anchors explode when used.
+    pub respawn_anchor_works: bool,
+    /// Whether players with the Bad Omen effect can cause raids.
+    pub has_raids: bool,
+    /// The maximum light level at which monsters can spawn. Must be between 0
+    /// and 15.
+    pub monster_spawn_light_level: i32,
+    /// The maximum block light level at which monsters can spawn. Must be
+    /// between 0 and 15.
+    pub monster_spawn_block_light_limit: i32,
+    /// The block tag determining which blocks burn indefinitely, e.g.
+    /// `#minecraft:infiniburn_overworld`.
+    pub infiniburn: String,
     // TODO: The following fields should be added if they can affect the
     //       appearance of the dimension to clients.
-    //       * infiniburn
-    //       * monster_spawn_light_level
-    //       * monster_spawn_block_light_level
-    //       * respawn_anchor_works
     //       * has_skylight
-    //       * bed_works
-    //       * has_raids
     //       * logical_height
     //       * coordinate_scale
-    //       * ultrawarm
-    //       * has_ceiling
 }
 
 impl Dimension {
     pub(crate) fn to_dimension_registry_item(&self) -> Compound {
         let mut item = compound! {
-            "piglin_safe" => true,
-            "has_raids" => true,
-            "monster_spawn_light_level" => 0,
-            "monster_spawn_block_light_limit" => 0,
+            "piglin_safe" => self.piglin_safe,
+            "has_raids" => self.has_raids,
+            "monster_spawn_light_level" => self.monster_spawn_light_level,
+            "monster_spawn_block_light_limit" => self.monster_spawn_block_light_limit,
             "natural" => self.natural,
             "ambient_light" => self.ambient_light,
-            "infiniburn" => "#minecraft:infiniburn_overworld",
-            "respawn_anchor_works" => true,
+            "infiniburn" => self.infiniburn.clone(),
+            "respawn_anchor_works" => self.respawn_anchor_works,
             "has_skylight" => true,
-            "bed_works" => true,
+            "bed_works" => self.bed_works,
             "effects" => match self.effects {
                 DimensionEffects::Overworld => "overworld",
                 DimensionEffects::TheNether => "the_nether",
@@ -107,8 +121,8 @@ impl Dimension {
             "height" => self.height,
             "logical_height" => self.height,
             "coordinate_scale" => 1.0,
-            "ultrawarm" => false,
-            "has_ceiling" => false,
+            "ultrawarm" => self.ultrawarm,
+            "has_ceiling" => self.has_ceiling,
         };
 
         if let Some(t) = self.fixed_time {
@@ -154,6 +168,16 @@ pub(crate) fn validate_dimensions(dimensions: &[Dimension]) -> anyhow::Result<()
                 "fixed_time is out of range in dimension #{i}",
             );
         }
+
+        ensure!(
+            (0..=15).contains(&dim.monster_spawn_light_level),
+            "monster_spawn_light_level is out of range in dimension #{i}",
+        );
+
+        ensure!(
+            (0..=15).contains(&dim.monster_spawn_block_light_limit),
+            "monster_spawn_block_light_limit is out of range in dimension #{i}",
+        );
     }
 
     Ok(())
@@ -168,6 +192,15 @@ impl Default for Dimension {
             effects: DimensionEffects::default(),
             min_y: -64,
             height: 384,
+            has_ceiling: false,
+            ultrawarm: false,
+            piglin_safe: false,
+            bed_works: true,
+            respawn_anchor_works: false,
+            has_raids: true,
+            monster_spawn_light_level: 0,
+            monster_spawn_block_light_limit: 0,
+            infiniburn: "#minecraft:infiniburn_overworld".into(),
         }
     }
 }
@@ -108,6 +108,32 @@ impl<C: Config> Entities<C> {
         }
     }
 
+    /// Like [`Self::insert`], but also places the new entity in `world` at
+    /// the given position and rotation. The head of the entity faces the same
+    /// direction as its body.
+    ///
+    /// The entity is spawned for clients with the entity in view at the end
+    /// of the tick, using the spawn packet appropriate for its kind.
+    pub fn spawn(
+        &mut self,
+        kind: EntityKind,
+        state: C::EntityState,
+        world: WorldId,
+        pos: impl Into<Vec3<f64>>,
+        yaw: f32,
+        pitch: f32,
+    ) -> (EntityId, &mut Entity<C>) {
+        let (id, entity) = self.insert(kind, state);
+
+        entity.world = world;
+        entity.position = pos.into();
+        entity.yaw = yaw;
+        entity.pitch = pitch;
+        entity.head_yaw = yaw;
+
+        (id, entity)
+    }
+
     /// Returns the number of entities in this container.
     pub fn len(&self) -> usize {
         self.slab.len()
@@ -832,14 +858,18 @@ impl<C: Config> Entity<C> {
     ) -> anyhow::Result<()> {
         let entity_id = VarInt(this_id.to_raw());
 
-        let position_delta = self.position - self.old_position;
-        let needs_teleport = position_delta.map(f64::abs).reduce_partial_max() >= 8.0;
+        // Relative moves are in units of 1/4096 of a block. The delta is taken
+        // between the rounded positions so that rounding errors do not
+        // accumulate on the client over many moves.
+        let delta = (self.position * 4096.0).round() - (self.old_position * 4096.0).round();
+        let needs_teleport = delta.map(f64::abs).reduce_partial_max() > i16::MAX as f64;
         let changed_position = self.position != self.old_position;
+        let delta = delta.as_::<i16>().into_array();
 
         if changed_position && !needs_teleport && self.bits.yaw_or_pitch_modified() {
             writer.write_packet(&UpdateEntityPositionAndRotation {
                 entity_id,
-                delta: (position_delta * 4096.0).as_::<i16>().into_array(),
+                delta,
                 yaw: ByteAngle::from_degrees(self.yaw),
                 pitch: ByteAngle::from_degrees(self.pitch),
                 on_ground: self.bits.on_ground(),
@@ -848,7 +878,7 @@ impl<C: Config> Entity<C> {
             if changed_position && !needs_teleport {
                 writer.write_packet(&UpdateEntityPosition {
                     entity_id,
-                    delta: (position_delta * 4096.0).as_::<i16>().into_array(),
+                    delta,
                     on_ground: self.bits.on_ground(),
                 })?;
             }
@@ -911,6 +941,46 @@ impl<C: Config> Entity<C> {
 
         Ok(())
     }
+
+    /// Writes packets which set the complete state of an already spawned
+    /// entity, for clients which missed some of its update packets.
+    pub(crate) fn write_resync_packets(
+        &self,
+        mut writer: impl WritePacket,
+        this_id: EntityId,
+        scratch: &mut Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let entity_id = VarInt(this_id.to_raw());
+
+        writer.write_packet(&TeleportEntity {
+            entity_id,
+            position: self.position.into_array(),
+            yaw: ByteAngle::from_degrees(self.yaw),
+            pitch: ByteAngle::from_degrees(self.pitch),
+            on_ground: self.bits.on_ground(),
+        })?;
+
+        writer.write_packet(&SetEntityVelocity {
+            entity_id,
+            velocity: velocity_to_packet_units(self.velocity).into_array(),
+        })?;
+
+        writer.write_packet(&SetHeadRotation {
+            entity_id,
+            head_yaw: ByteAngle::from_degrees(self.head_yaw),
+        })?;
+
+        scratch.clear();
+        self.variants.write_all_tracked_data(scratch);
+        if !scratch.is_empty() {
+            writer.write_packet(&SetEntityMetadata {
+                entity_id,
+                metadata: RawBytes(scratch),
+            })?;
+        }
+
+        Ok(())
+    }
 }
 
 pub(crate) fn velocity_to_packet_units(vel: Vec3<f32>) -> Vec3<i16> {
@@ -955,6 +1025,26 @@ mod tests {
         assert_eq!(entities.len(), 1);
     }
 
+    #[test]
+    fn entities_can_be_spawned() {
+        let mut entities: Entities<MockConfig> = Entities::new();
+        let (id, _) = entities.spawn(
+            EntityKind::ArmorStand,
+            3,
+            WorldId::NULL,
+            [1.0, 2.0, 3.0],
+            90.0,
+            -45.0,
+        );
+        let entity = &entities[id];
+        assert_eq!(entity.kind(), EntityKind::ArmorStand);
+        assert_eq!(entity.state, 3);
+        assert_eq!(entity.position(), Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(entity.yaw(), 90.0);
+        assert_eq!(entity.pitch(), -45.0);
+        assert_eq!(entity.head_yaw(), 90.0);
+    }
+
     #[test]
     fn entities_can_be_set_and_get_with_uuid() {
         let mut entities: Entities<MockConfig> = Entities::new();
@@ -997,4 +1087,61 @@ mod tests {
         assert_eq!(maybe_cat.state, 75);
         assert_eq!(entities.len(), 2);
     }
+
+    #[test]
+    fn small_moves_are_relative_and_large_moves_teleport() {
+        use valence_protocol::packets::S2cPlayPacket;
+        use valence_protocol::PacketDecoder;
+
+        use crate::packet::PacketWriter;
+
+        fn write_and_decode(entity: &Entity<MockConfig>, id: EntityId) -> Vec<String> {
+            let mut buf = vec![];
+            let mut scratch = vec![];
+            entity
+                .write_update_packets(
+                    PacketWriter::new(&mut buf, None, &mut scratch),
+                    id,
+                    &mut vec![],
+                )
+                .unwrap();
+
+            let mut dec = PacketDecoder::new();
+            dec.queue_slice(&buf);
+
+            let mut names = vec![];
+            while let Some(pkt) = dec.try_next_packet::<S2cPlayPacket>().unwrap() {
+                let name = match pkt {
+                    S2cPlayPacket::UpdateEntityPosition(p) => {
+                        assert_eq!(p.delta, [4096, -2048, 0]);
+                        "UpdateEntityPosition"
+                    }
+                    S2cPlayPacket::TeleportEntity(p) => {
+                        assert_eq!(p.position, [21.0, 0.5, 0.0]);
+                        "TeleportEntity"
+                    }
+                    other => panic!("unexpected packet {other:?}"),
+                };
+                names.push(name.to_owned());
+            }
+            names
+        }
+
+        let mut entities: Entities<MockConfig> = Entities::new();
+        let (id, _) = entities.insert(EntityKind::Zombie, 0);
+        entities.update();
+
+        entities[id].set_position([1.0, -0.5, 0.0]);
+        assert_eq!(
+            write_and_decode(&entities[id], id),
+            ["UpdateEntityPosition"]
+        );
+        entities.update();
+
+        entities[id].set_position([21.0, 0.5, 0.0]);
+        assert_eq!(write_and_decode(&entities[id], id), ["TeleportEntity"]);
+        entities.update();
+
+        assert!(write_and_decode(&entities[id], id).is_empty());
+    }
 }
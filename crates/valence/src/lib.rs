@@ -22,12 +22,25 @@
 //!
 //! # Logging
 //!
-//! Valence uses the [log] crate to report errors and other information. You may
-//! want to use a logging implementation such as [env_logger] to see these
-//! messages.
-//!
+//! Valence uses the [tracing] crate to report errors and other information.
+//! You may want to install a subscriber such as [tracing-subscriber] to see
+//! these messages.
+//!
+//! Events are logged with structured fields. Events concerning a single
+//! connection are emitted in a `connection` span carrying the `remote_addr`
+//! of the connection and, once they are known, the `username` and `uuid` of
+//! the player. This can be used to filter the logs of a single player.
+//!
+//! If no subscriber is installed, events are emitted as [log] records
+//! instead, so logging implementations such as [env_logger] keep working. To
+//! receive the records of crates using [log] in a tracing subscriber, use
+//! [tracing-log].
+//!
+//! [tracing]: https://docs.rs/tracing/latest/tracing/
+//! [tracing-subscriber]: https://docs.rs/tracing-subscriber/latest/tracing_subscriber/
 //! [log]: https://docs.rs/log/latest/log/
 //! [env_logger]: https://docs.rs/env_logger/latest/env_logger/
+//! [tracing-log]: https://docs.rs/tracing-log/latest/tracing_log/
 //!
 //! # An Important Note on [`mem::swap`]
 //!
@@ -100,9 +113,12 @@ pub use valence_protocol as protocol;
 #[doc(inline)]
 pub use {uuid, valence_nbt as nbt, vek};
 
+pub mod auth;
 pub mod biome;
+pub mod boss_bar;
 pub mod chunk;
 pub mod client;
+pub mod command;
 pub mod config;
 pub mod dimension;
 pub mod entity;
@@ -110,24 +126,33 @@ pub mod inventory;
 mod packet;
 pub mod player_list;
 pub mod player_textures;
+pub mod scoreboard;
 pub mod server;
 mod slab;
 mod slab_rc;
 mod slab_versioned;
+mod srv;
 pub mod util;
 pub mod world;
+pub mod world_border;
 
 /// Use `valence::prelude::*` to import the most commonly used items from the
 /// library.
 pub mod prelude {
     pub use biome::{Biome, BiomeId};
+    pub use boss_bar::{BossBar, BossBarId, BossBars};
     pub use chunk::{Chunk, ChunkPos, Chunks, LoadedChunk, UnloadedChunk};
-    pub use client::{Client, ClientEvent, ClientId, Clients};
-    pub use config::{Config, ConnectionMode, PlayerSampleEntry, ServerListPing};
+    pub use client::{BlockChange, Client, ClientEvent, ClientId, Clients};
+    pub use command::{CommandNodeId, CommandTree};
+    pub use config::{
+        Config, ConnectionMode, PlayerSampleEntry, ServerConfig, ServerConfigBuilder,
+        ServerListPing,
+    };
     pub use dimension::{Dimension, DimensionId};
     pub use entity::{Entities, Entity, EntityEvent, EntityId, EntityKind, TrackedData};
     pub use inventory::{Inventories, Inventory, InventoryId};
     pub use player_list::{PlayerList, PlayerListEntry, PlayerListId, PlayerLists};
+    pub use scoreboard::{DisplaySlot, Objective, ObjectiveRenderType, Scoreboard};
     pub use server::{NewClientData, Server, SharedServer, ShutdownResult};
     pub use util::{from_yaw_and_pitch, to_yaw_and_pitch};
     pub use uuid::Uuid;
@@ -137,14 +162,18 @@ pub mod prelude {
     pub use valence_protocol::ident::IdentError;
     pub use valence_protocol::packets::s2c::particle::Particle;
     pub use valence_protocol::packets::s2c::play::SetTitleAnimationTimes;
+    pub use valence_protocol::packets::s2c::update_advancements::FrameType;
     pub use valence_protocol::text::Color;
-    pub use valence_protocol::types::{GameMode, Hand, SoundCategory};
+    pub use valence_protocol::types::{
+        GameMode, Hand, SoundCategory, SoundId, SyncPlayerPosLookFlags,
+    };
     pub use valence_protocol::{
         ident, translation_key, BlockKind, BlockPos, BlockState, Ident, InventoryKind, ItemKind,
         ItemStack, Text, TextFormat, Username, MINECRAFT_VERSION, PROTOCOL_VERSION,
     };
     pub use vek::{Aabb, Mat2, Mat3, Mat4, Vec2, Vec3, Vec4};
-    pub use world::{World, WorldId, Worlds};
+    pub use world::{ChunkRetention, World, WorldId, Worlds};
+    pub use world_border::WorldBorder;
 
     use super::*;
     pub use crate::{async_trait, nbt, vek, Ticks, STANDARD_TPS};
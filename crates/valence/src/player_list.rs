@@ -381,6 +381,22 @@ impl<C: Config> PlayerList<C> {
         }
     }
 
+    /// Returns the number of entries in the player list.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the player list has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns `true` if the player list contains an entry with the given
+    /// UUID.
+    pub fn contains(&self, uuid: Uuid) -> bool {
+        self.entries.contains_key(&uuid)
+    }
+
     /// Returns a reference to the entry with the given UUID.
     ///
     /// If the entry does not exist, `None` is returned.
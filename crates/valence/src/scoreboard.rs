@@ -0,0 +1,436 @@
+//! Scoreboard objectives and their display slots.
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+use valence_protocol::packets::s2c::play::{DisplayObjective, UpdateObjectives, UpdateScore};
+pub use valence_protocol::types::{DisplaySlot, ObjectiveRenderType};
+use valence_protocol::types::{UpdateObjectivesMode, UpdateScoreAction};
+use valence_protocol::{Text, VarInt};
+
+use crate::packet::WritePacket;
+
+/// The maximum length of an objective name in characters.
+pub const MAX_OBJECTIVE_NAME_LEN: usize = 16;
+
+/// The maximum length of a score holder's name in characters.
+pub const MAX_ENTRY_NAME_LEN: usize = 40;
+
+/// The scoreboard of a [`World`](crate::world::World), visible to all clients
+/// in the world.
+///
+/// A scoreboard contains a set of named [`Objective`]s. Each objective can be
+/// shown in one of the [`DisplaySlot`]s such as the sidebar.
+#[derive(Default)]
+pub struct Scoreboard {
+    objectives: HashMap<String, Objective>,
+    /// Objectives removed this tick which clients know about.
+    removed: HashSet<String>,
+    displayed: HashMap<DisplaySlot, String>,
+    modified_slots: HashSet<DisplaySlot>,
+}
+
+impl Scoreboard {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts an objective with no scores into the scoreboard and returns an
+    /// exclusive reference to it.
+    ///
+    /// If an objective with the same name already exists, it is replaced.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is empty or longer than [`MAX_OBJECTIVE_NAME_LEN`]
+    /// characters.
+    pub fn insert_objective(
+        &mut self,
+        name: impl Into<String>,
+        display_name: impl Into<Text>,
+        render_type: ObjectiveRenderType,
+    ) -> &mut Objective {
+        let name = name.into();
+        assert!(
+            !name.is_empty() && name.chars().count() <= MAX_OBJECTIVE_NAME_LEN,
+            "objective name \"{name}\" must be between 1 and {MAX_OBJECTIVE_NAME_LEN} characters"
+        );
+
+        let objective = Objective {
+            display_name: display_name.into(),
+            render_type,
+            scores: HashMap::new(),
+            created_this_tick: true,
+            modified: false,
+            modified_scores: HashSet::new(),
+            removed_scores: HashSet::new(),
+        };
+
+        match self.objectives.entry(name) {
+            Entry::Occupied(mut oe) => {
+                // Objectives created this tick haven't been sent to clients yet, so there is
+                // nothing to remove.
+                if !oe.get().created_this_tick {
+                    self.removed.insert(oe.key().clone());
+
+                    // Clients clear the display slots of removed objectives, so they need to
+                    // be sent again for the replacement.
+                    for (&slot, displayed) in &self.displayed {
+                        if displayed == oe.key() {
+                            self.modified_slots.insert(slot);
+                        }
+                    }
+                }
+                oe.insert(objective);
+                oe.into_mut()
+            }
+            Entry::Vacant(ve) => ve.insert(objective),
+        }
+    }
+
+    /// Removes the objective with the given name. The objective is also
+    /// removed from any display slots it occupies.
+    ///
+    /// Returns whether the objective was present.
+    pub fn remove_objective(&mut self, name: &str) -> bool {
+        let Some(objective) = self.objectives.remove(name) else {
+            return false;
+        };
+
+        if !objective.created_this_tick {
+            self.removed.insert(name.to_owned());
+        }
+
+        self.displayed.retain(|_, displayed| displayed != name);
+
+        true
+    }
+
+    /// Returns a reference to the objective with the given name.
+    pub fn objective(&self, name: &str) -> Option<&Objective> {
+        self.objectives.get(name)
+    }
+
+    /// Returns a mutable reference to the objective with the given name.
+    pub fn objective_mut(&mut self, name: &str) -> Option<&mut Objective> {
+        self.objectives.get_mut(name)
+    }
+
+    /// Returns an iterator over all objectives in an unspecified order.
+    pub fn objectives(&self) -> impl Iterator<Item = (&str, &Objective)> + '_ {
+        self.objectives.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Returns the name of the objective shown in the given display slot.
+    pub fn displayed(&self, slot: DisplaySlot) -> Option<&str> {
+        self.displayed.get(&slot).map(|s| s.as_str())
+    }
+
+    /// Shows the objective with the given name in a display slot, or clears
+    /// the slot if `name` is `None`.
+    ///
+    /// Returns `false` and has no effect if the named objective does not
+    /// exist.
+    pub fn set_displayed(&mut self, slot: DisplaySlot, name: Option<&str>) -> bool {
+        match name {
+            Some(name) => {
+                if !self.objectives.contains_key(name) {
+                    return false;
+                }
+
+                if self.displayed.get(&slot).map(|s| s.as_str()) != Some(name) {
+                    self.displayed.insert(slot, name.to_owned());
+                    self.modified_slots.insert(slot);
+                }
+            }
+            None => {
+                if self.displayed.remove(&slot).is_some() {
+                    self.modified_slots.insert(slot);
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Writes the packets needed to completely initialize this scoreboard.
+    pub(crate) fn write_init_packets(&self, mut writer: impl WritePacket) -> anyhow::Result<()> {
+        for (name, objective) in &self.objectives {
+            objective.write_create_packets(name, &mut writer)?;
+        }
+
+        for (&position, name) in &self.displayed {
+            writer.write_packet(&DisplayObjective {
+                position,
+                score_name: name,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the packets needed to update this scoreboard from the previous
+    /// state to the current state.
+    pub(crate) fn write_update_packets(&self, mut writer: impl WritePacket) -> anyhow::Result<()> {
+        for name in &self.removed {
+            writer.write_packet(&UpdateObjectives {
+                objective_name: name,
+                mode: UpdateObjectivesMode::Remove,
+            })?;
+        }
+
+        for (name, objective) in &self.objectives {
+            if objective.created_this_tick {
+                objective.write_create_packets(name, &mut writer)?;
+                continue;
+            }
+
+            if objective.modified {
+                writer.write_packet(&UpdateObjectives {
+                    objective_name: name,
+                    mode: UpdateObjectivesMode::Update {
+                        objective_value: objective.display_name.clone(),
+                        kind: objective.render_type,
+                    },
+                })?;
+            }
+
+            for entry in &objective.removed_scores {
+                writer.write_packet(&UpdateScore {
+                    entity_name: entry,
+                    action: UpdateScoreAction::Remove {
+                        objective_name: name,
+                    },
+                })?;
+            }
+
+            for entry in &objective.modified_scores {
+                if let Some(&value) = objective.scores.get(entry) {
+                    writer.write_packet(&UpdateScore {
+                        entity_name: entry,
+                        action: UpdateScoreAction::CreateOrUpdate {
+                            objective_name: name,
+                            value: VarInt(value),
+                        },
+                    })?;
+                }
+            }
+        }
+
+        for &position in &self.modified_slots {
+            writer.write_packet(&DisplayObjective {
+                position,
+                score_name: self.displayed(position).unwrap_or(""),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the packets needed to completely clear this scoreboard from a
+    /// client that has received all of the previous updates.
+    pub(crate) fn write_clear_packets(&self, mut writer: impl WritePacket) -> anyhow::Result<()> {
+        let names = self
+            .objectives
+            .iter()
+            .filter(|(_, objective)| !objective.created_this_tick)
+            .map(|(name, _)| name)
+            .chain(&self.removed);
+
+        // Removing an objective also removes its scores and display slots.
+        for name in names {
+            writer.write_packet(&UpdateObjectives {
+                objective_name: name,
+                mode: UpdateObjectivesMode::Remove,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn update(&mut self) {
+        self.removed.clear();
+        self.modified_slots.clear();
+
+        for objective in self.objectives.values_mut() {
+            objective.created_this_tick = false;
+            objective.modified = false;
+            objective.modified_scores.clear();
+            objective.removed_scores.clear();
+        }
+    }
+}
+
+/// A named set of scores in a [`Scoreboard`].
+pub struct Objective {
+    display_name: Text,
+    render_type: ObjectiveRenderType,
+    /// Maps score holder names to scores.
+    scores: HashMap<String, i32>,
+    created_this_tick: bool,
+    modified: bool,
+    modified_scores: HashSet<String>,
+    removed_scores: HashSet<String>,
+}
+
+impl Objective {
+    /// Gets the name of this objective displayed to clients.
+    pub fn display_name(&self) -> &Text {
+        &self.display_name
+    }
+
+    /// Sets the name of this objective displayed to clients.
+    pub fn set_display_name(&mut self, display_name: impl Into<Text>) {
+        let display_name = display_name.into();
+        if self.display_name != display_name {
+            self.display_name = display_name;
+            self.modified = true;
+        }
+    }
+
+    /// Gets how the scores of this objective are rendered in the player list.
+    pub fn render_type(&self) -> ObjectiveRenderType {
+        self.render_type
+    }
+
+    /// Sets how the scores of this objective are rendered in the player list.
+    pub fn set_render_type(&mut self, render_type: ObjectiveRenderType) {
+        if self.render_type != render_type {
+            self.render_type = render_type;
+            self.modified = true;
+        }
+    }
+
+    /// Gets the score of the given score holder.
+    pub fn score(&self, entry: &str) -> Option<i32> {
+        self.scores.get(entry).copied()
+    }
+
+    /// Sets the score of a score holder. The score holder is a username for
+    /// players or an arbitrary line of text when displayed in the sidebar.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `entry` is longer than [`MAX_ENTRY_NAME_LEN`] characters.
+    pub fn set_score(&mut self, entry: impl Into<String>, score: i32) {
+        let entry = entry.into();
+        assert!(
+            entry.chars().count() <= MAX_ENTRY_NAME_LEN,
+            "score holder \"{entry}\" is longer than {MAX_ENTRY_NAME_LEN} characters"
+        );
+
+        if self.scores.get(&entry) != Some(&score) {
+            self.removed_scores.remove(&entry);
+            self.scores.insert(entry.clone(), score);
+            self.modified_scores.insert(entry);
+        }
+    }
+
+    /// Removes the score of a score holder. Returns the previous score if it
+    /// was present.
+    pub fn remove_score(&mut self, entry: &str) -> Option<i32> {
+        let score = self.scores.remove(entry)?;
+        self.modified_scores.remove(entry);
+        self.removed_scores.insert(entry.to_owned());
+        Some(score)
+    }
+
+    /// Returns an iterator over all scores in an unspecified order.
+    pub fn scores(&self) -> impl Iterator<Item = (&str, i32)> + '_ {
+        self.scores.iter().map(|(k, v)| (k.as_str(), *v))
+    }
+
+    fn write_create_packets(&self, name: &str, mut writer: impl WritePacket) -> anyhow::Result<()> {
+        writer.write_packet(&UpdateObjectives {
+            objective_name: name,
+            mode: UpdateObjectivesMode::Create {
+                objective_value: self.display_name.clone(),
+                kind: self.render_type,
+            },
+        })?;
+
+        for (entry, &value) in &self.scores {
+            writer.write_packet(&UpdateScore {
+                entity_name: entry,
+                action: UpdateScoreAction::CreateOrUpdate {
+                    objective_name: name,
+                    value: VarInt(value),
+                },
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::PacketDecoder;
+
+    use super::*;
+    use crate::packet::PacketWriter;
+
+    fn packets(f: impl FnOnce(PacketWriter) -> anyhow::Result<()>) -> Vec<&'static str> {
+        let mut buf = vec![];
+        let mut scratch = vec![];
+        f(PacketWriter::new(&mut buf, None, &mut scratch)).unwrap();
+
+        let mut dec = PacketDecoder::new();
+        dec.queue_slice(&buf);
+
+        let mut res = vec![];
+        while let Some(pkt) = dec.try_next_packet::<S2cPlayPacket>().unwrap() {
+            res.push(match pkt {
+                S2cPlayPacket::UpdateObjectives(_) => "UpdateObjectives",
+                S2cPlayPacket::UpdateScore(_) => "UpdateScore",
+                S2cPlayPacket::DisplayObjective(_) => "DisplayObjective",
+                other => panic!("unexpected packet {other:?}"),
+            });
+        }
+        res
+    }
+
+    #[test]
+    fn removed_objective_sends_remove() {
+        let mut sb = Scoreboard::new();
+        sb.insert_objective("kills", "Kills", ObjectiveRenderType::Integer)
+            .set_score("player", 3);
+        sb.set_displayed(DisplaySlot::Sidebar, Some("kills"));
+
+        assert_eq!(
+            packets(|w| sb.write_init_packets(w)),
+            ["UpdateObjectives", "UpdateScore", "DisplayObjective"]
+        );
+
+        sb.update();
+
+        assert!(sb.remove_objective("kills"));
+        assert_eq!(sb.displayed(DisplaySlot::Sidebar), None);
+        assert_eq!(
+            packets(|w| sb.write_update_packets(w)),
+            ["UpdateObjectives"]
+        );
+
+        sb.update();
+        assert!(packets(|w| sb.write_update_packets(w)).is_empty());
+    }
+
+    #[test]
+    fn replaced_objective_stays_displayed() {
+        let mut sb = Scoreboard::new();
+        sb.insert_objective("kills", "Kills", ObjectiveRenderType::Integer);
+        sb.set_displayed(DisplaySlot::Sidebar, Some("kills"));
+        sb.update();
+
+        sb.insert_objective("kills", "Kills", ObjectiveRenderType::Hearts);
+        assert_eq!(sb.displayed(DisplaySlot::Sidebar), Some("kills"));
+        assert_eq!(
+            packets(|w| sb.write_update_packets(w)),
+            ["UpdateObjectives", "UpdateObjectives", "DisplayObjective"]
+        );
+
+        sb.update();
+        assert!(packets(|w| sb.write_update_packets(w)).is_empty());
+    }
+}
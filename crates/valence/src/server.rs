@@ -1,54 +1,83 @@
 //! The heart of the server.
 
+use std::convert::Infallible;
 use std::error::Error;
 use std::iter::FusedIterator;
-use std::net::{IpAddr, SocketAddr};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::ops::{Deref, DerefMut};
-use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
-use std::{io, thread};
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use std::{fmt, io, thread};
 
-use anyhow::{ensure, Context};
+use anyhow::{anyhow, bail, ensure, Context};
+pub use connection::{ConnectionHandler, RawConnection};
 use flume::{Receiver, Sender};
-pub(crate) use packet_manager::{PlayPacketReceiver, PlayPacketSender};
+pub use local::LocalConnection;
+pub(crate) use metrics::ClientTally;
+pub use metrics::{
+    ClientTallySnapshot, ServerMetrics, TickProfile, MAX_TALLY_LABELS, MAX_TALLY_LABEL_LEN,
+    OTHER_TALLY_LABEL,
+};
+pub(crate) use packet_manager::{
+    inspect_packets, ByteCounters, PlayPacketReceiver, PlayPacketSender, SlowClientError,
+};
+#[cfg(not(feature = "deterministic_rng"))]
 use rand::rngs::OsRng;
+use rand_core::CryptoRngCore;
 use rayon::iter::ParallelIterator;
-use reqwest::Client as ReqwestClient;
 use rsa::{PublicKeyParts, RsaPrivateKey};
 use serde_json::{json, Value};
+use socket2::{Domain, Protocol, Socket, Type};
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::runtime::{Handle, Runtime};
-use tokio::sync::{OwnedSemaphorePermit, Semaphore};
-use tracing::{error, info, info_span, instrument, trace, warn};
+use tokio::runtime::{self, Handle, Runtime};
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+use tokio::time::error::Elapsed;
+use tracing::{debug, error, field, info, info_span, instrument, trace, warn, Span};
+pub use transfer::TransferProfile;
 use uuid::Uuid;
 use valence_nbt::{compound, Compound, List};
 use valence_protocol::packets::c2s::handshake::HandshakeOwned;
 use valence_protocol::packets::c2s::login::LoginStart;
 use valence_protocol::packets::c2s::status::{PingRequest, StatusRequest};
-use valence_protocol::packets::s2c::login::{DisconnectLogin, LoginSuccess, SetCompression};
+use valence_protocol::packets::s2c::login::{DisconnectLogin, SetCompression};
+use valence_protocol::packets::s2c::play::DisconnectPlay;
 use valence_protocol::packets::s2c::status::{PingResponse, StatusResponse};
-use valence_protocol::types::HandshakeNextState;
+use valence_protocol::text::Text;
+use valence_protocol::types::{GameMode, HandshakeNextState, SignedPropertyOwned};
 use valence_protocol::{
-    ident, PacketDecoder, PacketEncoder, Username, VarInt, MINECRAFT_VERSION, PROTOCOL_VERSION,
+    ident, Ident, PacketDecoder, PacketEncoder, Username, VarInt, MINECRAFT_VERSION,
+    PROTOCOL_VERSION,
 };
 
+use crate::auth::Authenticator;
 use crate::biome::{validate_biomes, Biome, BiomeId};
+use crate::boss_bar::BossBars;
 use crate::chunk::entity_partition::update_entity_partition;
+use crate::chunk::ChunkPos;
 use crate::client::{Client, Clients};
-use crate::config::{Config, ConnectionMode, ServerListPing};
+use crate::config::{
+    CompressionSetup, Config, ConnectionMode, OverflowPolicy, ServerListPing, UnknownPacketPolicy,
+};
 use crate::dimension::{validate_dimensions, Dimension, DimensionId};
-use crate::entity::Entities;
+use crate::entity::{Entities, EntityKind};
 use crate::inventory::Inventories;
 use crate::player_list::PlayerLists;
 use crate::player_textures::SignedPlayerTextures;
+use crate::server::metrics::{ClientTallies, ConnectionCounters, TickMetrics};
 use crate::server::packet_manager::InitialPacketManager;
-use crate::world::Worlds;
+use crate::server::transfer::UsedTransferTokens;
+use crate::world::{WorldId, Worlds};
 use crate::Ticks;
 
 mod byte_channel;
+mod connection;
+mod local;
 mod login;
+mod metrics;
 mod packet_manager;
+mod transfer;
 
 /// Contains the entire state of a running Minecraft server, accessible from
 /// within the [init] and [update] functions.
@@ -70,9 +99,14 @@ pub struct Server<C: Config> {
     pub player_lists: PlayerLists<C>,
     /// All of the inventories on the server.
     pub inventories: Inventories<C>,
+    /// All of the boss bars on the server.
+    pub boss_bars: BossBars,
     /// Incremented on every game tick.
     current_tick: Ticks,
     last_tick_duration: Duration,
+    /// The number of ticks that were skipped because the server fell too far
+    /// behind its tick schedule.
+    skipped_ticks: Ticks,
 }
 
 impl<C: Config> Server<C> {
@@ -86,8 +120,41 @@ impl<C: Config> Server<C> {
     pub fn last_tick_duration(&mut self) -> Duration {
         self.last_tick_duration
     }
+
+    /// Returns the total number of ticks that were skipped because the server
+    /// fell more than [`MAX_TICKS_BEHIND`] ticks behind its tick schedule.
+    ///
+    /// A steadily increasing value indicates that the server is unable to
+    /// keep up with the configured [tick rate](SharedServer::tick_rate).
+    pub fn skipped_ticks(&self) -> Ticks {
+        self.skipped_ticks
+    }
+
+    /// Disconnects every client on the server with the given reason and
+    /// returns the number of clients that were disconnected. Clients that had
+    /// already disconnected are not counted.
+    ///
+    /// The clients remain in [`Self::clients`] until they are removed like any
+    /// other disconnected client. This can be used to kick all players before
+    /// calling [`SharedServer::shutdown`].
+    pub fn disconnect_all(&mut self, reason: impl Into<Text> + Clone) -> usize {
+        let mut count = 0;
+
+        for (_, client) in self.clients.iter_mut() {
+            if !client.is_disconnected() {
+                client.disconnect(reason.clone());
+                count += 1;
+            }
+        }
+
+        count
+    }
 }
 
+/// The maximum number of ticks the update loop is allowed to fall behind its
+/// schedule before the missed ticks are skipped instead of caught up on.
+pub const MAX_TICKS_BEHIND: Ticks = 20;
+
 impl<C: Config> Deref for Server<C> {
     type Target = C::ServerState;
 
@@ -124,13 +191,36 @@ struct SharedServerInner<C: Config> {
     connection_mode: ConnectionMode,
     compression_threshold: Option<u32>,
     max_connections: usize,
+    listen_backlog: u32,
+    accept_concurrency: usize,
     incoming_capacity: usize,
     outgoing_capacity: usize,
+    max_chunks_per_tick: usize,
+    /// The tracking range of each entity kind in chunks, indexed by the kind's
+    /// discriminant.
+    entity_tracking_ranges: Vec<i32>,
+    default_game_mode: GameMode,
+    max_initial_packet_size: usize,
+    max_username_len: usize,
+    login_start_timeout: Duration,
+    connection_idle_timeout: Duration,
+    connection_read_timeout: Duration,
+    connection_packet_timeout: Duration,
+    unknown_packet_policy: UnknownPacketPolicy,
+    overflow_policy: OverflowPolicy,
+    inspect_packets: bool,
+    server_brand: Box<str>,
+    feature_flags: Vec<Ident<String>>,
+    server_list_ping_cache_ttl: Option<Duration>,
+    /// The most recent status responses for pings from clients on
+    /// [`PROTOCOL_VERSION`] and on any other version, in that order.
+    status_cache: Mutex<[Option<CachedStatus>; 2]>,
     /// The tokio handle used by the server.
     tokio_handle: Handle,
     /// Holding a runtime handle is not enough to keep tokio working. We need
-    /// to store the runtime here so we don't drop it.
-    _tokio_runtime: Option<Runtime>,
+    /// to store the runtime here so we don't drop it. This is `None` if the
+    /// runtime is kept alive by whoever provided the handle.
+    _tokio_runtime: Option<Arc<Runtime>>,
     dimensions: Vec<Dimension>,
     biomes: Vec<Biome>,
     /// Contains info about dimensions, biomes, and chats.
@@ -138,21 +228,50 @@ struct SharedServerInner<C: Config> {
     registry_codec: Compound,
     /// The instant the server was started.
     start_instant: Instant,
+    /// [`Server::current_tick`] as of the most recent tick.
+    current_tick: AtomicI64,
     /// Receiver for new clients past the login stage.
     new_clients_send: Sender<NewClientMessage>,
     new_clients_recv: Receiver<NewClientMessage>,
     /// A semaphore used to limit the number of simultaneous connections to the
     /// server. Closing this semaphore stops new connections.
     connection_sema: Arc<Semaphore>,
+    /// Set once [`SharedServer::drain`] is called. New connections are not
+    /// accepted while this is true.
+    draining: AtomicBool,
+    /// Notified when draining begins to wake up the accept loops. Each accept
+    /// loop notifies the next one as it stops.
+    drain_notify: Notify,
+    /// The transfer tokens accepted by this server which have not expired.
+    used_transfer_tokens: UsedTransferTokens,
+    /// Set with [`SharedServer::set_paused`] and
+    /// [`SharedServer::step_ticks`].
+    pause: Mutex<PauseState>,
+    /// Notified when `pause` changes to wake up the paused update loop.
+    pause_notify: Condvar,
+    /// The total number of bytes sent and received across all connections.
+    byte_counters: Arc<ByteCounters>,
+    /// Statistics published by the update loop.
+    tick_metrics: TickMetrics,
+    /// The number of handshakes requesting the status and login states.
+    connection_counters: ConnectionCounters,
+    /// The number of connected clients for each brand and locale.
+    client_tallies: Arc<ClientTallies>,
     /// The result that will be returned when the server is shut down.
     shutdown_result: Mutex<Option<ShutdownResult>>,
     /// The RSA keypair used for encryption with clients.
     rsa_key: RsaPrivateKey,
+    /// Replaces `OsRng` during login.
+    #[cfg(feature = "deterministic_rng")]
+    rng: Mutex<Box<dyn CryptoRngCore + Send>>,
     /// The public part of `rsa_key` encoded in DER, which is an ASN.1 format.
     /// This is sent to clients during the authentication process.
     public_key_der: Box<[u8]>,
-    /// For session server requests.
-    http_client: ReqwestClient,
+    /// Verifies players in online mode.
+    authenticator: Box<dyn Authenticator>,
+    /// Takes over connections after login instead of adding them to
+    /// [`Clients`].
+    connection_handler: Option<Box<dyn ConnectionHandler<C>>>,
 }
 
 /// Contains information about a new client joining the server.
@@ -167,6 +286,100 @@ pub struct NewClientData {
     /// The new client's player textures. May be `None` if the client does not
     /// have a skin or cape.
     pub textures: Option<SignedPlayerTextures>,
+    /// All of the profile properties resolved for the new client, including
+    /// the textures. Empty in offline mode.
+    pub properties: Vec<SignedPropertyOwned>,
+}
+
+/// Describes a connection which ended with an error before the client was
+/// added to the server. Passed to [`Config::connection_error`].
+#[derive(Clone, Debug)]
+pub struct ConnectionError {
+    /// The remote address of the connection. This is `None` if the connection
+    /// could not be accepted at all.
+    pub remote_addr: Option<SocketAddr>,
+    /// The stage the connection was in when the error occurred.
+    pub stage: ConnectionStage,
+    /// The error message, including its causes.
+    pub error: String,
+}
+
+/// The stages a connection goes through before the client is added to the
+/// server.
+///
+/// Errors which occur while handling a connection carry the stage they
+/// occurred in, which can be retrieved with [`ConnectionStage::of`].
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ConnectionStage {
+    /// Accepting the connection from the listening socket.
+    Accept,
+    /// Receiving the handshake packet.
+    Handshake,
+    /// Responding to a server list ping.
+    Status,
+    /// Authenticating the client and setting up compression and encryption.
+    Login,
+    /// Running the custom [`ConnectionHandler`] after login.
+    Play,
+}
+
+impl ConnectionStage {
+    /// Returns the stage attached to an error that occurred while handling a
+    /// connection, or `None` if the error does not carry a stage.
+    pub fn of(error: &anyhow::Error) -> Option<Self> {
+        error.downcast_ref().copied()
+    }
+}
+
+impl fmt::Display for ConnectionStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ConnectionStage::Accept => "error accepting connection",
+            ConnectionStage::Handshake => "error during handshake",
+            ConnectionStage::Status => "error handling status",
+            ConnectionStage::Login => "error handling login",
+            ConnectionStage::Play => "error in connection handler",
+        })
+    }
+}
+
+/// Attached to the error of a connection which did not send the login start
+/// packet within [`Config::login_start_timeout`].
+#[derive(Copy, Clone, Debug)]
+struct LoginStartTimeout;
+
+impl fmt::Display for LoginStartTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("no login start received after handshake")
+    }
+}
+
+struct CachedStatus {
+    expires: Instant,
+    json: Arc<str>,
+}
+
+/// Whether the update loop is paused. See [`SharedServer::set_paused`].
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+struct PauseState {
+    paused: bool,
+    /// The number of ticks left to run while paused.
+    steps: u32,
+}
+
+impl PauseState {
+    /// Returns whether the next tick should run, consuming a step if the
+    /// server is paused.
+    fn begin_tick(&mut self) -> bool {
+        if !self.paused {
+            true
+        } else if self.steps > 0 {
+            self.steps -= 1;
+            true
+        } else {
+            false
+        }
+    }
 }
 
 struct NewClientMessage {
@@ -201,7 +414,8 @@ impl<C: Config> SharedServer<C> {
     }
 
     /// Gets the compression threshold for packets. `None` indicates no
-    /// compression.
+    /// compression. Connections set up with [`CompressionSetup::Preset`] may
+    /// use a different threshold.
     pub fn compression_threshold(&self) -> Option<u32> {
         self.0.compression_threshold
     }
@@ -211,6 +425,41 @@ impl<C: Config> SharedServer<C> {
         self.0.max_connections
     }
 
+    /// Gets the number of additional connections that can be accepted before
+    /// [`max_connections`] is reached.
+    ///
+    /// This can be used to report the server as full in
+    /// [`Config::server_list_ping`] or to shed load before the server is
+    /// saturated.
+    ///
+    /// [`max_connections`]: Self::max_connections
+    /// [`Config::server_list_ping`]: crate::config::Config::server_list_ping
+    pub fn available_connection_permits(&self) -> usize {
+        self.0.connection_sema.available_permits()
+    }
+
+    /// Gets the number of open connections, including those that have not
+    /// finished logging in. This is [`max_connections`] minus
+    /// [`available_connection_permits`].
+    ///
+    /// [`max_connections`]: Self::max_connections
+    /// [`available_connection_permits`]: Self::available_connection_permits
+    pub fn active_connections(&self) -> usize {
+        self.0
+            .max_connections
+            .saturating_sub(self.available_connection_permits())
+    }
+
+    /// Discards the cached status response so that the next status ping
+    /// invokes [`Config::server_list_ping`] again. This has no effect if
+    /// [`Config::server_list_ping_cache_ttl`] returned `None`.
+    ///
+    /// [`Config::server_list_ping`]: crate::config::Config::server_list_ping
+    /// [`Config::server_list_ping_cache_ttl`]: crate::config::Config::server_list_ping_cache_ttl
+    pub fn invalidate_status_cache(&self) {
+        *self.0.status_cache.lock().unwrap() = Default::default();
+    }
+
     /// Gets the configured incoming capacity.
     pub fn incoming_capacity(&self) -> usize {
         self.0.incoming_capacity
@@ -221,6 +470,104 @@ impl<C: Config> SharedServer<C> {
         self.0.outgoing_capacity
     }
 
+    /// Gets the maximum number of chunks sent to each client per tick.
+    pub fn max_chunks_per_tick(&self) -> usize {
+        self.0.max_chunks_per_tick
+    }
+
+    /// Gets the distance in chunks at which entities of the given kind are
+    /// visible to clients. This is the value of
+    /// [`Config::entity_tracking_range`] rounded up to a whole number of
+    /// chunks.
+    pub fn entity_tracking_range(&self, kind: EntityKind) -> i32 {
+        self.0.entity_tracking_ranges[kind as usize]
+    }
+
+    /// Gets the game mode clients are in when they join the server.
+    pub fn default_game_mode(&self) -> GameMode {
+        self.0.default_game_mode
+    }
+
+    /// Gets the configured maximum packet size for connections that have not
+    /// finished logging in.
+    pub fn max_initial_packet_size(&self) -> usize {
+        self.0.max_initial_packet_size
+    }
+
+    /// Gets the configured maximum length of usernames accepted at login.
+    pub fn max_username_len(&self) -> usize {
+        self.0.max_username_len
+    }
+
+    /// Gets the time connections have to send the first login packet after
+    /// the handshake.
+    pub fn login_start_timeout(&self) -> Duration {
+        self.0.login_start_timeout
+    }
+
+    /// Gets the maximum time connections which have not finished logging in
+    /// may go without starting to send a packet.
+    pub fn connection_idle_timeout(&self) -> Duration {
+        self.0.connection_idle_timeout
+    }
+
+    /// Gets the maximum time a read or write of a packet may go without
+    /// progress before the connection is closed.
+    pub fn connection_read_timeout(&self) -> Duration {
+        self.0.connection_read_timeout
+    }
+
+    /// Gets the maximum total time to receive a packet from a connection which
+    /// has not finished logging in.
+    pub fn connection_packet_timeout(&self) -> Duration {
+        self.0.connection_packet_timeout
+    }
+
+    /// Gets the configured policy for unrecognized packets.
+    pub fn unknown_packet_policy(&self) -> UnknownPacketPolicy {
+        self.0.unknown_packet_policy
+    }
+
+    /// Gets the policy for clients whose outgoing packet buffer is full.
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.0.overflow_policy
+    }
+
+    /// Gets whether packets are passed to [`Config::inspect_outgoing`] and
+    /// [`Config::inspect_incoming`].
+    pub fn inspect_packets(&self) -> bool {
+        self.0.inspect_packets
+    }
+
+    /// Gets the configured server brand.
+    pub fn server_brand(&self) -> &str {
+        &self.0.server_brand
+    }
+
+    /// Gets the configured feature flags sent to clients when they join.
+    pub fn feature_flags(&self) -> &[Ident<String>] {
+        &self.0.feature_flags
+    }
+
+    /// Gets the server's RSA public key encoded in DER. This is the key sent
+    /// to clients in the encryption request during login, so it is not
+    /// secret.
+    ///
+    /// The key is generated at startup and changes every time the server is
+    /// started.
+    pub fn public_key_der(&self) -> &[u8] {
+        &self.0.public_key_der
+    }
+
+    /// Calls `f` with the random number generator used during login.
+    pub(crate) fn with_rng<R>(&self, f: impl FnOnce(&mut dyn CryptoRngCore) -> R) -> R {
+        #[cfg(feature = "deterministic_rng")]
+        return f(&mut **self.0.rng.lock().unwrap());
+
+        #[cfg(not(feature = "deterministic_rng"))]
+        return f(&mut OsRng);
+    }
+
     /// Gets a handle to the tokio instance this server is using.
     pub fn tokio_handle(&self) -> &Handle {
         &self.0.tokio_handle
@@ -270,7 +617,16 @@ impl<C: Config> SharedServer<C> {
             .map(|(i, b)| (BiomeId(i as u16), b))
     }
 
-    pub(crate) fn registry_codec(&self) -> &Compound {
+    /// Returns the registry codec sent to clients when they join the game.
+    ///
+    /// The codec contains the dimension type and biome registries, which are
+    /// built from [`Config::dimensions`] and [`Config::biomes`] once at
+    /// startup. All effects of custom biomes are included so that they render
+    /// correctly without a resource pack.
+    ///
+    /// [`Config::dimensions`]: crate::config::Config::dimensions
+    /// [`Config::biomes`]: crate::config::Config::biomes
+    pub fn registry_codec(&self) -> &Compound {
         &self.0.registry_codec
     }
 
@@ -279,6 +635,91 @@ impl<C: Config> SharedServer<C> {
         self.0.start_instant
     }
 
+    /// Returns the wall-clock time elapsed since the server was started.
+    ///
+    /// This can diverge from [`Self::uptime_ticks`] when the server falls
+    /// behind its tick schedule.
+    pub fn uptime(&self) -> Duration {
+        self.0.start_instant.elapsed()
+    }
+
+    /// Returns the number of ticks that have elapsed since the server was
+    /// started. This is the value of [`Server::current_tick`] as of the most
+    /// recent tick.
+    ///
+    /// Ticks that were skipped because the server fell behind are not counted,
+    /// so this can diverge from [`Self::uptime`] multiplied by the
+    /// [tick rate](Self::tick_rate).
+    pub fn uptime_ticks(&self) -> Ticks {
+        self.0.current_tick.load(Ordering::Relaxed)
+    }
+
+    /// Returns the total number of bytes sent to all connections since the
+    /// server was started.
+    ///
+    /// Bytes are counted after compression and encryption, so this reflects
+    /// the actual amount of data sent over the network.
+    pub fn bytes_sent(&self) -> u64 {
+        self.0.byte_counters.sent()
+    }
+
+    /// Returns the total number of bytes received from all connections since
+    /// the server was started.
+    ///
+    /// Bytes are counted before decryption and decompression, so this reflects
+    /// the actual amount of data received over the network.
+    pub fn bytes_received(&self) -> u64 {
+        self.0.byte_counters.received()
+    }
+
+    /// Returns the number of connections that requested the server status
+    /// (i.e. server list pings) since the server was started.
+    pub fn status_ping_count(&self) -> u64 {
+        self.0.connection_counters.status_pings()
+    }
+
+    /// Returns the number of connections that attempted to log in since the
+    /// server was started, including those that were rejected.
+    pub fn login_attempt_count(&self) -> u64 {
+        self.0.connection_counters.login_attempts()
+    }
+
+    /// Returns a snapshot of various server statistics. This is cheap enough
+    /// to be polled frequently, e.g. from a metrics endpoint.
+    ///
+    /// See [`ServerMetrics`] for more information.
+    pub fn metrics(&self) -> ServerMetrics {
+        self.0.tick_metrics.snapshot(
+            self.bytes_sent(),
+            self.bytes_received(),
+            self.active_connections(),
+            &self.0.connection_counters,
+        )
+    }
+
+    /// Returns the number of connected clients using each game brand and
+    /// locale.
+    ///
+    /// See [`ClientTallySnapshot`] for more information.
+    pub fn client_tallies(&self) -> ClientTallySnapshot {
+        self.0.client_tallies.snapshot()
+    }
+
+    /// Returns the time spent in each phase of the update loop, averaged over
+    /// recent ticks. Useful to find out whether [`Config::update`] or the
+    /// built-in updates are responsible when the server falls behind.
+    ///
+    /// See [`TickProfile`] for more information.
+    pub fn tick_profile(&self) -> TickProfile {
+        self.0.tick_metrics.profile()
+    }
+
+    /// Creates the contribution of a new client to the brand and locale
+    /// tallies.
+    pub(crate) fn new_client_tally(&self) -> ClientTally {
+        ClientTally::new(self.0.client_tallies.clone())
+    }
+
     /// Immediately stops new connections to the server and initiates server
     /// shutdown. The given result is returned through [`start_server`].
     ///
@@ -291,12 +732,216 @@ impl<C: Config> SharedServer<C> {
         self.0.connection_sema.close();
         *self.0.shutdown_result.lock().unwrap() = Some(res.map_err(|e| e.into()));
     }
+
+    /// Stops accepting new connections and initiates a graceful shutdown of
+    /// the server.
+    ///
+    /// Connections which have already completed the handshake are allowed to
+    /// continue logging in for the duration of `grace_period`. Once the grace
+    /// period has elapsed, the server is shut down as if by calling
+    /// [`Self::shutdown`] with `Ok(())`. Calling this function more than once
+    /// has no additional effect.
+    pub fn drain(&self, grace_period: Duration) {
+        if self.0.draining.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        info!(?grace_period, "draining server");
+
+        self.0.drain_notify.notify_one();
+
+        let shared = self.clone();
+        self.0.tokio_handle.spawn(async move {
+            tokio::time::sleep(grace_period).await;
+            shared.shutdown::<Infallible>(Ok(()));
+        });
+    }
+
+    /// Returns `true` if [`Self::drain`] has been called.
+    pub fn is_draining(&self) -> bool {
+        self.0.draining.load(Ordering::SeqCst)
+    }
+
+    /// Issues a token vouching for the given profile, which lets the player
+    /// join the server in the same network with the ID `target_server_id`
+    /// without authenticating with the session server again. The token should
+    /// be handed to the client or proxy, which sends it back when asked during
+    /// login. See [`ConnectionMode::Transfer`] for more information.
+    ///
+    /// Returns an error if the connection mode is not
+    /// [`ConnectionMode::Transfer`].
+    pub fn issue_transfer_token(
+        &self,
+        profile: &TransferProfile,
+        target_server_id: &str,
+    ) -> anyhow::Result<Vec<u8>> {
+        let ConnectionMode::Transfer { secret, .. } = self.connection_mode() else {
+            bail!("transfer tokens require the transfer connection mode")
+        };
+
+        let mut nonce = [0; 16];
+        self.with_rng(|rng| rng.fill_bytes(&mut nonce));
+
+        transfer::issue(secret, target_server_id, profile, nonce, SystemTime::now())
+    }
+
+    /// Verifies a token created with [`Self::issue_transfer_token`] by any
+    /// server sharing the same secret key and returns the profile it vouches
+    /// for.
+    ///
+    /// Returns an error if the token is invalid, has expired, was issued for
+    /// a server with a different ID, or was already accepted by this server,
+    /// or if the connection mode is not [`ConnectionMode::Transfer`].
+    pub fn verify_transfer_token(&self, token: &[u8]) -> anyhow::Result<TransferProfile> {
+        let ConnectionMode::Transfer { secret, server_id } = self.connection_mode() else {
+            bail!("transfer tokens require the transfer connection mode")
+        };
+
+        let now = SystemTime::now();
+        let token = transfer::verify(secret, server_id, token, now)?;
+        self.0.used_transfer_tokens.mark_used(&token, now)?;
+
+        Ok(token.profile)
+    }
+
+    /// Pauses or resumes the update loop without disconnecting anyone.
+    ///
+    /// While paused, clients that finish logging in are still added to
+    /// [`Clients`] and keepalives are still sent, but [`Config::update`] is not
+    /// called and nothing else is updated. Paused ticks do not advance
+    /// [`Server::current_tick`]. Clients that join while paused receive their
+    /// initial packets once the server resumes.
+    ///
+    /// If this is called during a tick, the tick is completed before the
+    /// server pauses.
+    ///
+    /// Any steps requested with [`step_ticks`](Self::step_ticks) that have not
+    /// run yet are canceled.
+    pub fn set_paused(&self, paused: bool) {
+        *self.0.pause.lock().unwrap() = PauseState { paused, steps: 0 };
+        self.0.pause_notify.notify_all();
+    }
+
+    /// Returns whether the update loop is paused. See
+    /// [`set_paused`](Self::set_paused).
+    ///
+    /// This remains `true` during ticks run with
+    /// [`step_ticks`](Self::step_ticks).
+    pub fn is_paused(&self) -> bool {
+        self.0.pause.lock().unwrap().paused
+    }
+
+    /// Runs exactly `n` full ticks while the server is paused, after which the
+    /// server remains paused. The steps are added to any that have not run
+    /// yet. Useful for advancing the game deterministically while debugging.
+    ///
+    /// This can be called from any thread. It wakes up the paused update loop
+    /// so that the first step starts immediately. Has no effect if the server
+    /// is not paused.
+    pub fn step_ticks(&self, n: u32) {
+        let mut pause = self.0.pause.lock().unwrap();
+
+        if pause.paused {
+            pause.steps = pause.steps.saturating_add(n);
+            self.0.pause_notify.notify_all();
+        }
+    }
+
+    /// Connects a client in the same process, such as the player of an
+    /// integrated server, without going through TCP. The client is added to
+    /// the server's [`Clients`] at the start of the next tick, as though it
+    /// had logged in with the given username and UUID.
+    ///
+    /// Like remote clients, the client must have a valid username and is
+    /// passed to [`Config::login`] and [`Config::login_profile`] first. It is
+    /// also disconnected when it joins if a client with the same username or
+    /// UUID is already connected.
+    ///
+    /// Local connections count towards [`Config::max_connections`]. An error
+    /// is returned if the server is full or draining, or if the client is
+    /// rejected by the login callbacks.
+    pub async fn connect_local(
+        &self,
+        username: Username<String>,
+        uuid: Uuid,
+    ) -> anyhow::Result<LocalConnection> {
+        ensure!(!self.is_draining(), "the server is draining");
+
+        ensure!(
+            Username::new_with_max_len(username.as_str(), self.0.max_username_len).is_ok(),
+            "invalid username \"{username}\""
+        );
+
+        let permit = self
+            .0
+            .connection_sema
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| anyhow!("the server is full"))?;
+
+        let mut ncd = NewClientData {
+            username,
+            uuid,
+            ip: Ipv4Addr::LOCALHOST.into(),
+            textures: None,
+            properties: vec![],
+        };
+
+        // The login callbacks are called from within the server's runtime,
+        // since this may be called from outside of it.
+        let server = self.clone();
+        let ncd = self
+            .tokio_handle()
+            .spawn(async move { run_login_callbacks(&server, &mut ncd).await.map(|()| ncd) })
+            .await?
+            .map_err(|reason| anyhow!("login rejected: {reason}"))?;
+
+        let (local, remote) = tokio::io::duplex(local::LOCAL_BUFFER_SIZE);
+        let (read, write) = tokio::io::split(remote);
+
+        let mngr = InitialPacketManager::new(
+            read,
+            write,
+            PacketEncoder::new(),
+            PacketDecoder::new(),
+            self.0.connection_idle_timeout,
+            self.0.connection_read_timeout,
+            permit,
+            self.0.byte_counters.clone(),
+        );
+
+        // The connection tasks are spawned on the server's runtime, since this
+        // may be called from outside of it.
+        let _guard = self.tokio_handle().enter();
+
+        let (send, recv, permit) = mngr.into_play(
+            self.0.incoming_capacity,
+            self.0.outgoing_capacity,
+            self.tokio_handle().clone(),
+        );
+
+        let msg = NewClientMessage {
+            ncd,
+            send,
+            recv,
+            permit,
+        };
+
+        self.0
+            .new_clients_send
+            .try_send(msg)
+            .map_err(|_| anyhow!("too many clients are waiting to join"))?;
+
+        Ok(LocalConnection::new(local))
+    }
 }
 
 /// Consumes the configuration and starts the server.
 ///
 /// This function blocks the current thread and returns once the server has shut
 /// down, a runtime error occurs, or the configuration is found to be invalid.
+/// An error returned from [`Config::try_init`] is returned before any
+/// connections are accepted.
 pub fn start_server<C: Config>(config: C, data: C::ServerState) -> ShutdownResult {
     let shared = setup_server(config)
         .context("failed to initialize server")
@@ -312,21 +957,162 @@ pub fn start_server<C: Config>(config: C, data: C::ServerState) -> ShutdownResul
         worlds: Worlds::new(shared.clone()),
         player_lists: PlayerLists::new(),
         inventories: Inventories::new(),
+        boss_bars: BossBars::new(),
         current_tick: 0,
         last_tick_duration: Default::default(),
+        skipped_ticks: 0,
     };
 
-    info_span!("configured_init").in_scope(|| shared.config().init(&mut server));
+    info_span!("configured_init")
+        .in_scope(|| shared.config().try_init(&mut server))
+        .context("failed to initialize server")
+        .map_err(Box::<dyn Error + Send + Sync + 'static>::from)?;
+
+    if let Some(world) = shared.config().default_world(&server) {
+        if server.worlds.get(world).is_none() {
+            return Err("the default world returned by the config does not exist".into());
+        }
+    }
 
-    tokio::spawn(do_accept_loop(shared));
+    if shared.config().listen() {
+        tokio::spawn(do_accept_loop(shared));
+    }
 
     do_update_loop(&mut server)
 }
 
+/// Updates the game mode of the player list entries of clients whose game mode
+/// changed during the tick.
+fn update_player_list_game_modes<C: Config>(server: &mut Server<C>) {
+    for (_, client) in server.clients.iter_mut() {
+        if !client.take_game_mode_modified() {
+            continue;
+        }
+
+        let Some(id) = client.player_list() else {
+            continue;
+        };
+
+        if let Some(entry) = server.player_lists.get_mut(id).entry_mut(client.uuid()) {
+            entry.set_game_mode(client.game_mode());
+        }
+    }
+}
+
+/// Moves clients out of worlds which were removed during the tick.
+fn relocate_clients<C: Config>(server: &mut Server<C>) {
+    let is_valid = |worlds: &Worlds<C>, id| worlds.get(id).is_some_and(|w| !w.deleted());
+
+    for (_, client) in server.clients.iter_mut() {
+        // Clients which were never placed in a world are left to the usual
+        // error handling.
+        if client.world() == WorldId::NULL
+            || client.is_disconnected()
+            || is_valid(&server.worlds, client.world())
+        {
+            continue;
+        }
+
+        match server
+            .shared
+            .config()
+            .relocate_client(client, &server.worlds)
+        {
+            Some((world, pos)) if is_valid(&server.worlds, world) => {
+                client.respawn(world);
+                client.teleport(pos, client.yaw(), client.pitch());
+            }
+            _ => client.disconnect("The world you were in no longer exists."),
+        }
+    }
+}
+
+/// Returns whether a connected client has the same username or UUID as a new
+/// client.
+fn is_already_connected<C: Config>(clients: &Clients<C>, ncd: &NewClientData) -> bool {
+    clients.iter().any(|(_, client)| {
+        !client.is_disconnected()
+            && (client.uuid() == ncd.uuid
+                || client
+                    .username()
+                    .as_str()
+                    .eq_ignore_ascii_case(ncd.username.as_str()))
+    })
+}
+
+/// Deletes the chunks which have been out of range of every client for longer
+/// than allowed by the [`ChunkRetention`](crate::world::ChunkRetention) policy
+/// of their world.
+fn unload_chunks<C: Config>(server: &mut Server<C>) {
+    let current_tick = server.current_tick;
+
+    for (_, client) in server.clients.iter() {
+        if client.is_disconnected() {
+            continue;
+        }
+
+        let Some(world) = server.worlds.get_mut(client.world()) else {
+            continue;
+        };
+
+        let Some(retention) = world.chunk_retention() else {
+            continue;
+        };
+
+        let pos = client.position();
+        let dist = client
+            .view_distance()
+            .saturating_add(retention.grace_radius);
+
+        for pos in ChunkPos::at(pos.x, pos.z).in_view(dist) {
+            if let Some(chunk) = world.chunks.get_mut(pos) {
+                chunk.last_in_range = Some(current_tick);
+            }
+        }
+    }
+
+    let cfg = server.shared.config();
+
+    for (id, world) in server.worlds.iter_mut() {
+        let Some(retention) = world.chunk_retention() else {
+            continue;
+        };
+
+        for (pos, chunk) in world.chunks.iter_mut() {
+            if chunk.deleted() {
+                continue;
+            }
+
+            let last_in_range = *chunk.last_in_range.get_or_insert(current_tick);
+
+            if current_tick - last_in_range < retention.timeout {
+                continue;
+            }
+
+            if chunk.is_unsaved() {
+                if !cfg.save_chunk(id, pos, chunk) {
+                    // Retry after another timeout instead of every tick.
+                    chunk.last_in_range = Some(current_tick);
+                    continue;
+                }
+
+                chunk.mark_saved();
+            }
+
+            chunk.set_deleted(true);
+        }
+    }
+}
+
 #[instrument(skip_all)]
 fn setup_server<C: Config>(cfg: C) -> anyhow::Result<SharedServer<C>> {
     let max_connections = cfg.max_connections();
     let address = cfg.address();
+    let listen_backlog = cfg.listen_backlog();
+    let accept_concurrency = cfg.accept_concurrency();
+
+    ensure!(accept_concurrency > 0, "accept concurrency must be nonzero");
+
     let tick_rate = cfg.tick_rate();
 
     ensure!(tick_rate > 0, "tick rate must be greater than zero");
@@ -342,64 +1128,217 @@ fn setup_server<C: Config>(cfg: C) -> anyhow::Result<SharedServer<C>> {
 
     let outgoing_packet_capacity = cfg.outgoing_capacity();
 
+    let max_chunks_per_tick = cfg.max_chunks_per_tick();
+
+    ensure!(
+        max_chunks_per_tick > 0,
+        "max chunks per tick must be nonzero"
+    );
+
+    let mut entity_tracking_ranges = vec![0; EntityKind::ALL.len()];
+
+    for kind in EntityKind::ALL {
+        let range = cfg.entity_tracking_range(kind);
+
+        ensure!(
+            range >= 0,
+            "tracking range of entity kind {kind:?} must not be negative"
+        );
+
+        entity_tracking_ranges[kind as usize] = num::Integer::div_ceil(&range, &16);
+    }
+
     ensure!(
         outgoing_packet_capacity > 0,
         "outgoing packet capacity must be nonzero"
     );
 
-    let compression_threshold = cfg.compression_threshold();
+    let max_initial_packet_size = cfg.max_initial_packet_size();
 
-    let tokio_handle = cfg.tokio_handle();
+    ensure!(
+        max_initial_packet_size > 0,
+        "max initial packet size must be nonzero"
+    );
 
-    let dimensions = cfg.dimensions();
-    validate_dimensions(&dimensions)?;
+    let default_game_mode = cfg.default_game_mode();
 
-    let biomes = cfg.biomes();
-    validate_biomes(&biomes)?;
+    let max_username_len = cfg.max_username_len();
 
-    let rsa_key = RsaPrivateKey::new(&mut OsRng, 1024)?;
+    ensure!(
+        max_username_len >= 3,
+        "max username length must be at least 3"
+    );
 
-    let public_key_der =
-        rsa_der::public_key_to_der(&rsa_key.n().to_bytes_be(), &rsa_key.e().to_bytes_be())
-            .into_boxed_slice();
+    let login_start_timeout = cfg.login_start_timeout();
 
-    let (new_clients_send, new_clients_recv) = flume::bounded(64);
+    ensure!(
+        !login_start_timeout.is_zero(),
+        "login start timeout must be nonzero"
+    );
 
-    let runtime = if tokio_handle.is_none() {
-        Some(Runtime::new()?)
-    } else {
-        None
-    };
+    let connection_idle_timeout = cfg.connection_idle_timeout();
 
-    let tokio_handle = match &runtime {
-        Some(rt) => rt.handle().clone(),
-        None => tokio_handle.unwrap(),
-    };
+    ensure!(
+        !connection_idle_timeout.is_zero(),
+        "connection idle timeout must be nonzero"
+    );
 
-    let registry_codec = make_registry_codec(&dimensions, &biomes);
+    let connection_read_timeout = cfg.connection_read_timeout();
 
-    let server = SharedServerInner {
-        cfg,
-        address,
-        tick_rate,
-        connection_mode,
-        compression_threshold,
-        max_connections,
-        incoming_capacity: incoming_packet_capacity,
-        outgoing_capacity: outgoing_packet_capacity,
-        tokio_handle,
-        _tokio_runtime: runtime,
-        dimensions,
-        biomes,
-        registry_codec,
-        start_instant: Instant::now(),
-        new_clients_send,
+    ensure!(
+        !connection_read_timeout.is_zero(),
+        "connection read timeout must be nonzero"
+    );
+
+    let connection_packet_timeout = cfg.connection_packet_timeout();
+
+    ensure!(
+        !connection_packet_timeout.is_zero(),
+        "connection packet timeout must be nonzero"
+    );
+
+    let unknown_packet_policy = cfg.unknown_packet_policy();
+
+    let overflow_policy = cfg.overflow_policy();
+
+    let inspect_packets = cfg.inspect_packets();
+
+    let server_brand = cfg.server_brand().into();
+
+    let feature_flags = cfg.feature_flags();
+
+    for (i, flag) in feature_flags.iter().enumerate() {
+        ensure!(
+            !feature_flags[..i].contains(flag),
+            "duplicate feature flag \"{flag}\""
+        );
+    }
+
+    let server_list_ping_cache_ttl = cfg.server_list_ping_cache_ttl();
+
+    let authenticator = cfg.authenticator();
+
+    let connection_handler = cfg.connection_handler();
+
+    let compression_threshold = cfg.compression_threshold();
+
+    let tokio_handle = cfg.tokio_handle();
+
+    let tokio_runtime = cfg.tokio_runtime();
+
+    ensure!(
+        tokio_handle.is_none() || tokio_runtime.is_none(),
+        "a tokio handle and runtime cannot both be provided"
+    );
+
+    let worker_threads = cfg.worker_threads();
+
+    ensure!(
+        worker_threads != Some(0),
+        "number of worker threads must be nonzero"
+    );
+
+    let dimensions = cfg.dimensions();
+    validate_dimensions(&dimensions)?;
+
+    let biomes = cfg.biomes();
+    validate_biomes(&biomes)?;
+
+    #[cfg(feature = "deterministic_rng")]
+    let mut rng = {
+        warn!("the deterministic_rng feature is enabled, which is insecure outside of tests");
+        cfg.rng()
+    };
+
+    #[cfg(not(feature = "deterministic_rng"))]
+    let mut rng = OsRng;
+
+    let rsa_key = RsaPrivateKey::new(&mut rng, 1024)?;
+
+    let public_key_der =
+        rsa_der::public_key_to_der(&rsa_key.n().to_bytes_be(), &rsa_key.e().to_bytes_be())
+            .into_boxed_slice();
+
+    let (new_clients_send, new_clients_recv) = flume::bounded(64);
+
+    let runtime = if tokio_runtime.is_some() {
+        tokio_runtime
+    } else if tokio_handle.is_none() {
+        let mut builder = runtime::Builder::new_multi_thread();
+
+        builder.enable_all().thread_name(cfg.thread_name());
+
+        if let Some(n) = worker_threads {
+            builder.worker_threads(n);
+        }
+
+        Some(Arc::new(
+            builder.build().context("failed to build tokio runtime")?,
+        ))
+    } else {
+        None
+    };
+
+    let tokio_handle = match &runtime {
+        Some(rt) => rt.handle().clone(),
+        None => tokio_handle.unwrap(),
+    };
+
+    let registry_codec = make_registry_codec(&dimensions, &biomes);
+
+    let server = SharedServerInner {
+        cfg,
+        address,
+        tick_rate,
+        connection_mode,
+        compression_threshold,
+        max_connections,
+        listen_backlog,
+        accept_concurrency,
+        incoming_capacity: incoming_packet_capacity,
+        outgoing_capacity: outgoing_packet_capacity,
+        max_chunks_per_tick,
+        entity_tracking_ranges,
+        default_game_mode,
+        max_initial_packet_size,
+        max_username_len,
+        login_start_timeout,
+        connection_idle_timeout,
+        connection_read_timeout,
+        connection_packet_timeout,
+        unknown_packet_policy,
+        overflow_policy,
+        inspect_packets,
+        server_brand,
+        feature_flags,
+        server_list_ping_cache_ttl,
+        status_cache: Mutex::new(Default::default()),
+        tokio_handle,
+        _tokio_runtime: runtime,
+        dimensions,
+        biomes,
+        registry_codec,
+        start_instant: Instant::now(),
+        current_tick: AtomicI64::new(0),
+        new_clients_send,
         new_clients_recv,
         connection_sema: Arc::new(Semaphore::new(max_connections)),
+        draining: AtomicBool::new(false),
+        drain_notify: Notify::new(),
+        used_transfer_tokens: UsedTransferTokens::default(),
+        pause: Mutex::new(PauseState::default()),
+        pause_notify: Condvar::new(),
+        byte_counters: Default::default(),
+        tick_metrics: TickMetrics::new(tick_rate as f64),
+        connection_counters: Default::default(),
+        client_tallies: Default::default(),
         shutdown_result: Mutex::new(None),
         rsa_key,
+        #[cfg(feature = "deterministic_rng")]
+        rng: Mutex::new(rng),
         public_key_der,
-        http_client: ReqwestClient::new(),
+        authenticator,
+        connection_handler,
     };
 
     Ok(SharedServer(Arc::new(server)))
@@ -433,23 +1372,61 @@ fn make_registry_codec(dimensions: &[Dimension], biomes: &[Biome]) -> Compound {
 }
 
 fn do_update_loop(server: &mut Server<impl Config>) -> ShutdownResult {
-    let mut tick_start = Instant::now();
     let shared = server.shared.clone();
 
     let threshold = shared.0.compression_threshold;
 
+    let tick_duration = Duration::from_secs_f64((shared.0.tick_rate as f64).recip());
+
+    // Ticks are scheduled relative to a fixed epoch rather than the end of the
+    // previous tick. This prevents scheduling jitter and overruns from
+    // accumulating as drift.
+    let mut next_tick = Instant::now();
+
+    let mut last_tick_start = None;
+
+    // The number of consecutive updates while paused.
+    let mut paused_ticks: Ticks = 0;
+
     loop {
+        let tick_start = Instant::now();
         let _span = info_span!("update_loop", tick = server.current_tick).entered();
 
         if let Some(res) = shared.0.shutdown_result.lock().unwrap().take() {
+            server.clients.retain(|_, client| {
+                client.disconnect_on_shutdown();
+                false
+            });
+
             return res;
         }
 
+        let mut profile = TickProfile::default();
+        let mut lap = tick_start;
+
         for _ in 0..shared.0.new_clients_recv.len() {
             let Ok(msg) = shared.0.new_clients_recv.try_recv() else {
                 break
             };
 
+            // Clients with the same profile may have logged in concurrently,
+            // so duplicates are only caught once they are about to join.
+            if is_already_connected(&server.clients, &msg.ncd) {
+                info!(
+                    username = %msg.ncd.username,
+                    uuid = %msg.ncd.uuid,
+                    ip = %msg.ncd.ip,
+                    "rejecting client which is already connected"
+                );
+
+                let mut send = msg.send;
+                let _ = send.append_packet(&DisconnectPlay {
+                    reason: "You are already connected to this server".into(),
+                });
+                let _ = send.flush();
+                continue;
+            }
+
             info!(
                 username = %msg.ncd.username,
                 uuid = %msg.ncd.uuid,
@@ -457,73 +1434,241 @@ fn do_update_loop(server: &mut Server<impl Config>) -> ShutdownResult {
                 "inserting client"
             );
 
-            server.clients.insert(Client::new(
+            let client = Client::new(
                 msg.send,
                 msg.recv,
                 msg.permit,
                 msg.ncd,
+                shared.clone(),
                 Default::default(),
-            ));
+            );
+
+            let spawn = shared
+                .config()
+                .default_world(server)
+                .map(|world| (world, shared.config().default_spawn_position(server, world)));
+
+            let (id, client) = server.clients.insert(client);
+
+            if shared.0.inspect_packets {
+                client.inspect_packets(id);
+            }
+
+            if let Some((world, position)) = spawn {
+                client.respawn(world);
+                client.teleport(position, 0.0, 0.0);
+            }
+        }
+
+        let run_tick = shared.0.pause.lock().unwrap().begin_tick();
+
+        if !run_tick {
+            paused_ticks += 1;
+
+            for (_, client) in server.clients.iter_mut() {
+                client.update_paused(paused_ticks, &shared);
+            }
+
+            // Paused time should not count towards the measured tick rate.
+            last_tick_start = None;
+
+            wait_while_paused(&shared, &mut next_tick, tick_duration);
+            continue;
+        }
+
+        // Keep counting through single steps so that keepalives are still sent
+        // when stepping slowly.
+        if !shared.is_paused() {
+            paused_ticks = 0;
         }
 
+        profile.new_clients = TickProfile::lap(&mut lap);
+
         // Get serverbound packets first so they are not dealt with a tick late.
         for (_, client) in server.clients.iter_mut() {
             client.prepare_c2s_packets();
         }
 
+        profile.client_packets = TickProfile::lap(&mut lap);
+
         info_span!("configured_update").in_scope(|| shared.config().update(server));
 
+        profile.config_update = TickProfile::lap(&mut lap);
+
+        relocate_clients(server);
+
+        unload_chunks(server);
+
         update_entity_partition(&mut server.entities, &mut server.worlds, threshold);
 
         for (_, world) in server.worlds.iter_mut() {
             world.chunks.update_caches();
         }
 
+        update_player_list_game_modes(server);
         server.player_lists.update_caches(threshold);
 
-        server.clients.par_iter_mut().for_each(|(_, client)| {
+        profile.world_update = TickProfile::lap(&mut lap);
+
+        server.clients.par_iter_mut().for_each(|(id, client)| {
             client.update(
+                id,
                 server.current_tick,
                 &shared,
                 &server.entities,
                 &server.worlds,
                 &server.player_lists,
                 &server.inventories,
+                &server.boss_bars,
             );
         });
 
+        profile.client_update = TickProfile::lap(&mut lap);
+
         server.entities.update();
 
+        profile.entity_update = TickProfile::lap(&mut lap);
+
         server.worlds.update();
 
         server.player_lists.clear_removed();
 
         server.inventories.update();
 
-        // Sleep for the remainder of the tick.
-        let tick_duration = Duration::from_secs_f64((shared.0.tick_rate as f64).recip());
+        server.boss_bars.update(&server.clients);
+
+        profile.finish = TickProfile::lap(&mut lap);
+
         server.last_tick_duration = tick_start.elapsed();
-        thread::sleep(tick_duration.saturating_sub(server.last_tick_duration));
 
-        tick_start = Instant::now();
+        shared.0.tick_metrics.record_tick(
+            server.last_tick_duration,
+            last_tick_start.map(|last| tick_start - last),
+            &profile,
+            server.clients.len(),
+            server
+                .worlds
+                .iter()
+                .map(|(_, world)| world.chunks.iter().count())
+                .sum(),
+            server.entities.len(),
+        );
+
+        last_tick_start = Some(tick_start);
+
+        wait_for_next_tick(&mut next_tick, tick_duration, &mut server.skipped_ticks);
+
         server.current_tick += 1;
+
+        shared
+            .0
+            .current_tick
+            .store(server.current_tick, Ordering::Relaxed);
+
+        // The tick counter only passes 1 once, so this is called exactly once.
+        if server.current_tick == 1 {
+            info_span!("configured_post_start").in_scope(|| shared.config().post_start(server));
+        }
+    }
+}
+
+/// Advances `next_tick` by one tick and sleeps until then, or until the
+/// server is resumed or a step is requested.
+fn wait_while_paused(
+    shared: &SharedServer<impl Config>,
+    next_tick: &mut Instant,
+    tick_duration: Duration,
+) {
+    // Time spent paused should never leave the server behind schedule.
+    *next_tick = (*next_tick + tick_duration).max(Instant::now());
+
+    let pause = shared.0.pause.lock().unwrap();
+    let timeout = next_tick.saturating_duration_since(Instant::now());
+
+    let _ = shared
+        .0
+        .pause_notify
+        .wait_timeout_while(pause, timeout, |p| p.paused && p.steps == 0)
+        .unwrap();
+}
+
+/// Advances `next_tick` by one tick and sleeps until then.
+fn wait_for_next_tick(next_tick: &mut Instant, tick_duration: Duration, skipped_ticks: &mut Ticks) {
+    *next_tick += tick_duration;
+
+    let now = Instant::now();
+
+    if now < *next_tick {
+        // Sleep for the remainder of the tick.
+        thread::sleep(*next_tick - now);
+    } else {
+        let behind = ((now - *next_tick).as_secs_f64() / tick_duration.as_secs_f64()) as Ticks;
+
+        // Run the missed ticks back-to-back if we're only slightly behind.
+        // Otherwise, give up on them so that we don't lag forever.
+        if behind > MAX_TICKS_BEHIND {
+            *next_tick += tick_duration * behind as u32;
+            *skipped_ticks += behind;
+        }
     }
 }
 
 #[instrument(skip_all)]
 async fn do_accept_loop(server: SharedServer<impl Config>) {
-    let listener = match TcpListener::bind(server.0.address).await {
-        Ok(listener) => listener,
+    let listener = match bind_listener(server.0.address, server.0.listen_backlog) {
+        Ok(listener) => Arc::new(listener),
         Err(e) => {
             server.shutdown(Err(e).context("failed to start TCP listener"));
             return;
         }
     };
 
+    for _ in 1..server.0.accept_concurrency {
+        tokio::spawn(accept_connections(server.clone(), listener.clone()));
+    }
+
+    accept_connections(server, listener).await;
+}
+
+/// Binds a nonblocking TCP listener with the given backlog using `socket2`,
+/// since tokio's `TcpListener::bind` always uses its own default backlog.
+fn bind_listener(address: SocketAddr, backlog: u32) -> io::Result<TcpListener> {
+    let socket = Socket::new(
+        Domain::for_address(address),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+
+    // Match the behavior of `TcpListener::bind`, which allows the address to be
+    // reused on Unix platforms.
+    #[cfg(not(windows))]
+    socket.set_reuse_address(true)?;
+
+    socket.set_nonblocking(true)?;
+    socket.bind(&address.into())?;
+    socket.listen(backlog.min(i32::MAX as u32) as i32)?;
+
+    TcpListener::from_std(socket.into())
+}
+
+/// Accepts connections until the server is drained or shut down. Multiple
+/// instances of this may run concurrently on the same listener.
+async fn accept_connections(server: SharedServer<impl Config>, listener: Arc<TcpListener>) {
     loop {
-        match server.0.connection_sema.clone().acquire_owned().await {
-            Ok(permit) => match listener.accept().await {
-                Ok((stream, remote_addr)) => {
+        if server.is_draining() {
+            break;
+        }
+
+        let accept = async {
+            let permit = server.0.connection_sema.clone().acquire_owned().await?;
+            anyhow::Ok((permit, listener.accept().await))
+        };
+
+        tokio::select! {
+            // Drop the listener promptly once draining begins.
+            _ = server.0.drain_notify.notified() => break,
+            res = accept => match res {
+                Ok((permit, Ok((stream, remote_addr)))) => {
                     tokio::spawn(handle_connection(
                         server.clone(),
                         stream,
@@ -531,17 +1676,36 @@ async fn do_accept_loop(server: SharedServer<impl Config>) {
                         permit,
                     ));
                 }
-                Err(e) => {
-                    error!("failed to accept incoming connection: {e}");
+                Ok((_, Err(e))) => {
+                    error!(error = %e, "failed to accept incoming connection");
+
+                    server.config().connection_error(
+                        &server,
+                        &ConnectionError {
+                            remote_addr: None,
+                            stage: ConnectionStage::Accept,
+                            error: e.to_string(),
+                        },
+                    );
                 }
-            },
-            // Closed semaphore indicates server shutdown.
-            Err(_) => return,
+                // Closed semaphore indicates server shutdown.
+                Err(_) => return,
+            }
         }
     }
+
+    // Pass the notification on so that the other accept tasks stop as well.
+    server.0.drain_notify.notify_one();
 }
 
-#[instrument(skip(server, stream))]
+/// Handles a new connection. Events logged while handling the connection are
+/// in a span carrying the remote address and, once they are known, the
+/// username and UUID of the player.
+#[instrument(
+    name = "connection",
+    skip_all,
+    fields(%remote_addr, username = field::Empty, uuid = field::Empty)
+)]
 async fn handle_connection(
     server: SharedServer<impl Config>,
     stream: TcpStream,
@@ -551,56 +1715,110 @@ async fn handle_connection(
     trace!("handling connection");
 
     if let Err(e) = stream.set_nodelay(true) {
-        error!("failed to set TCP_NODELAY: {e}");
+        error!(error = %e, "failed to set TCP_NODELAY");
     }
 
     let (read, write) = stream.into_split();
 
-    let mngr = InitialPacketManager::new(
+    let mut dec = PacketDecoder::new();
+    dec.set_max_packet_len(Some(
+        server
+            .0
+            .max_initial_packet_size
+            .try_into()
+            .unwrap_or(i32::MAX),
+    ));
+
+    let mut mngr = InitialPacketManager::new(
         read,
         write,
         PacketEncoder::new(),
-        PacketDecoder::new(),
-        Duration::from_secs(5),
+        dec,
+        server.0.connection_idle_timeout,
+        server.0.connection_read_timeout,
         permit,
+        server.0.byte_counters.clone(),
     );
 
+    mngr.set_packet_timeout(Some(server.0.connection_packet_timeout));
+
     // TODO: peek stream for 0xFE legacy ping
 
-    if let Err(e) = handle_handshake(server, mngr, remote_addr).await {
+    if let Err(e) = handle_handshake(server.clone(), mngr, remote_addr).await {
+        let stage = ConnectionStage::of(&e).unwrap_or(ConnectionStage::Handshake);
+
+        let error = ConnectionError {
+            remote_addr: Some(remote_addr),
+            stage,
+            error: format!("{e:#}"),
+        };
+
         // EOF can happen if the client disconnects while joining, which isn't
-        // very erroneous.
-        if let Some(e) = e.downcast_ref::<io::Error>() {
-            if e.kind() == io::ErrorKind::UnexpectedEof {
-                return;
-            }
+        // very erroneous. Connections which never send the login start are
+        // most likely not clients at all.
+        let expected = e.is::<LoginStartTimeout>()
+            || e.downcast_ref::<io::Error>()
+                .is_some_and(|e| e.kind() == io::ErrorKind::UnexpectedEof);
+
+        if expected {
+            debug!(error = error.error, ?stage, "connection ended");
+        } else {
+            warn!(error = error.error, ?stage, "connection ended with error");
         }
-        warn!("connection ended with error: {e:#}");
+
+        server.config().connection_error(&server, &error);
     }
 }
 
+/// Handles the connection from the handshake onwards. Errors are given the
+/// [`ConnectionStage`] they occurred in as context.
 async fn handle_handshake(
     server: SharedServer<impl Config>,
     mut mngr: InitialPacketManager<OwnedReadHalf, OwnedWriteHalf>,
     remote_addr: SocketAddr,
 ) -> anyhow::Result<()> {
-    let handshake = mngr.recv_packet::<HandshakeOwned>().await?;
+    let handshake = mngr
+        .recv_packet::<HandshakeOwned>()
+        .await
+        .context(ConnectionStage::Handshake)?;
 
-    ensure!(
-        matches!(server.connection_mode(), ConnectionMode::BungeeCord)
-            || handshake.server_address.chars().count() <= 255,
-        "handshake server address is too long"
-    );
+    // Connections which have not completed the handshake by the time draining
+    // begins are not allowed to proceed.
+    if server.is_draining() {
+        return Ok(());
+    }
+
+    if !matches!(server.connection_mode(), ConnectionMode::BungeeCord)
+        && handshake.server_address.chars().count() > 255
+    {
+        return Err(anyhow!("handshake server address is too long"))
+            .context(ConnectionStage::Handshake);
+    }
+
+    server.0.connection_counters.record(handshake.next_state);
 
     match handshake.next_state {
         HandshakeNextState::Status => handle_status(server, mngr, remote_addr, handshake)
             .await
-            .context("error handling status"),
+            .context(ConnectionStage::Status),
         HandshakeNextState::Login => match handle_login(&server, &mut mngr, remote_addr, handshake)
             .await
-            .context("error handling login")?
+            .context(ConnectionStage::Login)?
         {
             Some(ncd) => {
+                trace!(
+                    compressed = mngr.is_compressed(),
+                    encrypted = mngr.is_encrypted(),
+                    "login complete"
+                );
+
+                if let Some(handler) = &server.0.connection_handler {
+                    return handler
+                        .handle(&server, ncd, RawConnection::new(mngr, remote_addr))
+                        .await
+                        .context(ConnectionStage::Play);
+                }
+
                 let (send, recv, permit) = mngr.into_play(
                     server.0.incoming_capacity,
                     server.0.outgoing_capacity,
@@ -630,10 +1848,70 @@ async fn handle_status(
 ) -> anyhow::Result<()> {
     mngr.recv_packet::<StatusRequest>().await?;
 
+    let json = status_json(&server, remote_addr, &handshake).await;
+
+    let Some(json) = json else {
+        return Ok(());
+    };
+
+    mngr.send_packet(&StatusResponse { json: &json }).await?;
+
+    let PingRequest { payload } = mngr.recv_packet().await?;
+
+    mngr.send_packet(&PingResponse { payload }).await?;
+
+    Ok(())
+}
+
+/// Gets the serialized status response for a ping, reusing a cached response
+/// if [`Config::server_list_ping_cache_ttl`] allows it. Returns `None` if the
+/// ping should be ignored.
+async fn status_json(
+    server: &SharedServer<impl Config>,
+    remote_addr: SocketAddr,
+    handshake: &HandshakeOwned,
+) -> Option<Arc<str>> {
+    let Some(ttl) = server.0.server_list_ping_cache_ttl else {
+        return status_response_json(server, remote_addr, handshake).await;
+    };
+
+    // Clients choose the protocol version they send, so all other versions
+    // share one response. Otherwise, sending a new version with every ping
+    // would get around the cache.
+    let slot = usize::from(handshake.protocol_version.0 != PROTOCOL_VERSION);
+
+    let cached = server.0.status_cache.lock().unwrap()[slot]
+        .as_ref()
+        .filter(|cached| Instant::now() < cached.expires)
+        .map(|cached| cached.json.clone());
+
+    if cached.is_some() {
+        return cached;
+    }
+
+    // Ignored pings are not cached since the decision may depend on the
+    // address of the client, e.g. to ignore banned IPs.
+    let json = status_response_json(server, remote_addr, handshake).await?;
+
+    server.0.status_cache.lock().unwrap()[slot] = Some(CachedStatus {
+        expires: Instant::now() + ttl,
+        json: json.clone(),
+    });
+
+    Some(json)
+}
+
+/// Invokes [`Config::server_list_ping`] and serializes the response, or
+/// returns `None` if the ping should be ignored.
+async fn status_response_json(
+    server: &SharedServer<impl Config>,
+    remote_addr: SocketAddr,
+    handshake: &HandshakeOwned,
+) -> Option<Arc<str>> {
     match server
         .0
         .cfg
-        .server_list_ping(&server, remote_addr, handshake.protocol_version.0)
+        .server_list_ping(server, remote_addr, handshake.protocol_version.0)
         .await
     {
         ServerListPing::Respond {
@@ -641,11 +1919,13 @@ async fn handle_status(
             max_players,
             player_sample,
             description,
+            version_name,
+            previews_chat,
             favicon_png,
         } => {
             let mut json = json!({
                 "version": {
-                    "name": MINECRAFT_VERSION,
+                    "name": version_name.as_deref().unwrap_or(MINECRAFT_VERSION),
                     "protocol": PROTOCOL_VERSION
                 },
                 "players": {
@@ -654,6 +1934,7 @@ async fn handle_status(
                     "sample": player_sample,
                 },
                 "description": description,
+                "previewsChat": previews_chat,
             });
 
             if let Some(data) = favicon_png {
@@ -664,19 +1945,10 @@ async fn handle_status(
                     .insert("favicon".to_owned(), Value::String(buf));
             }
 
-            mngr.send_packet(&StatusResponse {
-                json: &json.to_string(),
-            })
-            .await?;
+            Some(json.to_string().into())
         }
-        ServerListPing::Ignore => return Ok(()),
+        ServerListPing::Ignore => None,
     }
-
-    let PingRequest { payload } = mngr.recv_packet().await?;
-
-    mngr.send_packet(&PingResponse { payload }).await?;
-
-    Ok(())
 }
 
 /// Handle the login process and return the new client's data if successful.
@@ -691,41 +1963,1865 @@ async fn handle_login(
         return Ok(None);
     }
 
+    mngr.set_idle_timeout(server.0.login_start_timeout);
+    mngr.set_packet_timeout(Some(server.0.login_start_timeout));
+
     let LoginStart {
         username,
         profile_id: _, // TODO
-    } = mngr.recv_packet().await?;
+    } = match mngr.recv_packet().await {
+        Ok(pkt) => pkt,
+        Err(e) if e.is::<Elapsed>() => return Err(e.context(LoginStartTimeout)),
+        Err(e) => return Err(e),
+    };
+
+    let Ok(username) = Username::new_with_max_len(username, server.0.max_username_len) else {
+        mngr.send_packet(&DisconnectLogin {
+            reason: "Invalid username".into(),
+        })
+        .await?;
+        return Ok(None);
+    };
 
     let username = username.to_owned_username();
 
-    let ncd = match server.connection_mode() {
+    mngr.set_idle_timeout(server.0.connection_idle_timeout);
+    mngr.set_packet_timeout(Some(server.0.connection_packet_timeout));
+
+    Span::current().record("username", username.as_str());
+
+    let mut ncd = match server.connection_mode() {
         ConnectionMode::Online => login::online(server, mngr, remote_addr, username).await?,
         ConnectionMode::Offline => login::offline(remote_addr, username)?,
         ConnectionMode::BungeeCord => login::bungeecord(&handshake.server_address, username)?,
         ConnectionMode::Velocity { secret } => login::velocity(mngr, username, secret).await?,
+        ConnectionMode::Transfer { .. } => {
+            login::transfer(server, mngr, remote_addr, username).await?
+        }
     };
 
-    if let Some(threshold) = server.0.compression_threshold {
-        mngr.send_packet(&SetCompression {
-            threshold: VarInt(threshold as i32),
-        })
-        .await?;
+    // The proxy or session server may have changed the username.
+    Span::current()
+        .record("username", ncd.username.as_str())
+        .record("uuid", field::display(ncd.uuid));
 
-        mngr.set_compression(Some(threshold));
+    match server
+        .0
+        .cfg
+        .compression_setup(&ncd, &handshake.server_address)
+    {
+        CompressionSetup::Negotiate => {
+            if let Some(threshold) = server.0.compression_threshold {
+                mngr.send_packet(&SetCompression {
+                    threshold: VarInt(threshold as i32),
+                })
+                .await?;
+
+                mngr.set_compression(Some(threshold));
+            }
+        }
+        CompressionSetup::Preset(threshold) => mngr.set_compression(threshold),
     }
 
-    if let Err(reason) = server.0.cfg.login(server, &ncd).await {
-        info!("disconnect at login: \"{reason}\"");
+    if let Err(reason) = run_login_callbacks(server, &mut ncd).await {
+        info!(%reason, "disconnect at login");
         mngr.send_packet(&DisconnectLogin { reason }).await?;
         return Ok(None);
     }
 
-    mngr.send_packet(&LoginSuccess {
-        uuid: ncd.uuid,
-        username: ncd.username.as_str_username(),
-        properties: vec![],
-    })
-    .await?;
+    Span::current()
+        .record("username", ncd.username.as_str())
+        .record("uuid", field::display(ncd.uuid));
+
+    mngr.send_packet(&login::login_success(&ncd)).await?;
 
     Ok(Some(ncd))
 }
+
+/// Passes a new client to [`Config::login`] and applies the profile returned
+/// by [`Config::login_profile`]. Returns the reason the client is rejected, if
+/// any.
+async fn run_login_callbacks<C: Config>(
+    server: &SharedServer<C>,
+    ncd: &mut NewClientData,
+) -> Result<(), Text> {
+    server.0.cfg.login(server, ncd).await?;
+
+    if let Some((username, uuid)) = server.0.cfg.login_profile(server, ncd).await {
+        // The replacement username must still be acceptable to the server.
+        if Username::new_with_max_len(username.as_str(), server.0.max_username_len).is_err() {
+            warn!(%username, "login profile has an invalid username");
+            return Err("Invalid username".into());
+        }
+
+        // The textures and properties are signed for the original profile.
+        if uuid != ncd.uuid {
+            ncd.textures = None;
+            ncd.properties.clear();
+        }
+
+        ncd.username = username;
+        ncd.uuid = uuid;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use async_trait::async_trait;
+    use rsa::{PaddingScheme, PublicKey, RsaPublicKey};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+    use valence_protocol::packets::c2s::handshake::Handshake;
+    use valence_protocol::packets::c2s::login::{EncryptionResponse, LoginPluginResponse};
+    use valence_protocol::packets::c2s::play::{KeepAliveC2s, LockDifficulty, SetPlayerPosition};
+    use valence_protocol::packets::s2c::login::S2cLoginPacket;
+    use valence_protocol::packets::s2c::play::{KeepAliveS2c, PluginMessageS2c};
+    use valence_protocol::packets::{C2sPlayPacket, S2cPlayPacket};
+    use valence_protocol::types::{GameEventKind, HandshakeNextState, PlayerAbilitiesFlags};
+    use valence_protocol::{BoundedArray, EncodePacket, PacketDecoder, RawBytes, RawPacket};
+
+    use super::*;
+    use crate::auth::ResolvedProfile;
+    use crate::chunk::{LoadedChunk, UnloadedChunk};
+    use crate::client::{ClientEvent, ClientId, DisconnectReason};
+    use crate::entity::TrackedData;
+    use crate::server::packet_manager::INSPECT_CAPACITY;
+    use crate::world::{ChunkRetention, World};
+
+    struct TestConfig {
+        game_mode: GameMode,
+        feature_flags: Vec<Ident<String>>,
+        /// The username and UUID assigned by the login callback.
+        login_profile: Option<(&'static str, Uuid)>,
+        /// The usernames and disconnect reasons of clients which have left.
+        left: Mutex<Vec<(String, DisconnectReason)>>,
+        outgoing_capacity: usize,
+        overflow_policy: OverflowPolicy,
+        status_cache_ttl: Option<Duration>,
+        /// Status pings from this address are ignored.
+        ignored_ip: Option<IpAddr>,
+        /// The number of times the server list ping callback was invoked.
+        pings: AtomicUsize,
+        connection_mode: ConnectionMode,
+        login_start_timeout: Duration,
+        max_movement: Option<f64>,
+        /// The connection errors reported to the callback.
+        connection_errors: Mutex<Vec<ConnectionError>>,
+        /// The result of saving a chunk.
+        save_chunks: bool,
+        /// The chunks passed to the save callback.
+        saved_chunks: Mutex<Vec<(WorldId, ChunkPos)>>,
+        /// The number of inspected outgoing system chat messages.
+        inspected_messages: AtomicUsize,
+        /// Held by tests to stall the inspection of outgoing packets.
+        inspect_gate: Mutex<()>,
+        /// The number of inspected incoming difficulty locks.
+        inspected_difficulty_locks: AtomicUsize,
+    }
+
+    impl Default for TestConfig {
+        fn default() -> Self {
+            Self {
+                game_mode: GameMode::Survival,
+                feature_flags: vec![ident!("vanilla")],
+                login_profile: None,
+                left: Mutex::new(vec![]),
+                outgoing_capacity: 1 << 20,
+                overflow_policy: OverflowPolicy::default(),
+                status_cache_ttl: None,
+                ignored_ip: None,
+                pings: AtomicUsize::new(0),
+                connection_mode: ConnectionMode::Offline,
+                login_start_timeout: Duration::from_secs(2),
+                max_movement: None,
+                connection_errors: Mutex::new(vec![]),
+                save_chunks: false,
+                saved_chunks: Mutex::new(vec![]),
+                inspected_messages: AtomicUsize::new(0),
+                inspect_gate: Mutex::new(()),
+                inspected_difficulty_locks: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Config for TestConfig {
+        type ServerState = ();
+        type ClientState = ();
+        type EntityState = ();
+        type WorldState = ();
+        type ChunkState = ();
+        type PlayerListState = ();
+        type InventoryState = ();
+
+        fn connection_mode(&self) -> ConnectionMode {
+            self.connection_mode.clone()
+        }
+
+        fn authenticator(&self) -> Box<dyn Authenticator> {
+            Box::new(TestAuthenticator)
+        }
+
+        fn worker_threads(&self) -> Option<usize> {
+            Some(1)
+        }
+
+        fn default_game_mode(&self) -> GameMode {
+            self.game_mode
+        }
+
+        fn feature_flags(&self) -> Vec<Ident<String>> {
+            self.feature_flags.clone()
+        }
+
+        fn compression_threshold(&self) -> Option<u32> {
+            None
+        }
+
+        fn outgoing_capacity(&self) -> usize {
+            self.outgoing_capacity
+        }
+
+        fn overflow_policy(&self) -> OverflowPolicy {
+            self.overflow_policy
+        }
+
+        fn server_list_ping_cache_ttl(&self) -> Option<Duration> {
+            self.status_cache_ttl
+        }
+
+        fn login_start_timeout(&self) -> Duration {
+            self.login_start_timeout
+        }
+
+        fn max_movement_per_tick(&self) -> Option<f64> {
+            self.max_movement
+        }
+
+        fn connection_error(&self, _shared: &SharedServer<Self>, error: &ConnectionError) {
+            self.connection_errors.lock().unwrap().push(error.clone());
+        }
+
+        async fn server_list_ping(
+            &self,
+            _shared: &SharedServer<Self>,
+            remote_addr: SocketAddr,
+            _protocol_version: i32,
+        ) -> ServerListPing {
+            self.pings.fetch_add(1, Ordering::SeqCst);
+
+            if self.ignored_ip == Some(remote_addr.ip()) {
+                return ServerListPing::Ignore;
+            }
+
+            ServerListPing::Respond {
+                online_players: 0,
+                max_players: 0,
+                player_sample: Cow::Borrowed(&[]),
+                description: Text::default(),
+                version_name: None,
+                previews_chat: false,
+                favicon_png: None,
+            }
+        }
+
+        async fn login_profile(
+            &self,
+            _shared: &SharedServer<Self>,
+            _ncd: &NewClientData,
+        ) -> Option<(Username<String>, Uuid)> {
+            let (username, uuid) = self.login_profile?;
+            // Skip validation to check that the server does it instead.
+            Some((
+                Username::new_with_max_len(username.to_owned(), usize::MAX).unwrap(),
+                uuid,
+            ))
+        }
+
+        fn client_left(
+            &self,
+            _shared: &SharedServer<Self>,
+            client: &Client<Self>,
+            reason: DisconnectReason,
+        ) {
+            assert_eq!(client.disconnect_reason(), Some(&reason));
+
+            let username = client.username().as_str().to_owned();
+            self.left.lock().unwrap().push((username, reason));
+        }
+
+        fn save_chunk(
+            &self,
+            world: WorldId,
+            pos: ChunkPos,
+            _chunk: &mut LoadedChunk<Self>,
+        ) -> bool {
+            self.saved_chunks.lock().unwrap().push((world, pos));
+            self.save_chunks
+        }
+
+        fn inspect_outgoing(&self, _client: ClientId, pkt: &S2cPlayPacket) {
+            if let S2cPlayPacket::SystemChatMessage(_) = pkt {
+                self.inspected_messages.fetch_add(1, Ordering::Relaxed);
+                drop(self.inspect_gate.lock().unwrap());
+            }
+        }
+
+        fn inspect_incoming(&self, _client: ClientId, pkt: &C2sPlayPacket) {
+            if let C2sPlayPacket::LockDifficulty(_) = pkt {
+                self.inspected_difficulty_locks
+                    .fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Verifies every player as online with the UUID 2.
+    struct TestAuthenticator;
+
+    #[async_trait]
+    impl Authenticator for TestAuthenticator {
+        async fn verify(
+            &self,
+            username: Username<&str>,
+            _shared_secret: &[u8],
+            _server_hash: &str,
+            _ip: IpAddr,
+        ) -> anyhow::Result<ResolvedProfile> {
+            Ok(ResolvedProfile {
+                uuid: Uuid::from_u128(2),
+                username: username.to_owned_username(),
+                properties: vec![textures_property()],
+            })
+        }
+    }
+
+    fn textures_property() -> SignedPropertyOwned {
+        SignedPropertyOwned {
+            name: "textures".into(),
+            value: base64::encode(
+                r#"{"textures":{"SKIN":{"url":"http://textures.minecraft.net/texture/abc"}}}"#,
+            ),
+            signature: Some(base64::encode("signature")),
+        }
+    }
+
+    /// Opens a local socket to the server on which the client has sent the
+    /// contents of `enc`.
+    async fn connect(
+        shared: &SharedServer<TestConfig>,
+        mut enc: PacketEncoder,
+    ) -> (
+        TcpStream,
+        InitialPacketManager<OwnedReadHalf, OwnedWriteHalf>,
+        SocketAddr,
+    ) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut remote = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (stream, remote_addr) = listener.accept().await.unwrap();
+        let (read, write) = stream.into_split();
+
+        remote.write_all(&enc.take()).await.unwrap();
+
+        let permit = shared
+            .0
+            .connection_sema
+            .clone()
+            .try_acquire_owned()
+            .unwrap();
+
+        let mngr = InitialPacketManager::new(
+            read,
+            write,
+            PacketEncoder::new(),
+            PacketDecoder::new(),
+            Duration::from_secs(5),
+            Duration::from_secs(5),
+            permit,
+            shared.0.byte_counters.clone(),
+        );
+
+        (remote, mngr, remote_addr)
+    }
+
+    /// Handles a connection on which the client sends a handshake to
+    /// `next_state` with the given server address, followed by a packet with
+    /// an unknown ID. Returns the stage of the resulting error.
+    fn error_stage(
+        next_state: HandshakeNextState,
+        server_address: &str,
+    ) -> Option<ConnectionStage> {
+        let shared = setup_server(TestConfig::default()).unwrap();
+
+        shared.tokio_handle().clone().block_on(async {
+            let mut enc = PacketEncoder::new();
+            enc.append_packet(&Handshake {
+                protocol_version: VarInt(PROTOCOL_VERSION),
+                server_address,
+                server_port: 25565,
+                next_state,
+            })
+            .unwrap();
+            // A packet of length 1 with ID 0x7f.
+            enc.append_bytes(&[1, 0x7f]);
+
+            let (_remote, mngr, remote_addr) = connect(&shared, enc).await;
+
+            let e = handle_handshake(shared.clone(), mngr, remote_addr)
+                .await
+                .unwrap_err();
+
+            ConnectionStage::of(&e)
+        })
+    }
+
+    /// Logs in as "Steve" over a local socket and returns the username and
+    /// UUID in the login success packet, or `None` if the client was
+    /// disconnected.
+    fn login(cfg: TestConfig) -> Option<(String, Uuid)> {
+        let shared = setup_server(cfg).unwrap();
+
+        shared.tokio_handle().clone().block_on(async {
+            let mut enc = PacketEncoder::new();
+            enc.append_packet(&LoginStart {
+                username: "Steve",
+                profile_id: None,
+            })
+            .unwrap();
+
+            let (mut remote, mut mngr, remote_addr) = connect(&shared, enc).await;
+
+            let handshake = HandshakeOwned {
+                protocol_version: VarInt(PROTOCOL_VERSION),
+                server_address: "localhost".into(),
+                server_port: 25565,
+                next_state: HandshakeNextState::Login,
+            };
+
+            let ncd = handle_login(&shared, &mut mngr, remote_addr, handshake)
+                .await
+                .unwrap();
+            drop(mngr);
+
+            let mut dec = PacketDecoder::new();
+
+            match recv_login_packet(&mut remote, &mut dec).await {
+                S2cLoginPacket::LoginSuccess(pkt) => {
+                    let ncd = ncd.unwrap();
+                    assert_eq!(ncd.username.as_str(), pkt.username.as_str());
+                    assert_eq!(ncd.uuid, pkt.uuid);
+                    Some((pkt.username.as_str().to_owned(), pkt.uuid))
+                }
+                S2cLoginPacket::DisconnectLogin(_) => {
+                    assert!(ncd.is_none());
+                    None
+                }
+                pkt => panic!("unexpected login packet {pkt:?}"),
+            }
+        })
+    }
+
+    /// Reads the next login packet the server sent to `remote`.
+    async fn recv_login_packet<'a>(
+        remote: &mut TcpStream,
+        dec: &'a mut PacketDecoder,
+    ) -> S2cLoginPacket<'a> {
+        while !dec.has_next_packet().unwrap() {
+            dec.reserve(4096);
+            let mut buf = dec.take_capacity();
+            assert_ne!(remote.read_buf(&mut buf).await.unwrap(), 0);
+            dec.queue_bytes(buf);
+        }
+
+        dec.try_next_packet().unwrap().unwrap()
+    }
+
+    /// Logs in as "Steve" to a server in transfer mode and answers the
+    /// transfer token request with `token`. Without a token, the client goes
+    /// through the encryption handshake of online mode. Returns the data of
+    /// the new client, or `None` if the login failed.
+    async fn transfer_login(
+        shared: &SharedServer<TestConfig>,
+        token: Option<&[u8]>,
+    ) -> Option<NewClientData> {
+        let mut enc = PacketEncoder::new();
+        enc.append_packet(&LoginStart {
+            username: "Steve",
+            profile_id: None,
+        })
+        .unwrap();
+
+        let (mut remote, mut mngr, remote_addr) = connect(shared, enc).await;
+
+        let server = shared.clone();
+        let login = tokio::spawn(async move {
+            let handshake = HandshakeOwned {
+                protocol_version: VarInt(PROTOCOL_VERSION),
+                server_address: "localhost".into(),
+                server_port: 25565,
+                next_state: HandshakeNextState::Login,
+            };
+
+            handle_login(&server, &mut mngr, remote_addr, handshake).await
+        });
+
+        let mut enc = PacketEncoder::new();
+        let mut dec = PacketDecoder::new();
+
+        let message_id = match recv_login_packet(&mut remote, &mut dec).await {
+            S2cLoginPacket::LoginPluginRequest(pkt) => {
+                assert_eq!(pkt.channel.as_str(), "valence:transfer_token");
+                pkt.message_id
+            }
+            pkt => panic!("unexpected login packet {pkt:?}"),
+        };
+
+        enc.append_packet(&LoginPluginResponse {
+            message_id,
+            data: token.map(RawBytes),
+        })
+        .unwrap();
+        remote.write_all(&enc.take()).await.unwrap();
+
+        if token.is_none() {
+            let verify_token = match recv_login_packet(&mut remote, &mut dec).await {
+                S2cLoginPacket::EncryptionRequest(pkt) => pkt.verify_token.to_vec(),
+                pkt => panic!("unexpected login packet {pkt:?}"),
+            };
+
+            let key = RsaPublicKey::from(&shared.0.rsa_key);
+            let secret = [5; 16];
+            let mut rng = rand::thread_rng();
+
+            let shared_secret = key
+                .encrypt(&mut rng, PaddingScheme::PKCS1v15Encrypt, &secret)
+                .unwrap();
+            let verify_token = key
+                .encrypt(&mut rng, PaddingScheme::PKCS1v15Encrypt, &verify_token)
+                .unwrap();
+
+            enc.append_packet(&EncryptionResponse {
+                shared_secret: BoundedArray(&shared_secret),
+                verify_token: BoundedArray(&verify_token),
+            })
+            .unwrap();
+            remote.write_all(&enc.take()).await.unwrap();
+
+            dec.enable_encryption(&secret);
+        }
+
+        let ncd = login.await.unwrap().ok()??;
+
+        match recv_login_packet(&mut remote, &mut dec).await {
+            S2cLoginPacket::LoginSuccess(pkt) => {
+                assert_eq!(ncd.uuid, pkt.uuid);
+                Some(ncd)
+            }
+            pkt => panic!("unexpected login packet {pkt:?}"),
+        }
+    }
+
+    /// A server with a single world which the test updates one tick at a time
+    /// instead of running the update loop.
+    ///
+    /// The connection tasks of its clients run on a separate single-threaded
+    /// runtime which only makes progress inside [`Self::recv`], so packets
+    /// stay in the outgoing buffers in between.
+    struct TestServer {
+        server: Server<TestConfig>,
+        world: WorldId,
+        rt: Runtime,
+    }
+
+    impl TestServer {
+        fn new(cfg: TestConfig) -> Self {
+            let shared = setup_server(cfg).unwrap();
+
+            let mut server = Server {
+                state: (),
+                shared: shared.clone(),
+                clients: Clients::new(),
+                entities: Entities::new(),
+                worlds: Worlds::new(shared.clone()),
+                player_lists: PlayerLists::new(),
+                inventories: Inventories::new(),
+                boss_bars: BossBars::new(),
+                // Keepalives are sent on tick 0, which would be mistaken for
+                // the end of the packets in `recv`.
+                current_tick: 1,
+                last_tick_duration: Duration::default(),
+                skipped_ticks: 0,
+            };
+
+            let (world, _) = server.worlds.insert(DimensionId::default(), ());
+
+            Self {
+                server,
+                world,
+                rt: runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap(),
+            }
+        }
+
+        /// Adds a client to the world. Returns the client and the remote end
+        /// of its connection.
+        fn join(&mut self, name: &str) -> (ClientId, DuplexStream) {
+            let shared = &self.server.shared;
+            let _guard = self.rt.enter();
+
+            let (stream, remote) = tokio::io::duplex(1 << 16);
+            let (read, write) = tokio::io::split(stream);
+
+            let permit = shared
+                .0
+                .connection_sema
+                .clone()
+                .try_acquire_owned()
+                .unwrap();
+
+            let mngr = InitialPacketManager::new(
+                read,
+                write,
+                PacketEncoder::new(),
+                PacketDecoder::new(),
+                Duration::from_secs(5),
+                Duration::from_secs(5),
+                permit,
+                shared.0.byte_counters.clone(),
+            );
+
+            let (send, recv, permit) = mngr.into_play(
+                1 << 16,
+                shared.outgoing_capacity(),
+                self.rt.handle().clone(),
+            );
+
+            let ncd = NewClientData {
+                username: Username::new(name.to_owned()).unwrap(),
+                uuid: Uuid::from_u128(1),
+                ip: IpAddr::from([127, 0, 0, 1]),
+                textures: None,
+                properties: vec![],
+            };
+
+            let mut client = Client::new(send, recv, permit, ncd, shared.clone(), ());
+            client.respawn(self.world);
+
+            (self.server.clients.insert(client).0, remote)
+        }
+
+        /// Runs the parts of the update loop which send packets to clients.
+        fn tick(&mut self) {
+            let server = &mut self.server;
+            let shared = server.shared.clone();
+
+            unload_chunks(server);
+
+            update_entity_partition(
+                &mut server.entities,
+                &mut server.worlds,
+                shared.compression_threshold(),
+            );
+
+            for (_, world) in server.worlds.iter_mut() {
+                world.chunks.update_caches();
+            }
+
+            update_player_list_game_modes(server);
+            server
+                .player_lists
+                .update_caches(shared.compression_threshold());
+
+            for (id, client) in server.clients.iter_mut() {
+                client.update(
+                    id,
+                    server.current_tick,
+                    &shared,
+                    &server.entities,
+                    &server.worlds,
+                    &server.player_lists,
+                    &server.inventories,
+                    &server.boss_bars,
+                );
+
+                assert!(!client.is_disconnected());
+            }
+
+            server.entities.update();
+            server.worlds.update();
+            server.player_lists.clear_removed();
+            server.current_tick += 1;
+        }
+
+        /// Passes every packet sent to a client so far to `visit`.
+        fn recv(
+            &mut self,
+            client: ClientId,
+            remote: &mut DuplexStream,
+            mut visit: impl FnMut(S2cPlayPacket),
+        ) {
+            // Keepalives are otherwise only sent every few hundred ticks, so
+            // one marks the end of the packets.
+            let shared = &self.server.shared;
+            self.server
+                .clients
+                .get_mut(client)
+                .unwrap()
+                .update_paused(0, shared);
+
+            let mut dec = PacketDecoder::new();
+
+            loop {
+                match self.read_packet(remote, &mut dec) {
+                    S2cPlayPacket::KeepAliveS2c(_) => break,
+                    pkt => visit(pkt),
+                }
+            }
+        }
+
+        /// Reads the next packet sent to a client, waiting for it if needed.
+        fn read_packet<'a>(
+            &self,
+            remote: &mut DuplexStream,
+            dec: &'a mut PacketDecoder,
+        ) -> S2cPlayPacket<'a> {
+            self.rt.block_on(async {
+                while !dec.has_next_packet().unwrap() {
+                    dec.reserve(4096);
+                    let mut buf = dec.take_capacity();
+                    assert_ne!(remote.read_buf(&mut buf).await.unwrap(), 0);
+                    dec.queue_bytes(buf);
+                }
+            });
+
+            dec.try_next_packet().unwrap().unwrap()
+        }
+
+        /// Sends a packet from the remote end of a client's connection and
+        /// gives the connection task time to receive it.
+        fn send(&self, remote: &mut DuplexStream, pkt: &impl EncodePacket) {
+            let mut enc = PacketEncoder::new();
+            enc.append_packet(pkt).unwrap();
+
+            self.rt.block_on(async {
+                remote.write_all(&enc.take()).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            });
+        }
+    }
+
+    /// Runs the first update of a client joining a server with the given
+    /// config. The packets sent to the client are passed to `visit` until it
+    /// returns `true`.
+    fn join(cfg: TestConfig, mut visit: impl FnMut(S2cPlayPacket) -> bool) {
+        let mut ts = TestServer::new(cfg);
+
+        let (client, mut remote) = ts.join("Steve");
+        ts.tick();
+
+        let mut done = false;
+        ts.recv(client, &mut remote, |pkt| done = done || visit(pkt));
+        assert!(done, "the expected packet was not sent");
+    }
+
+    #[test]
+    fn join_packet_has_default_game_mode() {
+        let cfg = TestConfig {
+            game_mode: GameMode::Creative,
+            ..Default::default()
+        };
+
+        join(cfg, |pkt| match pkt {
+            S2cPlayPacket::LoginPlay(pkt) => {
+                assert_eq!(pkt.game_mode, GameMode::Creative);
+                assert_eq!(pkt.previous_game_mode, -1);
+                true
+            }
+            _ => panic!("the first packet must be the join packet"),
+        });
+    }
+
+    #[test]
+    fn feature_flags_sent_on_join() {
+        let cfg = TestConfig {
+            feature_flags: vec![ident!("vanilla"), ident!("minecraft:bundle")],
+            ..Default::default()
+        };
+
+        join(cfg, |pkt| match pkt {
+            S2cPlayPacket::FeatureFlags(pkt) => {
+                assert_eq!(pkt.features, [ident!("vanilla"), ident!("bundle")]);
+                true
+            }
+            _ => false,
+        });
+    }
+
+    #[test]
+    fn login_profile_replaces_profile() {
+        let (username, _) = login(TestConfig::default()).unwrap();
+        assert_eq!(username, "Steve");
+
+        let cfg = TestConfig {
+            login_profile: Some(("steve", Uuid::from_u128(42))),
+            ..Default::default()
+        };
+
+        assert_eq!(login(cfg), Some(("steve".into(), Uuid::from_u128(42))));
+    }
+
+    #[test]
+    fn login_profile_discards_textures_of_other_uuid() {
+        let login_with = |login_profile| {
+            let shared = setup_server(TestConfig {
+                // Without a token, the client is authenticated as in online
+                // mode and has textures.
+                connection_mode: ConnectionMode::Transfer {
+                    secret: "hunter2".into(),
+                    server_id: "lobby".into(),
+                },
+                login_profile,
+                ..Default::default()
+            })
+            .unwrap();
+
+            shared
+                .tokio_handle()
+                .clone()
+                .block_on(transfer_login(&shared, None))
+                .unwrap()
+        };
+
+        let ncd = login_with(Some(("steve", Uuid::from_u128(2))));
+        assert_eq!(ncd.username.as_str(), "steve");
+        assert!(ncd.textures.is_some());
+        assert_eq!(ncd.properties, vec![textures_property()]);
+
+        let ncd = login_with(Some(("Steve", Uuid::from_u128(42))));
+        assert_eq!(ncd.uuid, Uuid::from_u128(42));
+        assert!(ncd.textures.is_none());
+        assert!(ncd.properties.is_empty());
+    }
+
+    #[test]
+    fn invalid_rewritten_username_rejected() {
+        let cfg = TestConfig {
+            login_profile: Some(("a_username_that_is_too_long", Uuid::from_u128(42))),
+            ..Default::default()
+        };
+
+        assert_eq!(login(cfg), None);
+    }
+
+    #[test]
+    fn connection_errors_carry_stage() {
+        let long_address = "a".repeat(256);
+
+        assert_eq!(
+            error_stage(HandshakeNextState::Login, &long_address),
+            Some(ConnectionStage::Handshake)
+        );
+        assert_eq!(
+            error_stage(HandshakeNextState::Status, "localhost"),
+            Some(ConnectionStage::Status)
+        );
+        assert_eq!(
+            error_stage(HandshakeNextState::Login, "localhost"),
+            Some(ConnectionStage::Login)
+        );
+    }
+
+    #[test]
+    fn login_start_timeout_is_reported() {
+        let shared = setup_server(TestConfig {
+            login_start_timeout: Duration::from_millis(100),
+            ..Default::default()
+        })
+        .unwrap();
+
+        shared.tokio_handle().clone().block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut remote = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (stream, remote_addr) = listener.accept().await.unwrap();
+
+            let mut enc = PacketEncoder::new();
+            enc.append_packet(&Handshake {
+                protocol_version: VarInt(PROTOCOL_VERSION),
+                server_address: "localhost",
+                server_port: 25565,
+                next_state: HandshakeNextState::Login,
+            })
+            .unwrap();
+            remote.write_all(&enc.take()).await.unwrap();
+
+            let permit = shared
+                .0
+                .connection_sema
+                .clone()
+                .try_acquire_owned()
+                .unwrap();
+
+            handle_connection(shared.clone(), stream, remote_addr, permit).await;
+        });
+
+        let errors = shared.config().connection_errors.lock().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].stage, ConnectionStage::Login);
+        assert!(errors[0].error.contains("no login start"));
+    }
+
+    #[test]
+    fn transfer_login_with_token() {
+        let shared = setup_server(TestConfig {
+            connection_mode: ConnectionMode::Transfer {
+                secret: "hunter2".into(),
+                server_id: "lobby".into(),
+            },
+            ..Default::default()
+        })
+        .unwrap();
+
+        let profile = TransferProfile {
+            username: Username::new("Steve".into()).unwrap(),
+            uuid: Uuid::from_u128(1),
+            properties: vec![textures_property()],
+        };
+
+        let token = shared.issue_transfer_token(&profile, "lobby").unwrap();
+        let other_token = shared.issue_transfer_token(&profile, "survival").unwrap();
+
+        shared.tokio_handle().clone().block_on(async {
+            assert_eq!(
+                transfer_login(&shared, Some(&token))
+                    .await
+                    .map(|ncd| ncd.uuid),
+                Some(profile.uuid)
+            );
+            // Tokens are single use and only accepted by their target.
+            assert!(transfer_login(&shared, Some(&token)).await.is_none());
+            assert!(transfer_login(&shared, Some(&other_token)).await.is_none());
+        });
+    }
+
+    #[test]
+    fn transfer_login_without_token_falls_back_to_online() {
+        let shared = setup_server(TestConfig {
+            connection_mode: ConnectionMode::Transfer {
+                secret: "hunter2".into(),
+                server_id: "lobby".into(),
+            },
+            ..Default::default()
+        })
+        .unwrap();
+
+        shared.tokio_handle().clone().block_on(async {
+            assert_eq!(
+                transfer_login(&shared, None).await.map(|ncd| ncd.uuid),
+                Some(Uuid::from_u128(2))
+            );
+        });
+    }
+
+    #[test]
+    fn local_connection() {
+        let shared = setup_server(TestConfig::default()).unwrap();
+        let handle = shared.tokio_handle().clone();
+
+        let mut conn = handle
+            .block_on(shared.connect_local(
+                Username::new("Alex".to_owned()).unwrap(),
+                Uuid::from_u128(7),
+            ))
+            .unwrap();
+
+        let mut msg = shared.0.new_clients_recv.try_recv().unwrap();
+        assert_eq!(msg.ncd.username.as_str(), "Alex");
+        assert_eq!(msg.ncd.uuid, Uuid::from_u128(7));
+        assert!(msg.ncd.ip.is_loopback());
+
+        msg.send.append_packet(&KeepAliveS2c { id: 5 }).unwrap();
+        msg.send.flush().unwrap();
+
+        handle.block_on(async {
+            match conn.recv_packet::<S2cPlayPacket>().await.unwrap() {
+                S2cPlayPacket::KeepAliveS2c(pkt) => assert_eq!(pkt.id, 5),
+                pkt => panic!("unexpected packet {pkt:?}"),
+            }
+
+            conn.send_packet(&KeepAliveC2s { id: 6 }).await.unwrap();
+
+            loop {
+                assert!(msg.recv.try_recv());
+
+                if let Some(pkt) = msg.recv.try_next_raw_packet().unwrap() {
+                    let mut r = pkt.data;
+                    let pkt = C2sPlayPacket::decode_with_id(pkt.id.0, &mut r).unwrap();
+                    assert!(matches!(pkt, Some(C2sPlayPacket::KeepAliveC2s(p)) if p.id == 6));
+                    break;
+                }
+
+                tokio::task::yield_now().await;
+            }
+        });
+
+        drop(msg);
+        assert!(handle
+            .block_on(conn.recv_packet::<S2cPlayPacket>())
+            .is_err());
+    }
+
+    #[test]
+    fn game_mode_updates_player_list() {
+        let mut ts = TestServer::new(TestConfig::default());
+
+        let (id, mut remote) = ts.join("Alex");
+        let (list, pl) = ts.server.player_lists.insert(());
+        pl.insert(
+            Uuid::from_u128(1),
+            "Alex",
+            None,
+            GameMode::Survival,
+            0,
+            None,
+            true,
+        );
+        ts.server
+            .clients
+            .get_mut(id)
+            .unwrap()
+            .set_player_list(Some(list.clone()));
+        ts.tick();
+        ts.recv(id, &mut remote, |_| {});
+
+        ts.server
+            .clients
+            .get_mut(id)
+            .unwrap()
+            .set_game_mode(GameMode::Creative);
+        ts.tick();
+
+        let entry = ts.server.player_lists.get(&list).entry(Uuid::from_u128(1));
+        assert_eq!(entry.unwrap().game_mode(), GameMode::Creative);
+
+        let mut updates = 0;
+        ts.recv(id, &mut remote, |pkt| {
+            if let S2cPlayPacket::PlayerInfoUpdate(pkt) = pkt {
+                assert!(pkt.actions.update_game_mode());
+                assert_eq!(pkt.entries[0].game_mode, GameMode::Creative);
+                updates += 1;
+            }
+        });
+        assert_eq!(updates, 1);
+    }
+
+    #[test]
+    fn unchanged_abilities_not_resent() {
+        let mut ts = TestServer::new(TestConfig::default());
+
+        let (id, mut remote) = ts.join("Alex");
+        ts.tick();
+        ts.recv(id, &mut remote, |_| {});
+
+        let client = ts.server.clients.get_mut(id).unwrap();
+        let abilities = PlayerAbilitiesFlags::new().with_allow_flying(true);
+
+        client.set_abilities(abilities, 0.1, 0.2);
+        client.set_abilities(abilities, 0.1, 0.2);
+        // Speeds are clamped before they are compared.
+        client.set_abilities(abilities, 2.0, 0.2);
+        client.set_abilities(abilities, 1.0, 0.2);
+        ts.tick();
+
+        let mut speeds = vec![];
+        ts.recv(id, &mut remote, |pkt| {
+            if let S2cPlayPacket::PlayerAbilitiesS2c(pkt) = pkt {
+                speeds.push(pkt.flying_speed);
+            }
+        });
+        assert_eq!(speeds, [0.1, 1.0]);
+    }
+
+    /// Joins a client at the origin of a world with a chunk in view and a
+    /// chunk out of view, which are unloaded after two ticks out of view.
+    fn unload_test_server(save_chunks: bool) -> TestServer {
+        let mut ts = TestServer::new(TestConfig {
+            save_chunks,
+            ..Default::default()
+        });
+
+        ts.join("Alex");
+
+        let world = ts.server.worlds.get_mut(ts.world).unwrap();
+        world.chunks.insert([0, 0], UnloadedChunk::default(), ());
+        world.chunks.insert([100, 0], UnloadedChunk::default(), ());
+        world.set_chunk_retention(Some(ChunkRetention {
+            grace_radius: 0,
+            timeout: 2,
+        }));
+
+        ts
+    }
+
+    #[test]
+    fn out_of_view_chunk_saved_and_unloaded_once() {
+        let mut ts = unload_test_server(true);
+
+        for _ in 0..6 {
+            ts.tick();
+        }
+
+        let saved = ts.server.shared.config().saved_chunks.lock().unwrap();
+        // Generated chunks are saved even if they were never modified.
+        assert_eq!(*saved, [(ts.world, ChunkPos::new(100, 0))]);
+
+        let world = ts.server.worlds.get(ts.world).unwrap();
+        assert_eq!(world.loaded_chunk_count(), 1);
+        assert!(world.chunks.get([0, 0]).is_some());
+    }
+
+    #[test]
+    fn failed_chunk_save_retried_after_timeout() {
+        let mut ts = unload_test_server(false);
+
+        for _ in 0..7 {
+            ts.tick();
+        }
+
+        let saved = ts.server.shared.config().saved_chunks.lock().unwrap();
+        assert_eq!(saved.len(), 3);
+
+        let world = ts.server.worlds.get(ts.world).unwrap();
+        assert_eq!(world.loaded_chunk_count(), 2);
+    }
+
+    /// Joins a client after `setup` is called with the client and its world.
+    /// Returns the time of day of each time packet and the kinds of weather
+    /// events sent to the client.
+    fn join_time_and_weather(
+        setup: impl FnOnce(&mut Client<TestConfig>, &mut World<TestConfig>),
+    ) -> (Vec<i64>, Vec<GameEventKind>) {
+        let mut ts = TestServer::new(TestConfig::default());
+
+        let (id, mut remote) = ts.join("Alex");
+        setup(
+            ts.server.clients.get_mut(id).unwrap(),
+            ts.server.worlds.get_mut(ts.world).unwrap(),
+        );
+        ts.tick();
+
+        let mut times = vec![];
+        let mut events = vec![];
+
+        ts.recv(id, &mut remote, |pkt| match pkt {
+            S2cPlayPacket::UpdateTime(pkt) => times.push(pkt.time_of_day),
+            S2cPlayPacket::GameEvent(pkt)
+                if matches!(
+                    pkt.kind,
+                    GameEventKind::BeginRaining
+                        | GameEventKind::EndRaining
+                        | GameEventKind::RainLevelChange
+                        | GameEventKind::ThunderLevelChange
+                ) =>
+            {
+                events.push(pkt.kind)
+            }
+            _ => {}
+        });
+
+        (times, events)
+    }
+
+    #[test]
+    fn join_sends_world_time_and_weather() {
+        let (times, events) = join_time_and_weather(|_, world| {
+            world.set_time_of_day(1000);
+            world.set_weather(1.0, 0.5);
+        });
+
+        assert_eq!(times, [1000]);
+        assert_eq!(
+            events,
+            [
+                GameEventKind::BeginRaining,
+                GameEventKind::RainLevelChange,
+                GameEventKind::ThunderLevelChange
+            ]
+        );
+
+        // Clients join without rain, so clear weather is not sent.
+        let (_, events) = join_time_and_weather(|_, _| {});
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn join_keeps_client_time_and_weather() {
+        let (times, events) = join_time_and_weather(|client, world| {
+            world.set_time_of_day(1000);
+            world.set_weather(0.0, 0.0);
+            client.set_time(5, 6000);
+            client.set_raining(true);
+        });
+
+        assert_eq!(times, [6000]);
+        assert_eq!(events, [GameEventKind::BeginRaining]);
+    }
+
+    /// Waits for `cond` to become true while other threads make progress.
+    fn wait_until(cond: impl Fn() -> bool) {
+        let start = Instant::now();
+
+        while !cond() {
+            assert!(start.elapsed() < Duration::from_secs(10), "timed out");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn inspected_packets_tapped() {
+        let mut ts = TestServer::new(TestConfig::default());
+
+        let (id, mut remote) = ts.join("Alex");
+        let client = ts.server.clients.get_mut(id).unwrap();
+        client.inspect_packets(id);
+        client.send_message("hello");
+        ts.tick();
+        ts.recv(id, &mut remote, |_| {});
+
+        ts.send(&mut remote, &LockDifficulty(true));
+
+        let client = ts.server.clients.get_mut(id).unwrap();
+        client.prepare_c2s_packets();
+        assert!(matches!(
+            client.next_event(),
+            Some(ClientEvent::LockDifficulty(true))
+        ));
+
+        let cfg = ts.server.shared.config();
+        wait_until(|| {
+            cfg.inspected_messages.load(Ordering::Relaxed) == 1
+                && cfg.inspected_difficulty_locks.load(Ordering::Relaxed) == 1
+        });
+    }
+
+    #[test]
+    fn inspected_packets_dropped_when_behind() {
+        let mut ts = TestServer::new(TestConfig::default());
+        let shared = ts.server.shared.clone();
+        let cfg = shared.config();
+
+        let (id, mut remote) = ts.join("Alex");
+        ts.server.clients.get_mut(id).unwrap().inspect_packets(id);
+
+        // Stalls the inspection task on the first message.
+        let gate = cfg.inspect_gate.lock().unwrap();
+
+        for _ in 0..INSPECT_CAPACITY + 11 {
+            ts.server.clients.get_mut(id).unwrap().send_message("hello");
+            ts.tick();
+            wait_until(|| cfg.inspected_messages.load(Ordering::Relaxed) == 1);
+        }
+
+        drop(gate);
+        ts.recv(id, &mut remote, |_| {});
+
+        // The first message and a full channel are inspected, the rest are
+        // dropped.
+        wait_until(|| cfg.inspected_messages.load(Ordering::Relaxed) == INSPECT_CAPACITY + 1);
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(
+            cfg.inspected_messages.load(Ordering::Relaxed),
+            INSPECT_CAPACITY + 1
+        );
+    }
+
+    fn long_move(cfg: TestConfig) -> Option<[f64; 3]> {
+        let mut ts = TestServer::new(cfg);
+
+        let (id, mut remote) = ts.join("Alex");
+        ts.tick();
+        ts.recv(id, &mut remote, |_| {});
+
+        ts.send(
+            &mut remote,
+            &SetPlayerPosition {
+                position: [100.0, 0.0, 0.0],
+                on_ground: true,
+            },
+        );
+        // Marks the end of the packets.
+        ts.send(&mut remote, &LockDifficulty(true));
+
+        let client = ts.server.clients.get_mut(id).unwrap();
+        client.prepare_c2s_packets();
+        while !matches!(client.next_event(), Some(ClientEvent::LockDifficulty(_))) {}
+
+        ts.tick();
+
+        let mut position = None;
+        ts.recv(id, &mut remote, |pkt| {
+            if let S2cPlayPacket::SynchronizePlayerPosition(pkt) = pkt {
+                position = Some(pkt.position);
+            }
+        });
+
+        position
+    }
+
+    #[test]
+    fn long_moves_accepted_by_default() {
+        assert_eq!(long_move(TestConfig::default()), None);
+    }
+
+    #[test]
+    fn rejected_move_corrected() {
+        let cfg = TestConfig {
+            max_movement: Some(10.0),
+            ..Default::default()
+        };
+
+        assert_eq!(long_move(cfg), Some([0.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn paused_keepalives_accepted() {
+        let mut ts = TestServer::new(TestConfig::default());
+
+        let (id, mut remote) = ts.join("Alex");
+        ts.tick();
+        ts.recv(id, &mut remote, |_| {});
+
+        // Two keepalives are sent before either response is received.
+        let shared = ts.server.shared.clone();
+        let client = ts.server.clients.get_mut(id).unwrap();
+        let interval = shared.tick_rate() * 10;
+        client.update_paused(interval, &shared);
+        client.update_paused(interval * 2, &shared);
+
+        let mut dec = PacketDecoder::new();
+
+        for _ in 0..2 {
+            match ts.read_packet(&mut remote, &mut dec) {
+                S2cPlayPacket::KeepAliveS2c(pkt) => {
+                    ts.send(&mut remote, &KeepAliveC2s { id: pkt.id })
+                }
+                pkt => panic!("unexpected packet {pkt:?}"),
+            }
+        }
+
+        // Marks the end of the responses.
+        ts.send(&mut remote, &LockDifficulty(true));
+
+        let client = ts.server.clients.get_mut(id).unwrap();
+        client.prepare_c2s_packets();
+
+        match client.next_event() {
+            Some(ClientEvent::LockDifficulty(true)) => {}
+            event => panic!("unexpected event {event:?}"),
+        }
+
+        assert!(!client.is_disconnected());
+    }
+
+    #[test]
+    fn paused_clients_not_read() {
+        let mut ts = TestServer::new(TestConfig::default());
+
+        let (id, mut remote) = ts.join("Alex");
+        ts.tick();
+        ts.recv(id, &mut remote, |_| {});
+
+        ts.send(&mut remote, &LockDifficulty(true));
+
+        // The packet stays in the byte channel until the server resumes.
+        let shared = ts.server.shared.clone();
+        let client = ts.server.clients.get_mut(id).unwrap();
+        client.update_paused(1, &shared);
+        assert!(client.next_event().is_none());
+
+        client.prepare_c2s_packets();
+        assert!(matches!(
+            client.next_event(),
+            Some(ClientEvent::LockDifficulty(true))
+        ));
+    }
+
+    #[test]
+    fn no_keepalives_before_join_packet() {
+        let mut ts = TestServer::new(TestConfig::default());
+
+        // The client joins while the server is paused.
+        let (id, mut remote) = ts.join("Alex");
+        let shared = ts.server.shared.clone();
+        ts.server
+            .clients
+            .get_mut(id)
+            .unwrap()
+            .update_paused(shared.tick_rate() * 10, &shared);
+
+        ts.tick();
+
+        let mut first = true;
+        ts.recv(id, &mut remote, |pkt| {
+            assert!(!first || matches!(pkt, S2cPlayPacket::LoginPlay(_)));
+            first = false;
+        });
+        assert!(!first, "a keepalive was sent before the join packet");
+    }
+
+    #[test]
+    fn step_ticks_wakes_paused_loop() {
+        let shared = setup_server(TestConfig::default()).unwrap();
+
+        // Steps are ignored unless paused.
+        shared.step_ticks(1);
+        assert!(shared.0.pause.lock().unwrap().begin_tick());
+        assert_eq!(shared.0.pause.lock().unwrap().steps, 0);
+
+        shared.set_paused(true);
+        assert!(!shared.0.pause.lock().unwrap().begin_tick());
+
+        let stepper = {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                shared.step_ticks(2);
+            })
+        };
+
+        let start = Instant::now();
+        let mut next_tick = start;
+        wait_while_paused(&shared, &mut next_tick, Duration::from_secs(60));
+        assert!(start.elapsed() < Duration::from_secs(30));
+        stepper.join().unwrap();
+
+        let mut pause = shared.0.pause.lock().unwrap();
+        assert!(pause.begin_tick());
+        assert!(pause.begin_tick());
+        assert!(!pause.begin_tick());
+        assert!(pause.paused);
+    }
+
+    #[test]
+    fn local_connection_runs_login_callbacks() {
+        let connect = |login_profile| {
+            let shared = setup_server(TestConfig {
+                login_profile,
+                ..Default::default()
+            })
+            .unwrap();
+
+            let res = shared.tokio_handle().block_on(shared.connect_local(
+                Username::new("Alex".to_owned()).unwrap(),
+                Uuid::from_u128(7),
+            ));
+
+            res.map(|_| shared.0.new_clients_recv.try_recv().unwrap().ncd)
+        };
+
+        let ncd = connect(Some(("steve", Uuid::from_u128(42)))).unwrap();
+        assert_eq!(ncd.username.as_str(), "steve");
+        assert_eq!(ncd.uuid, Uuid::from_u128(42));
+
+        assert!(connect(Some(("a_username_that_is_too_long", Uuid::from_u128(42)))).is_err());
+    }
+
+    #[test]
+    fn already_connected_profiles_detected() {
+        let shared = setup_server(TestConfig::default()).unwrap();
+        let mut clients = Clients::new();
+
+        let ncd = |name: &str, uuid| NewClientData {
+            username: Username::new(name.to_owned()).unwrap(),
+            uuid: Uuid::from_u128(uuid),
+            ip: IpAddr::from([127, 0, 0, 1]),
+            textures: None,
+            properties: vec![],
+        };
+
+        let _conn = shared
+            .tokio_handle()
+            .block_on(shared.connect_local(
+                Username::new("Alice".to_owned()).unwrap(),
+                Uuid::from_u128(1),
+            ))
+            .unwrap();
+        let msg = shared.0.new_clients_recv.try_recv().unwrap();
+        clients.insert(Client::new(
+            msg.send,
+            msg.recv,
+            msg.permit,
+            msg.ncd,
+            shared.clone(),
+            (),
+        ));
+
+        assert!(is_already_connected(&clients, &ncd("alice", 2)));
+        assert!(is_already_connected(&clients, &ncd("Bob", 1)));
+        assert!(!is_already_connected(&clients, &ncd("Bob", 2)));
+    }
+
+    #[test]
+    fn clients_iterate_in_join_order() {
+        let shared = setup_server(TestConfig::default()).unwrap();
+        let mut clients = Clients::new();
+
+        let join = |clients: &mut Clients<TestConfig>, name: &str| {
+            let _conn = shared
+                .tokio_handle()
+                .block_on(
+                    shared.connect_local(Username::new(name.to_owned()).unwrap(), Uuid::nil()),
+                )
+                .unwrap();
+
+            let msg = shared.0.new_clients_recv.try_recv().unwrap();
+            let client = Client::new(msg.send, msg.recv, msg.permit, msg.ncd, shared.clone(), ());
+            clients.insert(client).0
+        };
+
+        let a = join(&mut clients, "Alice");
+        join(&mut clients, "Bob");
+        join(&mut clients, "Carol");
+
+        // The new client takes the slot freed by "Alice".
+        clients.remove(a);
+        join(&mut clients, "Dave");
+
+        let names: Vec<_> = clients
+            .iter_mut_ordered()
+            .map(|(_, c)| c.username().as_str().to_owned())
+            .collect();
+
+        assert_eq!(names, ["Bob", "Carol", "Dave"]);
+    }
+
+    #[test]
+    fn client_left_with_reason() {
+        let shared = setup_server(TestConfig::default()).unwrap();
+        let mut clients = Clients::new();
+
+        let mut join = |name: &str| {
+            let conn = shared
+                .tokio_handle()
+                .block_on(
+                    shared.connect_local(Username::new(name.to_owned()).unwrap(), Uuid::nil()),
+                )
+                .unwrap();
+
+            let msg = shared.0.new_clients_recv.try_recv().unwrap();
+            let client = Client::new(msg.send, msg.recv, msg.permit, msg.ncd, shared.clone(), ());
+            (clients.insert(client).0, conn)
+        };
+
+        let (quitter, conn) = join("Quitter");
+        let (kicked, _conn) = join("Kicked");
+        let (idle, _conn) = join("Idle");
+        let (invalid, mut invalid_conn) = join("Invalid");
+        let (shutdown, _conn) = join("Shutdown");
+
+        drop(conn);
+
+        let client = clients.get_mut(quitter).unwrap();
+        while !client.is_disconnected() {
+            client.prepare_c2s_packets();
+            thread::yield_now();
+        }
+        assert_eq!(
+            client.disconnect_reason(),
+            Some(&DisconnectReason::ClientQuit)
+        );
+
+        let client = clients.get_mut(kicked).unwrap();
+        client.disconnect("Bye");
+        // Only the first reason is kept.
+        client.disconnect_abrupt();
+
+        shared
+            .tokio_handle()
+            .block_on(invalid_conn.send_packet(&RawPacket {
+                id: VarInt(0x7f),
+                data: &[],
+            }))
+            .unwrap();
+
+        let client = clients.get_mut(invalid).unwrap();
+        while !client.is_disconnected() {
+            client.prepare_c2s_packets();
+            client.next_event();
+            thread::yield_now();
+        }
+
+        clients.get_mut(shutdown).unwrap().disconnect_on_shutdown();
+
+        clients.remove(quitter);
+        clients.remove(kicked);
+        clients.remove(idle);
+        clients.remove(invalid);
+        clients.remove(shutdown);
+
+        assert_eq!(
+            *shared.config().left.lock().unwrap(),
+            [
+                ("Quitter".to_owned(), DisconnectReason::ClientQuit),
+                ("Kicked".to_owned(), DisconnectReason::Kicked("Bye".into())),
+                ("Idle".to_owned(), DisconnectReason::Kicked(Text::default())),
+                (
+                    "Invalid".to_owned(),
+                    DisconnectReason::ProtocolError("unknown packet ID 0x7f".into())
+                ),
+                ("Shutdown".to_owned(), DisconnectReason::ServerShutdown),
+            ]
+        );
+    }
+
+    #[test]
+    fn client_left_after_timeout() {
+        let mut ts = TestServer::new(TestConfig::default());
+
+        let (id, _remote) = ts.join("Alex");
+        let interval = ts.server.shared.tick_rate() * 10;
+
+        // A keepalive is sent and never answered.
+        ts.server.current_tick = interval;
+        ts.tick();
+
+        let server = &mut ts.server;
+        let client = server.clients.get_mut(id).unwrap();
+        client.update(
+            id,
+            interval * 2,
+            &server.shared,
+            &server.entities,
+            &server.worlds,
+            &server.player_lists,
+            &server.inventories,
+            &server.boss_bars,
+        );
+        assert_eq!(client.disconnect_reason(), Some(&DisconnectReason::Timeout));
+
+        server.clients.remove(id);
+
+        assert_eq!(
+            *server.shared.config().left.lock().unwrap(),
+            [("Alex".to_owned(), DisconnectReason::Timeout)]
+        );
+    }
+
+    #[test]
+    fn entities_spawned_within_tracking_range() {
+        let mut ts = TestServer::new(TestConfig::default());
+
+        let (client, mut remote) = ts.join("Steve");
+        ts.server
+            .clients
+            .get_mut(client)
+            .unwrap()
+            .set_view_distance(10);
+
+        // Place the arrow one chunk past its tracking range, but well within
+        // the view distance.
+        let range = ts.server.shared.entity_tracking_range(EntityKind::Arrow);
+        let (arrow, entity) = ts.server.entities.insert(EntityKind::Arrow, ());
+        entity.set_world(ts.world);
+        entity.set_position([(range + 1) as f64 * 16.0 + 8.0, 64.0, 8.0]);
+
+        let spawned = |ts: &mut TestServer, remote: &mut DuplexStream| {
+            let mut spawned = false;
+            ts.recv(client, remote, |pkt| {
+                if let S2cPlayPacket::SpawnEntity(pkt) = pkt {
+                    assert_eq!(pkt.entity_id.0, arrow.to_raw());
+                    spawned = true;
+                }
+            });
+            spawned
+        };
+
+        ts.tick();
+        assert!(!spawned(&mut ts, &mut remote));
+
+        ts.server.entities[arrow].set_position([range as f64 * 16.0 + 8.0, 64.0, 8.0]);
+
+        ts.tick();
+        assert!(spawned(&mut ts, &mut remote));
+    }
+
+    #[test]
+    fn dropped_entity_updates_resynced() {
+        let mut ts = TestServer::new(TestConfig {
+            outgoing_capacity: 4096,
+            overflow_policy: OverflowPolicy::DropNonEssentialPackets,
+            ..Default::default()
+        });
+
+        let (client, mut remote) = ts.join("Steve");
+
+        let (zombie, entity) = ts.server.entities.insert(EntityKind::Zombie, ());
+        entity.set_world(ts.world);
+        entity.set_position([8.0, 64.0, 8.0]);
+
+        // The position and `on_fire` flag of the zombie as seen by the client.
+        let mut view = ([0.0; 3], false);
+
+        let mut recv = |ts: &mut TestServer, (position, on_fire): &mut ([f64; 3], bool)| {
+            ts.recv(client, &mut remote, |pkt| match pkt {
+                S2cPlayPacket::SpawnEntity(pkt) => *position = pkt.position,
+                S2cPlayPacket::UpdateEntityPosition(pkt) => {
+                    for (pos, delta) in position.iter_mut().zip(pkt.delta) {
+                        *pos += delta as f64 / 4096.0;
+                    }
+                }
+                S2cPlayPacket::TeleportEntity(pkt) => *position = pkt.position,
+                // The flags are always the first entry when they are present.
+                S2cPlayPacket::SetEntityMetadata(pkt) => {
+                    if let [0, 0, flags, ..] = pkt.metadata.0 {
+                        *on_fire = flags & 1 == 1;
+                    }
+                }
+                _ => {}
+            });
+        };
+
+        ts.tick();
+        recv(&mut ts, &mut view);
+        assert_eq!(view, ([8.0, 64.0, 8.0], false));
+
+        // Fill most of the outgoing buffer. The packet is sent at the end of the
+        // next tick, and stays in the buffer since nothing reads from it.
+        ts.server
+            .clients
+            .get_mut(client)
+            .unwrap()
+            .queue_packet(&PluginMessageS2c {
+                channel: Ident::new("valence:filler").unwrap(),
+                data: RawBytes(&[0; 3500]),
+            });
+        ts.tick();
+
+        let entity = &mut ts.server.entities[zombie];
+        entity.set_position([9.5, 64.0, 8.0]);
+        let TrackedData::Zombie(data) = entity.data_mut() else {
+            unreachable!()
+        };
+        data.set_on_fire(true);
+
+        // The update is dropped.
+        ts.tick();
+        recv(&mut ts, &mut view);
+        assert_eq!(view, ([8.0, 64.0, 8.0], false));
+
+        // There is room again, so the complete state of the zombie is sent.
+        ts.tick();
+        recv(&mut ts, &mut view);
+        assert_eq!(view, ([9.5, 64.0, 8.0], true));
+    }
+
+    #[test]
+    fn overflow_disconnects_slow_client() {
+        let mut ts = TestServer::new(TestConfig {
+            outgoing_capacity: 4096,
+            ..Default::default()
+        });
+
+        let (id, _remote) = ts.join("Steve");
+        ts.tick();
+
+        // Nothing reads from the connection, so the packet never fits.
+        let server = &mut ts.server;
+        let client = server.clients.get_mut(id).unwrap();
+        client.queue_packet(&PluginMessageS2c {
+            channel: Ident::new("valence:filler").unwrap(),
+            data: RawBytes(&[0; 5000]),
+        });
+
+        client.update(
+            id,
+            server.current_tick,
+            &server.shared,
+            &server.entities,
+            &server.worlds,
+            &server.player_lists,
+            &server.inventories,
+            &server.boss_bars,
+        );
+
+        assert_eq!(
+            client.disconnect_reason(),
+            Some(&DisconnectReason::SlowClient)
+        );
+    }
+
+    /// A handshake for a status ping with the given protocol version.
+    fn status_handshake(protocol_version: i32) -> HandshakeOwned {
+        HandshakeOwned {
+            protocol_version: VarInt(protocol_version),
+            server_address: "localhost".into(),
+            server_port: 25565,
+            next_state: HandshakeNextState::Status,
+        }
+    }
+
+    #[test]
+    fn status_cache_ignores_protocol_version() {
+        let shared = setup_server(TestConfig {
+            status_cache_ttl: Some(Duration::from_secs(60)),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let addr = SocketAddr::from(([127, 0, 0, 1], 1234));
+
+        shared.tokio_handle().clone().block_on(async {
+            for version in [PROTOCOL_VERSION, 1, 2, 3, PROTOCOL_VERSION, 4] {
+                let json = status_json(&shared, addr, &status_handshake(version)).await;
+                assert!(json.is_some());
+            }
+        });
+
+        // Once for the server's version and once for all other versions.
+        assert_eq!(shared.config().pings.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn ignored_status_pings_not_cached() {
+        let ignored = SocketAddr::from(([10, 0, 0, 1], 1234));
+        let other = SocketAddr::from(([10, 0, 0, 2], 1234));
+
+        let shared = setup_server(TestConfig {
+            status_cache_ttl: Some(Duration::from_secs(60)),
+            ignored_ip: Some(ignored.ip()),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let handshake = status_handshake(PROTOCOL_VERSION);
+
+        shared.tokio_handle().clone().block_on(async {
+            assert!(status_json(&shared, ignored, &handshake).await.is_none());
+            assert!(status_json(&shared, ignored, &handshake).await.is_none());
+
+            // Other clients still get a response, which is then cached.
+            assert!(status_json(&shared, other, &handshake).await.is_some());
+            assert!(status_json(&shared, other, &handshake).await.is_some());
+        });
+
+        assert_eq!(shared.config().pings.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn duplicate_feature_flags_rejected() {
+        let cfg = TestConfig {
+            feature_flags: vec![ident!("vanilla"), ident!("minecraft:vanilla")],
+            ..Default::default()
+        };
+
+        assert!(setup_server(cfg).is_err());
+    }
+}
@@ -116,6 +116,17 @@ impl ByteSender {
     pub fn is_disconnected(&self) -> bool {
         self.shared.mtx.lock().unwrap().disconnected
     }
+
+    /// Returns the number of bytes in the channel which have not been received
+    /// yet.
+    pub fn len(&self) -> usize {
+        self.shared.mtx.lock().unwrap().bytes.len()
+    }
+
+    /// Returns the maximum number of bytes the channel can hold.
+    pub fn limit(&self) -> usize {
+        self.shared.limit
+    }
 }
 
 /// Contains any excess bytes not sent.
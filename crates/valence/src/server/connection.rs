@@ -0,0 +1,125 @@
+//! Handling connections outside of the main server loop.
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use valence_protocol::{DecodePacket, EncodePacket};
+
+use crate::config::Config;
+use crate::server::packet_manager::InitialPacketManager;
+use crate::server::{NewClientData, SharedServer};
+
+/// Takes over connections after login, bypassing the server's [`Clients`]
+/// entirely. Returned from [`Config::connection_handler`].
+///
+/// The handler is given a [`RawConnection`] to read and write play packets
+/// directly. No tasks or channels are created for the connection, which makes
+/// this suitable for lightweight relays and proxies built on Valence's
+/// handshake and login machinery.
+///
+/// This trait uses the [async_trait] attribute macro.
+///
+/// [`Clients`]: crate::client::Clients
+/// [async_trait]: https://docs.rs/async-trait/latest/async_trait/
+#[async_trait]
+pub trait ConnectionHandler<C: Config>: Send + Sync + 'static {
+    /// Called from within a tokio runtime for each client which has
+    /// successfully logged in. The connection is closed once this returns.
+    ///
+    /// Note that the client expects a [`LoginPlay`] packet after login and
+    /// will disconnect if keep alive packets are not sent periodically.
+    ///
+    /// If an error is returned, it is logged.
+    ///
+    /// [`LoginPlay`]: valence_protocol::packets::s2c::play::LoginPlay
+    async fn handle(
+        &self,
+        shared: &SharedServer<C>,
+        ncd: NewClientData,
+        conn: RawConnection,
+    ) -> anyhow::Result<()>;
+}
+
+/// A connection to a client in the play state which is driven directly by a
+/// [`ConnectionHandler`]. Compression and encryption are handled
+/// transparently.
+pub struct RawConnection {
+    mngr: InitialPacketManager<OwnedReadHalf, OwnedWriteHalf>,
+    remote_addr: SocketAddr,
+}
+
+impl RawConnection {
+    pub(crate) fn new(
+        mut mngr: InitialPacketManager<OwnedReadHalf, OwnedWriteHalf>,
+        remote_addr: SocketAddr,
+    ) -> Self {
+        // Login is over, so lift the initial packet size limit and the limit
+        // on the time to receive a packet.
+        mngr.set_max_packet_len(None);
+        mngr.set_packet_timeout(None);
+
+        Self { mngr, remote_addr }
+    }
+
+    /// Gets the remote address of the client.
+    pub fn remote_addr(&self) -> SocketAddr {
+        self.remote_addr
+    }
+
+    /// Returns whether packets sent over this connection are compressed.
+    pub fn is_compressed(&self) -> bool {
+        self.mngr.is_compressed()
+    }
+
+    /// Returns whether this connection is encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        self.mngr.is_encrypted()
+    }
+
+    /// Sets both the [idle timeout](Self::set_idle_timeout) and the
+    /// [read timeout](Self::set_read_timeout).
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.mngr.set_idle_timeout(timeout);
+        self.mngr.set_read_timeout(timeout);
+    }
+
+    /// Sets the maximum amount of time [`Self::recv_packet`] waits for the
+    /// next packet to begin arriving before returning an error. The default is
+    /// [`Config::connection_idle_timeout`].
+    ///
+    /// [`Config::connection_idle_timeout`]: crate::config::Config::connection_idle_timeout
+    pub fn set_idle_timeout(&mut self, timeout: Duration) {
+        self.mngr.set_idle_timeout(timeout);
+    }
+
+    /// Sets the maximum amount of time a read or write may make no progress
+    /// while a packet is being received or sent before returning an error.
+    /// The default is [`Config::connection_read_timeout`].
+    ///
+    /// [`Config::connection_read_timeout`]: crate::config::Config::connection_read_timeout
+    pub fn set_read_timeout(&mut self, timeout: Duration) {
+        self.mngr.set_read_timeout(timeout);
+    }
+
+    /// Encodes a packet and writes it to the client.
+    pub async fn send_packet<P>(&mut self, pkt: &P) -> anyhow::Result<()>
+    where
+        P: EncodePacket + ?Sized,
+    {
+        self.mngr.send_packet(pkt).await
+    }
+
+    /// Reads the next packet from the client. Use [`RawPacket`] to receive
+    /// packets of any type.
+    ///
+    /// [`RawPacket`]: valence_protocol::RawPacket
+    pub async fn recv_packet<'a, P>(&'a mut self) -> anyhow::Result<P>
+    where
+        P: DecodePacket<'a> + fmt::Debug,
+    {
+        self.mngr.recv_packet().await
+    }
+}
@@ -0,0 +1,77 @@
+//! In-process connections for integrated servers.
+
+use std::fmt;
+use std::io::{self, ErrorKind};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream, ReadHalf, WriteHalf};
+use valence_protocol::{DecodePacket, EncodePacket, PacketDecoder, PacketEncoder};
+
+/// The capacity of the in-memory stream in each direction of a
+/// [`LocalConnection`].
+pub(super) const LOCAL_BUFFER_SIZE: usize = 1 << 16;
+
+/// The client side of a connection made with [`SharedServer::connect_local`].
+///
+/// The connection skips the handshake and login packets and starts in the
+/// play state. Packets are exchanged through an in-memory stream without
+/// compression or encryption, so an integrated server embedded in a client
+/// does not need to go through the network stack at all.
+///
+/// [`SharedServer::connect_local`]: crate::server::SharedServer::connect_local
+pub struct LocalConnection {
+    reader: ReadHalf<DuplexStream>,
+    writer: WriteHalf<DuplexStream>,
+    enc: PacketEncoder,
+    dec: PacketDecoder,
+}
+
+impl LocalConnection {
+    pub(super) fn new(stream: DuplexStream) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+
+        Self {
+            reader,
+            writer,
+            enc: PacketEncoder::new(),
+            dec: PacketDecoder::new(),
+        }
+    }
+
+    /// Encodes a packet and sends it to the server.
+    pub async fn send_packet<P>(&mut self, pkt: &P) -> anyhow::Result<()>
+    where
+        P: EncodePacket + ?Sized,
+    {
+        self.enc.append_packet(pkt)?;
+        let bytes = self.enc.take();
+        self.writer.write_all(&bytes).await?;
+        Ok(())
+    }
+
+    /// Waits for the next packet from the server. Use [`S2cPlayPacket`] to
+    /// receive packets of any type.
+    ///
+    /// Returns an error once the server has closed the connection.
+    ///
+    /// [`S2cPlayPacket`]: valence_protocol::packets::S2cPlayPacket
+    pub async fn recv_packet<'a, P>(&'a mut self) -> anyhow::Result<P>
+    where
+        P: DecodePacket<'a> + fmt::Debug,
+    {
+        while !self.dec.has_next_packet()? {
+            self.dec.reserve(LOCAL_BUFFER_SIZE);
+            let mut buf = self.dec.take_capacity();
+
+            if self.reader.read_buf(&mut buf).await? == 0 {
+                return Err(io::Error::from(ErrorKind::UnexpectedEof).into());
+            }
+
+            self.dec.queue_bytes(buf);
+        }
+
+        Ok(self
+            .dec
+            .try_next_packet()?
+            .expect("decoder said it had another packet"))
+    }
+}
@@ -8,25 +8,31 @@ use anyhow::{anyhow, bail, ensure, Context};
 use hmac::digest::Update;
 use hmac::{Hmac, Mac};
 use num::BigInt;
-use reqwest::StatusCode;
 use rsa::PaddingScheme;
-use serde::Deserialize;
 use sha1::Sha1;
 use sha2::{Digest, Sha256};
+use subtle::{Choice, ConstantTimeEq};
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use uuid::Uuid;
 use valence_protocol::packets::c2s::login::{EncryptionResponse, LoginPluginResponse};
 use valence_protocol::packets::s2c::login::{
-    DisconnectLogin, EncryptionRequest, LoginPluginRequest,
+    DisconnectLogin, EncryptionRequest, LoginPluginRequest, LoginSuccess,
 };
 use valence_protocol::types::{SignedProperty, SignedPropertyOwned};
-use valence_protocol::{translation_key, Decode, Ident, RawBytes, Text, Username, VarInt};
+use valence_protocol::{
+    translation_key, BoundedArray, Decode, Ident, RawBytes, Text, Username, VarInt,
+};
 
 use crate::config::Config;
 use crate::player_textures::SignedPlayerTextures;
 use crate::server::packet_manager::InitialPacketManager;
+use crate::server::transfer::TRANSFER_TOKEN_CHANNEL;
 use crate::server::{NewClientData, SharedServer};
 
+/// The maximum length of a message encrypted with PKCS#1 v1.5 padding using
+/// the server's 1024 bit RSA key.
+const MAX_VERIFY_TOKEN_LEN: usize = 128 - 11;
+
 /// Login sequence for
 /// [`ConnectionMode::Online`](crate::config::ConnectionMode).
 pub(super) async fn online(
@@ -35,7 +41,12 @@ pub(super) async fn online(
     remote_addr: SocketAddr,
     username: Username<String>,
 ) -> anyhow::Result<NewClientData> {
-    let my_verify_token: [u8; 16] = rand::random();
+    let my_verify_token = server.with_rng(|rng| server.0.authenticator.verify_token(rng));
+
+    ensure!(
+        (1..=MAX_VERIFY_TOKEN_LEN).contains(&my_verify_token.len()),
+        "verify token must be between 1 and {MAX_VERIFY_TOKEN_LEN} bytes long"
+    );
 
     mngr.send_packet(&EncryptionRequest {
         server_id: "", // Always empty
@@ -44,90 +55,127 @@ pub(super) async fn online(
     })
     .await?;
 
+    // Since 1.19.3, clients always respond with the encrypted verify token.
+    // The older message signature form of the response no longer exists.
     let EncryptionResponse {
-        shared_secret,
-        verify_token: encrypted_verify_token,
+        shared_secret: BoundedArray(shared_secret),
+        verify_token: BoundedArray(encrypted_verify_token),
     } = mngr.recv_packet().await?;
 
+    // Both values are always decrypted before either result is examined. See
+    // `check_encryption_response`.
     let shared_secret = server
         .0
         .rsa_key
-        .decrypt(PaddingScheme::PKCS1v15Encrypt, shared_secret)
-        .context("failed to decrypt shared secret")?;
+        .decrypt(PaddingScheme::PKCS1v15Encrypt, shared_secret);
 
     let verify_token = server
         .0
         .rsa_key
-        .decrypt(PaddingScheme::PKCS1v15Encrypt, encrypted_verify_token)
-        .context("failed to decrypt verify token")?;
-
-    ensure!(
-        my_verify_token.as_slice() == verify_token,
-        "verify tokens do not match"
-    );
+        .decrypt(PaddingScheme::PKCS1v15Encrypt, encrypted_verify_token);
 
-    let crypt_key: [u8; 16] = shared_secret
-        .as_slice()
-        .try_into()
-        .context("shared secret has the wrong length")?;
+    let crypt_key = check_encryption_response(&my_verify_token, shared_secret, verify_token)?;
 
     mngr.enable_encryption(&crypt_key);
 
     let hash = Sha1::new()
-        .chain(&shared_secret)
+        .chain(crypt_key)
         .chain(&server.0.public_key_der)
         .finalize();
 
-    let url = server.config().session_server(
-        server,
-        username.as_str_username(),
-        &auth_digest(&hash),
-        &remote_addr.ip(),
-    );
-
-    let resp = server.0.http_client.get(url).send().await?;
-
-    match resp.status() {
-        StatusCode::OK => {}
-        StatusCode::NO_CONTENT => {
+    let profile = match server
+        .0
+        .authenticator
+        .verify(
+            username.as_str_username(),
+            &crypt_key,
+            &auth_digest(&hash),
+            remote_addr.ip(),
+        )
+        .await
+    {
+        Ok(profile) => profile,
+        Err(e) => {
             let reason = Text::translate(
                 translation_key::MULTIPLAYER_DISCONNECT_UNVERIFIED_USERNAME,
                 [],
             );
             mngr.send_packet(&DisconnectLogin { reason }).await?;
-            bail!("session server could not verify username");
-        }
-        status => {
-            bail!("session server GET request failed (status code {status})");
+            return Err(e.context("failed to authenticate player"));
         }
-    }
-
-    #[derive(Debug, Deserialize)]
-    struct AuthResponse {
-        id: String,
-        name: Username<String>,
-        properties: Vec<SignedPropertyOwned>,
-    }
-
-    let data: AuthResponse = resp.json().await?;
-
-    ensure!(data.name == username, "usernames do not match");
+    };
 
-    let uuid = Uuid::parse_str(&data.id).context("failed to parse player's UUID")?;
+    ensure!(profile.username == username, "usernames do not match");
 
-    let textures = match data.properties.into_iter().find(|p| p.name == "textures") {
+    let textures = match profile.properties.iter().find(|p| p.name == "textures") {
         Some(p) => SignedPlayerTextures::from_base64(
-            p.value,
-            p.signature.context("missing signature for textures")?,
+            p.value.as_str(),
+            p.signature
+                .as_deref()
+                .context("missing signature for textures")?,
         )?,
         None => bail!("failed to find textures in auth response"),
     };
 
     Ok(NewClientData {
-        uuid,
+        uuid: profile.uuid,
         username,
         ip: remote_addr.ip(),
         textures: Some(textures),
+        properties: profile.properties,
+    })
+}
+
+/// Login sequence for
+/// [`ConnectionMode::Transfer`](crate::config::ConnectionMode).
+pub(super) async fn transfer(
+    server: &SharedServer<impl Config>,
+    mngr: &mut InitialPacketManager<OwnedReadHalf, OwnedWriteHalf>,
+    remote_addr: SocketAddr,
+    username: Username<String>,
+) -> anyhow::Result<NewClientData> {
+    let message_id: i32 = 0;
+
+    mngr.send_packet(&LoginPluginRequest {
+        message_id: VarInt(message_id),
+        channel: Ident::new(TRANSFER_TOKEN_CHANNEL).unwrap(),
+        data: RawBytes(&[]),
+    })
+    .await?;
+
+    let plugin_response: LoginPluginResponse = mngr.recv_packet().await?;
+
+    ensure!(
+        plugin_response.message_id.0 == message_id,
+        "mismatched plugin response ID (got {}, expected {message_id})",
+        plugin_response.message_id.0,
+    );
+
+    let Some(RawBytes(token)) = plugin_response.data else {
+        // The client has no token, so it must authenticate like usual.
+        return online(server, mngr, remote_addr, username).await;
+    };
+
+    let profile = server.verify_transfer_token(token)?;
+
+    ensure!(profile.username == username, "usernames do not match");
+
+    let textures = match profile.properties.iter().find(|p| p.name == "textures") {
+        Some(p) => Some(SignedPlayerTextures::from_base64(
+            p.value.as_str(),
+            p.signature
+                .as_deref()
+                .context("missing signature for textures")?,
+        )?),
+        None => None,
+    };
+
+    Ok(NewClientData {
+        uuid: profile.uuid,
+        username,
+        ip: remote_addr.ip(),
+        textures,
+        properties: profile.properties,
     })
 }
 
@@ -142,6 +190,7 @@ pub(super) fn offline(
         uuid: Uuid::from_slice(&Sha256::digest(username.as_str())[..16])?,
         username,
         textures: None,
+        properties: vec![],
         ip: remote_addr.ip(),
     })
 }
@@ -165,7 +214,7 @@ pub(super) fn bungeecord(
         serde_json::from_str(properties).context("failed to parse BungeeCord player properties")?;
 
     let mut textures = None;
-    for prop in properties {
+    for prop in &properties {
         if prop.name == "textures" {
             textures = Some(
                 SignedPlayerTextures::from_base64(
@@ -183,6 +232,7 @@ pub(super) fn bungeecord(
         uuid: uuid.parse()?,
         username,
         textures,
+        properties: properties.iter().map(to_owned_property).collect(),
         ip: client_ip.parse()?,
     })
 }
@@ -249,10 +299,11 @@ pub(super) async fn velocity(
     );
 
     // Read properties and get textures
+    let properties = Vec::<SignedProperty>::decode(&mut data_without_signature)
+        .context("failed to decode velocity player properties")?;
+
     let mut textures = None;
-    for prop in Vec::<SignedProperty>::decode(&mut data_without_signature)
-        .context("failed to decode velocity player properties")?
-    {
+    for prop in &properties {
         if prop.name == "textures" {
             textures = Some(
                 SignedPlayerTextures::from_base64(
@@ -274,13 +325,72 @@ pub(super) async fn velocity(
         uuid,
         username,
         textures,
+        properties: properties.iter().map(to_owned_property).collect(),
         ip: remote_addr,
     })
 }
 
+fn to_owned_property(prop: &SignedProperty) -> SignedPropertyOwned {
+    SignedPropertyOwned {
+        name: prop.name.into(),
+        value: prop.value.into(),
+        signature: prop.signature.map(Into::into),
+    }
+}
+
+/// Creates the packet sent to finish the login process, which includes the
+/// new client's profile properties.
+pub(super) fn login_success(ncd: &NewClientData) -> LoginSuccess<'_> {
+    LoginSuccess {
+        uuid: ncd.uuid,
+        username: ncd.username.as_str_username(),
+        properties: ncd
+            .properties
+            .iter()
+            .map(|p| SignedProperty {
+                name: &p.name,
+                value: &p.value,
+                signature: p.signature.as_deref(),
+            })
+            .collect(),
+    }
+}
+
+/// Validates the decrypted contents of an [`EncryptionResponse`] and returns
+/// the shared secret.
+///
+/// The ciphertexts are controlled by the client, so this must not reveal why
+/// validation failed. Otherwise, the response could be used as a padding
+/// oracle (Bleichenbacher's attack). Every check is performed regardless of
+/// the outcome of the others, the verify token is compared in constant time,
+/// and all failures produce the same error.
+fn check_encryption_response(
+    expected_verify_token: &[u8],
+    shared_secret: rsa::errors::Result<Vec<u8>>,
+    verify_token: rsa::errors::Result<Vec<u8>>,
+) -> anyhow::Result<[u8; 16]> {
+    let (secret, secret_ok) = match shared_secret.map(<[u8; 16]>::try_from) {
+        Ok(Ok(secret)) => (secret, Choice::from(1)),
+        _ => ([0; 16], Choice::from(0)),
+    };
+
+    let token_ok = match &verify_token {
+        Ok(token) => token.as_slice().ct_eq(expected_verify_token),
+        Err(_) => Choice::from(0),
+    };
+
+    if bool::from(secret_ok & token_ok) {
+        Ok(secret)
+    } else {
+        bail!("invalid encryption response")
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use rsa::errors::Error as RsaError;
     use sha1::Digest;
+    use valence_protocol::{PacketDecoder, PacketEncoder};
 
     use super::*;
 
@@ -299,4 +409,76 @@ mod tests {
             "88e16a1019277b15d58faf0541e11910eb756f6"
         );
     }
+
+    #[test]
+    fn encryption_response_failures_are_indistinguishable() {
+        let token = [7; 16];
+        let secret = [3; 16];
+
+        assert_eq!(
+            check_encryption_response(&token, Ok(secret.to_vec()), Ok(token.to_vec())).unwrap(),
+            secret
+        );
+
+        let failures = [
+            check_encryption_response(&token, Err(RsaError::Decryption), Ok(token.to_vec())),
+            check_encryption_response(&token, Ok(secret.to_vec()), Err(RsaError::Decryption)),
+            check_encryption_response(&token, Ok(secret.to_vec()), Ok(vec![7; 15])),
+            check_encryption_response(&token, Ok(secret.to_vec()), Ok(vec![8; 16])),
+            check_encryption_response(&token, Ok(vec![3; 15]), Ok(token.to_vec())),
+        ];
+
+        for res in failures {
+            assert_eq!(res.unwrap_err().to_string(), "invalid encryption response");
+        }
+    }
+
+    #[test]
+    fn encryption_response_with_custom_token() {
+        let token = [1, 2, 3];
+        let secret = [3; 16];
+
+        assert!(check_encryption_response(&token, Ok(secret.to_vec()), Ok(token.to_vec())).is_ok());
+        assert!(check_encryption_response(&token, Ok(secret.to_vec()), Ok(vec![1, 2])).is_err());
+        assert!(check_encryption_response(&token, Ok(secret.to_vec()), Ok(vec![7; 16])).is_err());
+    }
+
+    #[test]
+    fn textures_forwarded_to_login_success() {
+        let payload = base64::encode(
+            r#"{"textures":{"SKIN":{"url":"http://textures.minecraft.net/texture/abc"}}}"#,
+        );
+        let signature = base64::encode("signature");
+
+        let properties = serde_json::to_string(&[SignedProperty {
+            name: "textures",
+            value: &payload,
+            signature: Some(&signature),
+        }])
+        .unwrap();
+
+        let server_address = format!(
+            "localhost\0127.0.0.1\0{}\0{properties}",
+            Uuid::from_u128(1234).simple()
+        );
+
+        let ncd = bungeecord(&server_address, Username::new("jeb_".into()).unwrap()).unwrap();
+        assert!(ncd.textures.is_some());
+
+        let mut enc = PacketEncoder::new();
+        enc.append_packet(&login_success(&ncd)).unwrap();
+
+        let mut dec = PacketDecoder::new();
+        dec.queue_bytes(enc.take());
+
+        let pkt = dec.try_next_packet::<LoginSuccess>().unwrap().unwrap();
+        assert_eq!(
+            pkt.properties,
+            [SignedProperty {
+                name: "textures",
+                value: &payload,
+                signature: Some(&signature),
+            }]
+        );
+    }
 }
@@ -0,0 +1,427 @@
+//! Server-wide statistics for monitoring.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use valence_protocol::types::HandshakeNextState;
+
+/// A point-in-time snapshot of various server statistics, obtained with
+/// [`SharedServer::metrics`].
+///
+/// The values are gathered from independent atomic counters, so the snapshot
+/// as a whole is **not** transactionally consistent. For instance,
+/// `player_count` may have been updated at the end of the most recent tick
+/// while `bytes_sent` reflects data sent a moment ago.
+///
+/// [`SharedServer::metrics`]: crate::server::SharedServer::metrics
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[non_exhaustive]
+pub struct ServerMetrics {
+    /// The number of clients on the server at the end of the most recent tick.
+    pub player_count: usize,
+    /// The measured number of ticks per second, averaged over recent ticks.
+    pub tps: f64,
+    /// The time taken to execute a tick (not including time spent sleeping),
+    /// averaged over recent ticks.
+    pub mean_tick_time: Duration,
+    /// The total number of bytes sent to all connections.
+    pub bytes_sent: u64,
+    /// The total number of bytes received from all connections.
+    pub bytes_received: u64,
+    /// The number of open connections, including those that have not finished
+    /// logging in.
+    pub active_connections: usize,
+    /// The number of loaded chunks across all worlds at the end of the most
+    /// recent tick.
+    pub chunks_loaded: usize,
+    /// The number of entities at the end of the most recent tick.
+    pub entities_count: usize,
+    /// The total number of status requests (server list pings).
+    pub status_pings: u64,
+    /// The total number of login attempts, successful or not.
+    pub login_attempts: u64,
+}
+
+/// The number of connected clients using each game brand and locale, obtained
+/// with [`SharedServer::client_tallies`].
+///
+/// Brands and locales are chosen by the clients, so at most
+/// [`MAX_TALLY_LABELS`] distinct values are counted in each map. Values beyond
+/// that, and values longer than [`MAX_TALLY_LABEL_LEN`], are counted under
+/// [`OTHER_TALLY_LABEL`].
+///
+/// [`SharedServer::client_tallies`]: crate::server::SharedServer::client_tallies
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+#[non_exhaustive]
+pub struct ClientTallySnapshot {
+    /// The number of connected clients using each game brand (e.g. "vanilla"
+    /// or "fabric"), as reported in the `minecraft:brand` plugin channel.
+    /// Clients that have not sent their brand are not counted.
+    pub brands: HashMap<String, usize>,
+    /// The number of connected clients using each locale (e.g. "en_us"), as
+    /// reported in the first client settings packet.
+    pub locales: HashMap<String, usize>,
+}
+
+/// The maximum number of distinct brands or locales in a
+/// [`ClientTallySnapshot`], not including [`OTHER_TALLY_LABEL`].
+pub const MAX_TALLY_LABELS: usize = 64;
+
+/// The maximum length of a brand or locale counted on its own in a
+/// [`ClientTallySnapshot`].
+pub const MAX_TALLY_LABEL_LEN: usize = 32;
+
+/// The label counting the brands and locales which do not fit in a
+/// [`ClientTallySnapshot`].
+pub const OTHER_TALLY_LABEL: &str = "other";
+
+/// The time spent in each phase of the update loop, obtained with
+/// [`SharedServer::tick_profile`]. Each duration is averaged over recent
+/// ticks.
+///
+/// The phases are listed in the order they are executed. Together they make
+/// up [`ServerMetrics::mean_tick_time`].
+///
+/// [`SharedServer::tick_profile`]: crate::server::SharedServer::tick_profile
+#[derive(Clone, PartialEq, Default, Debug)]
+#[non_exhaustive]
+pub struct TickProfile {
+    /// Adding clients that finished logging in since the previous tick.
+    pub new_clients: Duration,
+    /// Decoding the serverbound packets of every client.
+    pub client_packets: Duration,
+    /// Running [`Config::update`](crate::config::Config::update).
+    pub config_update: Duration,
+    /// Relocating clients, unloading chunks, partitioning entities, and
+    /// updating the chunk and player list caches.
+    pub world_update: Duration,
+    /// Writing the clientbound packets of every client.
+    pub client_update: Duration,
+    /// Updating entities after the clients have observed them.
+    pub entity_update: Duration,
+    /// Clearing the changes of the tick from worlds, player lists,
+    /// inventories, and boss bars.
+    pub finish: Duration,
+}
+
+impl TickProfile {
+    /// Returns the time elapsed since `*lap` and sets `*lap` to now. Used to
+    /// time consecutive phases.
+    pub(super) fn lap(lap: &mut Instant) -> Duration {
+        let now = Instant::now();
+        let elapsed = now - *lap;
+        *lap = now;
+        elapsed
+    }
+
+    fn lerp(&mut self, sample: &TickProfile) {
+        fn lerp_duration(avg: &mut Duration, sample: Duration) {
+            *avg = Duration::from_secs_f64(lerp(avg.as_secs_f64(), sample.as_secs_f64()));
+        }
+
+        lerp_duration(&mut self.new_clients, sample.new_clients);
+        lerp_duration(&mut self.client_packets, sample.client_packets);
+        lerp_duration(&mut self.config_update, sample.config_update);
+        lerp_duration(&mut self.world_update, sample.world_update);
+        lerp_duration(&mut self.client_update, sample.client_update);
+        lerp_duration(&mut self.entity_update, sample.entity_update);
+        lerp_duration(&mut self.finish, sample.finish);
+    }
+}
+
+/// The weight given to the latest sample in the moving averages.
+const SMOOTHING_FACTOR: f64 = 0.05;
+
+/// Statistics published by the update loop. There is only a single writer, so
+/// plain loads and stores are sufficient.
+#[derive(Default)]
+pub(super) struct TickMetrics {
+    player_count: AtomicUsize,
+    chunks_loaded: AtomicUsize,
+    entities_count: AtomicUsize,
+    /// The bits of an `f64`.
+    tps: AtomicU64,
+    /// The bits of an `f64` measured in seconds.
+    mean_tick_time: AtomicU64,
+    /// The moving average of the time spent in each phase of a tick.
+    profile: Mutex<TickProfile>,
+}
+
+impl TickMetrics {
+    pub(super) fn new(tick_rate: f64) -> Self {
+        let metrics = Self::default();
+        metrics.tps.store(tick_rate.to_bits(), Ordering::Relaxed);
+        metrics
+    }
+
+    /// Records the statistics for a completed tick. `tick_interval` is the
+    /// time elapsed between the start of the previous tick and the start of
+    /// the completed tick.
+    pub(super) fn record_tick(
+        &self,
+        tick_duration: Duration,
+        tick_interval: Option<Duration>,
+        profile: &TickProfile,
+        player_count: usize,
+        chunks_loaded: usize,
+        entities_count: usize,
+    ) {
+        self.player_count.store(player_count, Ordering::Relaxed);
+        self.chunks_loaded.store(chunks_loaded, Ordering::Relaxed);
+        self.entities_count.store(entities_count, Ordering::Relaxed);
+
+        let mean = f64::from_bits(self.mean_tick_time.load(Ordering::Relaxed));
+        let mean = lerp(mean, tick_duration.as_secs_f64());
+        self.mean_tick_time.store(mean.to_bits(), Ordering::Relaxed);
+
+        if let Some(interval) = tick_interval.filter(|i| !i.is_zero()) {
+            let tps = f64::from_bits(self.tps.load(Ordering::Relaxed));
+            let tps = lerp(tps, interval.as_secs_f64().recip());
+            self.tps.store(tps.to_bits(), Ordering::Relaxed);
+        }
+
+        self.profile.lock().unwrap().lerp(profile);
+    }
+
+    pub(super) fn profile(&self) -> TickProfile {
+        self.profile.lock().unwrap().clone()
+    }
+
+    pub(super) fn snapshot(
+        &self,
+        bytes_sent: u64,
+        bytes_received: u64,
+        active_connections: usize,
+        connection_counters: &ConnectionCounters,
+    ) -> ServerMetrics {
+        ServerMetrics {
+            player_count: self.player_count.load(Ordering::Relaxed),
+            tps: f64::from_bits(self.tps.load(Ordering::Relaxed)),
+            mean_tick_time: Duration::from_secs_f64(f64::from_bits(
+                self.mean_tick_time.load(Ordering::Relaxed),
+            )),
+            bytes_sent,
+            bytes_received,
+            active_connections,
+            chunks_loaded: self.chunks_loaded.load(Ordering::Relaxed),
+            entities_count: self.entities_count.load(Ordering::Relaxed),
+            status_pings: connection_counters.status_pings(),
+            login_attempts: connection_counters.login_attempts(),
+        }
+    }
+}
+
+/// Monotonic counters of the next state requested in handshakes, updated by
+/// the connection tasks.
+#[derive(Default)]
+pub(super) struct ConnectionCounters {
+    status_pings: AtomicU64,
+    login_attempts: AtomicU64,
+}
+
+impl ConnectionCounters {
+    pub(super) fn record(&self, next_state: HandshakeNextState) {
+        match next_state {
+            HandshakeNextState::Status => &self.status_pings,
+            HandshakeNextState::Login => &self.login_attempts,
+        }
+        .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn status_pings(&self) -> u64 {
+        self.status_pings.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn login_attempts(&self) -> u64 {
+        self.login_attempts.load(Ordering::Relaxed)
+    }
+}
+
+/// The number of connected clients for each brand and locale. Updated through
+/// the [`ClientTally`] owned by each client.
+#[derive(Default)]
+pub(crate) struct ClientTallies {
+    brands: Mutex<HashMap<String, usize>>,
+    locales: Mutex<HashMap<String, usize>>,
+}
+
+impl ClientTallies {
+    pub(super) fn snapshot(&self) -> ClientTallySnapshot {
+        ClientTallySnapshot {
+            brands: self.brands.lock().unwrap().clone(),
+            locales: self.locales.lock().unwrap().clone(),
+        }
+    }
+}
+
+/// The contribution of a single client to the [`ClientTallies`]. Only the first
+/// brand and locale received from the client are counted, and they are
+/// subtracted from the tallies again when this is dropped.
+pub(crate) struct ClientTally {
+    tallies: Arc<ClientTallies>,
+    /// The label the brand is counted under.
+    brand: Option<String>,
+    /// The label the locale is counted under.
+    locale: Option<String>,
+}
+
+impl ClientTally {
+    pub(crate) fn new(tallies: Arc<ClientTallies>) -> Self {
+        Self {
+            tallies,
+            brand: None,
+            locale: None,
+        }
+    }
+
+    pub(crate) fn record_brand(&mut self, brand: &str) {
+        if self.brand.is_none() {
+            self.brand = Some(increment(&self.tallies.brands, brand));
+        }
+    }
+
+    pub(crate) fn record_locale(&mut self, locale: &str) {
+        if self.locale.is_none() {
+            self.locale = Some(increment(&self.tallies.locales, locale));
+        }
+    }
+}
+
+impl Drop for ClientTally {
+    fn drop(&mut self) {
+        if let Some(brand) = &self.brand {
+            decrement(&self.tallies.brands, brand);
+        }
+
+        if let Some(locale) = &self.locale {
+            decrement(&self.tallies.locales, locale);
+        }
+    }
+}
+
+/// Counts a client under `key`, or under [`OTHER_TALLY_LABEL`] if `key` is too
+/// long or there are too many labels already. Returns the label used.
+fn increment(counts: &Mutex<HashMap<String, usize>>, key: &str) -> String {
+    let mut counts = counts.lock().unwrap();
+
+    let labels = counts.len() - counts.contains_key(OTHER_TALLY_LABEL) as usize;
+
+    let key = if key.len() > MAX_TALLY_LABEL_LEN
+        || (labels >= MAX_TALLY_LABELS && !counts.contains_key(key))
+    {
+        OTHER_TALLY_LABEL
+    } else {
+        key
+    };
+
+    *counts.entry(key.into()).or_insert(0) += 1;
+    key.into()
+}
+
+fn decrement(counts: &Mutex<HashMap<String, usize>>, key: &str) {
+    let mut counts = counts.lock().unwrap();
+
+    if let Some(count) = counts.get_mut(key) {
+        *count -= 1;
+        if *count == 0 {
+            counts.remove(key);
+        }
+    }
+}
+
+fn lerp(avg: f64, sample: f64) -> f64 {
+    avg + (sample - avg) * SMOOTHING_FACTOR
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_tallies_follow_connected_clients() {
+        let tallies = Arc::new(ClientTallies::default());
+        let counts = || {
+            let snapshot = tallies.snapshot();
+            (snapshot.brands, snapshot.locales)
+        };
+
+        let mut a = ClientTally::new(tallies.clone());
+        let mut b = ClientTally::new(tallies.clone());
+
+        a.record_brand("vanilla");
+        a.record_locale("en_us");
+        // Only the first brand and locale are counted.
+        a.record_brand("fabric");
+        a.record_locale("de_de");
+        b.record_brand("vanilla");
+
+        let (brands, locales) = counts();
+        assert_eq!(brands, HashMap::from([("vanilla".into(), 2)]));
+        assert_eq!(locales, HashMap::from([("en_us".into(), 1)]));
+
+        drop(a);
+
+        let (brands, locales) = counts();
+        assert_eq!(brands, HashMap::from([("vanilla".into(), 1)]));
+        assert!(locales.is_empty());
+
+        drop(b);
+
+        assert!(counts().0.is_empty());
+    }
+
+    #[test]
+    fn client_tally_labels_capped() {
+        let tallies = Arc::new(ClientTallies::default());
+
+        let mut clients: Vec<_> = (0..MAX_TALLY_LABELS + 2)
+            .map(|i| {
+                let mut tally = ClientTally::new(tallies.clone());
+                tally.record_brand(&format!("brand{i}"));
+                tally
+            })
+            .collect();
+
+        let mut long = ClientTally::new(tallies.clone());
+        long.record_locale(&"x".repeat(MAX_TALLY_LABEL_LEN + 1));
+
+        // Labels which are already counted are still counted on their own.
+        let mut known = ClientTally::new(tallies.clone());
+        known.record_brand("brand0");
+        clients.push(known);
+
+        let snapshot = tallies.snapshot();
+        assert_eq!(snapshot.brands.len(), MAX_TALLY_LABELS + 1);
+        assert_eq!(snapshot.brands["brand0"], 2);
+        assert_eq!(snapshot.brands[OTHER_TALLY_LABEL], 2);
+        assert_eq!(
+            snapshot.locales,
+            HashMap::from([(OTHER_TALLY_LABEL.into(), 1)])
+        );
+
+        drop(clients);
+        drop(long);
+
+        assert_eq!(tallies.snapshot(), ClientTallySnapshot::default());
+    }
+
+    #[test]
+    fn tick_profile_averages_phases() {
+        let metrics = TickMetrics::new(20.0);
+
+        let sample = TickProfile {
+            config_update: Duration::from_millis(10),
+            ..Default::default()
+        };
+
+        for _ in 0..1000 {
+            metrics.record_tick(Duration::from_millis(10), None, &sample, 0, 0, 0);
+        }
+
+        let profile = metrics.profile();
+        assert!((profile.config_update.as_secs_f64() - 0.010).abs() < 1e-6);
+        assert_eq!(profile.client_update, Duration::ZERO);
+    }
+}
@@ -1,51 +1,126 @@
 use std::fmt;
 use std::io::ErrorKind;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use anyhow::Result;
+use bytes::BytesMut;
+use thiserror::Error;
 use tokio::io;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::runtime::Handle;
-use tokio::sync::OwnedSemaphorePermit;
+use tokio::select;
+use tokio::sync::{Notify, OwnedSemaphorePermit};
 use tokio::task::JoinHandle;
 use tokio::time::timeout;
-use tracing::debug;
-use valence_protocol::{DecodePacket, EncodePacket, PacketDecoder, PacketEncoder};
-
+use tracing::{debug, info_span, warn, Instrument};
+use valence_protocol::packets::{C2sPlayPacket, S2cPlayPacket};
+use valence_protocol::{
+    DecodePacket, EncodePacket, PacketDecoder, PacketEncoder, RawPacket, MAX_PACKET_SIZE,
+};
+
+use crate::client::ClientId;
+use crate::config::{Config, OverflowPolicy};
 use crate::packet::WritePacket;
-use crate::server::byte_channel::{byte_channel, ByteReceiver, ByteSender, TryRecvError};
+use crate::server::byte_channel::{
+    byte_channel, ByteReceiver, ByteSender, TryRecvError, TrySendError,
+};
+use crate::server::SharedServer;
 
 pub struct InitialPacketManager<R, W> {
     reader: R,
     writer: W,
     enc: PacketEncoder,
     dec: PacketDecoder,
-    timeout: Duration,
+    /// The maximum time to wait for the next packet to begin arriving.
+    idle_timeout: Duration,
+    /// The maximum time for a single read or write to make progress once a
+    /// packet has started.
+    read_timeout: Duration,
+    /// The maximum total time to receive a packet, or `None` if there is no
+    /// limit.
+    packet_timeout: Option<Duration>,
     permit: OwnedSemaphorePermit,
+    counters: ConnectionCounters,
+}
+
+/// Counts the number of bytes sent and received over the network.
+///
+/// Bytes are counted after compression and encryption, so the counts match
+/// what is actually sent over the wire.
+#[derive(Default, Debug)]
+pub struct ByteCounters {
+    sent: AtomicU64,
+    received: AtomicU64,
+}
+
+impl ByteCounters {
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+}
+
+/// The byte counters for a single connection along with the server-wide
+/// counters they contribute to.
+#[derive(Clone)]
+struct ConnectionCounters {
+    conn: Arc<ByteCounters>,
+    global: Arc<ByteCounters>,
+}
+
+impl ConnectionCounters {
+    fn add_sent(&self, n: usize) {
+        self.conn.sent.fetch_add(n as u64, Ordering::Relaxed);
+        self.global.sent.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    fn add_received(&self, n: usize) {
+        self.conn.received.fetch_add(n as u64, Ordering::Relaxed);
+        self.global.received.fetch_add(n as u64, Ordering::Relaxed);
+    }
 }
 
 const READ_BUF_SIZE: usize = 4096;
 
+/// The maximum number of messages waiting for the inspection task of a
+/// client. A message holds the packets of one flush or a single incoming
+/// packet.
+pub(crate) const INSPECT_CAPACITY: usize = 256;
+
 impl<R, W> InitialPacketManager<R, W>
 where
     R: AsyncRead + Unpin,
     W: AsyncWrite + Unpin,
 {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         reader: R,
         writer: W,
         enc: PacketEncoder,
         dec: PacketDecoder,
-        timeout: Duration,
+        idle_timeout: Duration,
+        read_timeout: Duration,
         permit: OwnedSemaphorePermit,
+        global_counters: Arc<ByteCounters>,
     ) -> Self {
         Self {
             reader,
             writer,
             enc,
             dec,
-            timeout,
+            idle_timeout,
+            read_timeout,
+            packet_timeout: None,
             permit,
+            counters: ConnectionCounters {
+                conn: Default::default(),
+                global: global_counters,
+            },
         }
     }
 
@@ -55,55 +130,72 @@ where
     {
         self.enc.append_packet(pkt)?;
         let bytes = self.enc.take();
-        timeout(self.timeout, self.writer.write_all(&bytes)).await??;
+        timeout(self.read_timeout, self.writer.write_all(&bytes)).await??;
+        self.counters.add_sent(bytes.len());
         Ok(())
     }
 
+    /// Receives the next packet.
+    ///
+    /// Waiting for the first bytes of the packet is limited by the idle
+    /// timeout. After that, every read must receive more of the packet within
+    /// the read timeout. Large packets which arrive slowly but steadily are
+    /// not timed out, unless receiving the whole packet takes longer than the
+    /// packet timeout.
     pub async fn recv_packet<'a, P>(&'a mut self) -> Result<P>
     where
         P: DecodePacket<'a> + fmt::Debug,
     {
-        timeout(self.timeout, async {
-            while !self.dec.has_next_packet()? {
-                self.dec.reserve(READ_BUF_SIZE);
-                let mut buf = self.dec.take_capacity();
+        match self.packet_timeout {
+            Some(dur) => timeout(dur, self.fill_packet()).await??,
+            None => self.fill_packet().await?,
+        }
 
-                if self.reader.read_buf(&mut buf).await? == 0 {
-                    return Err(io::Error::from(ErrorKind::UnexpectedEof).into());
-                }
+        Ok(self
+            .dec
+            .try_next_packet()?
+            .expect("decoder said it had another packet"))
+    }
 
-                // This should always be an O(1) unsplit because we reserved space earlier and
-                // the call to `read_buf` shouldn't have grown the allocation.
-                self.dec.queue_bytes(buf);
+    /// Reads from the connection until the decoder has a complete packet.
+    async fn fill_packet(&mut self) -> Result<()> {
+        while !self.dec.has_next_packet()? {
+            let timeout_dur = if self.dec.queued_bytes().is_empty() {
+                self.idle_timeout
+            } else {
+                self.read_timeout
+            };
+
+            self.dec.reserve(READ_BUF_SIZE);
+            let mut buf = self.dec.take_capacity();
+
+            match timeout(timeout_dur, self.reader.read_buf(&mut buf)).await?? {
+                0 => return Err(io::Error::from(ErrorKind::UnexpectedEof).into()),
+                n => self.counters.add_received(n),
             }
 
-            Ok(self
-                .dec
-                .try_next_packet()?
-                .expect("decoder said it had another packet"))
+            // This should always be an O(1) unsplit because we reserved space earlier and
+            // the call to `read_buf` shouldn't have grown the allocation.
+            self.dec.queue_bytes(buf);
+        }
 
-            // The following is what I want to write but can't due to borrow
-            // checker errors I don't understand.
-            /*
-            loop {
-                if let Some(pkt) = self.dec.try_next_packet()? {
-                    return Ok(pkt);
-                }
+        Ok(())
+    }
 
-                self.dec.reserve(READ_BUF_SIZE);
-                let mut buf = self.dec.take_capacity();
+    pub fn set_idle_timeout(&mut self, timeout: Duration) {
+        self.idle_timeout = timeout;
+    }
 
-                if self.reader.read_buf(&mut buf).await? == 0 {
-                    return Err(io::Error::from(ErrorKind::UnexpectedEof).into());
-                }
+    pub fn set_packet_timeout(&mut self, timeout: Option<Duration>) {
+        self.packet_timeout = timeout;
+    }
 
-                // This should always be an O(1) unsplit because we reserved space earlier and
-                // the call to `read_buf` shouldn't have grown the allocation.
-                self.dec.queue_bytes(buf);
-            }
-            */
-        })
-        .await?
+    pub fn set_read_timeout(&mut self, timeout: Duration) {
+        self.read_timeout = timeout;
+    }
+
+    pub fn set_max_packet_len(&mut self, len: Option<i32>) {
+        self.dec.set_max_packet_len(len);
     }
 
     #[allow(dead_code)]
@@ -117,6 +209,14 @@ where
         self.dec.enable_encryption(key);
     }
 
+    pub fn is_compressed(&self) -> bool {
+        self.enc.is_compressed()
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.enc.is_encrypted()
+    }
+
     pub fn into_play(
         mut self,
         incoming_limit: usize,
@@ -127,51 +227,97 @@ where
         R: Send + 'static,
         W: Send + 'static,
     {
+        // Login is over, so lift the initial packet size limit.
+        self.dec.set_max_packet_len(None);
+
         let (mut incoming_sender, incoming_receiver) = byte_channel(incoming_limit);
 
-        let reader_task = tokio::spawn(async move {
+        // When either half of the connection stops, the other half is stopped
+        // too. Otherwise, a connection which can no longer be written to keeps
+        // reading until the client goes away on its own.
+        let stop_reader = Arc::new(Notify::new());
+        let stop_writer = Arc::new(Notify::new());
+
+        let reader_guard = NotifyOnDrop(stop_writer.clone());
+        let writer_guard = NotifyOnDrop(stop_reader.clone());
+
+        // The first I/O error to stop either half of the connection.
+        let io_error = Arc::new(Mutex::new(None));
+        let reader_error = io_error.clone();
+        let writer_error = io_error.clone();
+
+        let reader_counters = self.counters.clone();
+
+        let reader = async move {
+            let _guard = reader_guard;
+
             loop {
                 let mut buf = incoming_sender.take_capacity(READ_BUF_SIZE);
 
-                match self.reader.read_buf(&mut buf).await {
+                let res = select! {
+                    res = self.reader.read_buf(&mut buf) => res,
+                    _ = stop_reader.notified() => break,
+                };
+
+                match res {
                     Ok(0) => break,
                     Err(e) => {
-                        debug!("error reading packet data: {e}");
+                        debug!(error = %e, "error reading packet data");
+                        record_io_error(&reader_error, format!("error reading packet data: {e}"));
                         break;
                     }
-                    _ => {}
+                    Ok(n) => reader_counters.add_received(n),
                 }
 
                 // This should always be an O(1) unsplit because we reserved space earlier.
                 if let Err(e) = incoming_sender.send_async(buf).await {
-                    debug!("error sending packet data: {e}");
+                    debug!(error = %e, "error sending packet data");
                     break;
                 }
             }
-        });
+        };
+
+        // The tasks log in the span of the connection.
+        let reader_task = tokio::spawn(reader.in_current_span());
 
         let (outgoing_sender, mut outgoing_receiver) = byte_channel(outgoing_limit);
 
-        let writer_task = tokio::spawn(async move {
+        let writer_counters = self.counters.clone();
+
+        let writer = async move {
+            let _guard = writer_guard;
+
             loop {
-                let bytes = match outgoing_receiver.recv_async().await {
-                    Ok(bytes) => bytes,
+                let bytes = select! {
+                    res = outgoing_receiver.recv_async() => match res {
+                        Ok(bytes) => bytes,
+                        Err(e) => {
+                            debug!(error = %e, "error receiving packet data");
+                            break;
+                        }
+                    },
+                    _ = stop_writer.notified() => break,
+                };
+
+                match self.writer.write_all(&bytes).await {
+                    Ok(()) => writer_counters.add_sent(bytes.len()),
                     Err(e) => {
-                        debug!("error receiving packet data: {e}");
+                        debug!(error = %e, "error writing packet data");
+                        record_io_error(&writer_error, format!("error writing packet data: {e}"));
                         break;
                     }
-                };
-
-                if let Err(e) = self.writer.write_all(&bytes).await {
-                    debug!("error writing packet data: {e}");
                 }
             }
-        });
+        };
+
+        let writer_task = tokio::spawn(writer.in_current_span());
 
         (
             PlayPacketSender {
                 enc: self.enc,
+                inspect: None,
                 send: outgoing_sender,
+                overflow_policy: OverflowPolicy::default(),
                 writer_task: Some(writer_task),
                 handle,
             },
@@ -179,44 +325,148 @@ where
                 dec: self.dec,
                 recv: incoming_receiver,
                 reader_task,
+                counters: self.counters.conn,
+                inspect: None,
+                io_error,
             },
             self.permit,
         )
     }
 }
 
+fn record_io_error(slot: &Mutex<Option<String>>, error: String) {
+    slot.lock().unwrap().get_or_insert(error);
+}
+
+/// Wakes the task on the other half of a connection when dropped, including
+/// when the task owning it is aborted.
+struct NotifyOnDrop(Arc<Notify>);
+
+impl Drop for NotifyOnDrop {
+    fn drop(&mut self) {
+        self.0.notify_one();
+    }
+}
+
+/// Returned by [`PlayPacketSender::flush`] when the client is not reading the
+/// packets sent to it fast enough. See [`Config::overflow_policy`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Error)]
+#[error("client is not keeping up with outgoing packets (see `Config::outgoing_capacity`)")]
+pub struct SlowClientError;
+
 /// Manages a packet encoder and a byte channel to send the encoded packets
 /// through.
 pub struct PlayPacketSender {
     enc: PacketEncoder,
+    /// Encodes a copy of the outgoing packets without encryption for
+    /// inspection.
+    inspect: Option<(PacketEncoder, InspectSender)>,
     send: ByteSender,
+    overflow_policy: OverflowPolicy,
     writer_task: Option<JoinHandle<()>>,
     handle: Handle,
 }
 
 impl PlayPacketSender {
+    pub fn set_overflow_policy(&mut self, policy: OverflowPolicy) {
+        self.overflow_policy = policy;
+    }
+
     pub fn append_packet<P>(&mut self, pkt: &P) -> Result<()>
     where
         P: EncodePacket + ?Sized,
     {
+        if let Some((enc, _)) = &mut self.inspect {
+            enc.append_packet(pkt)?;
+        }
+
         self.enc.append_packet(pkt)
     }
 
     pub fn append_bytes(&mut self, bytes: &[u8]) {
+        if let Some((enc, _)) = &mut self.inspect {
+            enc.append_bytes(bytes);
+        }
+
         self.enc.append_bytes(bytes)
     }
 
+    /// Like [`Self::append_packet`], but the packet is dropped if it does not
+    /// need to be sent because the client is falling behind. See
+    /// [`OverflowPolicy::DropNonEssentialPackets`].
+    pub fn append_nonessential_packet<P>(&mut self, pkt: &P) -> Result<()>
+    where
+        P: EncodePacket + ?Sized,
+    {
+        if self.is_nearly_full() {
+            return Ok(());
+        }
+
+        self.append_packet(pkt)
+    }
+
+    /// Returns whether nonessential packets should be dropped, which is the
+    /// case when the bytes still waiting to be written and the packets
+    /// appended since the last flush take up more than three quarters of the
+    /// outgoing buffer. Always `false` unless the overflow policy is
+    /// [`OverflowPolicy::DropNonEssentialPackets`].
+    pub fn is_nearly_full(&self) -> bool {
+        self.overflow_policy == OverflowPolicy::DropNonEssentialPackets
+            && self.send.len() + self.enc.len() > self.send.limit() / 4 * 3
+    }
+
     pub fn prepend_packet<P>(&mut self, pkt: &P) -> Result<()>
     where
         P: EncodePacket + ?Sized,
     {
+        if let Some((enc, _)) = &mut self.inspect {
+            enc.prepend_packet(pkt)?;
+        }
+
         self.enc.prepend_packet(pkt)
     }
 
+    /// Sends the appended packets. Fails with [`SlowClientError`] if they do
+    /// not fit in the outgoing buffer.
     pub fn flush(&mut self) -> Result<()> {
+        let block = match self.overflow_policy {
+            OverflowPolicy::Block { timeout } => Some(timeout),
+            _ => None,
+        };
+
+        self.flush_inner(block)
+    }
+
+    /// Sends the appended packets. If `block` is `Some` and the outgoing
+    /// buffer is full, this waits up to the given duration for enough room.
+    fn flush_inner(&mut self, block: Option<Duration>) -> Result<()> {
+        if let Some((enc, send)) = &mut self.inspect {
+            let bytes = enc.take();
+
+            if !bytes.is_empty() {
+                send.send(InspectedPackets::Outgoing(bytes));
+            }
+        }
+
         let bytes = self.enc.take();
-        self.send.try_send(bytes)?;
-        Ok(())
+
+        match self.send.try_send(bytes) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(rest)) => {
+                let Some(block) = block else {
+                    return Err(SlowClientError.into());
+                };
+
+                match self
+                    .handle
+                    .block_on(timeout(block, self.send.send_async(rest)))
+                {
+                    Ok(res) => Ok(res?),
+                    Err(_) => Err(SlowClientError.into()),
+                }
+            }
+            Err(e) => Err(e.into()),
+        }
     }
 }
 
@@ -236,15 +486,25 @@ impl WritePacket for PlayPacketSender {
 
 impl Drop for PlayPacketSender {
     fn drop(&mut self) {
-        let _ = self.flush();
+        // Never block here, since the connection may be dropped because it is
+        // not making progress.
+        let _ = self.flush_inner(None);
 
-        if let Some(writer_task) = self.writer_task.take() {
+        if let Some(mut writer_task) = self.writer_task.take() {
             if !writer_task.is_finished() {
                 let _guard = self.handle.enter();
 
-                // Give any unsent packets a moment to send before we cut the connection.
-                self.handle
-                    .spawn(timeout(Duration::from_secs(1), writer_task));
+                // Give any unsent packets a moment to send before we cut the
+                // connection. The writer is stopped even if the client is not
+                // reading, which would otherwise keep it waiting forever.
+                self.handle.spawn(async move {
+                    if timeout(Duration::from_secs(1), &mut writer_task)
+                        .await
+                        .is_err()
+                    {
+                        writer_task.abort();
+                    }
+                });
             }
         }
     }
@@ -255,14 +515,54 @@ pub struct PlayPacketReceiver {
     dec: PacketDecoder,
     recv: ByteReceiver,
     reader_task: JoinHandle<()>,
+    counters: Arc<ByteCounters>,
+    inspect: Option<InspectSender>,
+    /// The I/O error which stopped the connection, if any. Set before the
+    /// byte channel is closed.
+    io_error: Arc<Mutex<Option<String>>>,
 }
 
 impl PlayPacketReceiver {
-    pub fn try_next_packet<'a, P>(&'a mut self) -> Result<Option<P>>
-    where
-        P: DecodePacket<'a> + fmt::Debug,
-    {
-        self.dec.try_next_packet()
+    /// Returns the byte counters for this connection. This includes bytes
+    /// sent and received prior to entering the play state.
+    pub fn byte_counters(&self) -> &ByteCounters {
+        &self.counters
+    }
+
+    /// Decodes the next packet without interpreting its body. A copy of the
+    /// packet is passed to the inspection task if packet inspection is
+    /// enabled.
+    pub fn try_next_raw_packet(&mut self) -> Result<Option<RawPacket<'_>>> {
+        let pkt = self.dec.try_next_packet::<RawPacket>()?;
+
+        if let (Some(send), Some(RawPacket { id, data })) = (&self.inspect, &pkt) {
+            send.send(InspectedPackets::Incoming {
+                id: id.0,
+                data: data.to_vec(),
+            });
+        }
+
+        Ok(pkt)
+    }
+
+    /// Stops reading from the connection. Packets that have already been
+    /// received can still be decoded.
+    pub fn close(&mut self) {
+        self.reader_task.abort();
+    }
+
+    /// Returns the I/O error which stopped the connection. `None` if the
+    /// connection is still open or was closed without an error, such as when
+    /// the client closed it.
+    pub fn io_error(&self) -> Option<String> {
+        self.io_error.lock().unwrap().clone()
+    }
+
+    /// Returns true if no more bytes will be received from the client. Bytes
+    /// which were received before may still be waiting to be taken by
+    /// [`Self::try_recv`].
+    pub fn is_disconnected(&self) -> bool {
+        self.recv.is_disconnected()
     }
 
     /// Returns true if the client is connected. Returns false otherwise.
@@ -283,3 +583,388 @@ impl Drop for PlayPacketReceiver {
         self.reader_task.abort();
     }
 }
+
+/// Packets sent to the inspection task of a client.
+enum InspectedPackets {
+    /// Encoded packets sent to the client, without encryption.
+    Outgoing(BytesMut),
+    /// The ID and body of a packet received from the client.
+    Incoming { id: i32, data: Vec<u8> },
+}
+
+/// Sends packets to the inspection task of a client without blocking.
+#[derive(Clone)]
+struct InspectSender {
+    send: flume::Sender<InspectedPackets>,
+    /// The number of messages dropped since the inspection task last caught
+    /// up.
+    dropped: Arc<AtomicUsize>,
+}
+
+impl InspectSender {
+    /// Drops the packets if the inspection task is falling behind.
+    fn send(&self, packets: InspectedPackets) {
+        if let Err(flume::TrySendError::Full(_)) = self.send.try_send(packets) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Spawns a task which passes copies of the packets sent through `send` and
+/// received through `recv` to [`Config::inspect_outgoing`] and
+/// [`Config::inspect_incoming`]. The task stops once both are dropped.
+///
+/// If the task falls more than [`INSPECT_CAPACITY`] messages behind, the
+/// packets which do not fit are not inspected and a warning is logged.
+pub fn inspect_packets<C: Config>(
+    shared: &SharedServer<C>,
+    client: ClientId,
+    send: &mut PlayPacketSender,
+    recv: &mut PlayPacketReceiver,
+) {
+    let (inspect_send, inspect_recv) = flume::bounded(INSPECT_CAPACITY);
+    let inspect_send = InspectSender {
+        send: inspect_send,
+        dropped: Arc::new(AtomicUsize::new(0)),
+    };
+    let dropped = inspect_send.dropped.clone();
+
+    // The copy only needs the same framing as the real packets. The
+    // threshold of the copy is irrelevant since the cached packets appended
+    // with `append_bytes` are compressed already.
+    let mut enc = PacketEncoder::new();
+    let compressed = send.enc.is_compressed();
+
+    if compressed {
+        enc.set_compression(Some(MAX_PACKET_SIZE as u32));
+    }
+
+    send.inspect = Some((enc, inspect_send.clone()));
+    recv.inspect = Some(inspect_send);
+
+    let shared = shared.clone();
+
+    let handle = shared.tokio_handle().clone();
+
+    let task = async move {
+        let mut dec = PacketDecoder::new();
+        dec.set_compression(compressed);
+
+        while let Ok(packets) = inspect_recv.recv_async().await {
+            let dropped = dropped.swap(0, Ordering::Relaxed);
+
+            if dropped > 0 {
+                warn!(dropped, "packet inspection is falling behind");
+            }
+
+            match packets {
+                InspectedPackets::Outgoing(bytes) => {
+                    dec.queue_bytes(bytes);
+
+                    loop {
+                        match dec.try_next_packet::<RawPacket>() {
+                            Ok(Some(RawPacket { id, mut data })) => {
+                                if let Ok(Some(pkt)) =
+                                    S2cPlayPacket::decode_with_id(id.0, &mut data)
+                                {
+                                    shared.config().inspect_outgoing(client, &pkt);
+                                }
+                            }
+                            Ok(None) => break,
+                            Err(e) => {
+                                // The stream of packets can't be recovered.
+                                warn!(
+                                    error = format!("{e:#}"),
+                                    "failed to decode packet for inspection"
+                                );
+                                return;
+                            }
+                        }
+                    }
+                }
+                InspectedPackets::Incoming { id, data } => {
+                    if let Ok(Some(pkt)) = C2sPlayPacket::decode_with_id(id, &mut data.as_slice()) {
+                        shared.config().inspect_incoming(client, &pkt);
+                    }
+                }
+            }
+        }
+    };
+
+    handle.spawn(task.instrument(info_span!("inspect_packets", ?client)));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use std::time::Instant;
+
+    use tokio::io::DuplexStream;
+    use tokio::sync::Semaphore;
+    use tokio::time::error::Elapsed;
+    use valence_protocol::packets::s2c::play::KeepAliveS2c;
+    use valence_protocol::VarInt;
+
+    use super::*;
+
+    /// A writer which fails on every write, like a socket whose peer has gone
+    /// away.
+    struct BrokenWriter;
+
+    impl AsyncWrite for BrokenWriter {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            _: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Ready(Err(ErrorKind::BrokenPipe.into()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn write_error_stops_reader() {
+        // The other end of the stream stays open but never sends anything, so
+        // the reader would wait forever on its own.
+        let (stream, _remote) = io::duplex(1024);
+
+        let permit = Arc::new(Semaphore::new(1)).try_acquire_owned().unwrap();
+
+        let mngr = InitialPacketManager::new(
+            stream,
+            BrokenWriter,
+            PacketEncoder::new(),
+            PacketDecoder::new(),
+            Duration::from_secs(5),
+            Duration::from_secs(5),
+            permit,
+            Default::default(),
+        );
+
+        let (mut send, mut recv, _permit) = mngr.into_play(1024, 1024, Handle::current());
+
+        send.append_bytes(&[1, 0]);
+        send.flush().unwrap();
+
+        timeout(Duration::from_secs(5), async {
+            while recv.try_recv() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("reader did not stop after the write error");
+    }
+
+    /// Creates a sender whose writer task writes to `writer`. The receiver and
+    /// the remote end of the stream it reads from must be kept alive, or the
+    /// writer task stops.
+    fn play_sender<W>(
+        writer: W,
+        outgoing_limit: usize,
+        policy: OverflowPolicy,
+    ) -> (PlayPacketSender, PlayPacketReceiver, DuplexStream)
+    where
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (reader, remote) = io::duplex(1);
+
+        let permit = Arc::new(Semaphore::new(1)).try_acquire_owned().unwrap();
+
+        let mngr = InitialPacketManager::new(
+            reader,
+            writer,
+            PacketEncoder::new(),
+            PacketDecoder::new(),
+            Duration::from_secs(5),
+            Duration::from_secs(5),
+            permit,
+            Default::default(),
+        );
+
+        let (mut send, recv, _permit) = mngr.into_play(1024, outgoing_limit, Handle::current());
+        send.set_overflow_policy(policy);
+        (send, recv, remote)
+    }
+
+    #[tokio::test]
+    async fn nonessential_packets_dropped_when_nearly_full() {
+        for policy in [
+            OverflowPolicy::DisconnectSlowClient,
+            OverflowPolicy::DropNonEssentialPackets,
+        ] {
+            // The writer task never gets to run, so the flushed bytes stay in
+            // the channel.
+            let (mut send, _recv, _remote) = play_sender(io::sink(), 1024, policy);
+
+            let drop = policy == OverflowPolicy::DropNonEssentialPackets;
+
+            send.append_nonessential_packet(&KeepAliveS2c { id: 0 })
+                .unwrap();
+            assert!(!send.enc.take().is_empty());
+
+            // Packets which have not been flushed yet count too.
+            send.append_bytes(&[0; 900]);
+            assert_eq!(send.is_nearly_full(), drop);
+
+            send.flush().unwrap();
+            assert_eq!(send.is_nearly_full(), drop);
+
+            send.append_nonessential_packet(&KeepAliveS2c { id: 0 })
+                .unwrap();
+            assert_eq!(send.enc.take().is_empty(), drop);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn block_policy_waits_for_room() {
+        let (stream, mut remote) = io::duplex(64);
+
+        let policy = OverflowPolicy::Block {
+            timeout: Duration::from_secs(5),
+        };
+        let (mut send, _recv, _remote) = play_sender(stream, 1024, policy);
+
+        let reader = tokio::spawn(async move {
+            let mut buf = vec![];
+            remote.read_to_end(&mut buf).await.unwrap();
+            buf.len()
+        });
+
+        tokio::task::spawn_blocking(move || {
+            for _ in 0..3 {
+                send.append_bytes(&[0; 1000]);
+                send.flush().unwrap();
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(reader.await.unwrap(), 3000);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn block_policy_gives_up_on_stalled_clients() {
+        // The remote end never reads, so nothing is ever written.
+        let (stream, _remote) = io::duplex(64);
+
+        let policy = OverflowPolicy::Block {
+            timeout: Duration::from_millis(100),
+        };
+        let (mut send, _recv, _remote) = play_sender(stream, 1024, policy);
+
+        // The writer task holds on to the first bytes and the channel is full
+        // of the second.
+        let res = tokio::task::spawn_blocking(move || {
+            for _ in 0..2 {
+                send.append_bytes(&[0; 1000]);
+                send.flush().unwrap();
+            }
+
+            send.append_bytes(&[0; 1000]);
+            send.flush()
+        })
+        .await
+        .unwrap();
+
+        assert!(res.unwrap_err().is::<SlowClientError>());
+    }
+
+    #[tokio::test]
+    async fn slow_packets_are_not_timed_out() {
+        let (stream, mut remote) = io::duplex(1024);
+        let (reader, writer) = io::split(stream);
+
+        let permit = Arc::new(Semaphore::new(1)).try_acquire_owned().unwrap();
+
+        let timeout = Duration::from_millis(100);
+
+        let mut mngr = InitialPacketManager::new(
+            reader,
+            writer,
+            PacketEncoder::new(),
+            PacketDecoder::new(),
+            timeout,
+            timeout,
+            permit,
+            Default::default(),
+        );
+
+        let mut enc = PacketEncoder::new();
+        enc.append_packet(&RawPacket {
+            id: VarInt(0),
+            data: &[7; 16384],
+        })
+        .unwrap();
+        let bytes = enc.take();
+
+        // Sending the whole packet takes far longer than the timeouts, but
+        // each piece arrives well within them.
+        let sender = tokio::spawn(async move {
+            for chunk in bytes.chunks(512) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                remote.write_all(chunk).await.unwrap();
+            }
+
+            remote
+        });
+
+        let pkt = mngr.recv_packet::<RawPacket>().await.unwrap();
+        assert_eq!(pkt.data, [7; 16384]);
+
+        // Nothing else is sent, so the connection is idle.
+        let _remote = sender.await.unwrap();
+
+        let err = mngr.recv_packet::<RawPacket>().await.unwrap_err();
+        assert!(err.is::<Elapsed>());
+    }
+
+    #[tokio::test]
+    async fn trickled_packets_time_out() {
+        let (stream, mut remote) = io::duplex(1024);
+        let (reader, writer) = io::split(stream);
+
+        let permit = Arc::new(Semaphore::new(1)).try_acquire_owned().unwrap();
+
+        let timeout = Duration::from_millis(100);
+
+        let mut mngr = InitialPacketManager::new(
+            reader,
+            writer,
+            PacketEncoder::new(),
+            PacketDecoder::new(),
+            timeout,
+            timeout,
+            permit,
+            Default::default(),
+        );
+
+        mngr.set_packet_timeout(Some(Duration::from_millis(300)));
+
+        // Every byte arrives well within the idle and read timeouts.
+        let sender = tokio::spawn(async move {
+            for _ in 0..100 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+
+                if remote.write_all(&[0x7f]).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let start = Instant::now();
+        let err = mngr.recv_packet::<RawPacket>().await.unwrap_err();
+        assert!(err.is::<Elapsed>());
+        assert!(start.elapsed() < Duration::from_millis(900));
+
+        drop(mngr);
+        sender.await.unwrap();
+    }
+}
@@ -0,0 +1,227 @@
+//! Signed tokens which vouch for a player's profile when they move between
+//! servers in the same network. See [`ConnectionMode::Transfer`].
+//!
+//! [`ConnectionMode::Transfer`]: crate::config::ConnectionMode::Transfer
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{ensure, Context};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+use valence_protocol::types::SignedPropertyOwned;
+use valence_protocol::{Decode, Encode, Username};
+
+use crate::client::Client;
+use crate::config::Config;
+
+/// The login plugin channel used to request a transfer token from the client.
+pub(super) const TRANSFER_TOKEN_CHANNEL: &str = "valence:transfer_token";
+
+/// How long a transfer token remains valid after it is issued.
+pub(super) const TRANSFER_TOKEN_TTL: Duration = Duration::from_secs(30);
+
+/// The length of the HMAC-SHA256 signature at the start of a token.
+const SIGNATURE_LEN: usize = 32;
+
+/// The length of the random nonce identifying a token.
+const NONCE_LEN: usize = 16;
+
+/// The player data vouched for by a transfer token.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TransferProfile {
+    /// The username of the player.
+    pub username: Username<String>,
+    /// The UUID of the player.
+    pub uuid: Uuid,
+    /// The player's profile properties, such as their textures.
+    pub properties: Vec<SignedPropertyOwned>,
+}
+
+impl<C: Config> From<&Client<C>> for TransferProfile {
+    fn from(client: &Client<C>) -> Self {
+        Self {
+            username: client.username().to_owned_username(),
+            uuid: client.uuid(),
+            properties: client
+                .textures()
+                .map(|t| SignedPropertyOwned {
+                    name: "textures".into(),
+                    value: t.payload().into(),
+                    signature: Some(t.signature().into()),
+                })
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+/// A transfer token with a valid signature which has not expired.
+#[derive(Debug)]
+pub(super) struct VerifiedToken {
+    pub profile: TransferProfile,
+    nonce: [u8; NONCE_LEN],
+    /// The expiry time in seconds since the Unix epoch.
+    expires: u64,
+}
+
+/// Creates a token vouching for `profile` which is only accepted by the server
+/// with the ID `target` and expires [`TRANSFER_TOKEN_TTL`] after `now`.
+///
+/// The token consists of an HMAC-SHA256 signature followed by the nonce, the
+/// expiry time, the target server ID, and the profile.
+pub(super) fn issue(
+    secret: &str,
+    target: &str,
+    profile: &TransferProfile,
+    nonce: [u8; NONCE_LEN],
+    now: SystemTime,
+) -> anyhow::Result<Vec<u8>> {
+    let expires = unix_secs(now + TRANSFER_TOKEN_TTL)?;
+
+    let mut payload = nonce.to_vec();
+    expires.encode(&mut payload)?;
+    target.encode(&mut payload)?;
+    profile.uuid.encode(&mut payload)?;
+    profile.username.encode(&mut payload)?;
+    profile.properties.encode(&mut payload)?;
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
+    mac.update(&payload);
+
+    let mut token = mac.finalize().into_bytes().to_vec();
+    token.extend_from_slice(&payload);
+
+    Ok(token)
+}
+
+/// Checks the signature, expiry, and target of a token created with [`issue`].
+/// `server_id` is the ID of the server the token is presented to.
+pub(super) fn verify(
+    secret: &str,
+    server_id: &str,
+    token: &[u8],
+    now: SystemTime,
+) -> anyhow::Result<VerifiedToken> {
+    ensure!(
+        token.len() >= SIGNATURE_LEN + NONCE_LEN,
+        "invalid transfer token length"
+    );
+
+    let (signature, payload) = token.split_at(SIGNATURE_LEN);
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
+    mac.update(payload);
+    mac.verify_slice(signature)
+        .context("invalid transfer token signature")?;
+
+    let (nonce, mut r) = payload.split_at(NONCE_LEN);
+
+    let expires = u64::decode(&mut r)?;
+    ensure!(unix_secs(now)? < expires, "transfer token has expired");
+
+    let target = <&str>::decode(&mut r)?;
+    ensure!(
+        target == server_id,
+        "transfer token was issued for another server"
+    );
+
+    let uuid = Uuid::decode(&mut r)?;
+    let username = Username::<&str>::decode(&mut r)?.to_owned_username();
+    let properties = Vec::<SignedPropertyOwned>::decode(&mut r)?;
+
+    ensure!(r.is_empty(), "trailing data in transfer token");
+
+    Ok(VerifiedToken {
+        profile: TransferProfile {
+            username,
+            uuid,
+            properties,
+        },
+        nonce: nonce.try_into().unwrap(),
+        expires,
+    })
+}
+
+/// The nonces of tokens which have been accepted and have not expired yet.
+/// Used to make sure each token is only accepted once.
+#[derive(Default)]
+pub(super) struct UsedTransferTokens {
+    used: Mutex<HashMap<[u8; NONCE_LEN], u64>>,
+}
+
+impl UsedTransferTokens {
+    /// Marks a verified token as used. Returns an error if it was already
+    /// used.
+    pub(super) fn mark_used(&self, token: &VerifiedToken, now: SystemTime) -> anyhow::Result<()> {
+        let now = unix_secs(now)?;
+        let mut used = self.used.lock().unwrap();
+
+        // Expired tokens are rejected regardless, so they can be forgotten.
+        used.retain(|_, &mut expires| now < expires);
+
+        ensure!(
+            used.insert(token.nonce, token.expires).is_none(),
+            "transfer token has already been used"
+        );
+
+        Ok(())
+    }
+}
+
+fn unix_secs(time: SystemTime) -> anyhow::Result<u64> {
+    Ok(time
+        .duration_since(UNIX_EPOCH)
+        .context("system time is before the Unix epoch")?
+        .as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "hunter2";
+    const SERVER_ID: &str = "lobby";
+
+    fn profile() -> TransferProfile {
+        TransferProfile {
+            username: Username::new("Steve".into()).unwrap(),
+            uuid: Uuid::from_u128(1),
+            properties: vec![SignedPropertyOwned {
+                name: "textures".into(),
+                value: "abc".into(),
+                signature: Some("def".into()),
+            }],
+        }
+    }
+
+    #[test]
+    fn token_round_trip() {
+        let now = SystemTime::now();
+        let token = issue(SECRET, SERVER_ID, &profile(), [7; NONCE_LEN], now).unwrap();
+
+        let verified = verify(SECRET, SERVER_ID, &token, now).unwrap();
+        assert_eq!(verified.profile, profile());
+
+        // Tokens are single use.
+        let used = UsedTransferTokens::default();
+        used.mark_used(&verified, now).unwrap();
+        assert!(used.mark_used(&verified, now).is_err());
+    }
+
+    #[test]
+    fn token_rejected() {
+        let now = SystemTime::now();
+        let token = issue(SECRET, SERVER_ID, &profile(), [7; NONCE_LEN], now).unwrap();
+
+        assert!(verify("hunter3", SERVER_ID, &token, now).is_err());
+        assert!(verify(SECRET, SERVER_ID, &token, now + TRANSFER_TOKEN_TTL).is_err());
+        assert!(verify(SECRET, "survival", &token, now).is_err());
+
+        let mut tampered = token;
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(verify(SECRET, SERVER_ID, &tampered, now).is_err());
+    }
+}
@@ -0,0 +1,358 @@
+//! A minimal DNS client for looking up the SRV records of Minecraft servers.
+
+use std::cmp::Reverse;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use anyhow::{bail, ensure, Context};
+use tracing::debug;
+
+/// How long to wait for each nameserver to answer.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+/// The largest DNS message sent over UDP without EDNS.
+const MAX_MESSAGE_LEN: usize = 512;
+/// The maximum number of compression pointers followed while reading a name.
+const MAX_POINTERS: usize = 16;
+
+const TYPE_SRV: u16 = 33;
+const CLASS_IN: u16 = 1;
+
+/// A DNS SRV record.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct SrvRecord {
+    priority: u16,
+    weight: u16,
+    port: u16,
+    target: String,
+}
+
+/// Looks up the `_minecraft._tcp` SRV record of `host` with the nameservers
+/// in `/etc/resolv.conf`. Returns the target host and port of the preferred
+/// record, or `None` if there is no record or the lookup failed.
+pub(crate) fn lookup_minecraft_srv(host: &str) -> Option<(String, u16)> {
+    let name = format!("_minecraft._tcp.{host}");
+
+    for nameserver in system_nameservers() {
+        match query_srv(nameserver, &name, QUERY_TIMEOUT) {
+            Ok(records) => {
+                return preferred_record(records).map(|r| (r.target, r.port));
+            }
+            Err(e) => debug!("SRV lookup of \"{name}\" with {nameserver} failed: {e:#}"),
+        }
+    }
+
+    None
+}
+
+/// Returns the nameservers listed in `/etc/resolv.conf`. This is empty on
+/// systems without the file.
+fn system_nameservers() -> Vec<SocketAddr> {
+    let Ok(conf) = std::fs::read_to_string("/etc/resolv.conf") else {
+        return vec![];
+    };
+
+    conf.lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .filter_map(|ip| ip.trim().parse::<IpAddr>().ok())
+        .map(|ip| SocketAddr::new(ip, 53))
+        .collect()
+}
+
+/// Picks the record with the lowest priority, preferring the highest weight
+/// among equal priorities. A target of `.` means the service is not
+/// available.
+fn preferred_record(records: Vec<SrvRecord>) -> Option<SrvRecord> {
+    records
+        .into_iter()
+        .min_by_key(|r| (r.priority, Reverse(r.weight)))
+        .filter(|r| !r.target.is_empty())
+}
+
+/// Sends an SRV query for `name` to `nameserver` over UDP and returns the SRV
+/// records in the answer.
+fn query_srv(
+    nameserver: SocketAddr,
+    name: &str,
+    timeout: Duration,
+) -> anyhow::Result<Vec<SrvRecord>> {
+    let local: IpAddr = match nameserver {
+        SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+
+    let socket = UdpSocket::bind((local, 0))?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket.connect(nameserver)?;
+
+    let id = rand::random();
+    socket.send(&encode_query(id, name)?)?;
+
+    let mut buf = [0; MAX_MESSAGE_LEN];
+    let len = socket.recv(&mut buf)?;
+
+    parse_response(id, &buf[..len])
+}
+
+fn encode_query(id: u16, name: &str) -> anyhow::Result<Vec<u8>> {
+    let mut msg = Vec::with_capacity(MAX_MESSAGE_LEN);
+
+    msg.extend_from_slice(&id.to_be_bytes());
+    // Flags with recursion desired.
+    msg.extend_from_slice(&0x0100_u16.to_be_bytes());
+    // One question and no other records.
+    msg.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+
+    for label in name.trim_end_matches('.').split('.') {
+        ensure!(
+            (1..=63).contains(&label.len()),
+            "invalid label \"{label}\" in domain name \"{name}\""
+        );
+        msg.push(label.len() as u8);
+        msg.extend_from_slice(label.as_bytes());
+    }
+    msg.push(0);
+
+    ensure!(msg.len() - 12 <= 255, "domain name \"{name}\" is too long");
+
+    msg.extend_from_slice(&TYPE_SRV.to_be_bytes());
+    msg.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+    Ok(msg)
+}
+
+fn parse_response(id: u16, msg: &[u8]) -> anyhow::Result<Vec<SrvRecord>> {
+    let mut r = Reader { msg, pos: 0 };
+
+    ensure!(r.u16()? == id, "mismatched transaction ID");
+
+    let flags = r.u16()?;
+    ensure!(flags & 0x8000 != 0, "message is not a response");
+
+    match flags & 0xf {
+        0 => {}
+        // The name does not exist.
+        3 => return Ok(vec![]),
+        code => bail!("nameserver responded with error code {code}"),
+    }
+
+    let question_count = r.u16()?;
+    let answer_count = r.u16()?;
+    r.skip(4)?;
+
+    for _ in 0..question_count {
+        r.name()?;
+        r.skip(4)?;
+    }
+
+    let mut records = vec![];
+
+    for _ in 0..answer_count {
+        r.name()?;
+        let ty = r.u16()?;
+        let class = r.u16()?;
+        r.skip(4)?;
+        let data_len = r.u16()? as usize;
+        let data_end = r.pos + data_len;
+
+        ensure!(data_end <= msg.len(), "truncated resource record");
+
+        if ty == TYPE_SRV && class == CLASS_IN {
+            records.push(SrvRecord {
+                priority: r.u16()?,
+                weight: r.u16()?,
+                port: r.u16()?,
+                target: r.name()?,
+            });
+        }
+
+        r.pos = data_end;
+    }
+
+    Ok(records)
+}
+
+struct Reader<'a> {
+    msg: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn skip(&mut self, n: usize) -> anyhow::Result<()> {
+        ensure!(self.pos + n <= self.msg.len(), "unexpected end of message");
+        self.pos += n;
+        Ok(())
+    }
+
+    fn u16(&mut self) -> anyhow::Result<u16> {
+        let bytes = self
+            .msg
+            .get(self.pos..self.pos + 2)
+            .context("unexpected end of message")?;
+        self.pos += 2;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Reads a domain name without the trailing dot, following compression
+    /// pointers.
+    fn name(&mut self) -> anyhow::Result<String> {
+        let mut name = String::new();
+        let mut pos = self.pos;
+        let mut end = None;
+        let mut pointers = 0;
+
+        loop {
+            let len = *self.msg.get(pos).context("truncated domain name")? as usize;
+
+            if len == 0 {
+                pos += 1;
+                break;
+            }
+
+            if len & 0xc0 == 0xc0 {
+                let low = *self.msg.get(pos + 1).context("truncated domain name")? as usize;
+
+                pointers += 1;
+                ensure!(pointers <= MAX_POINTERS, "too many compression pointers");
+
+                end.get_or_insert(pos + 2);
+                pos = (len & 0x3f) << 8 | low;
+                continue;
+            }
+
+            ensure!(len & 0xc0 == 0, "invalid label length");
+
+            let label = self
+                .msg
+                .get(pos + 1..pos + 1 + len)
+                .context("truncated domain name")?;
+
+            if !name.is_empty() {
+                name.push('.');
+            }
+            name.push_str(std::str::from_utf8(label).context("domain name is not UTF-8")?);
+
+            pos += 1 + len;
+        }
+
+        self.pos = end.unwrap_or(pos);
+        Ok(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    const NAME: &str = "_minecraft._tcp.example.com";
+
+    fn record(priority: u16, weight: u16, port: u16, target: &str) -> SrvRecord {
+        SrvRecord {
+            priority,
+            weight,
+            port,
+            target: target.into(),
+        }
+    }
+
+    /// Builds the answer to `query` containing `records`. Record owner names
+    /// point back to the question to exercise name compression.
+    fn build_response(query: &[u8], rcode: u8, records: &[SrvRecord]) -> Vec<u8> {
+        let mut msg = query.to_vec();
+
+        msg[2] |= 0x80;
+        msg[3] = 0x80 | rcode;
+        msg[6..8].copy_from_slice(&(records.len() as u16).to_be_bytes());
+
+        for r in records {
+            msg.extend_from_slice(&[0xc0, 12]);
+            msg.extend_from_slice(&TYPE_SRV.to_be_bytes());
+            msg.extend_from_slice(&CLASS_IN.to_be_bytes());
+            msg.extend_from_slice(&300_u32.to_be_bytes());
+
+            let mut data = vec![];
+            data.extend_from_slice(&r.priority.to_be_bytes());
+            data.extend_from_slice(&r.weight.to_be_bytes());
+            data.extend_from_slice(&r.port.to_be_bytes());
+            for label in r.target.split('.').filter(|l| !l.is_empty()) {
+                data.push(label.len() as u8);
+                data.extend_from_slice(label.as_bytes());
+            }
+            data.push(0);
+
+            msg.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            msg.extend_from_slice(&data);
+        }
+
+        msg
+    }
+
+    #[test]
+    fn query_local_nameserver() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let nameserver = server.local_addr().unwrap();
+
+        let handle = thread::spawn(move || {
+            let mut buf = [0; MAX_MESSAGE_LEN];
+            let (len, client) = server.recv_from(&mut buf).unwrap();
+
+            let id = u16::from_be_bytes([buf[0], buf[1]]);
+            assert_eq!(&buf[..len], encode_query(id, NAME).unwrap());
+
+            let records = [
+                record(10, 5, 25567, "backup.example.com"),
+                record(0, 1, 25566, "light.example.com"),
+                record(0, 10, 25565, "mc.example.com"),
+            ];
+            let response = build_response(&buf[..len], 0, &records);
+            server.send_to(&response, client).unwrap();
+        });
+
+        let records = query_srv(nameserver, NAME, Duration::from_secs(5)).unwrap();
+        handle.join().unwrap();
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(
+            preferred_record(records),
+            Some(record(0, 10, 25565, "mc.example.com"))
+        );
+    }
+
+    #[test]
+    fn parse_responses() {
+        let query = encode_query(1234, NAME).unwrap();
+
+        let nxdomain = build_response(&query, 3, &[]);
+        assert_eq!(parse_response(1234, &nxdomain).unwrap(), vec![]);
+
+        let refused = build_response(&query, 5, &[]);
+        assert!(parse_response(1234, &refused).is_err());
+
+        let ok = build_response(&query, 0, &[record(0, 0, 25565, "mc.example.com")]);
+        assert!(parse_response(4321, &ok).is_err());
+        assert!(parse_response(1234, &ok[..ok.len() - 1]).is_err());
+
+        // A target of "." means the service is unavailable.
+        let unavailable = build_response(&query, 0, &[record(0, 0, 0, ".")]);
+        let records = parse_response(1234, &unavailable).unwrap();
+        assert_eq!(records, vec![record(0, 0, 0, "")]);
+        assert_eq!(preferred_record(records), None);
+
+        // Compression pointers that loop are rejected.
+        let mut looping = query.clone();
+        looping[2] |= 0x80;
+        looping[5] = 0;
+        looping[7] = 1;
+        looping.extend_from_slice(&[0xc0, (looping.len() as u8)]);
+        assert!(parse_response(1234, &looping).is_err());
+    }
+
+    #[test]
+    fn invalid_names_rejected() {
+        assert!(encode_query(0, "example..com").is_err());
+        assert!(encode_query(0, &format!("{}.com", "a".repeat(64))).is_err());
+        assert!(encode_query(0, &vec!["a".repeat(63); 5].join(".")).is_err());
+        assert!(encode_query(0, "example.com.").is_ok());
+    }
+}
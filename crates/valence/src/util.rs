@@ -1,9 +1,17 @@
 //! Miscellaneous utilities.
 
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+
+use anyhow::{bail, Context};
 use num::cast::AsPrimitive;
 use num::Float;
 use vek::{Aabb, Vec3};
 
+use crate::srv;
+
+/// The port used by Minecraft servers when none is specified.
+pub const DEFAULT_PORT: u16 = 25565;
+
 pub(crate) fn aabb_from_bottom_and_size<T>(bottom: Vec3<T>, size: Vec3<T>) -> Aabb<T>
 where
     T: Float + 'static,
@@ -60,6 +68,73 @@ pub(crate) const fn bit_width(n: usize) -> usize {
     (usize::BITS - n.leading_zeros()) as _
 }
 
+/// Resolves a server address such as `example.com`, `example.com:25566` or
+/// `127.0.0.1` to a socket address.
+///
+/// Like the vanilla client, the `_minecraft._tcp` SRV record of the hostname
+/// is used if the address doesn't contain a port. SRV records are looked up
+/// with the nameservers in `/etc/resolv.conf`, so they are not consulted on
+/// systems without that file. If there is no SRV record, [`DEFAULT_PORT`] is
+/// used.
+///
+/// This blocks the current thread while the hostname is resolved, so it is
+/// intended to be used once when constructing a [`Config`]. An error is
+/// returned if the hostname could not be resolved to any address.
+///
+/// [`Config`]: crate::config::Config
+pub fn resolve_address(addr: &str) -> anyhow::Result<SocketAddr> {
+    resolve_address_with(addr, srv::lookup_minecraft_srv)
+}
+
+fn resolve_address_with(
+    addr: &str,
+    lookup_srv: impl FnOnce(&str) -> Option<(String, u16)>,
+) -> anyhow::Result<SocketAddr> {
+    let (host, port) = split_host_and_port(addr)?;
+
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, port.unwrap_or(DEFAULT_PORT)));
+    }
+
+    let (host, port) = match port {
+        Some(port) => (host.to_owned(), port),
+        None => lookup_srv(host).unwrap_or_else(|| (host.to_owned(), DEFAULT_PORT)),
+    };
+
+    (host.trim_end_matches('.'), port)
+        .to_socket_addrs()
+        .with_context(|| format!("failed to resolve address \"{addr}\""))?
+        .next()
+        .with_context(|| format!("address \"{addr}\" did not resolve to anything"))
+}
+
+/// Splits an address into its host and optional port. IPv6 addresses must be
+/// surrounded by brackets when followed by a port.
+fn split_host_and_port(addr: &str) -> anyhow::Result<(&str, Option<u16>)> {
+    let parse_port = |port: &str| {
+        port.parse::<u16>()
+            .with_context(|| format!("invalid port in address \"{addr}\""))
+    };
+
+    if let Some(rest) = addr.strip_prefix('[') {
+        let (host, rest) = rest
+            .split_once(']')
+            .with_context(|| format!("unclosed '[' in address \"{addr}\""))?;
+
+        return match rest.strip_prefix(':') {
+            Some(port) => Ok((host, Some(parse_port(port)?))),
+            None if rest.is_empty() => Ok((host, None)),
+            None => bail!("unexpected characters after ']' in address \"{addr}\""),
+        };
+    }
+
+    match addr.rsplit_once(':') {
+        // More than one colon is an IPv6 address without a port.
+        Some((host, port)) if !host.contains(':') => Ok((host, Some(parse_port(port)?))),
+        _ => Ok((addr, None)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use approx::assert_relative_eq;
@@ -78,4 +153,56 @@ mod tests {
             assert_relative_eq!(d, d_new, epsilon = f64::EPSILON * 100.0);
         }
     }
+
+    #[test]
+    fn resolve_addresses() {
+        let no_srv = |host: &str| -> Option<(String, u16)> {
+            panic!("unexpected SRV lookup for \"{host}\"")
+        };
+
+        assert_eq!(
+            resolve_address_with("127.0.0.1", no_srv).unwrap(),
+            SocketAddr::from(([127, 0, 0, 1], DEFAULT_PORT))
+        );
+        assert_eq!(
+            resolve_address_with("[::1]:1234", no_srv).unwrap(),
+            "[::1]:1234".parse().unwrap()
+        );
+        assert_eq!(
+            resolve_address_with("::1", no_srv).unwrap(),
+            "[::1]:25565".parse().unwrap()
+        );
+        assert_eq!(
+            resolve_address_with("[::1]", no_srv).unwrap(),
+            "[::1]:25565".parse().unwrap()
+        );
+        assert_eq!(
+            resolve_address_with("localhost:1234", no_srv)
+                .unwrap()
+                .port(),
+            1234
+        );
+
+        assert!(resolve_address_with("localhost:99999", no_srv).is_err());
+        assert!(resolve_address_with("localhost:", no_srv).is_err());
+        assert!(resolve_address_with("[::1", no_srv).is_err());
+        assert!(resolve_address_with("[::1]1234", no_srv).is_err());
+    }
+
+    #[test]
+    fn resolve_address_with_srv() {
+        let addr = resolve_address_with("example.com", |host| {
+            assert_eq!(host, "example.com");
+            Some(("localhost.".into(), 25566))
+        })
+        .unwrap();
+
+        assert!(addr.ip().is_loopback());
+        assert_eq!(addr.port(), 25566);
+
+        let addr = resolve_address_with("localhost", |_| None).unwrap();
+
+        assert!(addr.ip().is_loopback());
+        assert_eq!(addr.port(), DEFAULT_PORT);
+    }
 }
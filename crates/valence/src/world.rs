@@ -2,14 +2,24 @@
 
 use std::iter::FusedIterator;
 use std::ops::{Deref, DerefMut, Index, IndexMut};
+use std::time::Duration;
 
 use rayon::iter::ParallelIterator;
+use sha2::{Digest, Sha256};
+use valence_protocol::packets::s2c::particle::{Particle, ParticleS2c};
+use valence_protocol::packets::s2c::play::{GameEvent, UpdateTime};
+use valence_protocol::types::GameEventKind;
+use vek::Vec3;
 
 use crate::chunk::Chunks;
 use crate::config::Config;
 use crate::dimension::DimensionId;
+use crate::packet::WritePacket;
+use crate::scoreboard::Scoreboard;
 use crate::server::SharedServer;
 use crate::slab_versioned::{Key, VersionedSlab};
+use crate::world_border::WorldBorder;
+use crate::{Ticks, STANDARD_TPS};
 
 /// A container for all [`World`]s on a [`Server`](crate::server::Server).
 pub struct Worlds<C: Config> {
@@ -42,12 +52,23 @@ impl<C: Config> Worlds<C> {
         }
     }
 
-    /// Creates a new world on the server with the provided dimension. A
-    /// reference to the world along with its ID is returned.
+    /// Creates a new world on the server with the provided dimension and a
+    /// seed of zero. A reference to the world along with its ID is returned.
     pub fn insert(
         &mut self,
         dimension: DimensionId,
         state: C::WorldState,
+    ) -> (WorldId, &mut World<C>) {
+        self.insert_with_seed(dimension, 0, state)
+    }
+
+    /// Like [`Self::insert`], but creates the world with the provided seed.
+    /// See [`World::seed`].
+    pub fn insert_with_seed(
+        &mut self,
+        dimension: DimensionId,
+        seed: i64,
+        state: C::WorldState,
     ) -> (WorldId, &mut World<C>) {
         let dim = self.shared.dimension(dimension);
 
@@ -59,18 +80,31 @@ impl<C: Config> Worlds<C> {
                 self.shared.biomes().len(),
                 self.shared.compression_threshold(),
             ),
+            scoreboard: Scoreboard::new(),
+            border: WorldBorder::new(),
             dimension,
+            seed,
+            hashed_seed: hash_seed(seed),
+            fixed_time: dim.fixed_time.is_some(),
+            world_age: 0,
+            time_of_day: 0,
+            daylight_cycle: true,
+            modified_time: false,
+            weather: Weather::default(),
+            particles: vec![],
+            chunk_retention: None,
             deleted: false,
         });
 
         (WorldId(id), world)
     }
 
-    /// Deletes a world from the server.
+    /// Deletes a world from the server. Worlds can be inserted and removed at
+    /// any point during the tick.
     ///
-    /// Note that any entities located in the world are not deleted.
-    /// Additionally, clients that are still in the deleted world at the end
-    /// of the tick are disconnected.
+    /// Note that any entities located in the world are not deleted. Clients
+    /// that are still in the removed world after [`Config::update`] are
+    /// relocated with [`Config::relocate_client`] or disconnected.
     pub fn remove(&mut self, world: WorldId) -> Option<C::WorldState> {
         self.slab.remove(world.0).map(|w| w.state)
     }
@@ -135,8 +169,15 @@ impl<C: Config> Worlds<C> {
     pub(crate) fn update(&mut self) {
         self.slab.retain(|_, world| !world.deleted);
 
+        let tick_duration = Duration::from_secs_f64((self.shared.tick_rate() as f64).recip());
+
         self.par_iter_mut().for_each(|(_, world)| {
             world.chunks.update();
+            world.scoreboard.update();
+            world.border.update(tick_duration);
+            world.update_time();
+            world.weather.update();
+            world.particles.clear();
         });
     }
 }
@@ -155,15 +196,73 @@ impl<C: Config> IndexMut<WorldId> for Worlds<C> {
     }
 }
 
+/// The distance in blocks from which clients receive particles spawned with
+/// [`World::spawn_particle`].
+pub const PARTICLE_RANGE: f64 = 32.0;
+
+/// The distance in blocks from which clients receive particles spawned with
+/// [`World::spawn_particle`] when `long_distance` is `true`.
+pub const LONG_DISTANCE_PARTICLE_RANGE: f64 = 512.0;
+
 /// A space for chunks, entities, and clients to occupy.
 pub struct World<C: Config> {
     /// Custom state.
     pub state: C::WorldState,
     pub chunks: Chunks<C>,
+    /// The scoreboard shown to clients in this world.
+    pub scoreboard: Scoreboard,
+    /// The world border shown to clients in this world. It is not enforced
+    /// by the server; see [`WorldBorder`].
+    pub border: WorldBorder,
     dimension: DimensionId,
+    seed: i64,
+    /// The seed as sent to clients. See [`hash_seed`].
+    hashed_seed: i64,
+    /// Whether the dimension of this world pins the time of day.
+    fixed_time: bool,
+    world_age: i64,
+    time_of_day: i64,
+    daylight_cycle: bool,
+    /// Whether the time of day was changed this tick.
+    modified_time: bool,
+    weather: Weather,
+    /// Particles spawned this tick.
+    particles: Vec<ParticleS2c>,
+    chunk_retention: Option<ChunkRetention>,
     deleted: bool,
 }
 
+/// A policy for unloading the chunks of a [`World`] which are out of view of
+/// every client. Set with [`World::set_chunk_retention`].
+///
+/// A chunk is retained while it is within the view distance of any client in
+/// the world plus [`grace_radius`](Self::grace_radius) chunks. Once a chunk has
+/// been out of that range for [`timeout`](Self::timeout) ticks, it is unloaded
+/// at the end of the tick.
+///
+/// [Unsaved](crate::chunk::LoadedChunk::is_unsaved) chunks, including
+/// generated chunks which were never modified, are passed to
+/// [`Config::save_chunk`] first and are only unloaded if saving succeeds. If
+/// saving fails, it is attempted again after another timeout.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ChunkRetention {
+    /// The number of chunks beyond each client's view distance which are kept
+    /// loaded.
+    pub grace_radius: u8,
+    /// The number of ticks a chunk must be out of range of every client before
+    /// it is unloaded.
+    pub timeout: Ticks,
+}
+
+impl Default for ChunkRetention {
+    fn default() -> Self {
+        Self {
+            grace_radius: 2,
+            timeout: 30 * STANDARD_TPS,
+        }
+    }
+}
+
 impl<C: Config> Deref for World<C> {
     type Target = C::WorldState;
 
@@ -184,17 +283,367 @@ impl<C: Config> World<C> {
         self.dimension
     }
 
+    /// Gets the seed the world was created with.
+    pub fn seed(&self) -> i64 {
+        self.seed
+    }
+
+    /// Gets the hashed form of the seed which is sent to clients in the join
+    /// and respawn packets.
+    pub(crate) fn hashed_seed(&self) -> i64 {
+        self.hashed_seed
+    }
+
     pub fn deleted(&self) -> bool {
         self.deleted
     }
 
+    /// Returns the number of chunks currently loaded in this world.
+    pub fn loaded_chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Returns the chunk retention policy of this world, if any.
+    pub fn chunk_retention(&self) -> Option<ChunkRetention> {
+        self.chunk_retention
+    }
+
+    /// Sets the policy used to unload chunks which no client can see. With
+    /// `None` (the default), chunks stay loaded until they are removed
+    /// manually.
+    pub fn set_chunk_retention(&mut self, retention: Option<ChunkRetention>) {
+        if self.chunk_retention.is_none() && retention.is_some() {
+            // Start the timeout of every chunk from the current tick.
+            for (_, chunk) in self.chunks.iter_mut() {
+                chunk.last_in_range = None;
+            }
+        }
+
+        self.chunk_retention = retention;
+    }
+
+    /// Returns the number of ticks that have elapsed since the world was
+    /// created.
+    pub fn world_age(&self) -> i64 {
+        self.world_age
+    }
+
+    /// Returns the time of day in ticks. The time of day is incremented every
+    /// tick unless the daylight cycle is disabled. The number of days that
+    /// have passed is `time_of_day / 24000`.
+    pub fn time_of_day(&self) -> i64 {
+        self.time_of_day
+    }
+
+    /// Sets the time of day in ticks for all clients in the world.
+    ///
+    /// This has no effect if the world's [`Dimension`] has a
+    /// [`fixed_time`](crate::dimension::Dimension::fixed_time).
+    ///
+    /// [`Dimension`]: crate::dimension::Dimension
+    pub fn set_time_of_day(&mut self, time_of_day: i64) {
+        if !self.fixed_time && self.time_of_day != time_of_day {
+            self.time_of_day = time_of_day;
+            self.modified_time = true;
+        }
+    }
+
+    /// Returns whether the time of day advances every tick.
+    pub fn daylight_cycle(&self) -> bool {
+        self.daylight_cycle
+    }
+
+    /// Sets whether the time of day advances every tick. When disabled, the
+    /// sun and moon stop moving for all clients in the world.
+    pub fn set_daylight_cycle(&mut self, enabled: bool) {
+        if self.daylight_cycle != enabled {
+            self.daylight_cycle = enabled;
+            self.modified_time = true;
+        }
+    }
+
+    fn update_time(&mut self) {
+        self.modified_time = false;
+
+        self.world_age += 1;
+
+        if self.daylight_cycle {
+            self.time_of_day += 1;
+        }
+    }
+
+    /// Returns the current rain level between `0.0` and `1.0`.
+    pub fn rain_level(&self) -> f32 {
+        self.weather.rain_level
+    }
+
+    /// Returns the current thunder level between `0.0` and `1.0`.
+    pub fn thunder_level(&self) -> f32 {
+        self.weather.thunder_level
+    }
+
+    /// Immediately sets the rain and thunder levels for all clients in the
+    /// world. A rain level of zero stops the rain. Levels are clamped between
+    /// `0.0` and `1.0`.
+    ///
+    /// Thunder is only visible while it is raining.
+    pub fn set_weather(&mut self, rain_level: f32, thunder_level: f32) {
+        self.set_weather_over(rain_level, thunder_level, 0);
+    }
+
+    /// Like [`Self::set_weather`], but the rain and thunder levels change
+    /// smoothly from their current values over the given number of ticks.
+    pub fn set_weather_over(&mut self, rain_level: f32, thunder_level: f32, ticks: Ticks) {
+        let weather = &mut self.weather;
+
+        weather.target_rain_level = rain_level.clamp(0.0, 1.0);
+        weather.target_thunder_level = thunder_level.clamp(0.0, 1.0);
+        weather.remaining_ticks = ticks.max(0);
+
+        if weather.remaining_ticks == 0 {
+            weather.step();
+        }
+    }
+
+    /// Writes the time packet for clients entering this world.
+    pub(crate) fn write_time_init_packets(
+        &self,
+        mut writer: impl WritePacket,
+    ) -> anyhow::Result<()> {
+        if !self.fixed_time {
+            writer.write_packet(&self.time_packet())?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the weather packets for clients entering this world.
+    pub(crate) fn write_weather_init_packets(
+        &self,
+        mut writer: impl WritePacket,
+    ) -> anyhow::Result<()> {
+        writer.write_packet(&GameEvent {
+            kind: if self.weather.rain_level > 0.0 {
+                GameEventKind::BeginRaining
+            } else {
+                GameEventKind::EndRaining
+            },
+            value: 0.0,
+        })?;
+
+        self.weather.write_level_packets(&mut writer)
+    }
+
+    /// Writes the time packet if the time was changed this tick.
+    pub(crate) fn write_time_update_packets(
+        &self,
+        mut writer: impl WritePacket,
+    ) -> anyhow::Result<()> {
+        if self.modified_time {
+            writer.write_packet(&self.time_packet())?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the weather packets for changes made this tick.
+    pub(crate) fn write_weather_update_packets(
+        &self,
+        mut writer: impl WritePacket,
+    ) -> anyhow::Result<()> {
+        let weather = &self.weather;
+
+        if weather.modified {
+            if weather.old_rain_level == 0.0 && weather.rain_level > 0.0 {
+                writer.write_packet(&GameEvent {
+                    kind: GameEventKind::BeginRaining,
+                    value: 0.0,
+                })?;
+            } else if weather.old_rain_level > 0.0 && weather.rain_level == 0.0 {
+                writer.write_packet(&GameEvent {
+                    kind: GameEventKind::EndRaining,
+                    value: 0.0,
+                })?;
+            }
+
+            weather.write_level_packets(&mut writer)?;
+        }
+
+        Ok(())
+    }
+
+    fn time_packet(&self) -> UpdateTime {
+        UpdateTime {
+            world_age: self.world_age,
+            // A negative time of day stops the daylight cycle on the client.
+            time_of_day: if self.daylight_cycle {
+                self.time_of_day
+            } else {
+                -self.time_of_day.max(1)
+            },
+        }
+    }
+
+    /// Spawns particles in this world at the end of the tick. The particles
+    /// are sent to every client in the world within [`PARTICLE_RANGE`] blocks
+    /// of `position`, or [`LONG_DISTANCE_PARTICLE_RANGE`] blocks if
+    /// `long_distance` is `true`.
+    ///
+    /// `count` particles are spawned with positions randomly offset from
+    /// `position` by up to `offset` on each axis. If `count` is zero, a single
+    /// particle is spawned with `offset` used as its velocity (or color for
+    /// some particles) instead.
+    pub fn spawn_particle(
+        &mut self,
+        particle: Particle,
+        position: impl Into<Vec3<f64>>,
+        offset: impl Into<Vec3<f32>>,
+        speed: f32,
+        count: i32,
+        long_distance: bool,
+    ) {
+        self.particles.push(ParticleS2c {
+            particle,
+            long_distance,
+            position: position.into().into_array(),
+            offset: offset.into().into_array(),
+            max_speed: speed,
+            count,
+        });
+    }
+
+    /// Writes the particles spawned this tick which are in range of a client
+    /// at `client_pos`.
+    pub(crate) fn write_particle_packets(
+        &self,
+        client_pos: Vec3<f64>,
+        mut writer: impl WritePacket,
+    ) -> anyhow::Result<()> {
+        for pkt in &self.particles {
+            let range = if pkt.long_distance {
+                LONG_DISTANCE_PARTICLE_RANGE
+            } else {
+                PARTICLE_RANGE
+            };
+
+            if client_pos.distance_squared(Vec3::from(pkt.position)) <= range * range {
+                writer.write_packet(pkt)?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Whether or not this world should be marked as deleted. Deleted worlds
     /// are removed from the server at the end of the tick.
     ///
     /// Note that any entities located in the world are not deleted and their
-    /// location will not change. Additionally, clients that are still in
-    /// the deleted world at the end of the tick are disconnected.
+    /// location will not change. Clients that are still in the deleted world
+    /// after [`Config::update`] are relocated with
+    /// [`Config::relocate_client`] or disconnected.
     pub fn set_deleted(&mut self, deleted: bool) {
         self.deleted = deleted;
     }
 }
+
+/// The rain and thunder levels of a world.
+#[derive(Default)]
+struct Weather {
+    rain_level: f32,
+    thunder_level: f32,
+    /// The rain level as of the previous tick, used to detect the start and
+    /// end of rain.
+    old_rain_level: f32,
+    target_rain_level: f32,
+    target_thunder_level: f32,
+    /// The number of ticks until the target levels are reached.
+    remaining_ticks: Ticks,
+    /// Whether the levels changed this tick.
+    modified: bool,
+}
+
+impl Weather {
+    /// Moves the levels one tick closer to their targets.
+    fn step(&mut self) {
+        let (rain, thunder) = if self.remaining_ticks <= 1 {
+            (self.target_rain_level, self.target_thunder_level)
+        } else {
+            let t = (self.remaining_ticks as f32).recip();
+            (
+                self.rain_level + (self.target_rain_level - self.rain_level) * t,
+                self.thunder_level + (self.target_thunder_level - self.thunder_level) * t,
+            )
+        };
+
+        self.remaining_ticks = (self.remaining_ticks - 1).max(0);
+
+        if rain != self.rain_level || thunder != self.thunder_level {
+            self.rain_level = rain;
+            self.thunder_level = thunder;
+            self.modified = true;
+        }
+    }
+
+    fn update(&mut self) {
+        self.old_rain_level = self.rain_level;
+        self.modified = false;
+
+        if self.remaining_ticks > 0 {
+            self.step();
+        }
+    }
+
+    fn write_level_packets(&self, mut writer: impl WritePacket) -> anyhow::Result<()> {
+        writer.write_packet(&GameEvent {
+            kind: GameEventKind::RainLevelChange,
+            value: self.rain_level,
+        })?;
+
+        writer.write_packet(&GameEvent {
+            kind: GameEventKind::ThunderLevelChange,
+            value: self.thunder_level,
+        })
+    }
+}
+
+/// Hashes a world seed the way the vanilla server does before sending it to
+/// clients. The first eight bytes of the SHA-256 digest of the seed are used so
+/// that clients can't recover the real seed, but can still use it for biome
+/// noise.
+fn hash_seed(seed: i64) -> i64 {
+    let digest = Sha256::digest(seed.to_le_bytes());
+    i64::from_le_bytes(digest[..8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashed_seed_matches_vanilla() {
+        assert_eq!(hash_seed(0), 8794265229978523055);
+        assert_eq!(hash_seed(12345), 293737985876514017);
+        assert_eq!(hash_seed(-4172144997902289642), 2159143436479834350);
+    }
+
+    #[test]
+    fn weather_transition() {
+        let mut weather = Weather {
+            target_rain_level: 1.0,
+            remaining_ticks: 4,
+            ..Default::default()
+        };
+
+        for expected in [0.25, 0.5, 0.75, 1.0] {
+            weather.update();
+            assert!(weather.modified);
+            assert_eq!(weather.rain_level, expected);
+        }
+
+        assert_eq!(weather.old_rain_level, 0.75);
+
+        weather.update();
+        assert!(!weather.modified);
+        assert_eq!(weather.remaining_ticks, 0);
+    }
+}
@@ -0,0 +1,336 @@
+//! The border around a world.
+
+use std::time::Duration;
+
+use valence_protocol::packets::s2c::play::{
+    SetBorderCenter, SetBorderLerpSize, SetBorderSize, SetBorderWarningDelay,
+    SetBorderWarningDistance, WorldBorderInitialize,
+};
+use valence_protocol::{VarInt, VarLong};
+use vek::Vec3;
+
+use crate::packet::WritePacket;
+
+/// The diameter of the world border in vanilla Minecraft when it has not been
+/// changed.
+pub const DEFAULT_DIAMETER: f64 = 59_999_968.0;
+
+/// The world border of a [`World`](crate::world::World), visible to all
+/// clients in the world.
+///
+/// The border is a square centered on the XZ plane. Changes to the border are
+/// sent to clients at the end of the tick, and clients joining the world
+/// receive the current state of the border, including any size transition in
+/// progress.
+///
+/// The border is only enforced by clients, which refuse to move past it on
+/// their own. Valence does not push back or damage players that end up outside
+/// of it, e.g. after a teleport or when the border shrinks. Enforcing the
+/// border is left to the user: check [`Self::contains`] and teleport clients
+/// back, or use [`Self::damage_at`] to deal vanilla's damage with
+/// [`Client::set_health`].
+///
+/// [`Client::set_health`]: crate::client::Client::set_health
+pub struct WorldBorder {
+    center: [f64; 2],
+    old_diameter: f64,
+    new_diameter: f64,
+    lerp_duration: Duration,
+    lerp_elapsed: Duration,
+    warning_blocks: i32,
+    warning_time: i32,
+    damage_safe_zone: f64,
+    damage_per_block: f64,
+    modified_center: bool,
+    modified_diameter: bool,
+    modified_warning_blocks: bool,
+    modified_warning_time: bool,
+}
+
+impl WorldBorder {
+    pub(crate) fn new() -> Self {
+        Self {
+            center: [0.0; 2],
+            old_diameter: DEFAULT_DIAMETER,
+            new_diameter: DEFAULT_DIAMETER,
+            lerp_duration: Duration::ZERO,
+            lerp_elapsed: Duration::ZERO,
+            warning_blocks: 5,
+            warning_time: 15,
+            damage_safe_zone: 5.0,
+            damage_per_block: 0.2,
+            modified_center: false,
+            modified_diameter: false,
+            modified_warning_blocks: false,
+            modified_warning_time: false,
+        }
+    }
+
+    /// Returns the X and Z coordinates of the center of the border.
+    pub fn center(&self) -> [f64; 2] {
+        self.center
+    }
+
+    /// Sets the X and Z coordinates of the center of the border.
+    pub fn set_center(&mut self, x: f64, z: f64) {
+        if self.center != [x, z] {
+            self.center = [x, z];
+            self.modified_center = true;
+        }
+    }
+
+    /// Returns the current diameter of the border. If the border is in the
+    /// middle of a transition started by [`Self::lerp_diameter`], the
+    /// interpolated diameter is returned.
+    pub fn diameter(&self) -> f64 {
+        if self.lerp_elapsed >= self.lerp_duration {
+            return self.new_diameter;
+        }
+
+        let t = self.lerp_elapsed.as_secs_f64() / self.lerp_duration.as_secs_f64();
+        self.old_diameter + (self.new_diameter - self.old_diameter) * t
+    }
+
+    /// Returns the diameter the border is transitioning to, or the current
+    /// diameter if no transition is in progress.
+    pub fn target_diameter(&self) -> f64 {
+        self.new_diameter
+    }
+
+    /// Returns the time remaining until the border reaches
+    /// [`Self::target_diameter`].
+    pub fn lerp_remaining(&self) -> Duration {
+        self.lerp_duration.saturating_sub(self.lerp_elapsed)
+    }
+
+    /// Immediately sets the diameter of the border, cancelling any transition
+    /// in progress.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `diameter` is negative or not finite.
+    pub fn set_diameter(&mut self, diameter: f64) {
+        self.lerp_diameter(diameter, Duration::ZERO);
+    }
+
+    /// Smoothly grows or shrinks the border from its current diameter to
+    /// `diameter` over the course of `duration`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `diameter` is negative or not finite.
+    pub fn lerp_diameter(&mut self, diameter: f64, duration: Duration) {
+        assert!(
+            diameter.is_finite() && diameter >= 0.0,
+            "invalid world border diameter of {diameter}"
+        );
+
+        self.old_diameter = self.diameter();
+        self.new_diameter = diameter;
+        self.lerp_duration = duration;
+        self.lerp_elapsed = Duration::ZERO;
+        self.modified_diameter = true;
+    }
+
+    /// Returns the distance in blocks from the border at which clients start
+    /// displaying the warning effect.
+    pub fn warning_blocks(&self) -> i32 {
+        self.warning_blocks
+    }
+
+    /// Sets the distance in blocks from the border at which clients start
+    /// displaying the warning effect.
+    pub fn set_warning_blocks(&mut self, blocks: i32) {
+        if self.warning_blocks != blocks {
+            self.warning_blocks = blocks;
+            self.modified_warning_blocks = true;
+        }
+    }
+
+    /// Returns the warning time in seconds. Clients display the warning effect
+    /// when a shrinking border will reach them within this time.
+    pub fn warning_time(&self) -> i32 {
+        self.warning_time
+    }
+
+    /// Sets the warning time in seconds. Clients display the warning effect
+    /// when a shrinking border will reach them within this time.
+    pub fn set_warning_time(&mut self, seconds: i32) {
+        if self.warning_time != seconds {
+            self.warning_time = seconds;
+            self.modified_warning_time = true;
+        }
+    }
+
+    /// Returns the distance in blocks past the border that players can be
+    /// before taking damage from [`Self::damage_at`].
+    pub fn damage_safe_zone(&self) -> f64 {
+        self.damage_safe_zone
+    }
+
+    /// Sets the distance in blocks past the border that players can be before
+    /// taking damage from [`Self::damage_at`]. The default is `5.0`.
+    pub fn set_damage_safe_zone(&mut self, blocks: f64) {
+        self.damage_safe_zone = blocks;
+    }
+
+    /// Returns the damage dealt by [`Self::damage_at`] per block past the safe
+    /// zone.
+    pub fn damage_per_block(&self) -> f64 {
+        self.damage_per_block
+    }
+
+    /// Sets the damage dealt by [`Self::damage_at`] per block past the safe
+    /// zone. The default is `0.2`. A value of zero disables damage.
+    pub fn set_damage_per_block(&mut self, damage: f64) {
+        self.damage_per_block = damage;
+    }
+
+    /// Returns the distance from `position` to the nearest edge of the
+    /// border. The distance is positive inside the border and negative
+    /// outside of it.
+    pub fn distance_to_border(&self, position: impl Into<Vec3<f64>>) -> f64 {
+        let pos = position.into();
+        let radius = self.diameter() / 2.0;
+
+        let dx = (pos.x - self.center[0]).abs();
+        let dz = (pos.z - self.center[1]).abs();
+
+        radius - dx.max(dz)
+    }
+
+    /// Returns whether `position` is inside of the border.
+    pub fn contains(&self, position: impl Into<Vec3<f64>>) -> bool {
+        self.distance_to_border(position) >= 0.0
+    }
+
+    /// Returns the damage vanilla Minecraft deals to a player at `position`
+    /// each time they are hurt by the border, or `None` if the position is
+    /// inside the border or its safe zone.
+    ///
+    /// Valence never applies this damage on its own. Vanilla hurts players
+    /// outside of the border every half second, which can be done by
+    /// subtracting the damage from [`Client::health`] and passing the result
+    /// to [`Client::set_health`].
+    ///
+    /// [`Client::health`]: crate::client::Client::health
+    /// [`Client::set_health`]: crate::client::Client::set_health
+    pub fn damage_at(&self, position: impl Into<Vec3<f64>>) -> Option<f32> {
+        let past_safe_zone = -(self.distance_to_border(position) + self.damage_safe_zone);
+
+        if past_safe_zone > 0.0 && self.damage_per_block > 0.0 {
+            Some((past_safe_zone * self.damage_per_block).floor().max(1.0) as f32)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn write_init_packets(&self, mut writer: impl WritePacket) -> anyhow::Result<()> {
+        writer.write_packet(&WorldBorderInitialize {
+            x: self.center[0],
+            z: self.center[1],
+            old_diameter: self.diameter(),
+            new_diameter: self.new_diameter,
+            speed: VarLong(self.lerp_remaining().as_millis() as i64),
+            portal_teleport_boundary: VarInt(DEFAULT_DIAMETER as i32 / 2),
+            warning_blocks: VarInt(self.warning_blocks),
+            warning_time: VarInt(self.warning_time),
+        })
+    }
+
+    pub(crate) fn write_update_packets(&self, mut writer: impl WritePacket) -> anyhow::Result<()> {
+        if self.modified_center {
+            writer.write_packet(&SetBorderCenter {
+                x: self.center[0],
+                z: self.center[1],
+            })?;
+        }
+
+        if self.modified_diameter {
+            let remaining = self.lerp_remaining();
+
+            if remaining.is_zero() {
+                writer.write_packet(&SetBorderSize {
+                    diameter: self.new_diameter,
+                })?;
+            } else {
+                writer.write_packet(&SetBorderLerpSize {
+                    old_diameter: self.diameter(),
+                    new_diameter: self.new_diameter,
+                    speed: VarLong(remaining.as_millis() as i64),
+                })?;
+            }
+        }
+
+        if self.modified_warning_blocks {
+            writer.write_packet(&SetBorderWarningDistance {
+                warning_blocks: VarInt(self.warning_blocks),
+            })?;
+        }
+
+        if self.modified_warning_time {
+            writer.write_packet(&SetBorderWarningDelay {
+                warning_time: VarInt(self.warning_time),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Advances any transition in progress by `elapsed` and clears the
+    /// modifications made this tick.
+    pub(crate) fn update(&mut self, elapsed: Duration) {
+        self.lerp_elapsed = (self.lerp_elapsed + elapsed).min(self.lerp_duration);
+
+        self.modified_center = false;
+        self.modified_diameter = false;
+        self.modified_warning_blocks = false;
+        self.modified_warning_time = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lerp_diameter() {
+        let mut border = WorldBorder::new();
+        border.set_diameter(100.0);
+        border.update(Duration::from_millis(50));
+
+        border.lerp_diameter(50.0, Duration::from_secs(10));
+        assert_eq!(border.diameter(), 100.0);
+
+        border.update(Duration::from_secs(5));
+        assert_eq!(border.diameter(), 75.0);
+        assert_eq!(border.lerp_remaining(), Duration::from_secs(5));
+
+        // Retargeting starts from the interpolated diameter.
+        border.lerp_diameter(25.0, Duration::from_secs(2));
+        border.update(Duration::from_secs(1));
+        assert_eq!(border.diameter(), 50.0);
+
+        border.update(Duration::from_secs(60));
+        assert_eq!(border.diameter(), 25.0);
+        assert!(border.lerp_remaining().is_zero());
+    }
+
+    #[test]
+    fn damage_outside_border() {
+        let mut border = WorldBorder::new();
+        border.set_center(10.0, 0.0);
+        border.set_diameter(20.0);
+
+        assert!(border.contains([20.0, 0.0, 10.0]));
+        assert!(!border.contains([-0.5, 0.0, 0.0]));
+        assert_eq!(border.damage_at([-4.0, 0.0, 0.0]), None);
+
+        // One block past the safe zone deals the minimum damage.
+        assert_eq!(border.damage_at([10.0, 64.0, 16.0]), Some(1.0));
+        assert_eq!(border.damage_at([45.0, 64.0, 0.0]), Some(4.0));
+
+        border.set_damage_per_block(0.0);
+        assert_eq!(border.damage_at([45.0, 64.0, 0.0]), None);
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use num_integer::div_ceil;
+use valence::biome::BiomeId;
+use valence::chunk::Chunk;
+use valence::protocol::block::BlockState;
+use valence::protocol::Ident;
+use valence_nbt::{compound, Compound, List, Value};
+
+/// The data version of chunks written by Minecraft 1.19.3.
+const DATA_VERSION: i32 = 3218;
+
+/// Converts a Valence [`Chunk`] to an Anvil chunk in NBT form. This is the
+/// inverse of [`to_valence`]. Only block states and biomes are written.
+///
+/// # Arguments
+///
+/// - `chunk`: The Valence chunk to read from.
+/// - `chunk_x`, `chunk_z`: The position of the chunk in chunk coordinates.
+/// - `sect_offset`: The same offset that would be passed to [`to_valence`].
+///   Section `i` of `chunk` is written with a sector Y position of `i -
+///   sect_offset`.
+/// - `map_biome`: A function to map Valence [`BiomeId`]s to the biome resource
+///   identifiers written to the NBT data.
+///
+/// The returned compound is usually written to disk with [`write_chunk`].
+///
+/// [`to_valence`]: crate::to_valence
+/// [`write_chunk`]: crate::AnvilWorld::write_chunk
+pub fn from_valence<C, F>(
+    chunk: &C,
+    chunk_x: i32,
+    chunk_z: i32,
+    sect_offset: i32,
+    mut map_biome: F,
+) -> Compound
+where
+    C: Chunk,
+    F: FnMut(BiomeId) -> Ident<String>,
+{
+    let mut sections = vec![];
+
+    for sect_y in 0..chunk.section_count() {
+        let (block_palette, block_idxs) = build_palette(BLOCKS_PER_SECTION, |i| {
+            chunk.block_state(i % 16, sect_y * 16 + i / (16 * 16), i / 16 % 16)
+        });
+
+        let (biome_palette, biome_idxs) = build_palette(BIOMES_PER_SECTION, |i| {
+            chunk.biome(i % 4, sect_y * 4 + i / (4 * 4), i / 4 % 4)
+        });
+
+        let mut block_states = compound! {
+            "palette" => List::Compound(block_palette.into_iter().map(block_to_nbt).collect()),
+        };
+
+        if let Some(data) = pack_indices(&block_idxs, 4) {
+            block_states.insert("data", data);
+        }
+
+        let mut biomes = compound! {
+            "palette" => List::String(
+                biome_palette
+                    .into_iter()
+                    .map(|biome| map_biome(biome).into_inner())
+                    .collect(),
+            ),
+        };
+
+        if let Some(data) = pack_indices(&biome_idxs, 1) {
+            biomes.insert("data", data);
+        }
+
+        sections.push(compound! {
+            "Y" => (sect_y as i32 - sect_offset) as i8,
+            "block_states" => block_states,
+            "biomes" => biomes,
+        });
+    }
+
+    compound! {
+        "DataVersion" => DATA_VERSION,
+        "xPos" => chunk_x,
+        "zPos" => chunk_z,
+        "yPos" => -sect_offset,
+        "Status" => "full",
+        "sections" => List::Compound(sections),
+    }
+}
+
+/// Collects the distinct elements of a section in order of first appearance,
+/// along with the palette index of every element.
+fn build_palette<T: Copy + Eq + Hash>(
+    len: usize,
+    mut get: impl FnMut(usize) -> T,
+) -> (Vec<T>, Vec<u64>) {
+    let mut palette = vec![];
+    let mut lookup = HashMap::new();
+
+    let idxs = (0..len)
+        .map(|i| {
+            let elem = get(i);
+            *lookup.entry(elem).or_insert_with(|| {
+                palette.push(elem);
+                palette.len() as u64 - 1
+            })
+        })
+        .collect::<Vec<_>>();
+
+    (palette, idxs)
+}
+
+/// Packs palette indices into longs without letting indices span across longs.
+/// Returns `None` if the palette has a single element, in which case no data
+/// is stored.
+fn pack_indices(idxs: &[u64], min_bits_per_idx: usize) -> Option<Value> {
+    let max_idx = idxs.iter().copied().max().unwrap_or(0);
+
+    if max_idx == 0 {
+        return None;
+    }
+
+    let bits_per_idx = bit_width(max_idx as usize).max(min_bits_per_idx);
+    let idxs_per_long = 64 / bits_per_idx;
+
+    let mut data = Vec::with_capacity(div_ceil(idxs.len(), idxs_per_long));
+
+    for chunk in idxs.chunks(idxs_per_long) {
+        let mut long = 0_u64;
+
+        for (j, &idx) in chunk.iter().enumerate() {
+            long |= idx << (bits_per_idx * j);
+        }
+
+        data.push(long as i64);
+    }
+
+    Some(Value::LongArray(data))
+}
+
+fn block_to_nbt(state: BlockState) -> Compound {
+    let kind = state.to_kind();
+
+    let mut block = compound! {
+        "Name" => format!("minecraft:{}", kind.to_str()),
+    };
+
+    let props = kind.props();
+
+    if !props.is_empty() {
+        let mut properties = Compound::with_capacity(props.len());
+
+        for &name in props {
+            if let Some(value) = state.get(name) {
+                properties.insert(name.to_str(), value.to_str());
+            }
+        }
+
+        block.insert("Properties", properties);
+    }
+
+    block
+}
+
+const BLOCKS_PER_SECTION: usize = 16 * 16 * 16;
+const BIOMES_PER_SECTION: usize = 4 * 4 * 4;
+
+/// Returns the minimum number of bits needed to represent the integer `n`.
+const fn bit_width(n: usize) -> usize {
+    (usize::BITS - n.leading_zeros()) as _
+}
+
+#[cfg(test)]
+mod tests {
+    use valence::chunk::UnloadedChunk;
+    use valence::protocol::block::{BlockKind, PropName, PropValue};
+    use valence::protocol::ident;
+
+    use super::*;
+    use crate::{AnvilChunk, AnvilWorld};
+
+    #[test]
+    fn chunk_round_trip() {
+        let mut chunk = UnloadedChunk::new(24);
+
+        chunk.fill_block_states(2, BlockState::STONE);
+
+        let stairs = BlockKind::OakStairs
+            .to_state()
+            .set(PropName::Facing, PropValue::East);
+
+        chunk.set_block_state(1, 2, 3, stairs);
+        chunk.set_block_state(15, 40, 7, BlockState::GRASS_BLOCK);
+        chunk.set_block_state(0, 383, 0, BlockState::GLASS);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut world = AnvilWorld::new(dir.path());
+
+        let data = from_valence(&chunk, -1, 33, 4, |_| ident!("minecraft:plains"));
+
+        let anvil_chunk = AnvilChunk {
+            data,
+            timestamp: 42,
+        };
+        world.write_chunk(-1, 33, &anvil_chunk).unwrap();
+
+        // Overwrite the chunk with one that is larger to ensure that the chunk is
+        // relocated correctly.
+        for y in 0..384 {
+            chunk.set_block_state(y % 16, y, (y * 7) % 16, BlockState::OAK_LOG);
+        }
+
+        let data = from_valence(&chunk, -1, 33, 4, |_| ident!("minecraft:plains"));
+
+        let anvil_chunk = AnvilChunk {
+            data,
+            timestamp: 43,
+        };
+        world.write_chunk(-1, 33, &anvil_chunk).unwrap();
+
+        // Read the chunk with a new world so that the region file is reloaded.
+        let mut world = AnvilWorld::new(dir.path());
+
+        let anvil_chunk = world.read_chunk(-1, 33).unwrap().unwrap();
+        assert_eq!(anvil_chunk.timestamp, 43);
+        assert!(world.read_chunk(0, 33).unwrap().is_none());
+
+        let mut loaded = UnloadedChunk::new(24);
+        crate::to_valence(&anvil_chunk.data, &mut loaded, 4, |_| BiomeId::default()).unwrap();
+
+        for y in 0..384 {
+            for z in 0..16 {
+                for x in 0..16 {
+                    assert_eq!(loaded.block_state(x, y, z), chunk.block_state(x, y, z));
+                }
+            }
+        }
+    }
+}
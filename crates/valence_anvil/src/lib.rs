@@ -1,17 +1,24 @@
 use std::collections::btree_map::Entry;
 use std::collections::BTreeMap;
 use std::fs::File;
-use std::io;
-use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
 use std::path::PathBuf;
+use std::{fs, io};
 
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use flate2::bufread::{GzDecoder, ZlibDecoder};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+#[cfg(feature = "valence")]
+pub use from_valence::*;
+use num_integer::div_ceil;
 use thiserror::Error;
 #[cfg(feature = "valence")]
 pub use to_valence::*;
 use valence_nbt::Compound;
 
+#[cfg(feature = "valence")]
+mod from_valence;
 #[cfg(feature = "valence")]
 mod to_valence;
 
@@ -49,6 +56,17 @@ pub enum ReadChunkError {
     IncompleteNbtRead,
 }
 
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum WriteChunkError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Nbt(#[from] valence_nbt::Error),
+    #[error("compressed chunk data is too large")]
+    ChunkTooLarge,
+}
+
 #[derive(Debug)]
 struct Region {
     file: File,
@@ -58,6 +76,10 @@ struct Region {
 
 const SECTOR_SIZE: usize = 4096;
 
+/// The maximum number of sectors a single chunk can occupy. The sector count
+/// in the region header is a single byte.
+const MAX_CHUNK_SECTORS: usize = 255;
+
 impl AnvilWorld {
     pub fn new(world_root: impl Into<PathBuf>) -> Self {
         let mut region_root = world_root.into();
@@ -76,33 +98,12 @@ impl AnvilWorld {
         chunk_x: i32,
         chunk_z: i32,
     ) -> Result<Option<AnvilChunk>, ReadChunkError> {
-        let region_x = chunk_x.div_euclid(32);
-        let region_z = chunk_z.div_euclid(32);
-
-        let region = match self.regions.entry((region_x, region_z)) {
-            Entry::Vacant(ve) => {
-                // Load the region file if it exists. Otherwise, the chunk is considered absent.
-
-                let path = self
-                    .region_root
-                    .join(format!("r.{region_x}.{region_z}.mca"));
-
-                let mut file = match File::options().read(true).write(true).open(path) {
-                    Ok(file) => file,
-                    Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
-                    Err(e) => return Err(e.into()),
-                };
-
-                let mut header = [0; SECTOR_SIZE * 2];
-
-                file.read_exact(&mut header)?;
-
-                ve.insert(Region { file, header })
-            }
-            Entry::Occupied(oe) => oe.into_mut(),
+        // If the region file does not exist, then the chunk is considered absent.
+        let Some(region) = self.region(chunk_x, chunk_z, false)? else {
+            return Ok(None)
         };
 
-        let chunk_idx = (chunk_x.rem_euclid(32) + chunk_z.rem_euclid(32) * 32) as usize;
+        let chunk_idx = chunk_index(chunk_x, chunk_z);
 
         let location_bytes = (&region.header[chunk_idx * 4..]).read_u32::<BigEndian>()?;
         let timestamp = (&region.header[chunk_idx * 4 + SECTOR_SIZE..]).read_u32::<BigEndian>()?;
@@ -168,4 +169,125 @@ impl AnvilWorld {
 
         Ok(Some(AnvilChunk { data, timestamp }))
     }
+
+    /// Writes a chunk to the file system at the given chunk coordinates,
+    /// replacing the chunk that was previously there. The region file is
+    /// created if it does not exist.
+    ///
+    /// The chunk's NBT data is compressed with Zlib.
+    pub fn write_chunk(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk: &AnvilChunk,
+    ) -> Result<(), WriteChunkError> {
+        // Leave room for the length and compression scheme.
+        let mut buf = vec![0; 5];
+
+        let mut z = ZlibEncoder::new(buf, Compression::default());
+        valence_nbt::to_binary_writer(&mut z, &chunk.data, "")?;
+        buf = z.finish()?;
+
+        let exact_chunk_size = buf.len() - 4;
+        (&mut buf[..4]).write_u32::<BigEndian>(exact_chunk_size as u32)?;
+        // Zlib
+        buf[4] = 2;
+
+        let sector_count = div_ceil(buf.len(), SECTOR_SIZE);
+
+        if sector_count > MAX_CHUNK_SECTORS {
+            return Err(WriteChunkError::ChunkTooLarge);
+        }
+
+        // Pad the data to a whole number of sectors.
+        buf.resize(sector_count * SECTOR_SIZE, 0);
+
+        let region = self
+            .region(chunk_x, chunk_z, true)?
+            .expect("region should have been created");
+
+        let chunk_idx = chunk_index(chunk_x, chunk_z);
+
+        let old_location = (&region.header[chunk_idx * 4..]).read_u32::<BigEndian>()?;
+        let old_sector_offset = (old_location >> 8) as u64;
+        let old_sector_count = (old_location & 0xff) as usize;
+
+        let sector_offset = if old_sector_offset >= 2 && sector_count <= old_sector_count {
+            // Overwrite the chunk in place.
+            old_sector_offset
+        } else {
+            // Append the chunk to the end of the file. The space previously used by this
+            // chunk is not reclaimed.
+            let len = region.file.seek(SeekFrom::End(0))?;
+            div_ceil(len as usize, SECTOR_SIZE) as u64
+        };
+
+        region
+            .file
+            .seek(SeekFrom::Start(sector_offset * SECTOR_SIZE as u64))?;
+        region.file.write_all(&buf)?;
+
+        let location = (sector_offset as u32) << 8 | sector_count as u32;
+
+        (&mut region.header[chunk_idx * 4..]).write_u32::<BigEndian>(location)?;
+        (&mut region.header[chunk_idx * 4 + SECTOR_SIZE..])
+            .write_u32::<BigEndian>(chunk.timestamp)?;
+
+        region.file.seek(SeekFrom::Start(0))?;
+        region.file.write_all(&region.header)?;
+
+        Ok(())
+    }
+
+    /// Gets the region containing the given chunk, loading the region file if
+    /// necessary. If the region file does not exist, it is created if `create`
+    /// is `true`. Otherwise, `None` is returned.
+    fn region(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+        create: bool,
+    ) -> io::Result<Option<&mut Region>> {
+        let region_x = chunk_x.div_euclid(32);
+        let region_z = chunk_z.div_euclid(32);
+
+        match self.regions.entry((region_x, region_z)) {
+            Entry::Vacant(ve) => {
+                let path = self
+                    .region_root
+                    .join(format!("r.{region_x}.{region_z}.mca"));
+
+                let mut header = [0; SECTOR_SIZE * 2];
+
+                let file = match File::options().read(true).write(true).open(&path) {
+                    Ok(mut file) => {
+                        file.read_exact(&mut header)?;
+                        file
+                    }
+                    Err(e) if e.kind() == ErrorKind::NotFound && create => {
+                        fs::create_dir_all(&self.region_root)?;
+
+                        let mut file = File::options()
+                            .read(true)
+                            .write(true)
+                            .create_new(true)
+                            .open(&path)?;
+
+                        file.write_all(&header)?;
+                        file
+                    }
+                    Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+                    Err(e) => return Err(e),
+                };
+
+                Ok(Some(ve.insert(Region { file, header })))
+            }
+            Entry::Occupied(oe) => Ok(Some(oe.into_mut())),
+        }
+    }
+}
+
+/// Returns the index of a chunk within its region.
+fn chunk_index(chunk_x: i32, chunk_z: i32) -> usize {
+    (chunk_x.rem_euclid(32) + chunk_z.rem_euclid(32) * 32) as usize
 }
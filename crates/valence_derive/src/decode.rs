@@ -34,7 +34,7 @@ pub fn derive_decode(item: TokenStream) -> Result<TokenStream> {
                 Fields::Named(fields) => {
                     let init = fields.named.iter().map(|f| {
                         let name = f.ident.as_ref().unwrap();
-                        let ctx = format!("failed to decode field `{name}` in `{input_name}`");
+                        let ctx = format!("failed to decode field `{input_name}.{name}`");
                         quote! {
                             #name: Decode::decode(_r).context(#ctx)?,
                         }
@@ -49,7 +49,7 @@ pub fn derive_decode(item: TokenStream) -> Result<TokenStream> {
                 Fields::Unnamed(fields) => {
                     let init = (0..fields.unnamed.len())
                         .map(|i| {
-                            let ctx = format!("failed to decode field `{i}` in `{input_name}`");
+                            let ctx = format!("failed to decode field `{input_name}.{i}`");
                             quote! {
                                 Decode::decode(_r).context(#ctx)?,
                             }
@@ -100,8 +100,7 @@ pub fn derive_decode(item: TokenStream) -> Result<TokenStream> {
                                 .map(|f| {
                                     let field = f.ident.as_ref().unwrap();
                                     let ctx = format!(
-                                        "failed to decode field `{field}` in variant `{name}` in \
-                                         `{input_name}`",
+                                        "failed to decode field `{input_name}::{name}.{field}`",
                                     );
                                     quote! {
                                         #field: Decode::decode(_r).context(#ctx)?,
@@ -117,8 +116,7 @@ pub fn derive_decode(item: TokenStream) -> Result<TokenStream> {
                             let init = (0..fields.unnamed.len())
                                 .map(|i| {
                                     let ctx = format!(
-                                        "failed to decode field `{i}` in variant `{name}` in \
-                                         `{input_name}`",
+                                        "failed to decode field `{input_name}::{name}.{i}`",
                                     );
                                     quote! {
                                         Decode::decode(_r).context(#ctx)?,
@@ -25,7 +25,7 @@ pub fn derive_encode(item: TokenStream) -> Result<TokenStream> {
                     .iter()
                     .map(|f| {
                         let name = &f.ident.as_ref().unwrap();
-                        let ctx = format!("failed to encode field `{name}` in `{input_name}`");
+                        let ctx = format!("failed to encode field `{input_name}.{name}`");
                         quote! {
                             self.#name.encode(&mut _w).context(#ctx)?;
                         }
@@ -34,7 +34,7 @@ pub fn derive_encode(item: TokenStream) -> Result<TokenStream> {
                 Fields::Unnamed(fields) => (0..fields.unnamed.len())
                     .map(|i| {
                         let lit = LitInt::new(&i.to_string(), Span::call_site());
-                        let ctx = format!("failed to encode field `{lit}` in `{input_name}`");
+                        let ctx = format!("failed to encode field `{input_name}.{lit}`");
                         quote! {
                             self.#lit.encode(&mut _w).context(#ctx)?;
                         }
@@ -83,8 +83,7 @@ pub fn derive_encode(item: TokenStream) -> Result<TokenStream> {
                                 .iter()
                                 .map(|name| {
                                     let ctx = format!(
-                                        "failed to encode field `{name}` in variant \
-                                         `{variant_name}` in `{input_name}`",
+                                        "failed to encode field `{input_name}::{variant_name}.{name}`",
                                     );
 
                                     quote! {
@@ -109,10 +108,10 @@ pub fn derive_encode(item: TokenStream) -> Result<TokenStream> {
 
                             let encode_fields = field_names
                                 .iter()
-                                .map(|name| {
+                                .enumerate()
+                                .map(|(i, name)| {
                                     let ctx = format!(
-                                        "failed to encode field `{name}` in variant \
-                                         `{variant_name}` in `{input_name}`"
+                                        "failed to encode field `{input_name}::{variant_name}.{i}`"
                                     );
 
                                     quote! {
@@ -48,6 +48,9 @@
 //! - `preserve_order`: Causes the order of fields in [`Compound`]s to be
 //! preserved during insertion and deletion at a slight cost to performance.
 //! The iterators on `Compound` can then implement [`DoubleEndedIterator`].
+//! - `serde`: Enables conversions between [`Value`]s and types implementing
+//!   `Serialize` and `Deserialize` with [`to_value`] and [`from_value`]. See
+//!   the [`serde`] module for details on how Rust types are mapped to NBT.
 
 #![deny(
     rustdoc::broken_intra_doc_links,
@@ -66,6 +69,8 @@
 )]
 #![allow(clippy::unusual_byte_groupings)]
 
+#[cfg(feature = "serde")]
+pub use self::serde::{byte_array, from_value, int_array, long_array, to_compound, to_value};
 pub use compound::Compound;
 pub use error::Error;
 pub use from_binary_slice::from_binary_slice;
@@ -76,6 +81,8 @@ pub mod compound;
 mod error;
 mod from_binary_slice;
 mod modified_utf8;
+#[cfg(feature = "serde")]
+pub mod serde;
 mod to_binary_writer;
 pub mod value;
 
@@ -0,0 +1,945 @@
+//! Conversions between [`Value`]s and types implementing [`Serialize`] and
+//! [`Deserialize`].
+//!
+//! Integers are mapped to the NBT tag of the same width. Unsigned integers are
+//! stored with the bits of the signed tag reinterpreted. `bool`s are stored as
+//! bytes. Structs and maps become compounds and sequences become lists.
+//! `None` fields are omitted from their compound.
+//!
+//! Enums are externally tagged: unit variants are strings and other variants
+//! are compounds with a single entry named after the variant.
+//!
+//! Sequences are always serialized as lists. Use the [`byte_array`],
+//! [`int_array`], and [`long_array`] modules with `#[serde(with = "...")]` to
+//! serialize a field as one of the NBT array types instead.
+//!
+//! # Examples
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//! use valence_nbt::{compound, from_value, to_value, Value};
+//!
+//! #[derive(Serialize, Deserialize, PartialEq, Debug)]
+//! struct Player {
+//!     name: String,
+//!     health: f32,
+//!     #[serde(with = "valence_nbt::int_array")]
+//!     uuid: Vec<i32>,
+//! }
+//!
+//! let player = Player {
+//!     name: "Steve".into(),
+//!     health: 20.0,
+//!     uuid: vec![1, 2, 3, 4],
+//! };
+//!
+//! let value = to_value(&player).unwrap();
+//!
+//! assert_eq!(
+//!     value,
+//!     Value::Compound(compound! {
+//!         "name" => "Steve",
+//!         "health" => 20.0_f32,
+//!         "uuid" => vec![1, 2, 3, 4],
+//!     })
+//! );
+//!
+//! assert_eq!(from_value::<Player>(value).unwrap(), player);
+//! ```
+
+use std::fmt::Display;
+
+use ::serde::de::value::{MapAccessDeserializer, MapDeserializer, SeqDeserializer};
+use ::serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use ::serde::ser::{self, Impossible};
+use ::serde::{forward_to_deserialize_any, Deserializer, Serialize, Serializer};
+
+use crate::{Compound, Error, List, Result, Value};
+
+/// Converts a value implementing [`Serialize`] to an NBT [`Value`].
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value> {
+    value
+        .serialize(ValueSerializer)?
+        .ok_or_else(|| Error::new_static("cannot serialize `None` outside of a compound"))
+}
+
+/// Converts a value implementing [`Serialize`] to an NBT [`Compound`]. An
+/// error is returned if the value does not serialize to a compound.
+pub fn to_compound<T: Serialize + ?Sized>(value: &T) -> Result<Compound> {
+    match to_value(value)? {
+        Value::Compound(c) => Ok(c),
+        _ => Err(Error::new_static("value did not serialize to a compound")),
+    }
+}
+
+/// Converts an NBT [`Value`] to a type implementing [`Deserialize`].
+///
+/// [`Deserialize`]: ::serde::Deserialize
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T> {
+    T::deserialize(value)
+}
+
+/// Serializes a sequence of bytes as an NBT byte array rather than a list.
+/// Use with `#[serde(with = "valence_nbt::byte_array")]`.
+pub mod byte_array {
+    use ::serde::{Deserialize, Deserializer, Serializer};
+
+    /// Serializes the field as an NBT byte array.
+    pub fn serialize<S: Serializer>(value: &[i8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(super::BYTE_ARRAY_TOKEN, value)
+    }
+
+    /// Deserializes the field from an NBT byte array or list.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<i8>, D::Error> {
+        Vec::deserialize(deserializer)
+    }
+}
+
+/// Serializes a sequence of `i32`s as an NBT int array rather than a list.
+/// Use with `#[serde(with = "valence_nbt::int_array")]`.
+pub mod int_array {
+    use ::serde::{Deserialize, Deserializer, Serializer};
+
+    /// Serializes the field as an NBT int array.
+    pub fn serialize<S: Serializer>(value: &[i32], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(super::INT_ARRAY_TOKEN, value)
+    }
+
+    /// Deserializes the field from an NBT int array or list.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<i32>, D::Error> {
+        Vec::deserialize(deserializer)
+    }
+}
+
+/// Serializes a sequence of `i64`s as an NBT long array rather than a list.
+/// Use with `#[serde(with = "valence_nbt::long_array")]`.
+pub mod long_array {
+    use ::serde::{Deserialize, Deserializer, Serializer};
+
+    /// Serializes the field as an NBT long array.
+    pub fn serialize<S: Serializer>(value: &[i64], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(super::LONG_ARRAY_TOKEN, value)
+    }
+
+    /// Deserializes the field from an NBT long array or list.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<i64>, D::Error> {
+        Vec::deserialize(deserializer)
+    }
+}
+
+/// Newtype struct names used to mark sequences which should be serialized as
+/// arrays.
+const BYTE_ARRAY_TOKEN: &str = "$valence_nbt::byte_array";
+const INT_ARRAY_TOKEN: &str = "$valence_nbt::int_array";
+const LONG_ARRAY_TOKEN: &str = "$valence_nbt::long_array";
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::new_owned(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::new_owned(msg.to_string())
+    }
+}
+
+/// Serializes to a [`Value`]. `None` is returned for `None` options so that
+/// they can be omitted from compounds.
+struct ValueSerializer;
+
+impl Serializer for ValueSerializer {
+    type Ok = Option<Value>;
+    type Error = Error;
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeList;
+    type SerializeMap = SerializeCompound;
+    type SerializeStruct = SerializeCompound;
+    type SerializeStructVariant = SerializeCompound;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
+        Ok(Some(Value::Byte(v as i8)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
+        Ok(Some(Value::Byte(v)))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
+        Ok(Some(Value::Short(v)))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
+        Ok(Some(Value::Int(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
+        Ok(Some(Value::Long(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
+        Ok(Some(Value::Byte(v as i8)))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
+        Ok(Some(Value::Short(v as i16)))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
+        Ok(Some(Value::Int(v as i32)))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
+        Ok(Some(Value::Long(v as i64)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
+        Ok(Some(Value::Float(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
+        Ok(Some(Value::Double(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok> {
+        Ok(Some(Value::String(v.into())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok> {
+        Ok(Some(Value::String(v.into())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
+        Ok(Some(Value::ByteArray(v.iter().map(|&b| b as i8).collect())))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok> {
+        Ok(Some(Value::Compound(Compound::new())))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        let value = value.serialize(self)?;
+
+        let array = match (name, value) {
+            (BYTE_ARRAY_TOKEN, Some(Value::List(List::Byte(v)))) => Value::ByteArray(v),
+            (INT_ARRAY_TOKEN, Some(Value::List(List::Int(v)))) => Value::IntArray(v),
+            (LONG_ARRAY_TOKEN, Some(Value::List(List::Long(v)))) => Value::LongArray(v),
+            (BYTE_ARRAY_TOKEN, Some(Value::List(List::End))) => Value::ByteArray(vec![]),
+            (INT_ARRAY_TOKEN, Some(Value::List(List::End))) => Value::IntArray(vec![]),
+            (LONG_ARRAY_TOKEN, Some(Value::List(List::End))) => Value::LongArray(vec![]),
+            (BYTE_ARRAY_TOKEN | INT_ARRAY_TOKEN | LONG_ARRAY_TOKEN, _) => {
+                return Err(Error::new_static("invalid array element type"))
+            }
+            (_, value) => return Ok(value),
+        };
+
+        Ok(Some(array))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        let mut compound = Compound::new();
+
+        if let Some(value) = value.serialize(self)? {
+            compound.insert(variant, value);
+        }
+
+        Ok(Some(Value::Compound(compound)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(SerializeList {
+            values: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Ok(SerializeList {
+            values: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(SerializeCompound {
+            compound: Compound::new(),
+            next_key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        Ok(SerializeCompound {
+            compound: Compound::with_capacity(len),
+            next_key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Ok(SerializeCompound {
+            compound: Compound::with_capacity(len),
+            next_key: None,
+            variant: Some(variant),
+        })
+    }
+}
+
+/// Wraps the value of a newtype, tuple, or struct variant in a compound with a
+/// single entry named after the variant.
+fn wrap_variant(variant: Option<&'static str>, value: Value) -> Value {
+    match variant {
+        Some(variant) => Value::Compound(Compound::from_iter([(variant.into(), value)])),
+        None => value,
+    }
+}
+
+struct SerializeList {
+    values: Vec<Value>,
+    variant: Option<&'static str>,
+}
+
+impl SerializeList {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        match value.serialize(ValueSerializer)? {
+            Some(value) => {
+                self.values.push(value);
+                Ok(())
+            }
+            None => Err(Error::new_static("cannot serialize `None` in a list")),
+        }
+    }
+
+    fn finish(self) -> Result<Option<Value>> {
+        let list = values_to_list(self.values)?;
+        Ok(Some(wrap_variant(self.variant, Value::List(list))))
+    }
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+struct SerializeCompound {
+    compound: Compound,
+    next_key: Option<String>,
+    variant: Option<&'static str>,
+}
+
+impl SerializeCompound {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<()> {
+        if let Some(value) = value.serialize(ValueSerializer)? {
+            self.compound.insert(key, value);
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<Value>> {
+        Ok(Some(wrap_variant(
+            self.variant,
+            Value::Compound(self.compound),
+        )))
+    }
+}
+
+impl ser::SerializeMap for SerializeCompound {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.next_key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .next_key
+            .take()
+            .ok_or_else(|| Error::new_static("map value serialized before its key"))?;
+
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeCompound {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.insert(key.into(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeCompound {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.insert(key.into(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+/// Serializes map keys, which must be strings.
+struct KeySerializer;
+
+fn key_must_be_a_string() -> Error {
+    Error::new_static("compound keys must be strings")
+}
+
+impl Serializer for KeySerializer {
+    type Ok = String;
+    type Error = Error;
+    type SerializeSeq = Impossible<String, Error>;
+    type SerializeTuple = Impossible<String, Error>;
+    type SerializeTupleStruct = Impossible<String, Error>;
+    type SerializeTupleVariant = Impossible<String, Error>;
+    type SerializeMap = Impossible<String, Error>;
+    type SerializeStruct = Impossible<String, Error>;
+    type SerializeStructVariant = Impossible<String, Error>;
+
+    fn serialize_bool(self, _v: bool) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_char(self, v: char) -> Result<String> {
+        Ok(v.into())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<String> {
+        Ok(v.into())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_none(self) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit(self) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String> {
+        Ok(variant.into())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(key_must_be_a_string())
+    }
+}
+
+/// Converts a vector of values to a list. All values must have the same tag.
+fn values_to_list(values: Vec<Value>) -> Result<List> {
+    macro_rules! collect {
+        ($($variant:ident),*) => {
+            match values.first() {
+                None => Ok(List::End),
+                $(
+                    Some(Value::$variant(_)) => values
+                        .into_iter()
+                        .map(|v| match v {
+                            Value::$variant(v) => Ok(v),
+                            _ => Err(Error::new_static("list elements must have the same type")),
+                        })
+                        .collect::<Result<_>>()
+                        .map(List::$variant),
+                )*
+            }
+        }
+    }
+
+    collect!(
+        Byte, Short, Int, Long, Float, Double, ByteArray, String, List, Compound, IntArray,
+        LongArray
+    )
+}
+
+/// Converts a list to a vector of values.
+fn list_to_values(list: List) -> Vec<Value> {
+    macro_rules! convert {
+        ($($variant:ident),*) => {
+            match list {
+                List::End => vec![],
+                $(List::$variant(v) => v.into_iter().map(Value::$variant).collect(),)*
+            }
+        }
+    }
+
+    convert!(
+        Byte, Short, Int, Long, Float, Double, ByteArray, String, List, Compound, IntArray,
+        LongArray
+    )
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Value::Byte(v) => visitor.visit_i8(v),
+            Value::Short(v) => visitor.visit_i16(v),
+            Value::Int(v) => visitor.visit_i32(v),
+            Value::Long(v) => visitor.visit_i64(v),
+            Value::Float(v) => visitor.visit_f32(v),
+            Value::Double(v) => visitor.visit_f64(v),
+            Value::ByteArray(v) => {
+                visitor.visit_seq(SeqDeserializer::new(v.into_iter().map(Value::Byte)))
+            }
+            Value::String(v) => visitor.visit_string(v),
+            Value::List(v) => {
+                visitor.visit_seq(SeqDeserializer::new(list_to_values(v).into_iter()))
+            }
+            Value::Compound(v) => visitor.visit_map(MapDeserializer::new(v.into_iter())),
+            Value::IntArray(v) => {
+                visitor.visit_seq(SeqDeserializer::new(v.into_iter().map(Value::Int)))
+            }
+            Value::LongArray(v) => {
+                visitor.visit_seq(SeqDeserializer::new(v.into_iter().map(Value::Long)))
+            }
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Value::Byte(v) => visitor.visit_bool(v != 0),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Value::Byte(v) => visitor.visit_u8(v as u8),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Value::Short(v) => visitor.visit_u16(v as u16),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Value::Int(v) => visitor.visit_u32(v as u32),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Value::Long(v) => visitor.visit_u64(v as u64),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        // Absent fields are handled by serde, so a present value is always `Some`.
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self {
+            Value::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Value::Compound(c) if c.len() == 1 => visitor.visit_enum(MapAccessDeserializer::new(
+                MapDeserializer::new(c.into_iter()),
+            )),
+            _ => Err(Error::new_static(
+                "expected a string or a compound with a single entry for enum",
+            )),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u128 f32 f64 char str string bytes byte_buf unit unit_struct seq
+        tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use ::serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::{compound, from_binary_slice, to_binary_writer};
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct AllTags {
+        byte: i8,
+        short: i16,
+        int: i32,
+        long: i64,
+        float: f32,
+        double: f64,
+        #[serde(with = "byte_array")]
+        byte_array: Vec<i8>,
+        string: String,
+        list: Vec<String>,
+        compound: BTreeMap<String, i32>,
+        #[serde(with = "int_array")]
+        int_array: Vec<i32>,
+        #[serde(with = "long_array")]
+        long_array: Vec<i64>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Rect { w: u32, h: u32 },
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Extras {
+        flag: bool,
+        unsigned: u8,
+        missing: Option<i32>,
+        present: Option<i32>,
+        shape: Shape,
+        shapes: Vec<Shape>,
+        empty: Vec<i16>,
+    }
+
+    fn round_trip<T>(value: &T) -> Compound
+    where
+        T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug,
+    {
+        let compound = to_compound(value).unwrap();
+
+        let mut buf = vec![];
+        to_binary_writer(&mut buf, &compound, "").unwrap();
+        let (decoded, _) = from_binary_slice(&mut buf.as_slice()).unwrap();
+        assert_eq!(decoded, compound);
+
+        assert_eq!(&from_value::<T>(Value::Compound(decoded)).unwrap(), value);
+
+        compound
+    }
+
+    #[test]
+    fn round_trip_all_tags() {
+        let value = AllTags {
+            byte: -5,
+            short: i16::MAX,
+            int: i32::MIN,
+            long: 0xdead_beef_i64,
+            float: 1.5,
+            double: f64::INFINITY,
+            byte_array: vec![1, -2, 3],
+            string: "aé日".into(),
+            list: vec!["foo".into(), "bar".into()],
+            compound: BTreeMap::from([("a".into(), 1), ("b".into(), 2)]),
+            int_array: vec![5, -9, 0],
+            long_array: vec![i64::MIN, i64::MAX],
+        };
+
+        let compound = round_trip(&value);
+
+        assert_eq!(
+            compound,
+            compound! {
+                "byte" => -5_i8,
+                "short" => i16::MAX,
+                "int" => i32::MIN,
+                "long" => 0xdead_beef_i64,
+                "float" => 1.5_f32,
+                "double" => f64::INFINITY,
+                "byte_array" => vec![1_i8, -2, 3],
+                "string" => "aé日",
+                "list" => List::String(vec!["foo".into(), "bar".into()]),
+                "compound" => compound! { "a" => 1, "b" => 2 },
+                "int_array" => vec![5, -9, 0],
+                "long_array" => vec![i64::MIN, i64::MAX],
+            }
+        );
+    }
+
+    #[test]
+    fn round_trip_options_enums_and_unsigned() {
+        let value = Extras {
+            flag: true,
+            unsigned: 200,
+            missing: None,
+            present: Some(7),
+            shape: Shape::Empty,
+            shapes: vec![Shape::Circle(2.0), Shape::Rect { w: 3, h: u32::MAX }],
+            empty: vec![],
+        };
+
+        let compound = round_trip(&value);
+
+        assert!(!compound.contains_key("missing"));
+        assert_eq!(compound.get("flag"), Some(&Value::Byte(1)));
+        assert_eq!(compound.get("unsigned"), Some(&Value::Byte(200_u8 as i8)));
+        assert_eq!(compound.get("empty"), Some(&Value::List(List::End)));
+    }
+
+    #[test]
+    fn mixed_enum_list_is_rejected() {
+        // Unit variants are strings while other variants are compounds.
+        assert!(to_value(&vec![Shape::Empty, Shape::Circle(1.0)]).is_err());
+    }
+}
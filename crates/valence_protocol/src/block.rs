@@ -1,3 +1,9 @@
+//! Block states and kinds, generated from the block data of the supported
+//! Minecraft version.
+//!
+//! Every [`BlockState`] corresponds to an ID in the global palette expected by
+//! clients, available through [`BlockState::to_raw`].
+
 #![allow(clippy::all)] // TODO: block build script creates many warnings.
 
 use std::fmt;
@@ -110,4 +116,24 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn vanilla_block_state_ids() {
+        assert_eq!(BlockState::AIR.to_raw(), 0);
+        assert_eq!(BlockState::STONE.to_raw(), 1);
+
+        let stairs = BlockKind::OakStairs.to_state();
+        assert_eq!(stairs.to_raw(), 2833);
+
+        let stairs = stairs
+            .set(PropName::Facing, PropValue::East)
+            .set(PropName::Waterlogged, PropValue::True);
+        assert_eq!(stairs.to_raw(), 2892);
+
+        assert_eq!(BlockState::from_raw(2892), Some(stairs));
+        assert_eq!(
+            BlockState::from_kind(BlockKind::OakStairs),
+            BlockKind::OakStairs.to_state()
+        );
+    }
 }
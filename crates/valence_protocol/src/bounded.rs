@@ -1,4 +1,3 @@
-/*
 // TODO: implement BoundedFloat when floats are permitted in const generics.
 
 use std::io::Write;
@@ -97,4 +96,104 @@ where
         Ok(Self(s))
     }
 }
-*/
+
+/// A slice or vector with a minimum and maximum length known at compile time.
+/// `A` is the underlying array type, which is either `&[u8]` or `Vec<T>`.
+///
+/// If the length is not in bounds, an error is generated while encoding or
+/// decoding.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+pub struct BoundedArray<A, const MIN: usize, const MAX: usize>(pub A);
+
+fn check_array_len<const MIN: usize, const MAX: usize>(len: usize, op: &str) -> Result<()> {
+    ensure!(
+        len <= MAX,
+        "array length exceeded max length {MAX} while {op} (got {len})"
+    );
+    ensure!(
+        len >= MIN,
+        "array length is below min length {MIN} while {op} (got {len})"
+    );
+
+    Ok(())
+}
+
+impl<const MIN: usize, const MAX: usize> Encode for BoundedArray<&[u8], MIN, MAX> {
+    fn encode(&self, w: impl Write) -> Result<()> {
+        check_array_len::<MIN, MAX>(self.0.len(), "encoding")?;
+        self.0.encode(w)
+    }
+}
+
+impl<'a, const MIN: usize, const MAX: usize> Decode<'a> for BoundedArray<&'a [u8], MIN, MAX> {
+    fn decode(r: &mut &'a [u8]) -> Result<Self> {
+        let slice = <&[u8]>::decode(r)?;
+        check_array_len::<MIN, MAX>(slice.len(), "decoding")?;
+        Ok(Self(slice))
+    }
+}
+
+impl<T: Encode, const MIN: usize, const MAX: usize> Encode for BoundedArray<Vec<T>, MIN, MAX> {
+    fn encode(&self, w: impl Write) -> Result<()> {
+        check_array_len::<MIN, MAX>(self.0.len(), "encoding")?;
+        self.0.encode(w)
+    }
+}
+
+impl<'a, T, const MIN: usize, const MAX: usize> Decode<'a> for BoundedArray<Vec<T>, MIN, MAX>
+where
+    T: Decode<'a>,
+{
+    fn decode(r: &mut &'a [u8]) -> Result<Self> {
+        let vec = Vec::<T>::decode(r)?;
+        check_array_len::<MIN, MAX>(vec.len(), "decoding")?;
+        Ok(Self(vec))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::c2s::login::EncryptionResponse;
+    use crate::{DecodePacket, EncodePacket};
+
+    #[test]
+    fn bounded_array_error_names_field() {
+        let shared_secret = [0_u8; 129];
+
+        let mut buf = vec![];
+        crate::VarInt(<EncryptionResponse as DecodePacket>::PACKET_ID)
+            .encode(&mut buf)
+            .unwrap();
+        shared_secret.as_slice().encode(&mut buf).unwrap();
+        [0_u8; 128].as_slice().encode(&mut buf).unwrap();
+
+        let err = EncryptionResponse::decode_packet(&mut buf.as_slice()).unwrap_err();
+        let msg = format!("{err:#}");
+
+        assert!(
+            msg.contains(
+                "`EncryptionResponse.shared_secret`: array length exceeded max length 128 while \
+                 decoding (got 129)"
+            ),
+            "{msg}"
+        );
+
+        let pkt = EncryptionResponse {
+            shared_secret: BoundedArray(&shared_secret),
+            verify_token: BoundedArray(&[]),
+        };
+
+        assert!(pkt.encode_packet(&mut vec![]).is_err());
+    }
+
+    #[test]
+    fn bounded_string_in_bounds() {
+        let mut buf = vec![];
+        BoundedString::<_, 1, 3>("abc").encode(&mut buf).unwrap();
+        assert!(BoundedString::<_, 1, 2>("abc").encode(&mut buf).is_err());
+
+        let mut r = buf.as_slice();
+        assert!(BoundedString::<&str, 1, 2>::decode(&mut r).is_err());
+    }
+}
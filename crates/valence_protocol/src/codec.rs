@@ -141,6 +141,16 @@ impl PacketEncoder {
         Ok(())
     }
 
+    /// Returns the number of bytes written since the last call to
+    /// [`Self::take`].
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
     /// Takes all the packets written so far and encrypts them if encryption is
     /// enabled.
     pub fn take(&mut self) -> BytesMut {
@@ -161,6 +171,13 @@ impl PacketEncoder {
         self.compression_threshold = threshold;
     }
 
+    /// Returns whether compression has been enabled with
+    /// [`Self::set_compression`].
+    #[cfg(feature = "compression")]
+    pub fn is_compressed(&self) -> bool {
+        self.compression_threshold.is_some()
+    }
+
     /// Encrypts all future packets **and any packets that have
     /// not been [taken] yet.**
     ///
@@ -170,6 +187,13 @@ impl PacketEncoder {
         assert!(self.cipher.is_none(), "encryption is already enabled");
         self.cipher = Some(NewCipher::new(key.into(), key.into()));
     }
+
+    /// Returns whether encryption has been enabled with
+    /// [`Self::enable_encryption`].
+    #[cfg(feature = "encryption")]
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
 }
 
 pub fn encode_packet<P>(buf: &mut Vec<u8>, pkt: &P) -> Result<()>
@@ -279,6 +303,12 @@ pub struct PacketDecoder {
     compression_enabled: bool,
     #[cfg(feature = "encryption")]
     cipher: Option<Cipher>,
+    /// The maximum packet length to accept, or `None` for [`MAX_PACKET_SIZE`].
+    max_packet_len: Option<i32>,
+    /// The maximum decompressed packet length to accept, or `None` for
+    /// [`MAX_PACKET_SIZE`].
+    #[cfg(feature = "compression")]
+    max_decompressed_len: Option<i32>,
 }
 
 impl PacketDecoder {
@@ -286,6 +316,43 @@ impl PacketDecoder {
         Self::default()
     }
 
+    /// Sets the maximum length of a packet frame (not including the length
+    /// prefix) this decoder will accept. `None` restores the default of
+    /// [`MAX_PACKET_SIZE`]. Values larger than [`MAX_PACKET_SIZE`] are
+    /// clamped.
+    ///
+    /// Frames claiming to be longer than the maximum are rejected as soon as
+    /// their length prefix is read, without waiting for the rest of the frame
+    /// to arrive.
+    pub fn set_max_packet_len(&mut self, len: Option<i32>) {
+        self.max_packet_len = len;
+    }
+
+    /// Returns the maximum length of a packet frame this decoder will accept.
+    pub fn max_packet_len(&self) -> i32 {
+        self.max_packet_len
+            .map_or(MAX_PACKET_SIZE, |len| len.min(MAX_PACKET_SIZE))
+    }
+
+    /// Sets the exclusive upper bound on the length of a packet after
+    /// decompression this decoder will accept. `None` restores the default of
+    /// [`MAX_PACKET_SIZE`].
+    ///
+    /// Compressed packets declaring a decompressed length of `len` or more are
+    /// rejected before any memory is allocated for them or any data is
+    /// inflated.
+    #[cfg(feature = "compression")]
+    pub fn set_max_decompressed_len(&mut self, len: Option<i32>) {
+        self.max_decompressed_len = len;
+    }
+
+    /// Returns the exclusive upper bound on the length of a packet after
+    /// decompression this decoder will accept.
+    #[cfg(feature = "compression")]
+    pub fn max_decompressed_len(&self) -> i32 {
+        self.max_decompressed_len.unwrap_or(MAX_PACKET_SIZE)
+    }
+
     pub fn try_next_packet<'a, P>(&'a mut self) -> Result<Option<P>>
     where
         P: DecodePacket<'a> + fmt::Debug,
@@ -302,7 +369,7 @@ impl PacketDecoder {
         };
 
         ensure!(
-            (0..=MAX_PACKET_SIZE).contains(&packet_len),
+            (0..=self.max_packet_len()).contains(&packet_len),
             "packet length of {packet_len} is out of bounds"
         );
 
@@ -324,10 +391,12 @@ impl PacketDecoder {
             let data_len = VarInt::decode(&mut r)?.0;
 
             ensure!(
-                (0..MAX_PACKET_SIZE).contains(&data_len),
+                (0..self.max_decompressed_len()).contains(&data_len),
                 "decompressed packet length of {data_len} is out of bounds"
             );
 
+            // A data length of zero means the rest of the frame is not
+            // compressed.
             if data_len != 0 {
                 self.decompress_buf.clear();
                 self.decompress_buf.reserve_exact(data_len as usize);
@@ -336,6 +405,13 @@ impl PacketDecoder {
                 z.read_to_end(&mut self.decompress_buf)
                     .context("decompressing packet")?;
 
+                ensure!(
+                    self.decompress_buf.len() == data_len as usize,
+                    "decompressed packet length of {} does not match the declared length of \
+                     {data_len}",
+                    self.decompress_buf.len()
+                );
+
                 r = &self.decompress_buf;
                 P::decode_packet(&mut r)?
             } else {
@@ -368,7 +444,7 @@ impl PacketDecoder {
         match VarInt::decode_partial(&mut r) {
             Ok(packet_len) => {
                 ensure!(
-                    (0..=MAX_PACKET_SIZE).contains(&packet_len),
+                    (0..=self.max_packet_len()).contains(&packet_len),
                     "packet length of {packet_len} is out of bounds"
                 );
 
@@ -384,6 +460,13 @@ impl PacketDecoder {
         self.compression_enabled = enabled;
     }
 
+    /// Returns whether compression has been enabled with
+    /// [`Self::set_compression`].
+    #[cfg(feature = "compression")]
+    pub fn is_compressed(&self) -> bool {
+        self.compression_enabled
+    }
+
     #[cfg(feature = "encryption")]
     pub fn enable_encryption(&mut self, key: &[u8; 16]) {
         assert!(self.cipher.is_none(), "encryption is already enabled");
@@ -394,6 +477,13 @@ impl PacketDecoder {
         self.cipher = Some(cipher);
     }
 
+    /// Returns whether encryption has been enabled with
+    /// [`Self::enable_encryption`].
+    #[cfg(feature = "encryption")]
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
     pub fn queue_bytes(&mut self, mut bytes: BytesMut) {
         #![allow(unused_mut)]
 
@@ -437,6 +527,7 @@ mod tests {
     use crate::entity_meta::PaintingKind;
     use crate::ident::Ident;
     use crate::item::{ItemKind, ItemStack};
+    use crate::raw_bytes::RawPacket;
     use crate::text::{Text, TextFormat};
     use crate::username::Username;
     use crate::var_long::VarLong;
@@ -535,4 +626,206 @@ mod tests {
             .unwrap()
             .check("third");
     }
+
+    #[test]
+    fn compression_and_encryption_state() {
+        let mut enc = PacketEncoder::new();
+        let mut dec = PacketDecoder::new();
+
+        #[cfg(feature = "compression")]
+        {
+            assert!(!enc.is_compressed() && !dec.is_compressed());
+            enc.set_compression(Some(256));
+            dec.set_compression(true);
+            assert!(enc.is_compressed() && dec.is_compressed());
+        }
+
+        #[cfg(feature = "encryption")]
+        {
+            assert!(!enc.is_encrypted() && !dec.is_encrypted());
+            enc.enable_encryption(&CRYPT_KEY);
+            dec.enable_encryption(&CRYPT_KEY);
+            assert!(enc.is_encrypted() && dec.is_encrypted());
+        }
+    }
+
+    #[test]
+    fn raw_packet_round_trip() {
+        let mut enc = PacketEncoder::new();
+        enc.append_packet(&TestPacket::new("raw")).unwrap();
+
+        let mut dec = PacketDecoder::new();
+        dec.queue_bytes(enc.take());
+
+        let raw = dec.try_next_packet::<RawPacket>().unwrap().unwrap();
+        assert_eq!(raw.id, VarInt(42));
+        TestPacket::decode(&mut { raw.data }).unwrap().check("raw");
+
+        let data = raw.data.to_vec();
+        enc.append_packet(&RawPacket {
+            id: VarInt(42),
+            data: &data,
+        })
+        .unwrap();
+
+        dec.queue_bytes(enc.take());
+        dec.try_next_packet::<TestPacket>()
+            .unwrap()
+            .unwrap()
+            .check("raw");
+    }
+
+    #[test]
+    fn oversized_packet_len_rejected() {
+        let mut dec = PacketDecoder::new();
+        dec.set_max_packet_len(Some(1024));
+
+        let mut buf = vec![];
+        VarInt(1025).encode(&mut buf).unwrap();
+        dec.queue_slice(&buf);
+
+        // Rejected before the body has arrived.
+        assert!(dec.has_next_packet().is_err());
+        assert!(dec.try_next_packet::<TestPacket>().is_err());
+
+        let mut dec = PacketDecoder::new();
+        dec.set_max_packet_len(Some(1024));
+
+        let mut buf = vec![];
+        VarInt(1024).encode(&mut buf).unwrap();
+        dec.queue_slice(&buf);
+
+        assert!(!dec.has_next_packet().unwrap());
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn oversized_decompressed_len_rejected() {
+        // Frames declaring a decompressed length of 1 GiB or exactly the cap,
+        // followed by data which is not valid zlib at all.
+        for data_len in [1 << 30, 4096] {
+            let mut body = vec![];
+            VarInt(data_len).encode(&mut body).unwrap();
+            body.extend_from_slice(&[0xff; 8]);
+
+            let mut buf = vec![];
+            VarInt(body.len() as i32).encode(&mut buf).unwrap();
+            buf.extend_from_slice(&body);
+
+            let mut dec = PacketDecoder::new();
+            dec.set_compression(true);
+            dec.set_max_decompressed_len(Some(4096));
+            dec.queue_slice(&buf);
+
+            let err = dec.try_next_packet::<TestPacket>().unwrap_err();
+            assert!(err.to_string().contains("out of bounds"), "{err:#}");
+        }
+
+        // Packets within the limit are still accepted.
+        let mut enc = PacketEncoder::new();
+        enc.set_compression(Some(0));
+        enc.append_packet(&TestPacket::new("small")).unwrap();
+
+        let mut dec = PacketDecoder::new();
+        dec.set_compression(true);
+        dec.set_max_decompressed_len(Some(4096));
+        dec.queue_bytes(enc.take());
+
+        dec.try_next_packet::<TestPacket>()
+            .unwrap()
+            .unwrap()
+            .check("small");
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn compression_threshold_boundary() {
+        const THRESHOLD: u32 = 64;
+
+        // The packet ID takes up one byte of the packet data.
+        for data_len in [THRESHOLD - 1, THRESHOLD, THRESHOLD + 1] {
+            let data = vec![7; data_len as usize - 1];
+
+            let mut enc = PacketEncoder::new();
+            enc.set_compression(Some(THRESHOLD));
+            enc.append_packet(&RawPacket {
+                id: VarInt(42),
+                data: &data,
+            })
+            .unwrap();
+
+            let buf = enc.take();
+
+            let mut r = &buf[..];
+            VarInt::decode(&mut r).unwrap();
+            let declared_len = VarInt::decode(&mut r).unwrap().0;
+
+            if data_len > THRESHOLD {
+                assert_eq!(declared_len, data_len as i32);
+            } else {
+                // Uncompressed, with the packet data directly following.
+                assert_eq!(declared_len, 0);
+                assert_eq!(r.len(), data_len as usize);
+            }
+
+            let mut dec = PacketDecoder::new();
+            dec.set_compression(true);
+            dec.queue_bytes(buf);
+
+            let pkt = dec.try_next_packet::<RawPacket>().unwrap().unwrap();
+            assert_eq!(pkt.id, VarInt(42));
+            assert_eq!(pkt.data, data);
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn mismatched_decompressed_len_rejected() {
+        let mut enc = PacketEncoder::new();
+        enc.set_compression(Some(0));
+        enc.append_packet(&TestPacket::new("mismatch")).unwrap();
+
+        let buf = enc.take();
+
+        let mut r = &buf[..];
+        let packet_len = VarInt::decode(&mut r).unwrap().0;
+        let data_len = VarInt::decode(&mut r).unwrap().0;
+
+        // Claim one more byte than the compressed data inflates to. The new
+        // data length VarInt has the same size as the old one.
+        let mut frame = vec![];
+        VarInt(packet_len).encode(&mut frame).unwrap();
+        VarInt(data_len + 1).encode(&mut frame).unwrap();
+        frame.extend_from_slice(r);
+
+        let mut dec = PacketDecoder::new();
+        dec.set_compression(true);
+        dec.queue_slice(&frame);
+
+        assert!(dec.try_next_packet::<TestPacket>().is_err());
+    }
+
+    #[test]
+    fn unterminated_packet_len_rejected() {
+        let mut dec = PacketDecoder::new();
+
+        dec.queue_slice(&[0xff; 4]);
+        // Could still be a valid VarInt.
+        assert!(!dec.has_next_packet().unwrap());
+        assert!(dec.try_next_packet::<TestPacket>().unwrap().is_none());
+
+        dec.queue_slice(&[0xff; 4]);
+        assert!(dec.has_next_packet().is_err());
+        assert!(dec.try_next_packet::<TestPacket>().is_err());
+    }
+
+    #[test]
+    fn overflowing_packet_len_rejected() {
+        let mut dec = PacketDecoder::new();
+
+        // Five bytes, but the final byte has bits beyond the 32nd set.
+        dec.queue_slice(&[0x80, 0x80, 0x80, 0x80, 0x70]);
+        assert!(dec.has_next_packet().is_err());
+        assert!(dec.try_next_packet::<TestPacket>().is_err());
+    }
 }
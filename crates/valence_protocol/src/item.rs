@@ -1,9 +1,10 @@
 use std::io::Write;
 
 use anyhow::{ensure, Context};
-use valence_nbt::Compound;
+use valence_nbt::{compound, Compound, List, Value};
 
-use crate::{BlockKind, Decode, Encode, Result, VarInt};
+use crate::enchant::EnchantmentKind;
+use crate::{BlockKind, Decode, Encode, Result, Text, VarInt};
 
 include!(concat!(env!("OUT_DIR"), "/item.rs"));
 
@@ -36,6 +37,93 @@ impl ItemStack {
     pub fn set_count(&mut self, count: u8) {
         self.count = count.clamp(STACK_MIN, STACK_MAX);
     }
+
+    /// Creates a new item stack, returning an error if `count` is not in the
+    /// range `1..=item.max_stack()`.
+    pub fn try_new(item: ItemKind, count: u8, nbt: Option<Compound>) -> Result<Self> {
+        ensure!(
+            (STACK_MIN..=item.max_stack()).contains(&count),
+            "invalid item stack count for {} (got {count}, expected {STACK_MIN}..={})",
+            item.to_str(),
+            item.max_stack()
+        );
+
+        Ok(Self { item, count, nbt })
+    }
+
+    /// Returns `true` if the number of items in this stack is greater than the
+    /// maximum stack size of the item. Clients accept such stacks, but they
+    /// cannot be obtained in vanilla.
+    pub fn exceeds_max_stack(&self) -> bool {
+        self.count > self.item.max_stack()
+    }
+
+    /// Sets the custom name displayed for this stack, stored in the
+    /// `display.Name` NBT tag.
+    pub fn with_custom_name(mut self, name: impl Into<Text>) -> Self {
+        let name = serde_json::to_string(&name.into()).expect("failed to serialize text");
+        compound_entry(self.nbt_mut(), "display").insert("Name", name);
+        self
+    }
+
+    /// Adds an enchantment to this stack, stored in the `Enchantments` NBT
+    /// tag. If the enchantment is already present, its level is replaced.
+    ///
+    /// The level is not checked against the maximum level of the enchantment.
+    pub fn with_enchantment(mut self, enchantment: EnchantmentKind, level: i16) -> Self {
+        let id = format!("minecraft:{}", enchantment.name());
+
+        let value = self
+            .nbt_mut()
+            .entry("Enchantments")
+            .or_insert(List::Compound(vec![]));
+
+        if !matches!(value, Value::List(List::Compound(_))) {
+            *value = Value::List(List::Compound(vec![]));
+        }
+
+        let Value::List(List::Compound(enchantments)) = value else {
+            unreachable!()
+        };
+
+        match enchantments
+            .iter_mut()
+            .find(|e| e.get("id") == Some(&Value::String(id.clone())))
+        {
+            Some(existing) => {
+                existing.insert("lvl", level);
+            }
+            None => enchantments.push(compound! {
+                "id" => id,
+                "lvl" => level,
+            }),
+        }
+
+        self
+    }
+
+    /// Sets the `CustomModelData` NBT tag of this stack, which resource packs
+    /// use to select alternate item models.
+    pub fn with_custom_model_data(mut self, data: i32) -> Self {
+        self.nbt_mut().insert("CustomModelData", data);
+        self
+    }
+
+    fn nbt_mut(&mut self) -> &mut Compound {
+        self.nbt.get_or_insert_with(Compound::new)
+    }
+}
+
+/// Gets the compound at `key`, inserting an empty compound if the key is
+/// absent or is not a compound.
+fn compound_entry<'a>(nbt: &'a mut Compound, key: &str) -> &'a mut Compound {
+    let value = nbt.entry(key).or_insert_with(Compound::new);
+
+    if !value.is_compound() {
+        *value = Value::Compound(Compound::new());
+    }
+
+    value.as_compound_mut().unwrap()
 }
 
 impl Encode for Option<ItemStack> {
@@ -125,6 +213,52 @@ mod tests {
         assert_eq!(BlockKind::NetherPortal.to_item_kind(), ItemKind::Air);
     }
 
+    #[test]
+    fn item_stack_validates_max_stack() {
+        assert!(ItemStack::try_new(ItemKind::Stone, 64, None).is_ok());
+        assert!(ItemStack::try_new(ItemKind::Stone, 65, None).is_err());
+        assert!(ItemStack::try_new(ItemKind::EnderPearl, 17, None).is_err());
+        assert!(ItemStack::try_new(ItemKind::Stone, 0, None).is_err());
+
+        assert!(ItemStack::new(ItemKind::DiamondSword, 2, None).exceeds_max_stack());
+        assert!(!ItemStack::new(ItemKind::DiamondSword, 1, None).exceeds_max_stack());
+    }
+
+    #[test]
+    fn item_stack_with_custom_name_round_trip() {
+        let stack = ItemStack::new(ItemKind::DiamondSword, 1, None)
+            .with_custom_name("Excalibur")
+            .with_enchantment(EnchantmentKind::Sharpness, 3)
+            .with_enchantment(EnchantmentKind::Sharpness, 5)
+            .with_custom_model_data(42);
+
+        let nbt = stack.nbt.as_ref().unwrap();
+
+        assert_eq!(
+            nbt.get("display"),
+            Some(&Value::Compound(compound! {
+                "Name" => r#"{"text":"Excalibur"}"#,
+            }))
+        );
+        assert_eq!(
+            nbt.get("Enchantments"),
+            Some(&Value::List(List::Compound(vec![compound! {
+                "id" => "minecraft:sharpness",
+                "lvl" => 5_i16,
+            }])))
+        );
+        assert_eq!(nbt.get("CustomModelData"), Some(&Value::Int(42)));
+
+        let mut buf = vec![];
+        Some(&stack).encode(&mut buf).unwrap();
+
+        let mut r = buf.as_slice();
+        let decoded = Option::<ItemStack>::decode(&mut r).unwrap();
+
+        assert!(r.is_empty());
+        assert_eq!(decoded, Some(stack));
+    }
+
     #[test]
     fn item_stack_clamps_count() {
         let mut stack = ItemStack::new(ItemKind::Stone, 200, None);
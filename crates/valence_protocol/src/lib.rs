@@ -28,6 +28,52 @@
 //! assert_eq!(outgoing.item_name, incoming.item_name);
 //! ```
 //!
+//! # Custom Packets
+//!
+//! Packets outside of the vanilla protocol can be defined with the same derive
+//! macros used by the packets in this crate. [`EncodePacket::PACKET_ID`] and
+//! [`DecodePacket::PACKET_ID`] are assigned with the `#[packet_id = ...]`
+//! attribute.
+//!
+//! Packets with unknown IDs can be read as a [`RawPacket`] and decoded once the
+//! ID has been examined.
+//!
+//! ```
+//! use valence_protocol::{
+//!     Decode, DecodePacket, Encode, EncodePacket, PacketDecoder, PacketEncoder, RawPacket,
+//! };
+//!
+//! #[derive(Clone, PartialEq, Debug, Encode, EncodePacket, Decode, DecodePacket)]
+//! #[packet_id = 0x7f]
+//! struct ModdedAbility<'a> {
+//!     ability: &'a str,
+//!     power: i32,
+//! }
+//!
+//! let mut enc = PacketEncoder::new();
+//!
+//! let outgoing = ModdedAbility {
+//!     ability: "blink",
+//!     power: 3,
+//! };
+//!
+//! enc.append_packet(&outgoing).unwrap();
+//!
+//! let mut dec = PacketDecoder::new();
+//!
+//! dec.queue_bytes(enc.take());
+//!
+//! let raw = dec.try_next_packet::<RawPacket>().unwrap().unwrap();
+//!
+//! if raw.id.0 == <ModdedAbility as DecodePacket>::PACKET_ID {
+//!     let mut r = raw.data;
+//!     let incoming = ModdedAbility::decode(&mut r).unwrap();
+//!
+//!     assert!(r.is_empty());
+//!     assert_eq!(outgoing, incoming);
+//! }
+//! ```
+//!
 //! # Stability
 //!
 //! The Minecraft protocol is not stable. Updates to Minecraft may change the
@@ -75,12 +121,13 @@ pub use anyhow::{Error, Result};
 pub use array::LengthPrefixedArray;
 pub use block::{BlockFace, BlockKind, BlockState};
 pub use block_pos::BlockPos;
+pub use bounded::{BoundedArray, BoundedInt, BoundedString};
 pub use byte_angle::ByteAngle;
 pub use codec::*;
 pub use ident::Ident;
 pub use inventory::InventoryKind;
 pub use item::{ItemKind, ItemStack};
-pub use raw_bytes::RawBytes;
+pub use raw_bytes::{RawBytes, RawPacket};
 pub use text::{Text, TextFormat};
 pub use username::Username;
 pub use uuid::Uuid;
@@ -284,7 +331,7 @@ pub trait Decode<'a>: Sized {
 /// println!("{buf:?}");
 /// ```
 ///
-/// [macro]: valence_derive::DecodePacket
+/// [macro]: valence_derive::EncodePacket
 pub trait EncodePacket {
     /// The packet ID that is written when [`Self::encode_packet`] is called. A
     /// negative value indicates that the packet ID is not statically known.
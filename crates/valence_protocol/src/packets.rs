@@ -52,18 +52,32 @@ macro_rules! packet_enum {
             }
         }
 
+        impl<$enum_life> $enum_name<$enum_life> {
+            /// Decodes the packet with the given ID from the data following
+            /// the packet ID. Returns `Ok(None)` if none of the packets in this
+            /// enum have the ID.
+            pub fn decode_with_id(id: i32, r: &mut &$enum_life [u8]) -> crate::Result<Option<Self>> {
+                use crate::Decode;
+
+                Ok(Some(match id {
+                    $(
+                        <$packet as crate::DecodePacket>::PACKET_ID =>
+                            Self::$packet($packet::decode(r)?),
+                    )*
+                    _ => return Ok(None),
+                }))
+            }
+        }
+
         impl<$enum_life> crate::DecodePacket<$enum_life> for $enum_name<$enum_life> {
             fn decode_packet(r: &mut &$enum_life [u8]) -> crate::Result<Self> {
                 use crate::{Decode, VarInt};
 
                 let id = VarInt::decode(r)?.0;
-                Ok(match id {
-                    $(
-                        <$packet as crate::DecodePacket>::PACKET_ID =>
-                            Self::$packet($packet::decode(r)?),
-                    )*
-                    id => anyhow::bail!("unknown packet ID {:#02x} while decoding {}", id, stringify!($enum_name)),
-                })
+                match Self::decode_with_id(id, r)? {
+                    Some(pkt) => Ok(pkt),
+                    None => anyhow::bail!("unknown packet ID {:#02x} while decoding {}", id, stringify!($enum_name)),
+                }
             }
         }
 
@@ -116,18 +130,32 @@ macro_rules! packet_enum {
             }
         }
 
-        impl crate::DecodePacket<'_> for $enum_name {
-            fn decode_packet(r: &mut &[u8]) -> crate::Result<Self> {
-                use crate::{Decode, VarInt};
+        impl $enum_name {
+            /// Decodes the packet with the given ID from the data following
+            /// the packet ID. Returns `Ok(None)` if none of the packets in this
+            /// enum have the ID.
+            pub fn decode_with_id(id: i32, r: &mut &[u8]) -> crate::Result<Option<Self>> {
+                use crate::Decode;
 
-                let id = VarInt::decode(r)?.0;
-                Ok(match id {
+                Ok(Some(match id {
                     $(
                         <$packet as crate::DecodePacket>::PACKET_ID =>
                             Self::$packet($packet::decode(r)?),
                     )*
-                    id => anyhow::bail!("unknown packet ID {:#02x} while decoding {}", id, stringify!($enum_name)),
-                })
+                    _ => return Ok(None),
+                }))
+            }
+        }
+
+        impl crate::DecodePacket<'_> for $enum_name {
+            fn decode_packet(r: &mut &[u8]) -> crate::Result<Self> {
+                use crate::{Decode, VarInt};
+
+                let id = VarInt::decode(r)?.0;
+                match Self::decode_with_id(id, r)? {
+                    Some(pkt) => Ok(pkt),
+                    None => anyhow::bail!("unknown packet ID {:#02x} while decoding {}", id, stringify!($enum_name)),
+                }
             }
         }
 
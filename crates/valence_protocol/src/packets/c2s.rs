@@ -2,6 +2,7 @@ use uuid::Uuid;
 
 use crate::block::BlockFace;
 use crate::block_pos::BlockPos;
+use crate::bounded::BoundedArray;
 use crate::ident::Ident;
 use crate::item::ItemStack;
 use crate::raw_bytes::RawBytes;
@@ -12,7 +13,6 @@ use crate::types::{
     StructureBlockAction, StructureBlockFlags, StructureBlockMirror, StructureBlockMode,
     StructureBlockRotation,
 };
-use crate::username::Username;
 use crate::var_int::VarInt;
 use crate::var_long::VarLong;
 use crate::{Decode, DecodePacket, Encode, EncodePacket};
@@ -74,15 +74,17 @@ pub mod login {
     #[derive(Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
     #[packet_id = 0x00]
     pub struct LoginStart<'a> {
-        pub username: Username<&'a str>,
+        /// Not validated as a [`Username`](crate::username::Username) because
+        /// servers may accept usernames which are not valid in vanilla.
+        pub username: &'a str,
         pub profile_id: Option<Uuid>,
     }
 
     #[derive(Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
     #[packet_id = 0x01]
     pub struct EncryptionResponse<'a> {
-        pub shared_secret: &'a [u8],
-        pub verify_token: &'a [u8],
+        pub shared_secret: BoundedArray<&'a [u8], 0, 128>,
+        pub verify_token: BoundedArray<&'a [u8], 0, 128>,
     }
 
     #[derive(Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
@@ -9,9 +9,10 @@ use crate::item::ItemStack;
 use crate::raw_bytes::RawBytes;
 use crate::text::Text;
 use crate::types::{
-    AttributeProperty, BossBarAction, ChunkDataBlockEntity, Difficulty, GameEventKind, GameMode,
-    GlobalPos, PlayerAbilitiesFlags, SignedProperty, SoundCategory, Statistic,
-    SyncPlayerPosLookFlags, TagGroup,
+    AttributeProperty, BossBarAction, ChunkDataBlockEntity, CommandSuggestionMatch, Difficulty,
+    DisplaySlot, GameEventKind, GameMode, GlobalPos, PlayerAbilitiesFlags, SignedProperty,
+    SoundCategory, SoundId, Statistic, SyncPlayerPosLookFlags, TagGroup, UpdateObjectivesMode,
+    UpdateScoreAction,
 };
 use crate::username::Username;
 use crate::var_int::VarInt;
@@ -210,6 +211,17 @@ pub mod play {
         pub reset: bool,
     }
 
+    #[derive(Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
+    #[packet_id = 0x0d]
+    pub struct CommandSuggestionResponse<'a> {
+        pub id: VarInt,
+        /// The index of the first character of the text to replace.
+        pub start: VarInt,
+        /// The number of characters of text to replace.
+        pub length: VarInt,
+        pub matches: Vec<CommandSuggestionMatch<'a>>,
+    }
+
     #[derive(Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
     #[packet_id = 0x0e]
     pub struct Commands<'a> {
@@ -583,6 +595,41 @@ pub mod play {
     #[packet_id = 0x42]
     pub struct SetActionBarText(pub Text);
 
+    #[derive(Copy, Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
+    #[packet_id = 0x43]
+    pub struct SetBorderCenter {
+        pub x: f64,
+        pub z: f64,
+    }
+
+    #[derive(Copy, Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
+    #[packet_id = 0x44]
+    pub struct SetBorderLerpSize {
+        pub old_diameter: f64,
+        pub new_diameter: f64,
+        /// The time until the new diameter is reached in milliseconds.
+        pub speed: VarLong,
+    }
+
+    #[derive(Copy, Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
+    #[packet_id = 0x45]
+    pub struct SetBorderSize {
+        pub diameter: f64,
+    }
+
+    #[derive(Copy, Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
+    #[packet_id = 0x46]
+    pub struct SetBorderWarningDelay {
+        /// The warning time in seconds.
+        pub warning_time: VarInt,
+    }
+
+    #[derive(Copy, Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
+    #[packet_id = 0x47]
+    pub struct SetBorderWarningDistance {
+        pub warning_blocks: VarInt,
+    }
+
     #[derive(Copy, Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
     #[packet_id = 0x49]
     pub struct SetHeldItemS2c {
@@ -607,6 +654,14 @@ pub mod play {
         pub angle: f32,
     }
 
+    #[derive(Copy, Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
+    #[packet_id = 0x4d]
+    pub struct DisplayObjective<'a> {
+        pub position: DisplaySlot,
+        /// The name of the objective to display, or empty to clear the slot.
+        pub score_name: &'a str,
+    }
+
     #[derive(Copy, Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
     #[packet_id = 0x4e]
     pub struct SetEntityMetadata<'a> {
@@ -637,6 +692,13 @@ pub mod play {
         pub food_saturation: f32,
     }
 
+    #[derive(Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
+    #[packet_id = 0x54]
+    pub struct UpdateObjectives<'a> {
+        pub objective_name: &'a str,
+        pub mode: UpdateObjectivesMode,
+    }
+
     #[derive(Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
     #[packet_id = 0x55]
     pub struct SetPassengers {
@@ -645,6 +707,14 @@ pub mod play {
         pub passengers: Vec<VarInt>,
     }
 
+    #[derive(Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
+    #[packet_id = 0x57]
+    pub struct UpdateScore<'a> {
+        /// The username or entity UUID the score belongs to.
+        pub entity_name: &'a str,
+        pub action: UpdateScoreAction<'a>,
+    }
+
     #[derive(Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
     #[packet_id = 0x59]
     pub struct SetSubtitleText(pub Text);
@@ -677,19 +747,21 @@ pub mod play {
 
     #[derive(Copy, Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
     #[packet_id = 0x5d]
-    pub struct EntitySoundEffect {
-        pub id: VarInt,
+    pub struct EntitySoundEffect<'a> {
+        pub id: SoundId<'a>,
         pub category: SoundCategory,
         pub entity_id: VarInt,
         pub volume: f32,
         pub pitch: f32,
+        pub seed: i64,
     }
 
     #[derive(Copy, Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
     #[packet_id = 0x5e]
-    pub struct SoundEffect {
-        pub id: VarInt,
+    pub struct SoundEffect<'a> {
+        pub id: SoundId<'a>,
         pub category: SoundCategory,
+        /// The position of the sound multiplied by 8.
         pub position: [i32; 3],
         pub volume: f32,
         pub pitch: f32,
@@ -767,6 +839,7 @@ pub mod play {
             BossBar,
             SetDifficulty,
             ClearTitles,
+            CommandSuggestionResponse<'a>,
             Commands<'a>,
             CloseContainerS2c,
             SetContainerContent,
@@ -803,22 +876,30 @@ pub mod play {
             UpdateSectionBlocks,
             ServerData<'a>,
             SetActionBarText,
+            SetBorderCenter,
+            SetBorderLerpSize,
+            SetBorderSize,
+            SetBorderWarningDelay,
+            SetBorderWarningDistance,
             SetHeldItemS2c,
             SetCenterChunk,
             SetRenderDistance,
             SetDefaultSpawnPosition,
+            DisplayObjective<'a>,
             SetEntityMetadata<'a>,
             SetEntityVelocity,
             SetEquipment,
             SetExperience,
             SetHealth,
+            UpdateObjectives<'a>,
             SetPassengers,
+            UpdateScore<'a>,
             SetSubtitleText,
             UpdateTime,
             SetTitleText,
             SetTitleAnimationTimes,
-            EntitySoundEffect,
-            SoundEffect,
+            EntitySoundEffect<'a>,
+            SoundEffect<'a>,
             SystemChatMessage,
             SetTabListHeaderAndFooter,
             PickupItem,
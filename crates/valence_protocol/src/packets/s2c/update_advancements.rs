@@ -1,6 +1,6 @@
 use std::io::Write;
 
-use crate::{Decode, DecodePacket, Encode, EncodePacket, Ident, ItemStack, Text, VarInt};
+use crate::{Decode, DecodePacket, Encode, EncodePacket, Ident, ItemStack, Text};
 
 #[derive(Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
 #[packet_id = 0x65]
@@ -29,13 +29,25 @@ pub struct AdvancementDisplay<'a> {
     pub title: Text,
     pub description: Text,
     pub icon: Option<ItemStack>,
-    pub frame_type: VarInt,
+    pub frame_type: FrameType,
+    /// `0x01`: has a background texture, `0x02`: show a toast when completed,
+    /// `0x04`: hidden in the advancements screen.
     pub flags: i32,
     pub background_texture: Option<Ident<&'a str>>,
     pub x_coord: f64,
     pub y_coord: f64,
 }
 
+/// The shape of the frame around an advancement's icon, which also determines
+/// the color of its toast.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Encode, Decode)]
+pub enum FrameType {
+    #[default]
+    Task,
+    Challenge,
+    Goal,
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Encode, Decode)]
 pub struct AdvancementCriteria<'a> {
     pub criterion_identifier: Ident<&'a str>,
@@ -69,7 +81,7 @@ impl<'a> Decode<'a> for AdvancementDisplay<'a> {
         let title = Text::decode(r)?;
         let description = Text::decode(r)?;
         let icon = Option::<ItemStack>::decode(r)?;
-        let frame_type = VarInt::decode(r)?;
+        let frame_type = FrameType::decode(r)?;
         let flags = i32::decode(r)?;
 
         let background_texture = if flags & 1 == 1 {
@@ -93,3 +105,30 @@ impl<'a> Decode<'a> for AdvancementDisplay<'a> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ItemKind;
+
+    #[test]
+    fn advancement_display_round_trip() {
+        let display = AdvancementDisplay {
+            title: "title".into(),
+            description: Text::default(),
+            icon: Some(ItemStack::new(ItemKind::Diamond, 1, None)),
+            frame_type: FrameType::Challenge,
+            flags: 0x02,
+            background_texture: None,
+            x_coord: 1.0,
+            y_coord: 2.0,
+        };
+
+        let mut buf = vec![];
+        display.encode(&mut buf).unwrap();
+
+        let mut r = buf.as_slice();
+        assert_eq!(AdvancementDisplay::decode(&mut r).unwrap(), display);
+        assert!(r.is_empty());
+    }
+}
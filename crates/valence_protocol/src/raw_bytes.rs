@@ -1,6 +1,6 @@
 use std::io::Write;
 
-use crate::{Decode, Encode, Result};
+use crate::{Decode, DecodePacket, Encode, EncodePacket, Result, VarInt};
 
 /// While [encoding], the contained slice is written directly to the output
 /// without any length prefix or metadata.
@@ -26,3 +26,30 @@ impl<'a> Decode<'a> for RawBytes<'a> {
         Ok(Self(slice))
     }
 }
+
+/// A packet with an arbitrary ID and unparsed contents.
+///
+/// This can be used to send or receive packets which are not modeled by this
+/// library. While decoding, the remainder of the input following the packet
+/// ID is returned as `data`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct RawPacket<'a> {
+    pub id: VarInt,
+    pub data: &'a [u8],
+}
+
+impl EncodePacket for RawPacket<'_> {
+    fn encode_packet(&self, mut w: impl Write) -> Result<()> {
+        self.id.encode(&mut w)?;
+        Ok(w.write_all(self.data)?)
+    }
+}
+
+impl<'a> DecodePacket<'a> for RawPacket<'a> {
+    fn decode_packet(r: &mut &'a [u8]) -> Result<Self> {
+        Ok(Self {
+            id: VarInt::decode(r)?,
+            data: RawBytes::decode(r)?.0,
+        })
+    }
+}
@@ -1,5 +1,7 @@
 //! Miscellaneous type definitions used in packets.
 
+use std::io::Write;
+
 use bitfield_struct::bitfield;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -226,6 +228,93 @@ pub enum Difficulty {
     Hard,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Encode, Decode)]
+pub enum DisplaySlot {
+    List,
+    Sidebar,
+    BelowName,
+}
+
+#[derive(Clone, PartialEq, Debug, Encode, Decode)]
+pub enum UpdateObjectivesMode {
+    Create {
+        objective_value: Text,
+        kind: ObjectiveRenderType,
+    },
+    Remove,
+    Update {
+        objective_value: Text,
+        kind: ObjectiveRenderType,
+    },
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Encode, Decode)]
+pub enum ObjectiveRenderType {
+    #[default]
+    Integer,
+    Hearts,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Encode, Decode)]
+pub enum UpdateScoreAction<'a> {
+    CreateOrUpdate {
+        objective_name: &'a str,
+        value: VarInt,
+    },
+    Remove {
+        objective_name: &'a str,
+    },
+}
+
+/// Identifies the sound played by the sound effect packets.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SoundId<'a> {
+    /// A sound given by its resource location, such as a custom sound from a
+    /// resource pack.
+    Direct {
+        id: Ident<&'a str>,
+        /// The fixed distance the sound can be heard from. If `None`, the
+        /// distance depends on the volume.
+        range: Option<f32>,
+    },
+    /// A sound given by its ID in the sound event registry.
+    Reference { id: VarInt },
+}
+
+impl<'a> From<Ident<&'a str>> for SoundId<'a> {
+    fn from(id: Ident<&'a str>) -> Self {
+        Self::Direct { id, range: None }
+    }
+}
+
+impl Encode for SoundId<'_> {
+    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+        match self {
+            SoundId::Direct { id, range } => {
+                VarInt(0).encode(&mut w)?;
+                id.encode(&mut w)?;
+                range.encode(&mut w)
+            }
+            SoundId::Reference { id } => VarInt(id.0 + 1).encode(w),
+        }
+    }
+}
+
+impl<'a> Decode<'a> for SoundId<'a> {
+    fn decode(r: &mut &'a [u8]) -> anyhow::Result<Self> {
+        let i = VarInt::decode(r)?.0;
+
+        if i == 0 {
+            Ok(SoundId::Direct {
+                id: Decode::decode(r)?,
+                range: Decode::decode(r)?,
+            })
+        } else {
+            Ok(SoundId::Reference { id: VarInt(i - 1) })
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Encode, Decode)]
 pub enum SoundCategory {
     Master,
@@ -274,6 +363,12 @@ pub enum GameMode {
     Spectator,
 }
 
+#[derive(Clone, PartialEq, Debug, Encode, Decode)]
+pub struct CommandSuggestionMatch<'a> {
+    pub suggested_match: &'a str,
+    pub tooltip: Option<Text>,
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Encode, Decode)]
 pub struct GlobalPos<'a> {
     pub dimension_name: Ident<&'a str>,
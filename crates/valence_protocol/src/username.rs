@@ -35,6 +35,9 @@ use crate::{Decode, Encode, Result};
 /// assert!(Username::new("notavalidusername").is_err());
 /// assert!(Username::new("NotValid!").is_err());
 /// ```
+///
+/// Usernames created with [`Username::new_with_max_len`] are exempt from some
+/// of these rules.
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize)]
 #[repr(transparent)]
 #[serde(transparent)]
@@ -51,6 +54,38 @@ impl<S: AsRef<str>> Username<S> {
         }
     }
 
+    /// Like [`Self::new`], but allows usernames up to `max_len` characters
+    /// long (including the prefix) with an optional leading `.` or `*`.
+    ///
+    /// This is intended for servers accepting players from Bedrock Edition
+    /// bridges and modded clients, whose usernames do not always follow the
+    /// vanilla rules.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valence_protocol::username::Username;
+    ///
+    /// assert!(Username::new_with_max_len(".BedrockPlayer", 16).is_ok());
+    /// assert!(Username::new_with_max_len("*a_long_bedrock_name", 20).is_ok());
+    ///
+    /// assert!(Username::new_with_max_len("*a_long_bedrock_name", 16).is_err());
+    /// assert!(Username::new_with_max_len("..double", 16).is_err());
+    /// ```
+    pub fn new_with_max_len(string: S, max_len: usize) -> Result<Self, UsernameError<S>> {
+        let s = string.as_ref();
+        let name = s.strip_prefix(['.', '*']).unwrap_or(s);
+
+        if (3..=max_len).contains(&s.len())
+            && !name.is_empty()
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            Ok(Self(string))
+        } else {
+            Err(UsernameError(string))
+        }
+    }
+
     pub fn as_str(&self) -> &str {
         self.0.as_ref()
     }
@@ -29,6 +29,9 @@ impl VarInt {
         let mut val = 0;
         for i in 0..Self::MAX_SIZE {
             let byte = r.read_u8().map_err(|_| VarIntDecodeError::Incomplete)?;
+            if i == Self::MAX_SIZE - 1 && byte & !LAST_BYTE_MASK != 0 {
+                return Err(VarIntDecodeError::TooLarge);
+            }
             val |= (byte as i32 & 0b01111111) << (i * 7);
             if byte & 0b10000000 == 0 {
                 return Ok(val);
@@ -39,6 +42,10 @@ impl VarInt {
     }
 }
 
+/// The bits that may be set in the last byte of a VarInt. Anything else would
+/// either continue the VarInt past [`VarInt::MAX_SIZE`] or overflow an `i32`.
+const LAST_BYTE_MASK: u8 = 0b00001111;
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Error)]
 pub enum VarIntDecodeError {
     #[error("incomplete VarInt decode")]
@@ -66,6 +73,9 @@ impl Decode<'_> for VarInt {
         let mut val = 0;
         for i in 0..Self::MAX_SIZE {
             let byte = r.read_u8()?;
+            if i == Self::MAX_SIZE - 1 && byte & !LAST_BYTE_MASK != 0 {
+                bail!("VarInt is too large")
+            }
             val |= (byte as i32 & 0b01111111) << (i * 7);
             if byte & 0b10000000 == 0 {
                 return Ok(VarInt(val));